@@ -0,0 +1,67 @@
+"""Benchmark sequential vs parallel skeleton extraction.
+
+This script generates a synthetic tree of source files copied from the language
+samples and times extracting all of them one by one against
+`get_skeletons_parallel`.
+
+Usage:
+    python examples/benchmark_parallel.py [NUM_FILES] [MAX_WORKERS]
+"""
+
+from __future__ import annotations
+
+import sys
+import tempfile
+import time
+from pathlib import Path
+
+from loppers import get_skeleton, get_skeletons_parallel
+
+
+def build_tree(root: Path, num_files: int) -> list[Path]:
+    """Write `num_files` copies of the sample files under root.
+
+    Args:
+        root: Directory to populate
+        num_files: Number of files to write
+
+    Returns:
+        Paths of the written files
+    """
+    samples = sorted(Path(__file__).parent.glob("sample.*"))
+    paths: list[Path] = []
+    for i in range(num_files):
+        sample = samples[i % len(samples)]
+        directory = root / f"pkg{i // 100:03d}"
+        directory.mkdir(exist_ok=True)
+        path = directory / f"file{i:05d}{sample.suffix}"
+        path.write_text(sample.read_text())
+        paths.append(path)
+    return paths
+
+
+def main() -> None:
+    """Main entry point."""
+    num_files = int(sys.argv[1]) if len(sys.argv) > 1 else 3000
+    max_workers = int(sys.argv[2]) if len(sys.argv) > 2 else None
+
+    with tempfile.TemporaryDirectory() as tmpdir:
+        paths = build_tree(Path(tmpdir), num_files)
+
+        start = time.perf_counter()
+        sequential = [get_skeleton(path) for path in paths]
+        sequential_time = time.perf_counter() - start
+
+        start = time.perf_counter()
+        results = get_skeletons_parallel(paths, max_workers=max_workers)
+        parallel_time = time.perf_counter() - start
+
+    assert [result.skeleton for result in results] == sequential
+    print(f"Files:      {num_files}")
+    print(f"Sequential: {sequential_time:.2f}s")
+    print(f"Parallel:   {parallel_time:.2f}s")
+    print(f"Speedup:    {sequential_time / parallel_time:.1f}x")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,49 @@
+//! Generic containers.
+
+use std::fmt::Debug;
+
+/// Returns the largest item.
+pub fn largest<'a, T: PartialOrd + 'a>(items: &'a [T]) -> Option<&'a T> {
+    let mut best = items.first()?;
+    for item in items {
+        if item > best {
+            best = item;
+        }
+    }
+    Some(best)
+}
+
+pub fn describe<K, V, const N: usize>(pairs: [(K, V); N]) -> String
+where
+    K: Debug + Ord,
+    V: Debug + Clone + Default,
+{
+    format!("{:?}", pairs.len())
+}
+
+pub struct Cache<K, V>
+where
+    K: Eq + std::hash::Hash,
+{
+    entries: Vec<(K, V)>,
+}
+
+impl<K, V> Cache<K, V>
+where
+    K: Eq + std::hash::Hash + Clone,
+    V: Clone,
+{
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Eq,
+    {
+        self.entries.iter().find(|(k, _)| k.borrow() == key).map(|(_, v)| v)
+    }
+}
+
+pub trait Visitor<'de> {
+    fn visit<T>(&mut self, value: T) -> Result<(), String>
+    where
+        T: Debug + 'de;
+}
@@ -6,21 +6,199 @@ Compatible with tree-sitter >= 0.25
 
 from __future__ import annotations
 
-from loppers.extensions import EXTENSION_TO_LANGUAGE, get_language
+from loppers.budget import (
+    BudgetReport,
+    BudgetedFile,
+    BudgetedSkeleton,
+    estimate_tokens,
+    fit_files_to_budget,
+    fit_to_budget,
+)
+from loppers.bundle import LoppedFile, render_bundle, render_repo_map
+from loppers.cache import LopCache
+from loppers.changes import DiffSkeleton, SymbolChange, skeletonize_diff
+from loppers.config import CONFIG_FILE_NAMES, LopperConfig, find_config, load_config, read_config
+from loppers.ctags import render_ctags
+from loppers.documents import (
+    DOCUMENT_LANGUAGES,
+    EmbeddedCode,
+    extract_document,
+    lop_regions,
+    markdown_code_blocks,
+    script_blocks,
+)
+from loppers.elision import ElidedRegion, ElidedSkeleton, extract_with_regions
+from loppers.errors import (
+    ConfigError,
+    InvalidNotebookError,
+    InvalidQueryError,
+    InvalidUtf8Error,
+    LopError,
+    ParseFailedError,
+    SymbolNotFoundError,
+    UnsupportedLanguageError,
+)
+from loppers.extensions import (
+    EXTENSION_TO_LANGUAGE,
+    detect_language,
+    get_language,
+    sniff_language,
+)
+from loppers.focus import diff_line_ranges, git_diff_line_ranges, lop_symbol, lop_with_focus
+from loppers.highlight import ANSI_COLORS, highlight
+from loppers.incremental import IncrementalLopper, InputEdit, SymbolChanges
+from loppers.loppers import (
+    DEFAULT_CTAGS_KINDS,
+    ExtractionResult,
+    LanguageConfig,
+    LopperOptions,
+    LopperOptionsBuilder,
+    ParseDiagnostic,
+    SourceMap,
+)
+from loppers.notebooks import lop_notebook
+from loppers.outline import OUTLINE_GROUPS, OutlineOptions, render_outline
+from loppers.ranking import RankedSymbol, rank_symbols
+from loppers.registry import register_language
+from loppers.service import AsyncLopper, LopResult, serve
+from loppers.shortcuts import lop, lop_dir, lop_file, lop_symbols, supported_languages
 from loppers.source_utils import (
+    SkeletonReport,
+    SkeletonResult,
     concatenate_files,
+    detect_file_language,
     extract_skeleton,
+    extract_skeleton_result,
     find_files,
     get_skeleton,
+    get_skeletons,
+    get_skeletons_parallel,
     get_tree,
+    matches_pattern,
+    skeletonize_with_query,
+    stream_skeleton,
+    stream_skeleton_reader,
+    walk_dir,
+)
+from loppers.stats import LopStats, StatsReport, collect_stats, lop_stats, lop_to_writer
+from loppers.symbol_model import Symbol, match_symbols, signature_text
+from loppers.symbols import (
+    ItemSource,
+    extract_item,
+    extract_symbols,
+    find_matching_symbols,
+    find_symbols,
+    iter_symbols,
+    sort_symbols,
+    source_hash,
+    symbols_from_json,
+    symbols_to_json,
 )
 
 __all__ = [
+    "ANSI_COLORS",
+    "CONFIG_FILE_NAMES",
+    "DEFAULT_CTAGS_KINDS",
+    "DOCUMENT_LANGUAGES",
     "EXTENSION_TO_LANGUAGE",
+    "OUTLINE_GROUPS",
+    "AsyncLopper",
+    "BudgetReport",
+    "BudgetedFile",
+    "BudgetedSkeleton",
+    "ConfigError",
+    "DiffSkeleton",
+    "ElidedRegion",
+    "ElidedSkeleton",
+    "EmbeddedCode",
+    "ExtractionResult",
+    "IncrementalLopper",
+    "InputEdit",
+    "InvalidNotebookError",
+    "InvalidQueryError",
+    "InvalidUtf8Error",
+    "ItemSource",
+    "LanguageConfig",
+    "LopCache",
+    "LopError",
+    "LopResult",
+    "LopStats",
+    "LoppedFile",
+    "LopperConfig",
+    "LopperOptions",
+    "LopperOptionsBuilder",
+    "OutlineOptions",
+    "ParseDiagnostic",
+    "ParseFailedError",
+    "RankedSymbol",
+    "SkeletonReport",
+    "SkeletonResult",
+    "SourceMap",
+    "StatsReport",
+    "Symbol",
+    "SymbolChange",
+    "SymbolChanges",
+    "SymbolNotFoundError",
+    "UnsupportedLanguageError",
+    "collect_stats",
     "concatenate_files",
+    "detect_file_language",
+    "detect_language",
+    "diff_line_ranges",
+    "estimate_tokens",
+    "extract_document",
+    "extract_item",
     "extract_skeleton",
+    "extract_skeleton_result",
+    "extract_symbols",
+    "extract_with_regions",
+    "find_config",
     "find_files",
+    "find_matching_symbols",
+    "find_symbols",
+    "fit_files_to_budget",
+    "fit_to_budget",
     "get_language",
     "get_skeleton",
+    "get_skeletons",
+    "get_skeletons_parallel",
     "get_tree",
+    "git_diff_line_ranges",
+    "highlight",
+    "iter_symbols",
+    "load_config",
+    "lop",
+    "lop_dir",
+    "lop_file",
+    "lop_notebook",
+    "lop_regions",
+    "lop_stats",
+    "lop_symbol",
+    "lop_symbols",
+    "lop_to_writer",
+    "lop_with_focus",
+    "markdown_code_blocks",
+    "match_symbols",
+    "matches_pattern",
+    "rank_symbols",
+    "read_config",
+    "register_language",
+    "render_bundle",
+    "render_ctags",
+    "render_outline",
+    "render_repo_map",
+    "script_blocks",
+    "serve",
+    "signature_text",
+    "skeletonize_diff",
+    "skeletonize_with_query",
+    "sniff_language",
+    "sort_symbols",
+    "source_hash",
+    "stream_skeleton",
+    "stream_skeleton_reader",
+    "supported_languages",
+    "symbols_from_json",
+    "symbols_to_json",
+    "walk_dir",
 ]
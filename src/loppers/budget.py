@@ -0,0 +1,369 @@
+"""Token budget fitting for skeletons.
+
+A skeleton that is still too large for a context window can be trimmed further
+by dropping whole symbols, least important first, until its estimated token
+count fits a budget. A set of files is fitted by eliding more of each file,
+step by step, until their summary fits.
+"""
+
+from __future__ import annotations
+
+import dataclasses
+from collections.abc import Callable, Mapping, Sequence
+from dataclasses import dataclass
+from pathlib import Path
+from typing import Literal
+
+from tree_sitter import Node
+
+from loppers.errors import InvalidUtf8Error
+from loppers.loppers import (
+    LANGUAGE_CONFIGS,
+    PRIVATE_VISIBILITIES,
+    LopperOptions,
+    get_extractor,
+    is_hidden,
+    item_span,
+    line_starts_of,
+)
+from loppers.source_utils import detect_file_language, extract_skeleton
+from loppers.symbol_model import Symbol, base_name, symbol_nodes
+
+# Estimates the number of tokens of a text
+TokenEstimator = Callable[[str], int]
+
+# How much of a file a budgeted summary includes, from most to least
+BudgetLevel = Literal["full", "elided", "public", "dropped"]
+
+# Symbol kinds whose nested symbols are dropped first
+FUNCTION_KINDS: frozenset[str] = frozenset({"function", "method"})
+
+# Symbol kinds declaring a type, kept longest when top-level
+TYPE_KINDS: frozenset[str] = frozenset(
+    {"class", "struct", "union", "enum", "interface", "trait", "type"}
+)
+
+
+def estimate_tokens(text: str) -> int:
+    """Roughly estimate the number of tokens of a text as one per four characters.
+
+    Args:
+        text: Text to estimate
+
+    Returns:
+        Estimated token count
+    """
+    return -(-len(text) // 4)
+
+
+@dataclass(frozen=True)
+class BudgetedSkeleton:
+    """A skeleton trimmed to fit a token budget.
+
+    Attributes:
+        skeleton: Skeleton text
+        tokens: Estimated token count of the skeleton
+        max_tokens: Token budget the skeleton was fitted to
+        dropped: Number of symbols dropped (nested symbols included)
+    """
+
+    skeleton: str
+    tokens: int
+    max_tokens: int
+    dropped: int = 0
+
+    @property
+    def fits(self) -> bool:
+        """Whether the skeleton fits the token budget."""
+        return self.tokens <= self.max_tokens
+
+
+def fit_to_budget(
+    source_code: str,
+    language: str,
+    max_tokens: int,
+    *,
+    options: LopperOptions | None = None,
+    estimator: TokenEstimator = estimate_tokens,
+    references: Mapping[str, int] | None = None,
+) -> BudgetedSkeleton:
+    """Extract a skeleton, dropping the lowest-priority symbols until it fits a budget.
+
+    Symbols are dropped in this order: functions nested in functions, private items,
+    other public items, and finally top-level types. Within each group, the least
+    referenced symbols go first when reference counts are given, then nested and
+    later ones. Languages without symbol support are never trimmed, so their result
+    may not fit.
+
+    Args:
+        source_code: Source code to process
+        language: Programming language identifier
+        max_tokens: Maximum estimated token count of the skeleton
+        options: Extraction options (defaults to LopperOptions())
+        estimator: Token estimator (defaults to a characters / 4 heuristic)
+        references: Number of references to the symbols of each name, such as the
+            counts found by `rank_symbols` (symbols of unlisted names have none)
+
+    Returns:
+        Fitted skeleton with its token count and the number of dropped symbols
+
+    Raises:
+        UnsupportedLanguageError: If the language is not supported
+        ValueError: If max_tokens is negative
+    """
+    if max_tokens < 0:
+        raise ValueError(f"max_tokens must be non-negative, got {max_tokens}")
+    extractor = get_extractor(language, options)
+
+    source = source_code.encode()
+    line_starts = line_starts_of(source)
+    tree = extractor.parse(source)
+    rendered: dict[int, tuple[str, int]] = {}
+
+    def render(count: int) -> tuple[str, int]:
+        if count not in rendered:
+            skeleton = extractor.extract(
+                source_code,
+                tree=tree,
+                remove=[item_span(node, source, line_starts) for _, node in candidates[:count]],
+            )
+            rendered[count] = (skeleton, estimator(skeleton))
+        return rendered[count]
+
+    candidates: list[tuple[Symbol, Node]] = []
+    skeleton, tokens = render(0)
+    if tokens <= max_tokens or not extractor.config.symbol_query:
+        return BudgetedSkeleton(skeleton, tokens, max_tokens)
+
+    # Items below the kept visibility are already gone
+    pairs = symbol_nodes(extractor, tree, source)
+    pairs = [
+        pair for pair in pairs if not is_hidden(pair[0].visibility, extractor.options.visibility)
+    ]
+    candidates = _drop_order(pairs, references or {})
+
+    # Find the fewest dropped symbols that fit; each drop only shrinks the skeleton
+    low, high = 0, len(candidates)
+    if render(high)[1] <= max_tokens:
+        while low < high:
+            middle = (low + high) // 2
+            if render(middle)[1] <= max_tokens:
+                high = middle
+            else:
+                low = middle + 1
+    skeleton, tokens = render(high)
+    return BudgetedSkeleton(
+        skeleton, tokens, max_tokens, dropped=_count_dropped(candidates[:high])
+    )
+
+
+def _drop_order(
+    pairs: list[tuple[Symbol, Node]], references: Mapping[str, int]
+) -> list[tuple[Symbol, Node]]:
+    """Order symbols from the first to drop to the last."""
+    parents: dict[int, Symbol] = {}
+    depths: dict[int, int] = {}
+    in_function: dict[int, bool] = {}
+    for symbol, _ in pairs:
+        for child in symbol.children:
+            parents[id(child)] = symbol
+    for symbol, _ in pairs:
+        parent = parents.get(id(symbol))
+        depths[id(symbol)] = depths[id(parent)] + 1 if parent is not None else 0
+        in_function[id(symbol)] = parent is not None and (
+            parent.kind in FUNCTION_KINDS or in_function[id(parent)]
+        )
+
+    def priority(pair: tuple[Symbol, Node]) -> tuple[int, int, int, int]:
+        symbol = pair[0]
+        if in_function[id(symbol)]:
+            tier = 0
+        elif symbol.visibility in PRIVATE_VISIBILITIES:
+            tier = 1
+        elif depths[id(symbol)] > 0 or symbol.kind not in TYPE_KINDS:
+            tier = 2
+        else:
+            tier = 3
+        count = references.get(base_name(symbol.name), 0)
+        return tier, count, -depths[id(symbol)], -symbol.start_byte
+
+    return sorted(pairs, key=priority)
+
+
+def _count_dropped(dropped: list[tuple[Symbol, Node]]) -> int:
+    """Count dropped symbols together with the symbols nested in them."""
+    seen: set[int] = set()
+    pending: list[Symbol] = [symbol for symbol, _ in dropped]
+    while pending:
+        symbol = pending.pop()
+        if id(symbol) not in seen:
+            seen.add(id(symbol))
+            pending.extend(symbol.children)
+    return len(seen)
+
+
+@dataclass(frozen=True)
+class BudgetedFile:
+    """One file of a summary fitted to a token budget.
+
+    Attributes:
+        path: File path, as given
+        level: How much of the file is included: "full" (its source), "elided" (bodies
+            spanning more than `max_body_lines` lines elided), "public" (every body
+            elided and non-public items dropped) or "dropped" (left out)
+        text: Text included for the file, empty when dropped
+        tokens: Estimated token count of the file's header and text, 0 when dropped
+        max_body_lines: Longest body kept verbatim at the "elided" level
+    """
+
+    path: str
+    level: BudgetLevel
+    text: str
+    tokens: int
+    max_body_lines: int | None = None
+
+
+@dataclass(frozen=True)
+class BudgetReport:
+    """Files fitted together to a token budget.
+
+    Attributes:
+        files: One entry per input file, in input order
+        max_tokens: Token budget the files were fitted to
+    """
+
+    files: list[BudgetedFile]
+    max_tokens: int
+
+    @property
+    def tokens(self) -> int:
+        """Estimated token count of the included files."""
+        return sum(file.tokens for file in self.files)
+
+    @property
+    def fits(self) -> bool:
+        """Whether the included files fit the token budget."""
+        return self.tokens <= self.max_tokens
+
+    @property
+    def text(self) -> str:
+        """Included files, each under a "--- <path>" header."""
+        return "\n\n".join(
+            f"--- {file.path}\n{file.text}" for file in self.files if file.level != "dropped"
+        )
+
+
+def fit_files_to_budget(
+    files: Sequence[str | Path],
+    max_tokens: int,
+    *,
+    root: str | Path | None = None,
+    options: LopperOptions | None = None,
+    estimator: TokenEstimator = estimate_tokens,
+) -> BudgetReport:
+    """Summarize files in the most detail that fits a token budget.
+
+    Files start out as full source. While they do not fit, the steps are:
+
+    1. Elide the longest bodies in every file, lowering the longest body kept
+       verbatim until the files fit or every body is elided
+    2. Drop non-public items (and elide every body) file by file, from the last file
+    3. Drop whole files, from the last file
+
+    Files listed first are therefore kept longest. Files in languages without
+    extraction support are included verbatim until they are dropped. The result only
+    depends on the files and arguments.
+
+    Args:
+        files: Paths of the files to summarize, most important first
+        max_tokens: Maximum estimated token count of the summary
+        root: Directory the paths are relative to (defaults to the working directory)
+        options: Extraction options (defaults to LopperOptions()); `max_body_lines` and
+            `visibility` are set by each step
+        estimator: Token estimator (defaults to a characters / 4 heuristic)
+
+    Returns:
+        Report with the level applied to each file
+
+    Raises:
+        FileNotFoundError: If a file does not exist
+        InvalidUtf8Error: If a file is not valid UTF-8
+        ValueError: If max_tokens is negative
+    """
+    if max_tokens < 0:
+        raise ValueError(f"max_tokens must be non-negative, got {max_tokens}")
+    base = options or LopperOptions()
+    root_path = Path(root) if root is not None else Path()
+    sources: list[tuple[str, str, str | None]] = [
+        (str(path), *read_source(root_path / path)) for path in files
+    ]
+    rendered: dict[tuple[int, BudgetLevel, int | None], BudgetedFile] = {}
+
+    def render(index: int, level: BudgetLevel, max_body_lines: int | None = None) -> BudgetedFile:
+        key = (index, level, max_body_lines)
+        if key not in rendered:
+            path, source, language = sources[index]
+            if level == "dropped":
+                text = ""
+            elif language is None or level == "full":
+                level, text = "full", source.rstrip()
+            else:
+                lopped = (
+                    dataclasses.replace(base, max_body_lines=max_body_lines)
+                    if level == "elided"
+                    else dataclasses.replace(base, max_body_lines=None, visibility="public")
+                )
+                text = extract_skeleton(source, language, options=lopped)
+            rendered[key] = BudgetedFile(
+                path,
+                level,
+                text,
+                estimator(f"--- {path}\n{text}") if level != "dropped" else 0,
+                max_body_lines if level == "elided" else None,
+            )
+        return rendered[key]
+
+    def fits(chosen: list[BudgetedFile]) -> bool:
+        return sum(file.tokens for file in chosen) <= max_tokens
+
+    def elided(max_body_lines: int) -> list[BudgetedFile]:
+        return [render(index, "elided", max_body_lines) for index in range(len(sources))]
+
+    chosen = [render(index, "full") for index in range(len(sources))]
+    if fits(chosen):
+        return BudgetReport(chosen, max_tokens)
+
+    # Step 1: find the longest body kept verbatim for which the files still fit
+    low, high = 0, max((source.count("\n") + 1 for _, source, _ in sources), default=0)
+    if fits(elided(low)):
+        while low < high:
+            middle = (low + high + 1) // 2
+            if fits(elided(middle)):
+                low = middle
+            else:
+                high = middle - 1
+        return BudgetReport(elided(low), max_tokens)
+
+    # Steps 2 and 3: trim whole files, starting from the last one
+    chosen = elided(0)
+    steps: tuple[BudgetLevel, ...] = ("public", "dropped")
+    for level in steps:
+        for index in reversed(range(len(sources))):
+            language = sources[index][2]
+            config = LANGUAGE_CONFIGS.get(language or "")
+            if level == "public" and not (config and config.symbol_query):
+                continue
+            chosen[index] = render(index, level)
+            if fits(chosen):
+                return BudgetReport(chosen, max_tokens)
+    return BudgetReport(chosen, max_tokens)
+
+
+def read_source(path: Path) -> tuple[str, str | None]:
+    """Read a file's source and detect its language."""
+    if not path.is_file():
+        raise FileNotFoundError(f"File not found: {path}")
+    try:
+        return path.read_text(encoding="utf-8"), detect_file_language(path)
+    except UnicodeDecodeError as e:
+        raise InvalidUtf8Error(f"Could not read file as text: {e}", path=path) from e
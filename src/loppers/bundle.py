@@ -0,0 +1,168 @@
+"""Bundling skeletons of several files into one document.
+
+A bundle puts each file's skeleton under a header naming the file, so that the
+skeletons of a whole project can be pasted into a prompt at once.
+"""
+
+from __future__ import annotations
+
+import re
+from collections.abc import Sequence
+from dataclasses import dataclass
+from pathlib import Path, PurePath
+from typing import Literal
+
+from loppers.extensions import detect_language
+from loppers.loppers import LopperOptions, is_hidden
+from loppers.source_utils import SkeletonReport, detect_file_language
+from loppers.symbols import extract_symbols, iter_symbols
+
+# Layout of a bundle: "plain" text, or "markdown" with each file in a fenced code block
+BundleFormat = Literal["plain", "markdown"]
+
+# Header template used by default; `{path}` expands to the file path
+DEFAULT_HEADER: str = "=== {path} ==="
+
+# Note standing in for skeletons that are empty, when empty files are shown
+DEFAULT_EMPTY_NOTE: str = "(no public items)"
+
+# Header of each file of a repo map
+REPO_MAP_HEADER: str = "## {path}"
+
+# Markdown fence info strings of languages whose identifier is not one
+_FENCE_LANGUAGES: dict[str, str] = {"objc": "objectivec"}
+
+# Backtick runs that would close a code fence
+_BACKTICK_RUN = re.compile(r"`{3,}")
+
+
+@dataclass(frozen=True)
+class LoppedFile:
+    """A file's skeleton, as bundled by `render_bundle`.
+
+    Attributes:
+        path: File path, shown in the file's header
+        skeleton: Skeleton text
+        language: Language identifier, tagging the Markdown code block; detected
+            from the path when None
+    """
+
+    path: str | PurePath
+    skeleton: str
+    language: str | None = None
+
+
+def render_bundle(
+    files: Sequence[LoppedFile],
+    *,
+    format: BundleFormat = "plain",
+    root: str | PurePath | None = None,
+    header: str = DEFAULT_HEADER,
+    show_empty: bool = False,
+    empty_note: str = DEFAULT_EMPTY_NOTE,
+) -> str:
+    """Render skeletons of several files as one document, each under a header.
+
+    Files appear in input order, separated by blank lines. In the "markdown"
+    format each skeleton is fenced as a code block tagged with its language, using
+    a fence longer than any backtick run in the skeleton.
+
+    Args:
+        files: Files to bundle
+        format: "plain" or "markdown"
+        root: Directory header paths are shown relative to (paths outside it are
+            shown as given)
+        header: Header template, where `{path}` expands to the file path
+        show_empty: Show files whose skeleton is empty with `empty_note` instead of
+            leaving them out
+        empty_note: Text shown for empty skeletons when `show_empty` is set
+
+    Returns:
+        The bundled document
+
+    Raises:
+        ValueError: If the format is unknown or the header template is invalid
+    """
+    if format not in ("plain", "markdown"):
+        raise ValueError(f"Invalid format '{format}'. Expected 'plain' or 'markdown'")
+    try:
+        header.format(path="")
+    except (IndexError, KeyError, ValueError) as e:
+        raise ValueError(f"Invalid header '{header}': {e!r}") from e
+
+    sections: list[str] = []
+    for file in files:
+        skeleton = file.skeleton.rstrip()
+        if not skeleton:
+            if not show_empty:
+                continue
+            sections.append(f"{header.format(path=_shown_path(file.path, root))}\n{empty_note}")
+            continue
+        if format == "markdown":
+            language = file.language or detect_language(file.path) or ""
+            runs = [len(run) for run in _BACKTICK_RUN.findall(skeleton)]
+            fence = "`" * max([3, *(length + 1 for length in runs)])
+            skeleton = f"{fence}{_FENCE_LANGUAGES.get(language, language)}\n{skeleton}\n{fence}"
+        sections.append(f"{header.format(path=_shown_path(file.path, root))}\n{skeleton}")
+    return "\n\n".join(sections)
+
+
+def _shown_path(path: str | PurePath, root: str | PurePath | None) -> str:
+    """Return a path as shown in headers: relative to the root when inside it."""
+    shown = PurePath(path)
+    if root is not None:
+        root_path = PurePath(root)
+        if shown.is_absolute() != root_path.is_absolute():
+            shown, root_path = Path(shown).resolve(), Path(root_path).resolve()
+        if shown.is_relative_to(root_path):
+            shown = shown.relative_to(root_path)
+    return shown.as_posix()
+
+
+def render_repo_map(
+    report: SkeletonReport,
+    root: str | Path,
+    *,
+    options: LopperOptions | None = None,
+    format: BundleFormat = "plain",
+) -> str:
+    """Render the skeletons of a directory as one repo map.
+
+    Each file appears under a `## <path>` header, in path order, and a summary line
+    counting the files and the symbols they define ends the map. Symbols below the
+    kept visibility of the options are not counted.
+
+    Args:
+        report: Skeletons extracted from the directory (see `get_skeletons`)
+        root: Directory the report's paths are relative to
+        options: Options the skeletons were extracted with
+        format: "plain", or "markdown" to fence each skeleton as a code block tagged
+            with the language it was parsed as
+
+    Returns:
+        The repo map
+    """
+    visibility = (options or LopperOptions()).visibility
+    symbols = 0
+    for path, _ in report.skeletons:
+        full_path = Path(root) / path
+        language = detect_file_language(full_path)
+        try:
+            outline = extract_symbols(full_path.read_text(encoding="utf-8"), language or "")
+        except ValueError:
+            # Languages without symbol support define no symbols to count
+            continue
+        symbols += sum(
+            not is_hidden(symbol.visibility, visibility) for symbol in iter_symbols(outline)
+        )
+
+    files = [
+        LoppedFile(path, skeleton, detect_file_language(Path(root) / path))
+        for path, skeleton in sorted(report.skeletons)
+    ]
+    summary = (
+        f"{len(files)} file{'s' if len(files) != 1 else ''}, "
+        f"{symbols} symbol{'s' if symbols != 1 else ''}"
+    )
+    bundle = render_bundle(files, format=format, header=REPO_MAP_HEADER)
+    return f"{bundle}\n\n{summary}" if bundle else summary
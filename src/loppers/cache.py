@@ -0,0 +1,250 @@
+"""On-disk cache of extraction results, for runs over mostly unchanged trees.
+
+Regenerating the skeletons of a repository on every commit lops the same files
+again and again: a `LopCache` stores each extraction result, with the statistics
+of the file once measured, in a directory, keyed by the hash of the source, its
+language, the fingerprint of the options and the loppers version, so a file is
+parsed again only when one of them changes. Entries are files of their own,
+written to a temporary file and renamed into place, so worker processes can
+share a cache directory. Writing an entry is best-effort: a read-only or full
+cache directory only costs parsing again.
+"""
+
+from __future__ import annotations
+
+import contextlib
+import hashlib
+import json
+import os
+import tempfile
+from dataclasses import asdict, dataclass
+from importlib.metadata import PackageNotFoundError, version
+from pathlib import Path
+from collections.abc import Mapping
+from typing import Any
+
+from loppers.loppers import ExtractionResult, LopperOptions, ParseDiagnostic, SourceMap
+from loppers.symbols import source_hash
+
+# Version of the layout of cache entries, part of every key
+CACHE_FORMAT: int = 1
+
+
+@dataclass
+class LopCache:
+    """A directory of cached extraction results.
+
+    Pass it to the batch APIs (`get_skeletons`, `get_skeletons_parallel`) to
+    reuse the results of unchanged files, and to `collect_stats` to reuse their
+    statistics. Results of options with a placeholder
+    function are not cached, as functions have no stable fingerprint.
+
+    Attributes:
+        directory: Directory holding the entries, one file per result
+        hits: Number of results read from the cache in this process
+        misses: Number of results missing from the cache in this process (each
+            one extracted, so parsed, and stored)
+    """
+
+    directory: Path
+    hits: int = 0
+    misses: int = 0
+
+    @classmethod
+    def open(cls, directory: str | Path) -> LopCache:
+        """Open a cache directory, creating it if needed.
+
+        Args:
+            directory: Directory of the cache
+
+        Returns:
+            The cache
+        """
+        path = Path(directory)
+        path.mkdir(parents=True, exist_ok=True)
+        return cls(path)
+
+    def key(self, source: str, language: str, options: LopperOptions | None = None) -> str | None:
+        """Return the key of the result of extracting a source.
+
+        Args:
+            source: Source code
+            language: Programming language identifier
+            options: Extraction options (defaults to `LopperOptions()`)
+
+        Returns:
+            Hexadecimal key, or None if the options cannot be fingerprinted
+        """
+        try:
+            fingerprint = (options or LopperOptions()).fingerprint()
+        except ValueError:
+            return None
+        parts = [CACHE_FORMAT, _loppers_version(), language, fingerprint, source_hash(source)]
+        return hashlib.sha256(json.dumps(parts).encode()).hexdigest()
+
+    def get(
+        self, source: str, language: str, options: LopperOptions | None = None
+    ) -> ExtractionResult | None:
+        """Look up the result of extracting a source, counting a hit or a miss.
+
+        Args:
+            source: Source code
+            language: Programming language identifier
+            options: Extraction options (defaults to `LopperOptions()`)
+
+        Returns:
+            The cached result, or None if it is missing or unreadable
+        """
+        key = self.key(source, language, options)
+        if key is None:
+            return None
+        entry = self._entry(key)
+        try:
+            data = json.loads(entry.read_text(encoding="utf-8"))
+            result = ExtractionResult(
+                data["skeleton"],
+                [ParseDiagnostic(**diagnostic) for diagnostic in data["diagnostics"]],
+                SourceMap(
+                    [tuple(lines) for lines in data["lines"]],
+                    [tuple(lines) for lines in data["elided"]],
+                ),
+            )
+        except (OSError, ValueError, KeyError, TypeError):
+            self.misses += 1
+            return None
+        self._hit(entry)
+        return result
+
+    def get_stats(
+        self, source: str, language: str, options: LopperOptions | None = None
+    ) -> dict[str, Any] | None:
+        """Look up the statistics of a source, counting a hit or a miss.
+
+        Args:
+            source: Source code
+            language: Programming language identifier
+            options: Extraction options (defaults to `LopperOptions()`)
+
+        Returns:
+            The cached statistics (the fields of `LopStats`), or None if they are
+            missing or unreadable
+        """
+        key = self.key(source, language, options)
+        if key is None:
+            return None
+        entry = self._entry(key)
+        try:
+            stats = json.loads(entry.read_text(encoding="utf-8")).get("stats")
+        except (OSError, ValueError, AttributeError):
+            stats = None
+        if not isinstance(stats, dict):
+            self.misses += 1
+            return None
+        self._hit(entry)
+        return stats
+
+    def put(
+        self,
+        source: str,
+        language: str,
+        result: ExtractionResult,
+        options: LopperOptions | None = None,
+        *,
+        stats: Mapping[str, Any] | None = None,
+    ) -> None:
+        """Store the result of extracting a source, replacing any entry of it.
+
+        Storing is best-effort: the entry is left out when the cache directory
+        cannot be written.
+
+        Args:
+            source: Source code
+            language: Programming language identifier
+            result: Result of extracting the source with the options
+            options: Extraction options (defaults to `LopperOptions()`)
+            stats: Statistics of the source with the options (the fields of
+                `LopStats`), to store with the result
+        """
+        key = self.key(source, language, options)
+        if key is None:
+            return
+        data: dict[str, Any] = {
+            "skeleton": result.skeleton,
+            "diagnostics": [asdict(diagnostic) for diagnostic in result.diagnostics],
+            "lines": result.source_map.lines,
+            "elided": result.source_map.elided,
+        }
+        if stats is not None:
+            data["stats"] = dict(stats)
+        entry = self._entry(key)
+        temporary: str | None = None
+        try:
+            entry.parent.mkdir(parents=True, exist_ok=True)
+            with tempfile.NamedTemporaryFile(
+                "w", encoding="utf-8", dir=entry.parent, suffix=".tmp", delete=False
+            ) as file:
+                temporary = file.name
+                json.dump(data, file)
+            os.replace(temporary, entry)
+        except OSError:
+            if temporary is not None:
+                with contextlib.suppress(OSError):
+                    os.unlink(temporary)
+
+    def size(self) -> int:
+        """Return the total size of the entries, in bytes."""
+        return sum(size for _, size, _ in self._entries())
+
+    def prune(self, max_bytes: int) -> int:
+        """Remove the least recently used entries until the cache fits a size.
+
+        Args:
+            max_bytes: Largest total size of the entries to keep, in bytes
+
+        Returns:
+            Number of entries removed
+
+        Raises:
+            ValueError: If `max_bytes` is negative
+        """
+        if max_bytes < 0:
+            raise ValueError(f"max_bytes must be non-negative, got {max_bytes}")
+        entries = sorted(self._entries(), key=lambda entry: entry[2])
+        total: int = sum(size for _, size, _ in entries)
+        removed: int = 0
+        for path, size, _ in entries:
+            if total <= max_bytes:
+                break
+            with contextlib.suppress(FileNotFoundError):
+                path.unlink()
+                removed += 1
+            total -= size
+        return removed
+
+    def _hit(self, entry: Path) -> None:
+        """Count a hit on an entry, marking it as recently used."""
+        # Recently used entries are the last ones pruned
+        with contextlib.suppress(OSError):
+            os.utime(entry)
+        self.hits += 1
+
+    def _entry(self, key: str) -> Path:
+        """Return the path of the entry of a key, in a subdirectory per key prefix."""
+        return self.directory / key[:2] / f"{key}.json"
+
+    def _entries(self) -> list[tuple[Path, int, float]]:
+        """List the entries with their size and last modification time."""
+        entries: list[tuple[Path, int, float]] = []
+        for path in self.directory.glob("*/*.json"):
+            with contextlib.suppress(FileNotFoundError):
+                stat = path.stat()
+                entries.append((path, stat.st_size, stat.st_mtime))
+        return entries
+
+
+def _loppers_version() -> str:
+    """Return the installed loppers version, which results are cached under."""
+    try:
+        return version("loppers")
+    except PackageNotFoundError:
+        return "unknown"
@@ -0,0 +1,218 @@
+"""Skeletons of the symbols that changed between two versions of a source.
+
+Reviewing a change to an API needs the items it adds, removes and modifies, not
+the whole outline: both versions are parsed, their symbols are matched by
+qualified name, and only the items that differ are lopped and reported.
+"""
+
+from __future__ import annotations
+
+from bisect import bisect_right
+from collections import Counter
+from dataclasses import dataclass, field
+from typing import Literal
+
+from tree_sitter import Node
+
+from loppers.loppers import (
+    LopperOptions,
+    SkeletonExtractor,
+    get_extractor,
+    is_hidden,
+    item_span,
+    line_starts_of,
+)
+from loppers.symbol_model import Symbol, symbol_nodes
+
+# How a symbol changed between two versions of a source
+ChangeKind = Literal["added", "removed", "modified", "body_changed"]
+
+# Identity of a symbol across versions: its kind, qualified name and trait, and how
+# many symbols with the same ones come before it
+_SymbolKey = tuple[str, str, str | None, int]
+
+
+@dataclass(frozen=True)
+class SymbolChange:
+    """A symbol that differs between two versions of a source.
+
+    Attributes:
+        kind: "added" or "removed" for symbols found in one version only;
+            "modified" when the signature changed, or the doc comments and
+            attributes above it; "body_changed" when only the rest of its
+            definition changed, such as the statements of a function or the fields
+            of a struct (changes of nested symbols are reported apart)
+        symbol: The symbol in the new source, or in the old one when removed
+        old_symbol: Its match in the old source; None when added or removed
+        skeleton: Lines of the skeleton of the source `symbol` is from that the
+            item spans, with its doc comments and attributes
+    """
+
+    kind: ChangeKind
+    symbol: Symbol
+    old_symbol: Symbol | None = None
+    skeleton: str = ""
+
+
+@dataclass(frozen=True)
+class DiffSkeleton:
+    """The symbols that changed between two versions of a source.
+
+    Attributes:
+        changes: Changes of the symbols of the new source, in its source order,
+            followed by the removed symbols, in the old source's order
+        skeleton: The skeleton of each change under a line comment naming its kind
+            and qualified name (`// modified: Calculator::add`), separated by
+            blank lines
+    """
+
+    changes: list[SymbolChange] = field(default_factory=list)
+    skeleton: str = ""
+
+
+@dataclass
+class _Version:
+    """The symbols of one version of a source, keyed across versions."""
+
+    symbols: dict[_SymbolKey, tuple[Symbol, Node]]
+    parents: dict[_SymbolKey, _SymbolKey | None]
+    nodes: dict[int, Node]
+    source: bytes
+    line_starts: list[int]
+    skeleton_lines: list[str]
+    source_lines: list[tuple[int, int]]
+
+
+def skeletonize_diff(
+    old_source: str,
+    new_source: str,
+    language: str,
+    *,
+    options: LopperOptions | None = None,
+    body_changes: bool = True,
+) -> DiffSkeleton:
+    """Extract the skeletons of the symbols that changed between two versions of a source.
+
+    Symbols are matched by kind, qualified name and trait and, among symbols with
+    the same ones, by order. Symbols nested in an added or removed one are not
+    reported apart, and changes of whitespace alone are ignored. Symbols the
+    `visibility` option hides are left out.
+
+    Args:
+        old_source: Source before the change
+        new_source: Source after the change
+        language: Programming language identifier
+        options: Extraction options of the skeletons (defaults to `LopperOptions()`)
+        body_changes: Report symbols whose signature is unchanged but whose body
+            changed, as "body_changed"
+
+    Returns:
+        The changes and their rendered skeleton
+
+    Raises:
+        UnsupportedLanguageError: If the language is not supported or has no symbol query
+        ParseFailedError: If a source has syntax errors and `on_error` is "fail"
+    """
+    extractor = get_extractor(language, options)
+    old, new = _version(extractor, old_source), _version(extractor, new_source)
+
+    changes: list[SymbolChange] = []
+    for key, (symbol, node) in new.symbols.items():
+        parent = new.parents[key]
+        if key not in old.symbols:
+            if parent is None or parent in old.symbols:
+                changes.append(SymbolChange("added", symbol, None, _skeleton(new, node)))
+            continue
+        old_symbol, old_node = old.symbols[key]
+        if _header(old, old_symbol, old_node) != _header(new, symbol, node):
+            kind: ChangeKind = "modified"
+        elif body_changes and _body(old, old_symbol, old_node) != _body(new, symbol, node):
+            kind = "body_changed"
+        else:
+            continue
+        changes.append(SymbolChange(kind, symbol, old_symbol, _skeleton(new, node)))
+    for key, (symbol, node) in old.symbols.items():
+        parent = old.parents[key]
+        if key not in new.symbols and (parent is None or parent in new.symbols):
+            changes.append(SymbolChange("removed", symbol, None, _skeleton(old, node)))
+
+    changes = [
+        change
+        for change in changes
+        if not is_hidden(change.symbol.visibility, extractor.options.visibility)
+    ]
+    comment = extractor.config.line_comment
+    blocks = [
+        f"{comment} {change.kind}: {change.symbol.qualified_name}\n{change.skeleton}"
+        for change in changes
+    ]
+    return DiffSkeleton(changes, "\n\n".join(blocks))
+
+
+def _version(extractor: SkeletonExtractor, text: str) -> _Version:
+    """Parse one version of a source, keying its symbols and extracting its skeleton."""
+    source = text.encode()
+    tree = extractor.parse(source)
+    pairs = symbol_nodes(extractor, tree, source)
+    nested = {id(child) for symbol, _ in pairs for child in symbol.children}
+    nodes = {id(symbol): node for symbol, node in pairs}
+    seen: Counter[tuple[str, str, str | None]] = Counter()
+    symbols: dict[_SymbolKey, tuple[Symbol, Node]] = {}
+    parents: dict[_SymbolKey, _SymbolKey | None] = {}
+
+    def visit(symbol: Symbol, parent: _SymbolKey | None) -> None:
+        identity = (symbol.kind, symbol.qualified_name, symbol.trait)
+        key = (*identity, seen[identity])
+        seen[identity] += 1
+        symbols[key], parents[key] = (symbol, nodes[id(symbol)]), parent
+        for child in symbol.children:
+            visit(child, key)
+
+    for symbol, _ in pairs:
+        if id(symbol) not in nested:
+            visit(symbol, None)
+    result = extractor.extract_result(text, tree=tree)
+    return _Version(
+        symbols,
+        parents,
+        nodes,
+        source,
+        line_starts_of(source),
+        result.skeleton.splitlines(),
+        result.source_map.lines,
+    )
+
+
+def _header(version: _Version, symbol: Symbol, node: Node) -> str:
+    """Return the signature of a symbol with the doc comments and attributes above it."""
+    start, _ = item_span(node, version.source, version.line_starts)
+    trivia = version.source[start : node.start_byte].decode(errors="replace")
+    return " ".join(f"{trivia} {symbol.signature}".split())
+
+
+def _body(version: _Version, symbol: Symbol, node: Node) -> bytes:
+    """Return the definition text of a symbol past its signature, without nested symbols.
+
+    Whitespace is left out, so reformatting, and the blank lines around an added
+    nested symbol, do not count as a change.
+    """
+    parts: list[bytes] = []
+    position = symbol.signature_end_byte
+    for child in symbol.children:
+        start, end = item_span(version.nodes[id(child)], version.source, version.line_starts)
+        parts.append(version.source[position:start])
+        position = max(position, end)
+    parts.append(version.source[position : node.end_byte])
+    return b"".join(b"".join(parts).split())
+
+
+def _skeleton(version: _Version, node: Node) -> str:
+    """Return the skeleton lines coming from the lines of an item."""
+    start, end = item_span(node, version.source, version.line_starts)
+    first = bisect_right(version.line_starts, start)
+    last = bisect_right(version.line_starts, max(end - 1, start))
+    return "\n".join(
+        line
+        for line, (low, high) in zip(version.skeleton_lines, version.source_lines)
+        if first <= low and high <= last
+    )
@@ -3,12 +3,50 @@
 from __future__ import annotations
 
 import argparse
+import os
 import sys
+from dataclasses import fields
 from importlib.metadata import PackageNotFoundError, version
 from pathlib import Path
+from typing import Any
 
-from loppers import concatenate_files, extract_skeleton, find_files, get_skeleton, get_tree
-from loppers.extensions import get_language
+from loppers import (
+    LopCache,
+    LoppedFile,
+    LopperOptions,
+    StatsReport,
+    Symbol,
+    UnsupportedLanguageError,
+    collect_stats,
+    concatenate_files,
+    detect_file_language,
+    extract_skeleton_result,
+    extract_symbols,
+    find_files,
+    fit_files_to_budget,
+    fit_to_budget,
+    get_skeleton,
+    get_skeletons,
+    get_tree,
+    git_diff_line_ranges,
+    highlight,
+    load_config,
+    lop_stats,
+    lop_symbol,
+    lop_with_focus,
+    render_bundle,
+    render_ctags,
+    render_outline,
+    render_repo_map,
+    serve,
+    sort_symbols,
+    symbols_to_json,
+    walk_dir,
+)
+from loppers.bundle import REPO_MAP_HEADER
+from loppers.extensions import detect_language
+from loppers.highlight import paint
+from loppers.symbols import is_test_file
 
 
 def get_app_version() -> str:
@@ -40,6 +78,20 @@ def add_shared_args(parser: argparse.ArgumentParser) -> None:
         help="Add custom ignore pattern (gitignore syntax, can be used multiple times)",
     )
 
+    parser.add_argument(
+        "--include",
+        action="append",
+        metavar="PATTERN",
+        help="Only process files matching PATTERN, a glob such as 'src/**/*.rs' (repeatable)",
+    )
+
+    parser.add_argument(
+        "--exclude",
+        action="append",
+        metavar="PATTERN",
+        help="Skip files matching PATTERN, a glob such as '**/tests/**' (repeatable)",
+    )
+
     parser.add_argument(
         "--no-default-ignore",
         action="store_true",
@@ -49,7 +101,13 @@ def add_shared_args(parser: argparse.ArgumentParser) -> None:
     parser.add_argument(
         "--no-gitignore",
         action="store_true",
-        help="Don't respect .gitignore file",
+        help="Don't respect .gitignore/.ignore files and git's global excludes",
+    )
+
+    parser.add_argument(
+        "--no-ignore",
+        action="store_true",
+        help="Include everything: disable ignore files and built-in ignore patterns",
     )
 
     parser.add_argument(
@@ -79,10 +137,574 @@ def add_shared_args(parser: argparse.ArgumentParser) -> None:
     )
 
 
+def add_extraction_args(parser: argparse.ArgumentParser) -> None:
+    """Add arguments controlling skeleton extraction."""
+    parser.add_argument(
+        "--config",
+        metavar="PATH",
+        help="Read default options from PATH instead of the loppers.toml or .loppers.toml "
+        "found from the processed path up (given flags always win)",
+    )
+    parser.add_argument(
+        "--no-config",
+        action="store_true",
+        help="Don't read default options from a config file",
+    )
+    parser.add_argument(
+        "--no-doc-comments",
+        action="store_false",
+        dest="keep_doc_comments",
+        default=None,
+        help="Drop doc comments and docstrings from skeletons",
+    )
+    parser.add_argument(
+        "--no-attributes",
+        action="store_false",
+        dest="keep_attributes",
+        default=None,
+        help="Drop attributes, annotations and decorators from skeletons",
+    )
+    parser.add_argument(
+        "--max-attr-len",
+        type=int,
+        metavar="N",
+        help="Replace the arguments of attributes longer than N characters with (...)",
+    )
+    parser.add_argument(
+        "--max-signature-len",
+        type=int,
+        metavar="N",
+        help="Shorten function signatures longer than N characters by collapsing where "
+        "clauses and cutting trailing parameters",
+    )
+    parser.add_argument(
+        "--max-body-lines",
+        type=int,
+        metavar="N",
+        help="Keep function bodies spanning at most N lines",
+    )
+    parser.add_argument(
+        "--body-head-lines",
+        type=int,
+        metavar="N",
+        help="Keep the leading statements fitting in the first N lines of each body",
+    )
+    parser.add_argument(
+        "--placeholders",
+        action="store_true",
+        dest="body_placeholders",
+        default=None,
+        help="Replace elided bodies with a placeholder showing how many lines were removed",
+    )
+    parser.add_argument(
+        "--placeholder",
+        metavar="TEXT",
+        help="Replace elided bodies with TEXT ({lines} and {count} expand to the line count)",
+    )
+    parser.add_argument(
+        "--placeholder-comment",
+        metavar="TEXT",
+        help="Replace elided bodies with a comment holding TEXT, in each language's syntax",
+    )
+    parser.add_argument(
+        "--body-braces",
+        choices=["inline", "block"],
+        help="Put the placeholder of elided brace-delimited bodies between the braces "
+        "(default), or on its own line",
+    )
+    parser.add_argument(
+        "--annotate-sizes",
+        action="store_true",
+        default=None,
+        help="End the header line of each elided body with a comment counting its lines",
+    )
+    parser.add_argument(
+        "--annotate-complexity",
+        action="store_true",
+        default=None,
+        help="Also count the branches of each elided body (requires --annotate-sizes)",
+    )
+    parser.add_argument(
+        "--public-only",
+        action="store_true",
+        help="Emit only the public API (shorthand for --visibility public)",
+    )
+    parser.add_argument(
+        "--visibility",
+        choices=["all", "crate", "public"],
+        help="Drop items less visible than this: private ones (crate) or crate-visible too",
+    )
+    parser.add_argument(
+        "--prototypes",
+        action="store_true",
+        default=None,
+        help="Turn C/C++ function definitions into prototypes, like a header",
+    )
+    parser.add_argument(
+        "--stub-mode",
+        action="store_true",
+        default=None,
+        help="Keep Rust skeletons compiling: bodies become '{ unimplemented!() }'",
+    )
+    parser.add_argument(
+        "--keep-body",
+        action="append",
+        dest="keep_bodies",
+        metavar="PATTERN",
+        help="Keep the bodies of items matching PATTERN: a name, a path such as "
+        "Calculator::add or a glob (repeatable)",
+    )
+    parser.add_argument(
+        "--max-depth",
+        type=int,
+        metavar="N",
+        help="Keep items nested at most N deep (0 for top-level items only)",
+    )
+    parser.add_argument(
+        "--collapse-types",
+        action="store_true",
+        default=None,
+        help="Elide type bodies (struct fields, enum variants, interface members)",
+    )
+    parser.add_argument(
+        "--struct-fields",
+        choices=["keep", "collapse", "public"],
+        help="Keep struct fields (default), replace each field list with a count, or keep "
+        "only public fields",
+    )
+    parser.add_argument(
+        "--enum-variants",
+        choices=["keep", "collapse"],
+        help="Keep enum variants (default), or replace each variant list with a count",
+    )
+    parser.add_argument(
+        "--collapse-known-impls",
+        action="store_true",
+        default=None,
+        help="Collapse Rust trait impls made of methods only to one line",
+    )
+    parser.add_argument(
+        "--collapse-impl-trait",
+        action="append",
+        dest="collapse_impl_traits",
+        metavar="PATTERN",
+        help="Collapse the impls of traits matching PATTERN, a name or a glob (repeatable)",
+    )
+    parser.add_argument(
+        "--keep-impl-trait",
+        action="append",
+        dest="keep_impl_traits",
+        metavar="PATTERN",
+        help="Never collapse the impls of traits matching PATTERN (repeatable)",
+    )
+    parser.add_argument(
+        "--keep-macro-rules",
+        action="store_true",
+        default=None,
+        help="Keep the rules of Rust macro_rules! definitions instead of eliding them",
+    )
+    parser.add_argument(
+        "--imports",
+        choices=["keep", "drop", "collapse"],
+        help="Keep import statements (default), drop them, or collapse each group of them",
+    )
+    parser.add_argument(
+        "--keep-section-comments",
+        action="store_true",
+        default=None,
+        help="Keep section comments such as '// ---- Parsing ----' above dropped items",
+    )
+    parser.add_argument(
+        "--section-comment-pattern",
+        metavar="REGEX",
+        help="Tell section comments apart by REGEX, searched in the comment text",
+    )
+    parser.add_argument(
+        "--strip-tests",
+        action="store_true",
+        default=None,
+        help="Remove test code (#[cfg(test)], test_ functions, describe/it blocks)",
+    )
+    parser.add_argument(
+        "--skip-tests",
+        action="store_true",
+        default=None,
+        help="In the JSON or sorted outline and ctags, leave out test items and the symbols "
+        "nested in them, and in the ctags of a directory the files of __tests__ directories",
+    )
+    parser.add_argument(
+        "--empty-containers",
+        choices=["keep", "drop", "collapse"],
+        help="Keep the modules, impl blocks and classes whose items were all filtered out "
+        "(default), drop them, or collapse each onto one line",
+    )
+    parser.add_argument(
+        "--dedup-symbols",
+        choices=["off", "signature", "name"],
+        help="Collapse runs of items repeating the one before them, by signature or by "
+        "name, into it and a '+N similar' note (default: off)",
+    )
+    parser.add_argument(
+        "--redact-literals",
+        action="store_true",
+        default=None,
+        help='Replace string literals with "\u2026" and numbers with 0 in kept code',
+    )
+    parser.add_argument(
+        "--max-literal-len",
+        type=int,
+        metavar="N",
+        help="Truncate array, map and string literals longer than N bytes in kept code",
+    )
+    parser.add_argument(
+        "--expand-aliases",
+        action="store_true",
+        default=None,
+        help="Replace references to type aliases declared in the same file with their "
+        "definitions, outside bodies (experimental, best-effort)",
+    )
+    parser.add_argument(
+        "--line-numbers",
+        action="store_true",
+        default=None,
+        help="Prefix each line with its line number in the source file",
+    )
+    parser.add_argument(
+        "--indent-width",
+        type=int,
+        metavar="N",
+        help="Re-indent the skeleton with N spaces per nesting level of its symbols",
+    )
+    parser.add_argument(
+        "--sort",
+        choices=["source", "alphabetical"],
+        help="Order items as in the source (default), or alphabetically at each level",
+    )
+    parser.add_argument(
+        "--on-error",
+        choices=["passthrough", "drop", "fail"],
+        help="Keep regions with syntax errors verbatim (default), drop them, or fail",
+    )
+    parser.add_argument(
+        "--notebook-format",
+        choices=["text", "json"],
+        help="Render Jupyter notebooks as text with a line per cell (default), or as "
+        "cleaned notebook JSON",
+    )
+
+
+def explicit_options(args: argparse.Namespace) -> dict[str, Any]:
+    """Return the extraction options given on the command line, by option name."""
+    given = {option.name: getattr(args, option.name, None) for option in fields(LopperOptions)}
+    if args.public_only:
+        given["visibility"] = "public"
+    return {name: value for name, value in given.items() if value is not None}
+
+
+def build_options(args: argparse.Namespace) -> LopperOptions:
+    """Build extraction options from parsed arguments, over the config applying to them.
+
+    The config file is the one passed with --config, or else the one found from the
+    processed path up (none with --no-config). Exits if it is missing or invalid.
+    """
+    path = getattr(args, "file", None) or getattr(args, "root", None)
+    try:
+        config = load_config(
+            path if path not in (None, "-") else ".",
+            config_path=args.config,
+            discover=not args.no_config,
+        )
+        return config.resolve(explicit_options(args))
+    except (OSError, ValueError) as e:
+        print(f"Error: {e}", file=sys.stderr)
+        sys.exit(1)
+
+
+def markdown_bundle(files: list[tuple[str, str]], *, root: str | Path | None = None) -> str:
+    """Render (path, skeleton) pairs as Markdown, fenced in the language each is parsed as."""
+    root_path = Path(root) if root is not None else Path()
+    return render_bundle(
+        [LoppedFile(path, text, detect_file_language(root_path / path)) for path, text in files],
+        format="markdown",
+        header=REPO_MAP_HEADER,
+    )
+
+
+def use_color(args: argparse.Namespace) -> bool:
+    """Whether to color the text printed to stdout, as `--color` and `NO_COLOR` ask."""
+    if args.color == "auto":
+        return not os.environ.get("NO_COLOR") and sys.stdout.isatty()
+    return args.color == "always"
+
+
+def cmd_extract_dir(args: argparse.Namespace) -> None:
+    """Extract skeletons from every supported file in a directory."""
+    if args.max_tokens is not None:
+        cmd_extract_dir_budget(args)
+        return
+
+    cache = LopCache.open(args.cache_dir) if args.cache_dir else None
+    try:
+        report = get_skeletons(
+            args.file,
+            use_default_ignore=not args.no_ignore,
+            respect_gitignore=not args.no_ignore,
+            include=args.include,
+            exclude=args.exclude,
+            options=build_options(args),
+            max_workers=args.jobs or None,
+            cache=cache,
+        )
+    except Exception as e:
+        print(f"Error: {e}", file=sys.stderr)
+        sys.exit(1)
+
+    if args.diff:
+        try:
+            focus = git_diff_line_ranges(args.file, args.diff)
+        except ValueError as e:
+            print(f"Error: {e}", file=sys.stderr)
+            sys.exit(1)
+        report.skeletons = [
+            (path, focused_skeleton(Path(args.file) / path, focus.get(path), skeleton, args))
+            for path, skeleton in report.skeletons
+        ]
+
+    if args.repo_map:
+        result = render_repo_map(
+            report, args.file, options=build_options(args), format=args.format
+        )
+    elif args.format == "markdown":
+        result = markdown_bundle(report.skeletons, root=args.file)
+    elif not (args.output or args.out_dir) and use_color(args):
+        result = "\n\n".join(
+            f"{paint(f'--- {path}', 'header')}\n"
+            f"{highlight(skeleton, detect_file_language(Path(args.file) / path) or '')}"
+            for path, skeleton in report.skeletons
+        )
+    else:
+        result = "\n\n".join(f"--- {path}\n{skeleton}" for path, skeleton in report.skeletons)
+
+    if args.verbose:
+        for path, error in report.failed:
+            print(f"⚠ Could not process {path}: {error}", file=sys.stderr)
+        for path, count in report.syntax_errors:
+            print(f"⚠ {path}: {count} syntax errors", file=sys.stderr)
+        print(
+            f"ℹ {report.processed} processed, {len(report.skipped)} skipped, "  # noqa: RUF001
+            f"{len(report.failed)} failed",
+            file=sys.stderr,
+        )
+
+    # Output result
+    if args.out_dir:
+        written, unchanged = write_skeleton_tree(report.skeletons, Path(args.out_dir))
+        if args.verbose:
+            print(
+                f"✓ Written {written} file{'s' if written != 1 else ''} to {args.out_dir} "
+                f"({unchanged} unchanged)",
+                file=sys.stderr,
+            )
+    elif args.output:
+        Path(args.output).write_text(result, encoding="utf-8")
+        if args.verbose:
+            print(f"✓ Written to {args.output}", file=sys.stderr)
+    else:
+        print(result)
+
+    if args.stats:
+        paths = [path for path, _ in report.skeletons] + [path for path, _ in report.failed]
+        stats = collect_stats(
+            sorted(paths), root=args.file, options=build_options(args), cache=cache
+        )
+        stats.skipped.extend(report.skipped)
+        print(f"{stats.table()}\n\n{stats.census()}", file=sys.stderr)
+
+
+def write_skeleton_tree(skeletons: list[tuple[str, str]], out_dir: Path) -> tuple[int, int]:
+    """Write skeletons to the same relative paths under a directory.
+
+    Files whose content is already the skeleton are left alone, keeping their
+    modification times.
+
+    Returns:
+        Number of files written, and of files left unchanged
+    """
+    written = unchanged = 0
+    for path, skeleton in skeletons:
+        target = out_dir / path
+        content = f"{skeleton}\n" if skeleton else ""
+        if target.is_file() and target.read_text(encoding="utf-8", errors="replace") == content:
+            unchanged += 1
+            continue
+        target.parent.mkdir(parents=True, exist_ok=True)
+        target.write_text(content, encoding="utf-8")
+        written += 1
+    return written, unchanged
+
+
+def focused_skeleton(
+    path: Path, focus: list[tuple[int, int]] | None, skeleton: str, args: argparse.Namespace
+) -> str:
+    """Re-extract the skeleton of a file a diff touches, keeping the changed items whole."""
+    if not focus:
+        return skeleton
+    try:
+        source = path.read_text(encoding="utf-8")
+        return lop_with_focus(
+            source, detect_file_language(path) or "", focus, options=build_options(args)
+        )
+    except (OSError, UnicodeDecodeError, UnsupportedLanguageError):
+        # Documents and notebooks are not focused
+        return skeleton
+
+
+def cmd_extract_dir_budget(args: argparse.Namespace) -> None:
+    """Summarize every supported file in a directory within a token budget."""
+    try:
+        files = list(
+            walk_dir(
+                args.file,
+                use_default_ignore=not args.no_ignore,
+                respect_gitignore=not args.no_ignore,
+                include=args.include,
+                exclude=args.exclude,
+            )
+        )
+        report = fit_files_to_budget(
+            files, args.max_tokens, root=args.file, options=build_options(args)
+        )
+    except Exception as e:
+        print(f"Error: {e}", file=sys.stderr)
+        sys.exit(1)
+
+    if args.verbose:
+        for file in report.files:
+            detail = f" (bodies over {file.max_body_lines} lines)" if file.level == "elided" else ""
+            print(f"ℹ {file.path}: {file.level}{detail}", file=sys.stderr)  # noqa: RUF001
+        print(f"ℹ ~{report.tokens} tokens", file=sys.stderr)  # noqa: RUF001
+
+    if args.format == "markdown":
+        included = [(file.path, file.text) for file in report.files if file.level != "dropped"]
+        result = markdown_bundle(included, root=args.file)
+    else:
+        result = report.text
+
+    if args.output:
+        Path(args.output).write_text(result, encoding="utf-8")
+        if args.verbose:
+            print(f"✓ Written to {args.output}", file=sys.stderr)
+    else:
+        print(result)
+
+
+def cmd_extract_tags(args: argparse.Namespace) -> None:
+    """Write a ctags tag file of the symbols of a file or a directory."""
+    if not args.file or args.file == "-":
+        print("Error: --format ctags requires FILE (a file or a directory)", file=sys.stderr)
+        sys.exit(1)
+    root = Path(args.file)
+    try:
+        if root.is_dir():
+            files = [
+                (root / path).as_posix()
+                for path in walk_dir(
+                    root,
+                    use_default_ignore=not args.no_ignore,
+                    respect_gitignore=not args.no_ignore,
+                    include=args.include,
+                    exclude=args.exclude,
+                )
+            ]
+        elif root.is_file():
+            files = [root.as_posix()]
+        else:
+            raise FileNotFoundError(f"File not found: {root}")
+    except Exception as e:
+        print(f"Error: {e}", file=sys.stderr)
+        sys.exit(1)
+
+    options = build_options(args)
+    if options.skip_tests:
+        files = [path for path in files if not is_test_file(path)]
+    symbols: dict[str, list[Symbol]] = {}
+    languages: dict[str, str] = {}
+    sources: dict[str, str] = {}
+    for path in files:
+        language = args.language or detect_file_language(path)
+        try:
+            source = Path(path).read_text(encoding="utf-8")
+            symbols[path] = extract_symbols(source, language or "", options=options)
+        except (OSError, UnicodeDecodeError, ValueError) as e:
+            # Files that cannot be read and languages without symbol support define no tags
+            if args.verbose:
+                print(f"⚠ Could not process {path}: {e}", file=sys.stderr)
+            continue
+        languages[path] = language or ""
+        sources[path] = source
+
+    result = render_ctags(symbols, languages=languages, sources=sources)
+    if args.output:
+        Path(args.output).write_text(result, encoding="utf-8")
+        if args.verbose:
+            print(f"✓ Written to {args.output}", file=sys.stderr)
+    else:
+        print(result, end="")
+
+
 def cmd_extract(args: argparse.Namespace) -> None:
-    """Extract skeleton from a file or stdin."""
+    """Extract skeleton from a file, a directory or stdin."""
+    for flag, given in (
+        ("--stats", args.stats),
+        ("--repo-map", args.repo_map),
+        ("--diff", args.diff),
+        ("--symbol", args.symbol),
+    ):
+        if given and (args.format in ("json", "outline", "ctags") or args.max_tokens is not None):
+            print(
+                f"Error: {flag} cannot be combined with --format json, outline or ctags, "
+                "or --max-tokens",
+                file=sys.stderr,
+            )
+            sys.exit(1)
+    if args.out_dir:
+        for flag, given in (
+            ("--output", bool(args.output)),
+            ("--repo-map", args.repo_map),
+            ("--max-tokens", args.max_tokens is not None),
+            (f"--format {args.format}", args.format != "text"),
+        ):
+            if given:
+                print(f"Error: --out-dir cannot be combined with {flag}", file=sys.stderr)
+                sys.exit(1)
+        if not (args.file and Path(args.file).is_dir()):
+            print("Error: --out-dir requires a directory", file=sys.stderr)
+            sys.exit(1)
+    if args.format == "ctags":
+        cmd_extract_tags(args)
+        return
+    if args.file and Path(args.file).is_dir():
+        for flag, given in (
+            ("--symbol", args.symbol),
+            (f"--format {args.format}", args.format in ("json", "outline")),
+        ):
+            if given:
+                print(f"Error: {flag} requires a file, not a directory", file=sys.stderr)
+                sys.exit(1)
+        cmd_extract_dir(args)
+        return
+    if args.repo_map:
+        print("Error: --repo-map requires a directory", file=sys.stderr)
+        sys.exit(1)
+    if args.symbol and args.diff:
+        print("Error: --symbol cannot be combined with --diff", file=sys.stderr)
+        sys.exit(1)
+    if args.diff and (not args.file or args.file == "-"):
+        print("Error: --diff requires FILE (a file or a directory)", file=sys.stderr)
+        sys.exit(1)
+
     # Determine input source
-    if args.file:
+    if args.file and args.file != "-":
         file_path = Path(args.file)
         if not file_path.is_file():
             print(f"Error: File not found: {file_path}", file=sys.stderr)
@@ -97,39 +719,96 @@ def cmd_extract(args: argparse.Namespace) -> None:
         if args.language:
             language = args.language
         else:
-            language = get_language(str(file_path.suffix))
+            language = detect_language(file_path, source, sniff=True)
             if not language:
                 print(
-                    f"Error: Could not auto-detect language from {file_path.suffix}",
+                    f"Error: Could not auto-detect language of {file_path.name}",
                     file=sys.stderr,
                 )
                 sys.exit(1)
     else:
         # Read from stdin
+        if not args.file and sys.stdin.isatty():
+            print("Error: No input (pass FILE, or pipe source code to stdin)", file=sys.stderr)
+            sys.exit(1)
         source = sys.stdin.read()
-        if not args.language:
+        language = args.language or detect_language(None, source, sniff=True)
+        if not language:
             print(
-                "Error: Language required when reading from stdin (use -l/--language)",
+                "Error: Could not auto-detect the language of stdin (use -l/--lang)",
                 file=sys.stderr,
             )
             sys.exit(1)
-        language = args.language
 
-    # Extract skeleton
+    # Extract skeleton (or symbol outline)
     try:
-        skeleton = extract_skeleton(source, language)
+        if args.format == "json":
+            outline = extract_symbols(
+                source,
+                language,
+                group_impls=args.group_impls,
+                keep_closures=args.keep_closures,
+                options=build_options(args),
+            )
+            if build_options(args).sort == "alphabetical":
+                outline = sort_symbols(outline)
+            skeleton = symbols_to_json(outline)
+        elif args.format == "outline":
+            outline = extract_symbols(
+                source, language, keep_closures=args.keep_closures, options=build_options(args)
+            )
+            skeleton = render_outline(outline)
+        elif args.max_tokens is not None:
+            budgeted = fit_to_budget(
+                source, language, args.max_tokens, options=build_options(args)
+            )
+            skeleton = budgeted.skeleton
+            if args.verbose:
+                print(
+                    f"ℹ ~{budgeted.tokens} tokens, "  # noqa: RUF001
+                    f"{budgeted.dropped} symbols dropped",
+                    file=sys.stderr,
+                )
+        elif args.symbol:
+            skeleton = lop_symbol(source, language, args.symbol, options=build_options(args))
+        elif args.diff:
+            focus = git_diff_line_ranges(args.file, args.diff).get(Path(args.file).name, [])
+            skeleton = lop_with_focus(source, language, focus, options=build_options(args))
+        else:
+            extraction = extract_skeleton_result(source, language, options=build_options(args))
+            skeleton = extraction.skeleton
+            if args.verbose:
+                for diagnostic in extraction.diagnostics:
+                    message = f"⚠ Line {diagnostic.start_line}: {diagnostic.message}"
+                    print(message, file=sys.stderr)
     except ValueError as e:
         print(f"Error: {e}", file=sys.stderr)
         sys.exit(1)
 
+    if args.format == "markdown":
+        shown_path = args.file if args.file not in (None, "-") else "<stdin>"
+        skeleton = render_bundle(
+            [LoppedFile(shown_path, skeleton, language)],
+            format="markdown",
+            header=REPO_MAP_HEADER,
+        )
+
     # Output result
     if args.output:
         Path(args.output).write_text(skeleton, encoding="utf-8")
         if args.verbose:
             print(f"✓ Written to {args.output}", file=sys.stderr)
+    elif args.format == "text" and use_color(args):
+        print(highlight(skeleton, language))
     else:
         print(skeleton)
 
+    if args.stats:
+        stats = lop_stats(source, language, options=build_options(args))
+        path = args.file or "-"
+        report = StatsReport([(path, stats)], languages={path: language})
+        print(f"{report.table()}\n\n{report.census()}", file=sys.stderr)
+
 
 def cmd_concatenate(args: argparse.Namespace) -> None:
     """Concatenate files with optional skeleton extraction."""
@@ -141,8 +820,10 @@ def cmd_concatenate(args: argparse.Namespace) -> None:
             root_path,
             recursive=not args.no_recursive,
             ignore_patterns=args.ignore_patterns,
-            use_default_ignore=not args.no_default_ignore,
-            respect_gitignore=not args.no_gitignore,
+            use_default_ignore=not (args.no_default_ignore or args.no_ignore),
+            respect_gitignore=not (args.no_gitignore or args.no_ignore),
+            include=args.include,
+            exclude=args.exclude,
         )
     except Exception as e:
         print(f"Error: {e}", file=sys.stderr)
@@ -152,6 +833,8 @@ def cmd_concatenate(args: argparse.Namespace) -> None:
         print("Warning: No files found", file=sys.stderr)
         sys.exit(1)
 
+    options = build_options(args)
+
     # Log verbose information for each file
     if args.verbose:
         for relative_file_path in files:
@@ -162,17 +845,16 @@ def cmd_concatenate(args: argparse.Namespace) -> None:
                 else:
                     # Check if file can be extracted
                     try:
-                        get_skeleton(full_file_path, add_header=False)
+                        get_skeleton(full_file_path, add_header=False, options=options)
                         print(f"✓ Extracted skeleton from {relative_file_path}", file=sys.stderr)
+                    except UnsupportedLanguageError:
+                        msg = (
+                            f"ℹ Included {relative_file_path} "  # noqa: RUF001
+                            "(unsupported type, no extraction)"
+                        )
+                        print(msg, file=sys.stderr)
                     except ValueError as e:
-                        if "Unsupported file type" in str(e):
-                            msg = (
-                                f"ℹ Included {relative_file_path} "  # noqa: RUF001
-                                "(unsupported type, no extraction)"
-                            )
-                            print(msg, file=sys.stderr)
-                        else:
-                            print(f"⚠ Could not process {relative_file_path}: {e}", file=sys.stderr)
+                        print(f"⚠ Could not process {relative_file_path}: {e}", file=sys.stderr)
             except Exception as e:
                 print(f"⚠ Could not process {relative_file_path}: {e}", file=sys.stderr)
 
@@ -183,6 +865,7 @@ def cmd_concatenate(args: argparse.Namespace) -> None:
             files,
             extract=not args.no_extract,
             ignore_not_found=True,  # Ignore any files that can't be processed
+            options=options,
         )
     except Exception as e:
         print(f"Error: {e}", file=sys.stderr)
@@ -204,8 +887,10 @@ def cmd_tree(args: argparse.Namespace) -> None:
             args.root,
             recursive=not args.no_recursive,
             ignore_patterns=args.ignore_patterns,
-            use_default_ignore=not args.no_default_ignore,
-            respect_gitignore=not args.no_gitignore,
+            use_default_ignore=not (args.no_default_ignore or args.no_ignore),
+            respect_gitignore=not (args.no_gitignore or args.no_ignore),
+            include=args.include,
+            exclude=args.exclude,
             collapse_single_dirs=args.collapse_single_dirs,
             show_sizes=args.show_sizes,
         )
@@ -233,8 +918,10 @@ def cmd_files(args: argparse.Namespace) -> None:
             args.root,
             recursive=not args.no_recursive,
             ignore_patterns=args.ignore_patterns,
-            use_default_ignore=not args.no_default_ignore,
-            respect_gitignore=not args.no_gitignore,
+            use_default_ignore=not (args.no_default_ignore or args.no_ignore),
+            respect_gitignore=not (args.no_gitignore or args.no_ignore),
+            include=args.include,
+            exclude=args.exclude,
         )
     except Exception as e:
         print(f"Error: {e}", file=sys.stderr)
@@ -256,6 +943,14 @@ def cmd_files(args: argparse.Namespace) -> None:
         print(result)
 
 
+def cmd_serve(args: argparse.Namespace) -> None:
+    """Answer extraction requests read from stdin, one JSON object per line."""
+    if args.jobs < 0:
+        print("Error: --jobs must not be negative", file=sys.stderr)
+        sys.exit(1)
+    serve(sys.stdin, sys.stdout, max_workers=args.jobs or None, options=build_options(args))
+
+
 def main() -> None:
     """Main CLI entry point."""
     parser = argparse.ArgumentParser(
@@ -275,36 +970,158 @@ def main() -> None:
     # Extract command
     extract_parser = subparsers.add_parser(
         "extract",
-        help="Extract skeleton from file or stdin",
+        help="Extract skeleton from file, directory or stdin",
         formatter_class=argparse.RawDescriptionHelpFormatter,
         epilog="""
 Examples:
   loppers extract file.py                         # From file
+  loppers extract src/                            # Every supported file in a directory
   cat file.py | loppers extract -l python         # From stdin
+  cat file.py | loppers extract                   # Language guessed from the code
   echo 'def foo(): pass' | loppers extract -l python
+  loppers extract --lang rust - < snippet.txt     # "-" reads stdin too
+  loppers extract --lang rust snippet.txt         # Override language detection
+  loppers extract --format json file.rs           # JSON symbol outline
+  loppers extract --format outline file.rs        # Signatures grouped and sorted
+  loppers extract --format markdown src/          # Fenced code blocks per file
+  loppers extract --format ctags src/ -o tags     # Tag file for editors
+  loppers extract --diff HEAD~1 src/              # Changed items in full, the rest lopped
+  loppers extract --symbol Calculator lib.rs      # One type and the items related to it
+  loppers extract src/ --out-dir skeletons/       # One skeleton file per source file
+  loppers extract --max-tokens 2000 big.py        # Trim to a token budget
+  loppers extract --max-tokens 50000 src/          # Fit a directory to a token budget
+  loppers extract --stats src/                    # Report how much lopping saves
+  loppers extract --repo-map src/                 # One repo map for an LLM prompt
         """,
     )
     extract_parser.add_argument(
         "file",
         nargs="?",
-        help="File to extract (omit for stdin)",
+        help="File or directory to extract (omit or '-' for stdin)",
     )
     extract_parser.add_argument(
         "-l",
         "--language",
-        help="Language (auto-detected from extension if FILE provided)",
+        "--lang",
+        help="Language (auto-detected from extension, name, shebang or content)",
     )
     extract_parser.add_argument(
         "-o",
         "--output",
         help="Output file (default: stdout)",
     )
+    extract_parser.add_argument(
+        "--out-dir",
+        metavar="DIR",
+        help="When FILE is a directory, write each skeleton to the same relative path "
+        "under DIR (files already holding it are left untouched)",
+    )
+    extract_parser.add_argument(
+        "-f",
+        "--format",
+        choices=["text", "json", "outline", "markdown", "ctags"],
+        default="text",
+        help="Output format: skeleton text, JSON symbol outline, symbol signatures "
+        "grouped by kind and sorted by name, skeletons fenced as Markdown code blocks "
+        "under path headings, or a ctags tag file of the symbols (default: text)",
+    )
+    extract_parser.add_argument(
+        "--json",
+        action="store_const",
+        const="json",
+        dest="format",
+        help="Print the JSON symbol outline (same as --format json)",
+    )
+    extract_parser.add_argument(
+        "--group-impls",
+        action="store_true",
+        help="In the JSON outline, list the items of Rust impl blocks under the type they "
+        "implement",
+    )
+    extract_parser.add_argument(
+        "--keep-closures",
+        action="store_true",
+        help="In the JSON or sorted outline, list closures and anonymous functions as "
+        "children of the function they are written in",
+    )
+    extract_parser.add_argument(
+        "-j",
+        "--jobs",
+        type=int,
+        default=1,
+        metavar="N",
+        help="When FILE is a directory, extract with N worker processes (0 for one per CPU)",
+    )
+    extract_parser.add_argument(
+        "--color",
+        choices=["auto", "always", "never"],
+        default="auto",
+        help="Color the keywords, type names and placeholders of text output: always, "
+        "never, or when stdout is a terminal and NO_COLOR is unset (default: auto)",
+    )
+    extract_parser.add_argument(
+        "--cache-dir",
+        metavar="DIR",
+        help="When FILE is a directory, reuse the skeletons (and --stats statistics) "
+        "cached in DIR for files unchanged since an earlier run, and cache the others",
+    )
+    extract_parser.add_argument(
+        "--no-ignore",
+        action="store_true",
+        help="When FILE is a directory, don't skip ignored paths",
+    )
+    extract_parser.add_argument(
+        "--include",
+        action="append",
+        metavar="PATTERN",
+        help="When FILE is a directory, only extract files matching PATTERN, a glob such "
+        "as 'src/**/*.rs' (repeatable)",
+    )
+    extract_parser.add_argument(
+        "--exclude",
+        action="append",
+        metavar="PATTERN",
+        help="When FILE is a directory, skip files matching PATTERN, a glob such as "
+        "'**/tests/**' (repeatable)",
+    )
+    extract_parser.add_argument(
+        "--max-tokens",
+        type=int,
+        metavar="N",
+        help="Drop the least important symbols until the skeleton fits ~N tokens; for a "
+        "directory, elide bodies, private items and then files until they fit",
+    )
+    extract_parser.add_argument(
+        "--diff",
+        metavar="REV",
+        help="Keep the items changed since the git revision REV whole (e.g. HEAD~1), "
+        "and lop the rest",
+    )
+    extract_parser.add_argument(
+        "--symbol",
+        metavar="NAME",
+        help="Only extract the top-level symbol NAME and the items related to it: its "
+        "impl blocks or methods, and the functions mentioning it in their signature",
+    )
+    extract_parser.add_argument(
+        "--repo-map",
+        action="store_true",
+        help="When FILE is a directory, print one map with a '## path' header per file "
+        "and a file and symbol count",
+    )
+    extract_parser.add_argument(
+        "--stats",
+        action="store_true",
+        help="Print the sizes of the sources and skeletons, and a census of their lines "
+        "and items per language, to stderr",
+    )
     extract_parser.add_argument(
         "-v",
         "--verbose",
         action="store_true",
         help="Print verbose output to stderr",
     )
+    add_extraction_args(extract_parser)
     extract_parser.set_defaults(func=cmd_extract)
 
     # Concatenate command (default)
@@ -327,6 +1144,7 @@ Examples:
         action="store_true",
         help="Include original files without skeleton extraction",
     )
+    add_extraction_args(concatenate_parser)
     concatenate_parser.set_defaults(func=cmd_concatenate)
 
     # Tree command
@@ -360,9 +1178,37 @@ Examples:
     add_shared_args(files_parser)
     files_parser.set_defaults(func=cmd_files)
 
-    args = parser.parse_args()
+    # Serve command
+    serve_parser = subparsers.add_parser(
+        "serve",
+        help="Answer extraction requests from stdin, one JSON object per line",
+        formatter_class=argparse.RawDescriptionHelpFormatter,
+        epilog="""
+Examples:
+  loppers serve                                  # One worker thread per CPU
+  loppers serve -j 4 --public-only               # 4 workers, public API by default
+  echo '{"id": 1, "path": "src/lib.rs"}' | loppers serve
+        """,
+    )
+    serve_parser.add_argument(
+        "-j",
+        "--jobs",
+        type=int,
+        default=0,
+        metavar="N",
+        help="Extract with N worker threads (default: one per CPU)",
+    )
+    add_extraction_args(serve_parser)
+    serve_parser.set_defaults(func=cmd_serve)
+
+    # Without a command, extract: `cat foo.rs | loppers --lang rust`, `loppers foo.rs`
+    argv: list[str] = sys.argv[1:]
+    if argv and argv[0] not in subparsers.choices and argv[0] not in ("-h", "--help", "--version"):
+        argv = ["extract", *argv]
+    elif not argv and not sys.stdin.isatty():
+        argv = ["extract"]
+    args = parser.parse_args(argv)
 
-    # If no command specified, default to concatenate
     if not hasattr(args, "func"):
         if not args.command:
             # No command provided, show help
@@ -0,0 +1,167 @@
+"""Default options read from a `loppers.toml` config file of a repository.
+
+A config file holds option values at its top level, as named by `LopperOptions`,
+and per-language tables overriding them for the sources of one language:
+
+    keep_doc_comments = true
+    strip_tests = true
+
+    [lang.python]
+    placeholder = "..."
+
+Options given explicitly always win over those of a config. Library functions
+never read config files by themselves: call `load_config` to apply one.
+"""
+
+from __future__ import annotations
+
+import sys
+from collections.abc import Mapping
+from dataclasses import dataclass, field
+from pathlib import Path
+from typing import Any
+
+from loppers.errors import ConfigError
+from loppers.loppers import LANGUAGE_CONFIGS, LopperOptions
+
+if sys.version_info >= (3, 11):
+    import tomllib
+else:
+    import tomli as tomllib
+
+# Names of config files, in order of preference within a directory
+CONFIG_FILE_NAMES: tuple[str, ...] = ("loppers.toml", ".loppers.toml")
+
+
+@dataclass(frozen=True)
+class LopperConfig:
+    """Option values of a config file.
+
+    Attributes:
+        path: The config file, or None for an empty config
+        options: Option values for every language, by option name
+        languages: Option values overriding `options` for one language, by
+            language identifier
+    """
+
+    path: Path | None = None
+    options: Mapping[str, Any] = field(default_factory=dict)
+    languages: Mapping[str, Mapping[str, Any]] = field(default_factory=dict)
+
+    def resolve(self, explicit: Mapping[str, Any] | None = None) -> LopperOptions:
+        """Merge the config under explicitly given option values.
+
+        Explicit values win over the per-language tables, which win over the top
+        level of the config; options set nowhere keep their defaults.
+
+        Args:
+            explicit: Option values given explicitly, by option name (such as the
+                flags of a command line)
+
+        Returns:
+            The options, with the per-language tables as `language_options`
+
+        Raises:
+            ConfigError: If the config and explicit values together are invalid
+            ValueError: If an explicit value is invalid
+        """
+        explicit = dict(explicit or {})
+        LopperOptions.from_dict(explicit)
+        languages = {
+            language: {name: value for name, value in values.items() if name not in explicit}
+            for language, values in self.languages.items()
+        }
+        try:
+            return LopperOptions.from_dict(
+                {**self.options, **explicit, "language_options": languages}
+            )
+        except ValueError as e:
+            raise ConfigError(f"Invalid config {self.path}: {e}", path=self.path) from e
+
+
+def find_config(path: str | Path) -> Path | None:
+    """Find the config file applying to a path.
+
+    Args:
+        path: File or directory being processed
+
+    Returns:
+        The first config file (see `CONFIG_FILE_NAMES`) in the directory of the
+        path or one of its parents, up to the filesystem root; None if there is none
+    """
+    start = Path(path).resolve()
+    directory = start if start.is_dir() else start.parent
+    for candidate in (directory, *directory.parents):
+        for name in CONFIG_FILE_NAMES:
+            config_file = candidate / name
+            if config_file.is_file():
+                return config_file
+    return None
+
+
+def read_config(path: str | Path) -> LopperConfig:
+    """Read a config file.
+
+    Args:
+        path: The config file
+
+    Returns:
+        Its option values
+
+    Raises:
+        FileNotFoundError: If the file does not exist
+        ConfigError: If the file is not valid TOML, or holds unknown options,
+            unknown languages or values of the wrong type
+    """
+    path = Path(path)
+    try:
+        with path.open("rb") as config_file:
+            data = tomllib.load(config_file)
+    except tomllib.TOMLDecodeError as e:
+        raise ConfigError(f"Invalid config {path}: {e}", path=path) from e
+
+    languages = data.pop("lang", {})
+    try:
+        if not isinstance(languages, dict):
+            raise ValueError("lang must be a table of per-language tables")
+        unknown = sorted(set(languages) - set(LANGUAGE_CONFIGS))
+        if unknown:
+            raise ValueError(f"Unknown languages in lang: {', '.join(unknown)}")
+        if "language_options" in data:
+            raise ValueError("language_options are set in per-language [lang.*] tables")
+        for language, values in languages.items():
+            if not isinstance(values, dict):
+                raise ValueError(f"lang.{language} must be a table of options")
+        LopperOptions.from_dict({**data, "language_options": languages})
+    except ValueError as e:
+        raise ConfigError(f"Invalid config {path}: {e}", path=path) from e
+    return LopperConfig(path, data, languages)
+
+
+def load_config(
+    path: str | Path,
+    *,
+    config_path: str | Path | None = None,
+    discover: bool = True,
+) -> LopperConfig:
+    """Load the config applying to a path.
+
+    Args:
+        path: File or directory being processed, where the search for a config
+            file starts (see `find_config`)
+        config_path: Config file to read instead of searching for one
+        discover: Search for a config file; when False and no `config_path` is
+            given, the config is empty, so extraction only depends on the options
+            given explicitly
+
+    Returns:
+        The config, empty when no config file applies
+
+    Raises:
+        FileNotFoundError: If `config_path` does not exist
+        ConfigError: If the config file is invalid (see `read_config`)
+    """
+    if config_path is not None:
+        return read_config(config_path)
+    found = find_config(path) if discover else None
+    return read_config(found) if found is not None else LopperConfig()
@@ -0,0 +1,102 @@
+"""Symbol indexes in the tag file format of Exuberant Ctags.
+
+Editors jump to definitions through tag files: one line per symbol naming it, its
+file, where it is (a search pattern matching its first line, or its line number),
+its kind and the scope it is defined in, sorted by name.
+"""
+
+from __future__ import annotations
+
+from collections.abc import Iterator, Mapping, Sequence
+
+from loppers.extensions import detect_language
+from loppers.loppers import DEFAULT_CTAGS_KINDS, LANGUAGE_CONFIGS
+from loppers.symbol_model import Symbol, base_name
+
+# Header lines of a sorted tag file in the extended format
+CTAGS_HEADER: tuple[str, ...] = (
+    '!_TAG_FILE_FORMAT\t2\t/extended format; --format=1 will not append ;" to lines/',
+    "!_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted, 2=foldcase/",
+    "!_TAG_PROGRAM_NAME\tloppers\t//",
+    "!_TAG_PROGRAM_URL\thttps://github.com/undo76/loppers\t//",
+)
+
+
+def render_ctags(
+    symbols: Mapping[str, Sequence[Symbol]],
+    *,
+    languages: Mapping[str, str] | None = None,
+    sources: Mapping[str, str] | None = None,
+) -> str:
+    """Render the symbols of files as a tag file.
+
+    Every symbol, nested ones included, gets a line of its name, file, address and
+    kind letter (from the `ctags_kinds` of its language), followed by a scope field
+    naming the kind and name of the enclosing symbol (`struct:Calculator`). The
+    address is a search pattern matching the first line of the symbol
+    (`/^pub struct Calculator {$/`) when the source of its file is given, so tags
+    survive edits above the symbol, and its line number otherwise. Items of Rust
+    `impl` blocks and Go methods are scoped to the type they belong to when the
+    file defines it. Lines are sorted by name, as ctags requires.
+
+    Args:
+        symbols: Symbols of each file (see `extract_symbols`), by path as written in
+            the tag file
+        languages: Language of each file, by path; detected from the path when
+            missing
+        sources: Source code of each file, by path, for search pattern addresses
+
+    Returns:
+        The tag file, header lines first
+    """
+    lines: list[str] = []
+    for path, outline in symbols.items():
+        source = (sources or {}).get(path)
+        # Lines end at "\n" only, as in tree-sitter's row numbering
+        source_lines = (
+            [line.removesuffix("\r") for line in source.split("\n")]
+            if source is not None
+            else None
+        )
+        language = (languages or {}).get(path) or detect_language(path)
+        config = LANGUAGE_CONFIGS.get(language or "")
+        kinds = config.ctags_kinds if config else DEFAULT_CTAGS_KINDS
+        types = {
+            symbol.name: symbol.kind for symbol in outline if symbol.kind not in ("impl", "module")
+        }
+        for symbol, enclosing in _with_enclosing(outline, None):
+            kind = kinds.get(symbol.kind, symbol.kind[:1])
+            address = _address(symbol, source_lines)
+            line = f'{symbol.name}\t{path}\t{address};"\t{kind}'
+            scope = _scope(symbol, enclosing, types)
+            lines.append(f"{line}\t{scope}" if scope else line)
+    return "\n".join((*CTAGS_HEADER, *sorted(lines))) + "\n"
+
+
+def _address(symbol: Symbol, source_lines: Sequence[str] | None) -> str:
+    """Return the search pattern of a symbol's first line, or its line number."""
+    if source_lines is None or not 0 < symbol.start_line <= len(source_lines):
+        return str(symbol.start_line)
+    text = source_lines[symbol.start_line - 1].replace("\\", "\\\\").replace("/", "\\/")
+    return f"/^{text}$/"
+
+
+def _with_enclosing(
+    symbols: Sequence[Symbol], enclosing: Symbol | None
+) -> Iterator[tuple[Symbol, Symbol | None]]:
+    """Walk symbols depth-first, pairing each with the symbol it is nested in."""
+    for symbol in symbols:
+        yield symbol, enclosing
+        yield from _with_enclosing(symbol.children, symbol)
+
+
+def _scope(symbol: Symbol, enclosing: Symbol | None, types: Mapping[str, str]) -> str | None:
+    """Return the scope field of a symbol's tag, if it has a parent."""
+    if symbol.parent is None:
+        return None
+    if enclosing is not None and enclosing.kind != "impl":
+        return f"{enclosing.kind}:{symbol.parent}"
+    name = base_name(symbol.parent)
+    if name in types:
+        return f"{types[name]}:{name}"
+    return f"{'impl' if enclosing is not None else 'type'}:{symbol.parent}"
@@ -0,0 +1,353 @@
+"""Skeletons of host documents that embed code, such as Markdown code blocks or HTML scripts.
+
+A host document is not parsed as code itself: the regions of code embedded in it
+are found, lopped in the language each is written in, and spliced back in place.
+Everything outside those regions, the lines delimiting them included, is kept
+byte for byte.
+"""
+
+from __future__ import annotations
+
+import os
+import re
+from bisect import bisect_right
+from collections.abc import Callable
+from dataclasses import dataclass, replace
+
+from loppers.extensions import get_language
+from loppers.loppers import (
+    GUTTER_SEPARATOR,
+    LANGUAGE_CONFIGS,
+    ExtractionResult,
+    LopperOptions,
+    ParseDiagnostic,
+    SourceMap,
+    get_extractor,
+    line_break_of,
+    line_starts_of,
+)
+
+
+@dataclass(frozen=True)
+class EmbeddedCode:
+    """A region of a host document holding code.
+
+    Attributes:
+        start: Offset of the region in the document text, at the start of a line
+        end: Offset just past the region, at the start of a line (or the end of the
+            document)
+        language: Language identifier of the code
+        indent: Indentation of the region's lines, removed before lopping and
+            restored after (code blocks nested in list items, indented scripts)
+    """
+
+    start: int
+    end: int
+    language: str
+    indent: str = ""
+
+
+# Fence info strings naming a language neither by its identifier nor by an extension
+FENCE_ALIASES: dict[str, str] = {
+    "c++": "cpp",
+    "c#": "csharp",
+    "golang": "go",
+    "jsx": "javascript",
+    "node": "javascript",
+    "objective-c": "objc",
+    "objectivec": "objc",
+    "shell": "bash",
+    "zsh": "bash",
+}
+
+# Opening line of a fenced code block: indentation, fence and info string
+_FENCE_OPEN: re.Pattern[str] = re.compile(r"(?P<indent>[ \t]*)(?P<fence>`{3,}|~{3,})(?P<info>.*)")
+
+# An HTML comment, or the opening tag of a script element (attribute values may hold `>`)
+_SCRIPT_OPEN: re.Pattern[str] = re.compile(
+    r"<!--.*?-->|<script\b(?P<attributes>(?:[^>\"']|\"[^\"]*\"|'[^']*')*)>",
+    re.DOTALL | re.IGNORECASE,
+)
+
+# Closing tag of a script element
+_SCRIPT_CLOSE: re.Pattern[str] = re.compile(r"</script\s*>", re.IGNORECASE)
+
+# An attribute of an HTML tag, with an optional quoted or bare value
+_ATTRIBUTE: re.Pattern[str] = re.compile(
+    r"(?P<name>[^\s\"'>/=]+)"
+    r"(?:\s*=\s*(?:\"(?P<double>[^\"]*)\"|'(?P<single>[^']*)'|(?P<bare>[^\s>]+)))?"
+)
+
+# Values of the `type` attribute of script elements holding JavaScript
+_SCRIPT_TYPES: frozenset[str] = frozenset(
+    {"", "module", "text/javascript", "application/javascript", "text/ecmascript"}
+)
+
+
+def fence_language(info: str) -> str | None:
+    """Return the language named by the info string of a code fence.
+
+    The first word of the info string is looked up as a language identifier, an
+    alias (`FENCE_ALIASES`) or a file extension (`py`, `rs`), ignoring case and the
+    braces and dot of attribute syntax (`{.python}`).
+
+    Args:
+        info: Text following the fence on its opening line
+
+    Returns:
+        Language identifier, or None if the fence names no supported language
+    """
+    words = info.split(maxsplit=1)
+    return named_language(words[0].strip("{}").removeprefix(".")) if words else None
+
+
+def named_language(name: str) -> str | None:
+    """Return the supported language named by an identifier, an alias or an extension."""
+    tag = name.lower()
+    language = FENCE_ALIASES.get(tag) or (tag if tag in LANGUAGE_CONFIGS else get_language(tag))
+    return language if language in LANGUAGE_CONFIGS else None
+
+
+def markdown_code_blocks(source: str) -> list[EmbeddedCode]:
+    """Find the fenced code blocks of a Markdown document written in a supported language.
+
+    Blocks are delimited by backtick or tilde fences; the closing fence uses the
+    same character and is at least as long as the opening one. Blocks without a
+    language tag or with an unsupported one, unclosed blocks and indented code
+    blocks are left out.
+
+    Args:
+        source: Markdown text
+
+    Returns:
+        Regions between the fence lines of each block, in document order
+    """
+    blocks: list[EmbeddedCode] = []
+    opening: tuple[str, str, str | None, int] | None = None
+    offset: int = 0
+    for line in source.splitlines(keepends=True):
+        text: str = line.rstrip("\r\n")
+        if opening is None:
+            match = _FENCE_OPEN.fullmatch(text)
+            # Backtick fences cannot have backticks in their info string
+            if match and not (match["fence"][0] == "`" and "`" in match["info"]):
+                language = fence_language(match["info"])
+                opening = (match["fence"], match["indent"], language, offset + len(line))
+        else:
+            fence, indent, language, start = opening
+            closing: str = text.strip()
+            if closing and set(closing) == {fence[0]} and len(closing) >= len(fence):
+                if language is not None:
+                    blocks.append(EmbeddedCode(start, offset, language, indent))
+                opening = None
+        offset += len(line)
+    return blocks
+
+
+def script_blocks(source: str) -> list[EmbeddedCode]:
+    """Find the script elements of an HTML document or a Vue or Svelte component.
+
+    The language of a script is named by its `lang` attribute (`lang="ts"`) and is
+    JavaScript without one. Scripts whose `type` is not JavaScript (JSON, templates),
+    whose language is unsupported, that are commented out or that share a line with
+    their opening or closing tag (`<script>init()</script>`) are left out.
+
+    Args:
+        source: HTML, Vue or Svelte text
+
+    Returns:
+        Regions between the lines of the opening and closing tags of each script, in
+        document order
+    """
+    blocks: list[EmbeddedCode] = []
+    position: int = 0
+    while match := _SCRIPT_OPEN.search(source, position):
+        position = match.end()
+        if match["attributes"] is None:
+            continue
+        closing = _SCRIPT_CLOSE.search(source, position)
+        if closing is None:
+            break
+        position = closing.end()
+
+        attributes: dict[str, str] = {
+            attribute["name"].lower(): next(
+                (value for value in attribute.group("double", "single", "bare") if value), ""
+            )
+            for attribute in _ATTRIBUTE.finditer(match["attributes"])
+        }
+        if attributes.get("type", "").strip().lower() not in _SCRIPT_TYPES:
+            continue
+        language = named_language(attributes["lang"]) if "lang" in attributes else "javascript"
+        line_end: int = source.find("\n", match.end(), closing.start())
+        start: int = line_end + 1
+        end: int = source.rfind("\n", match.end(), closing.start()) + 1
+        inline: bool = line_end < 0 or bool(source[match.end() : line_end].strip())
+        if language is None or inline or source[end : closing.start()].strip() or end <= start:
+            continue
+        blocks.append(EmbeddedCode(start, end, language, _common_indent(source[start:end])))
+    return blocks
+
+
+def _common_indent(text: str) -> str:
+    """Return the leading whitespace shared by the non-blank lines of a text."""
+    indents: list[str] = [
+        line[: len(line) - len(line.lstrip())] for line in text.splitlines() if line.strip()
+    ]
+    return os.path.commonprefix(indents) if indents else ""
+
+
+# Finds the embedded code regions of a host document language
+DocumentScanner = Callable[[str], list[EmbeddedCode]]
+
+# Scanners of the host document languages, by language identifier
+DOCUMENT_SCANNERS: dict[str, DocumentScanner] = {
+    "markdown": markdown_code_blocks,
+    "html": script_blocks,
+    "vue": script_blocks,
+    "svelte": script_blocks,
+}
+
+# Languages of host documents, lopped region by region
+DOCUMENT_LANGUAGES: frozenset[str] = frozenset(DOCUMENT_SCANNERS)
+
+
+def extract_document(
+    source: str, language: str, *, options: LopperOptions | None = None
+) -> ExtractionResult:
+    """Extract the skeleton of a host document, lopping each embedded code region.
+
+    The diagnostics and source map of the result refer to the lines and bytes of
+    the whole document.
+
+    Args:
+        source: Document text
+        language: Host document language (see `DOCUMENT_LANGUAGES`)
+        options: Extraction options applied to every region (defaults to
+            `LopperOptions()`); with `line_numbers`, lines are numbered by their
+            line in the document
+
+    Returns:
+        Skeleton of the document, with the syntax errors of its regions
+
+    Raises:
+        KeyError: If the language is not a host document language
+        ParseFailedError: If a region has syntax errors and `on_error` is "fail"
+    """
+    return lop_regions(source, DOCUMENT_SCANNERS[language](source), options=options)
+
+
+def lop_regions(
+    source: str, regions: list[EmbeddedCode], *, options: LopperOptions | None = None
+) -> ExtractionResult:
+    """Lop the code regions of a document, keeping the text around them verbatim.
+
+    Args:
+        source: Document text
+        regions: Disjoint, line-aligned regions of code, in document order
+        options: Extraction options applied to every region
+
+    Returns:
+        Skeleton of the document, with the syntax errors of its regions
+
+    Raises:
+        ParseFailedError: If a region has syntax errors and `on_error` is "fail"
+    """
+    base: LopperOptions = options or LopperOptions()
+    region_options: LopperOptions = replace(base, line_numbers=False)
+    encoded: bytes = source.encode()
+    line_starts: list[int] = line_starts_of(encoded)
+    line_break: str = line_break_of(encoded).decode()
+    pieces: list[str] = []
+    lines: list[tuple[int, int]] = []
+    elided: list[tuple[int, int]] = []
+    diagnostics: list[ParseDiagnostic] = []
+    position: int = 0
+    for region in [*regions, EmbeddedCode(len(source), len(source), "")]:
+        # The text before the region is kept, each line mapping to itself
+        kept: str = source[position : region.start]
+        first_line: int = source.count("\n", 0, position) + 1
+        pieces.append(kept)
+        lines.extend((line, line) for line in range(first_line, first_line + _line_count(kept)))
+        position = region.end
+        if region.start == region.end:
+            continue
+
+        shift: int = source.count("\n", 0, region.start)
+        code, widths = _dedented(source[region.start : region.end], region.indent)
+        result = get_extractor(region.language, region_options).extract_result(code)
+        offsets = (line_starts_of(code.encode()), widths, line_starts, shift)
+        diagnostics.extend(
+            replace(
+                diagnostic,
+                start_byte=_host_byte(diagnostic.start_byte, *offsets),
+                end_byte=_host_byte(diagnostic.end_byte, *offsets),
+                start_line=diagnostic.start_line + shift,
+                end_line=diagnostic.end_line + shift,
+            )
+            for diagnostic in result.diagnostics
+        )
+        if result.skeleton:
+            indented = (
+                region.indent + line if line.strip() else line
+                for line in result.skeleton.split("\n")
+            )
+            pieces.append("\n".join(indented) + line_break)
+            lines.extend((start + shift, end + shift) for start, end in result.source_map.lines)
+            elided.extend((start + shift, end + shift) for start, end in result.source_map.elided)
+        elif region.end > region.start:
+            elided.append((shift + 1, shift + _line_count(source[region.start : region.end])))
+
+    skeleton: str = "".join(pieces).rstrip()
+    del lines[_line_count(skeleton) :]
+    if base.line_numbers:
+        skeleton = _numbered(skeleton, lines, len(str(_line_count(source))))
+    return ExtractionResult(skeleton, diagnostics, SourceMap(lines, elided))
+
+
+def _line_count(text: str) -> int:
+    """Return the number of lines of a text, not counting an empty last line."""
+    return text.count("\n") + (not text.endswith("\n") and bool(text))
+
+
+def _host_byte(
+    offset: int, code_starts: list[int], widths: list[int], line_starts: list[int], shift: int
+) -> int:
+    """Map a byte offset in dedented region code to the document's encoded text.
+
+    Args:
+        offset: Byte offset in the encoded code
+        code_starts: Byte offset at which each line of the code starts
+        widths: Bytes of indentation removed from each line of the code
+        line_starts: Byte offset at which each line of the document starts
+        shift: Number of document lines before the region
+
+    Returns:
+        Byte offset in the encoded document
+    """
+    index: int = bisect_right(code_starts, offset) - 1
+    return line_starts[shift + index] + widths[index] + offset - code_starts[index]
+
+
+def _dedented(text: str, indent: str) -> tuple[str, list[int]]:
+    """Remove an indentation from the lines of a text that start with it.
+
+    Returns:
+        The text, and the number of UTF-8 bytes removed from each of its lines
+    """
+    lines: list[str] = []
+    widths: list[int] = []
+    for line in text.splitlines(keepends=True) or [""]:
+        stripped: bool = bool(indent) and line.startswith(indent)
+        lines.append(line[len(indent) :] if stripped else line)
+        widths.append(len(indent.encode()) if stripped else 0)
+    return "".join(lines), [*widths, 0]
+
+
+def _numbered(skeleton: str, lines: list[tuple[int, int]], width: int) -> str:
+    """Prefix each line of a skeleton with the document line it comes from."""
+    numbered: list[str] = []
+    for line, (number, _) in zip(skeleton.split("\n"), lines):
+        gutter: str = f"{number:>{width}}{GUTTER_SEPARATOR}"
+        numbered.append(f"{gutter} {line}" if line else gutter)
+    return "\n".join(numbered)
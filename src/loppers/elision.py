@@ -0,0 +1,148 @@
+"""Skeletons with the regions they elide, for expanding them again on demand.
+
+An interactive viewer shows a skeleton and expands a collapsed body when it is
+clicked: each elided region tells where its placeholder is in the skeleton and
+which source bytes it stands for, so the original text can be spliced back in.
+"""
+
+from __future__ import annotations
+
+from collections.abc import Iterable
+from dataclasses import dataclass, field
+
+from loppers.loppers import LopperOptions, get_extractor
+from loppers.symbol_model import Symbol, symbol_nodes
+
+
+@dataclass(frozen=True)
+class ElidedRegion:
+    """A region of a source left out of its skeleton.
+
+    Spans are half-open byte ranges of the UTF-8 encoded texts.
+
+    Attributes:
+        output_span: Range of the skeleton holding the placeholder of the region
+            (empty where nothing stands in for it)
+        original_span: Range of the source the region covers
+        item_name: Name of the innermost item the region is part of (the function
+            of an elided body), or of the first item it holds; None outside items
+            and for languages without symbol support
+        item_kind: Symbol kind of that item (e.g. "function", "method")
+    """
+
+    output_span: tuple[int, int]
+    original_span: tuple[int, int]
+    item_name: str | None = None
+    item_kind: str | None = None
+
+
+@dataclass(frozen=True)
+class ElidedSkeleton:
+    """A skeleton together with the regions of its source it elides.
+
+    Attributes:
+        skeleton: Extracted skeleton, the same as `extract_skeleton` returns
+        source: Source the skeleton was extracted from
+        regions: Elided regions, in order; whitespace trimmed from the end of the
+            skeleton is the last one
+    """
+
+    skeleton: str
+    source: str
+    regions: list[ElidedRegion] = field(default_factory=list)
+
+    def expand(self, regions: Iterable[ElidedRegion] | None = None) -> str:
+        """Replace the placeholders of regions with the source text they stand for.
+
+        Args:
+            regions: Regions of this skeleton to expand (all of them when None)
+
+        Returns:
+            The skeleton with those regions expanded; expanding all of them gives
+            back the source
+        """
+        output, source = self.skeleton.encode(), self.source.encode()
+        chosen = self.regions if regions is None else regions
+        for region in sorted(chosen, key=lambda region: region.output_span, reverse=True):
+            (start, end), (original_start, original_end) = region.output_span, region.original_span
+            output = output[:start] + source[original_start:original_end] + output[end:]
+        return output.decode()
+
+
+def extract_with_regions(
+    source: str, language: str, *, options: LopperOptions | None = None
+) -> ElidedSkeleton:
+    """Extract a skeleton, reporting every region of the source it elides.
+
+    Each removed range of the source (elided bodies, dropped doc comments, items,
+    imports, ...) becomes one region, with the placeholder standing in for it, so
+    expanding every region reproduces the source byte for byte.
+
+    Args:
+        source: Source code to process
+        language: Programming language identifier
+        options: Extraction options (defaults to `LopperOptions()`)
+
+    Returns:
+        Skeleton with its elided regions
+
+    Raises:
+        UnsupportedLanguageError: If language is not supported
+        ValueError: If the options number, re-indent or reorder the skeleton's lines,
+            as no source text stands for the gutters or indentation, and expanding
+            reordered regions would not give back the source
+        ParseFailedError: If the source has syntax errors and `on_error` is "fail"
+    """
+    extractor = get_extractor(language, options)
+    if extractor.options.line_numbers:
+        raise ValueError("Elided regions cannot be reported for skeletons with line numbers")
+    if extractor.options.indent_width is not None:
+        raise ValueError("Elided regions cannot be reported for re-indented skeletons")
+    if extractor.options.sort != "source":
+        raise ValueError("Elided regions cannot be reported for reordered skeletons")
+    encoded = source.encode()
+    tree = extractor.parse(encoded)
+    _, _, pending = extractor.segments(encoded, tree=tree)
+    segments = list(pending)
+    skeleton = b"".join(text for text, _, _ in segments).decode().rstrip()
+    limit = len(skeleton.encode())
+
+    # Gaps between the source segments of the skeleton are the elided regions
+    spans: list[tuple[tuple[int, int], tuple[int, int]]] = []
+    output = position = gap_start = 0
+    for text, offset, is_source in segments:
+        text = text[: max(limit - output, 0)]
+        if not text:
+            continue
+        if is_source:
+            if offset > position or output > gap_start:
+                spans.append(((gap_start, output), (position, offset)))
+            position = offset + len(text)
+            gap_start = output + len(text)
+        output += len(text)
+    if position < len(encoded) or output > gap_start:
+        spans.append(((gap_start, output), (position, len(encoded))))
+
+    items: list[Symbol] = []
+    if extractor.config.symbol_query:
+        items = [symbol for symbol, _ in symbol_nodes(extractor, tree, encoded)]
+    regions: list[ElidedRegion] = []
+    for output_span, original_span in spans:
+        item = _region_item(items, *original_span)
+        regions.append(
+            ElidedRegion(
+                output_span,
+                original_span,
+                item.name if item else None,
+                item.kind if item else None,
+            )
+        )
+    return ElidedSkeleton(skeleton, source, regions)
+
+
+def _region_item(items: list[Symbol], start: int, end: int) -> Symbol | None:
+    """Return the innermost item holding a byte range, or else the first item inside it."""
+    holding = [item for item in items if item.start_byte <= start and end <= item.end_byte]
+    if holding:
+        return min(holding, key=lambda item: item.end_byte - item.start_byte)
+    return next((item for item in items if start <= item.start_byte < end), None)
@@ -0,0 +1,110 @@
+"""Error types raised by loppers.
+
+All errors about the input itself derive from `LopError`, so callers can tell a
+file to skip (`UnsupportedLanguageError`) from one that is broken
+(`InvalidUtf8Error`, `ParseFailedError`). They also derive from `ValueError`, as
+the plain errors they replace did. I/O failures are raised as the usual `OSError`
+subclasses (`FileNotFoundError`, `PermissionError`, ...).
+"""
+
+from __future__ import annotations
+
+from pathlib import Path
+
+
+class LopError(Exception):
+    """Base class of errors about the input of an extraction.
+
+    Attributes:
+        path: File the error is about, or None for in-memory sources
+    """
+
+    def __init__(self, message: str, *, path: Path | None = None) -> None:
+        """Initialize the error.
+
+        Args:
+            message: Error message
+            path: File the error is about
+        """
+        super().__init__(message)
+        self.path: Path | None = path
+
+
+class UnsupportedLanguageError(LopError, ValueError):
+    """The language of the input is unknown or not supported for the operation.
+
+    Attributes:
+        path: File whose language was not detected or supported, if any
+        extension: Extension of that file, if any
+        language: Requested language identifier, if one was given
+    """
+
+    def __init__(
+        self,
+        message: str,
+        *,
+        path: Path | None = None,
+        extension: str | None = None,
+        language: str | None = None,
+    ) -> None:
+        """Initialize the error.
+
+        Args:
+            message: Error message
+            path: File whose language was not detected or supported
+            extension: Extension of that file
+            language: Requested language identifier
+        """
+        super().__init__(message, path=path)
+        self.extension: str | None = extension
+        self.language: str | None = language
+
+
+class InvalidUtf8Error(LopError, ValueError):
+    """The input is not valid UTF-8 text."""
+
+
+class InvalidQueryError(LopError, ValueError):
+    """A user-supplied tree-sitter query is malformed or uses unknown captures."""
+
+
+class InvalidNotebookError(LopError, ValueError):
+    """A Jupyter notebook is not valid JSON or lacks the structure of nbformat 4."""
+
+
+class ParseFailedError(LopError, ValueError):
+    """The input could not be parsed.
+
+    Raised when tree-sitter produces no syntax tree, or when the input has syntax
+    errors and `LopperOptions.on_error` is "fail".
+    """
+
+
+class ConfigError(LopError, ValueError):
+    """A config file is not valid TOML or holds invalid options.
+
+    The message names the file (also in `path`) and what is wrong with it.
+    """
+
+
+class SymbolNotFoundError(LopError, ValueError):
+    """No top-level item of the input has the requested name.
+
+    Attributes:
+        symbol: Requested symbol name
+        names: Names of the top-level items of the input, in source order, to
+            help spot typos
+    """
+
+    def __init__(self, symbol: str, names: list[str], *, path: Path | None = None) -> None:
+        """Initialize the error.
+
+        Args:
+            symbol: Requested symbol name
+            names: Names of the top-level items of the input
+            path: File the symbol was looked up in
+        """
+        found = ", ".join(names) if names else "none"
+        super().__init__(f"Symbol '{symbol}' not found (top-level symbols: {found})", path=path)
+        self.symbol: str = symbol
+        self.names: list[str] = names
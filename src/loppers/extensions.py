@@ -1,7 +1,10 @@
-"""File extension to language mapping."""
+"""File extension, file name, shebang and content to language mapping."""
 
 from __future__ import annotations
 
+import re
+from pathlib import PurePath
+
 # Map file extensions to language identifiers
 EXTENSION_TO_LANGUAGE: dict[str, str] = {
     # Python
@@ -29,15 +32,21 @@ EXTENSION_TO_LANGUAGE: dict[str, str] = {
     ".cxx": "cpp",
     ".c++": "cpp",
     ".hpp": "cpp",
+    ".hh": "cpp",
+    ".hxx": "cpp",
+    ".h++": "cpp",
+    # Headers may be C or C++; the C++ grammar parses both
+    ".h": "cpp",
     # C
     ".c": "c",
-    ".h": "c",
     # C#
     ".cs": "csharp",
     # Ruby
     ".rb": "ruby",
     ".erb": "ruby",
     ".rbx": "ruby",
+    ".rake": "ruby",
+    ".gemspec": "ruby",
     # PHP
     ".php": "php",
     ".phtml": "php",
@@ -48,6 +57,164 @@ EXTENSION_TO_LANGUAGE: dict[str, str] = {
     # Kotlin
     ".kt": "kotlin",
     ".kts": "kotlin",
+    # Shell
+    ".sh": "bash",
+    ".bash": "bash",
+    # Markdown documents, lopped code block by code block
+    ".md": "markdown",
+    ".markdown": "markdown",
+    ".mdx": "markdown",
+    # HTML documents and single-file components, lopped script by script
+    ".html": "html",
+    ".htm": "html",
+    ".vue": "vue",
+    ".svelte": "svelte",
+    # Jupyter notebooks, lopped cell by cell
+    ".ipynb": "notebook",
+}
+
+# Map well-known file names without a telling extension to language identifiers
+FILENAME_TO_LANGUAGE: dict[str, str] = {
+    # Bazel and SCons files are written in Python dialects
+    "BUILD": "python",
+    "BUILD.bazel": "python",
+    "WORKSPACE": "python",
+    "SConstruct": "python",
+    "SConscript": "python",
+    "Gemfile": "ruby",
+    "Rakefile": "ruby",
+    "Jenkinsfile": "groovy",
+    ".bashrc": "bash",
+    ".bash_profile": "bash",
+    ".profile": "bash",
+}
+
+# Map shebang interpreters (without version suffixes) to language identifiers
+INTERPRETER_TO_LANGUAGE: dict[str, str] = {
+    "python": "python",
+    "pypy": "python",
+    "node": "javascript",
+    "nodejs": "javascript",
+    "ts-node": "typescript",
+    "ruby": "ruby",
+    "php": "php",
+    "lua": "lua",
+    "luajit": "lua",
+    "groovy": "groovy",
+    "kotlin": "kotlin",
+    "sh": "bash",
+    "bash": "bash",
+    "dash": "bash",
+    "ksh": "bash",
+}
+
+# Characters of content read when sniffing its language
+_SNIFF_LENGTH: int = 16384
+
+# Telltale lines and tokens of languages, for content without a shebang: content is
+# guessed to be in the language matching the most of its patterns (at least two)
+_CONTENT_PATTERNS: dict[str, tuple[re.Pattern[str], ...]] = {
+    language: tuple(re.compile(pattern, re.MULTILINE) for pattern in patterns)
+    for language, patterns in {
+        "python": (
+            r"^[ \t]*def \w+\(.*\)[ \t]*(?:->[^:]+)?:[ \t]*(?:#.*)?$",
+            r"^[ \t]*class \w+(?:\([^)]*\))?:[ \t]*$",
+            r"^(?:from [\w.]+ import \w|import [\w.]+(?:, [\w.]+)*[ \t]*$)",
+            r"^if __name__ == ['\"]__main__['\"]:",
+            r"^[ \t]*(?:elif .+|else|try|except(?: .+)?|finally):[ \t]*$",
+        ),
+        "javascript": (
+            r"\brequire\(['\"][^'\"]+['\"]\)",
+            r"^[ \t]*(?:export[ \t]+)?(?:async[ \t]+)?function\*?[ \t]+\w+[ \t]*\(",
+            r"^[ \t]*import[ \t].*[ \t]from[ \t]+['\"]",
+            r"^[ \t]*(?:const|var)[ \t]+\w+[ \t]*=",
+            r"\bconsole\.\w+\(",
+            r"=>[ \t]*[{(]",
+            r"^[ \t]*(?:module\.exports\b|export[ \t]+(?:default|const|class)\b)",
+        ),
+        # Patterns of JavaScript and type annotations: TypeScript wins where it has them
+        "typescript": (
+            r"\brequire\(['\"][^'\"]+['\"]\)",
+            r"^[ \t]*(?:export[ \t]+)?(?:async[ \t]+)?function\*?[ \t]+\w+[ \t]*[(<]",
+            r"^[ \t]*import[ \t].*[ \t]from[ \t]+['\"]",
+            r"^[ \t]*(?:const|var)[ \t]+\w+[ \t]*[=:]",
+            r"\bconsole\.\w+\(",
+            r"=>[ \t]*[{(]",
+            r"^[ \t]*(?:module\.exports\b|export[ \t]+(?:default|const|class)\b)",
+            r"^[ \t]*(?:export[ \t]+)?(?:interface|type)[ \t]+\w+(?:<[^>]*>)?[ \t]*[={]",
+            r"\w\)?[ \t]*:[ \t]*(?:string|number|boolean|void|unknown)\b",
+        ),
+        "rust": (
+            r"^[ \t]*(?:pub(?:\([\w: ]+\))?[ \t]+)?(?:async[ \t]+)?(?:unsafe[ \t]+)?fn[ \t]+\w+",
+            r"^[ \t]*use[ \t]+\w+::",
+            r"\blet[ \t]+mut[ \t]+\w+",
+            r"^[ \t]*impl\b",
+            r"^[ \t]*#!?\[\w+",
+            r"^[ \t]*(?:pub[ \t]+)?(?:struct|enum|trait|mod)[ \t]+\w+",
+        ),
+        "go": (
+            r"^package[ \t]+\w+[ \t]*$",
+            r"^func[ \t]+(?:\([^)]*\)[ \t]*)?\w+\(",
+            r'^import[ \t]+(?:\(|"[\w./-]+")',
+            r"\w+[ \t]*:=[ \t]*",
+        ),
+        "java": (
+            r"^package[ \t]+[\w.]+;",
+            r"^import[ \t]+(?:static[ \t]+)?[\w.]+(?:\.\*)?;",
+            r"^[ \t]*(?:public|private|protected)[ \t]+(?:(?:static|final|abstract)[ \t]+)*"
+            r"(?:class|interface|enum|record)[ \t]+\w+",
+            r"\bSystem\.(?:out|err)\.print",
+            r"^[ \t]*@Override\b",
+        ),
+        "c": (
+            r'^[ \t]*#[ \t]*include[ \t]*[<"]',
+            r"\b(?:printf|fprintf|malloc|free)\(",
+            r"^[ \t]*typedef[ \t]+struct\b",
+        ),
+        "cpp": (
+            r'^[ \t]*#[ \t]*include[ \t]*[<"]',
+            r"\bstd::\w+",
+            r"^[ \t]*(?:template[ \t]*<|namespace[ \t]+\w+|using[ \t]+namespace\b)",
+            r"\b(?:cout|cerr)[ \t]*<<",
+        ),
+        "csharp": (
+            r"^[ \t]*using[ \t]+System(?:\.[\w.]+)?;",
+            r"^[ \t]*namespace[ \t]+[\w.]+[ \t]*[;{]?[ \t]*$",
+            r"\bConsole\.Write(?:Line)?\(",
+            r"\{[ \t]*get;[ \t]*(?:set;[ \t]*)?\}",
+        ),
+        "ruby": (
+            r"^[ \t]*def[ \t]+(?:self\.)?\w+[?!=]?(?:\(.*\))?[ \t]*$",
+            r"^[ \t]*end[ \t]*$",
+            r"^[ \t]*require(?:_relative)?[ \t]+['\"]",
+            r"^[ \t]*(?:class|module)[ \t]+[A-Z]\w*(?:[ \t]*<[ \t]*[\w:]+)?[ \t]*$",
+            r"[ \t]do(?:[ \t]*\|[^|]*\|)?[ \t]*$",
+        ),
+        "php": (
+            r"<\?php\b",
+            r"\$\w+[ \t]*=",
+            r"^[ \t]*(?:namespace|use)[ \t]+[\w\\]+;",
+        ),
+        "lua": (
+            r"^[ \t]*local[ \t]+(?:function[ \t]+)?\w+",
+            r"^[ \t]*function[ \t]+[\w.:]+\(",
+            r"^[ \t]*end[ \t]*$",
+            r"\bthen[ \t]*$",
+        ),
+        "kotlin": (
+            r"^[ \t]*(?:(?:private|internal|public|override|suspend|inline)[ \t]+)*"
+            r"fun[ \t]+(?:<[^>]+>[ \t]*)?[\w.]+\(",
+            r"^[ \t]*(?:val|var)[ \t]+\w+",
+            r"\b(?:data class|companion object)\b",
+        ),
+        "bash": (
+            r"^[ \t]*(?:if|while)[ \t]+\[\[?[ \t]",
+            r"^[ \t]*(?:fi|done|esac)[ \t]*$",
+            r"^[ \t]*(?:export[ \t]+)?[A-Z_][A-Z0-9_]*=",
+            r"^[ \t]*(?:function[ \t]+)?\w+[ \t]*\(\)[ \t]*\{",
+            r"^[ \t]*echo[ \t]",
+        ),
+    }.items()
 }
 
 
@@ -62,3 +229,88 @@ def get_language(extension: str) -> str | None:
     """
     ext = extension if extension.startswith(".") else f".{extension}"
     return EXTENSION_TO_LANGUAGE.get(ext.lower())
+
+
+def detect_language(
+    path: str | PurePath | None, content: str | None = None, *, sniff: bool = False
+) -> str | None:
+    """Detect the language of a file from its extension, name, shebang line or content.
+
+    The extension wins when it is known. Otherwise well-known file names (`BUILD`,
+    `Rakefile`, ...) are looked up, then the interpreter of a `#!` first line
+    (`#!/usr/bin/env -S python3 -u`, `#!/bin/bash`, ...), and when sniffing, the
+    syntax of the content (see `sniff_language`).
+
+    Args:
+        path: File path (only its name is used), or None for content without one,
+            such as standard input
+        content: File content, or just its first line; None skips shebang detection
+        sniff: Guess the language from the syntax of the content as a last resort
+
+    Returns:
+        Language identifier, or None if not detected
+    """
+    language: str | None = None
+    name = PurePath(path).name if path is not None else ""
+    if name:
+        language = get_language(PurePath(name).suffix) or FILENAME_TO_LANGUAGE.get(name)
+    if language is None and content is not None:
+        interpreter = shebang_interpreter(content)
+        if interpreter is not None:
+            language = INTERPRETER_TO_LANGUAGE.get(interpreter.rstrip("0123456789."))
+        elif sniff:
+            language = sniff_language(content)
+    return language
+
+
+def sniff_language(content: str) -> str | None:
+    """Guess the language of source code from telltale lines and tokens.
+
+    Each language has a few patterns (Python `def f():` lines, Rust `let mut`, Go
+    `package main`, ...), searched in the first 16 KiB of the content. The language
+    matching the most distinct patterns wins, provided it matches two of them and
+    no other language matches as many (JavaScript wins ties with TypeScript).
+
+    Args:
+        content: Source code
+
+    Returns:
+        Language identifier, or None if the content is not telling enough
+    """
+    head = content[:_SNIFF_LENGTH]
+    scores: dict[str, int] = {
+        language: sum(1 for pattern in patterns if pattern.search(head))
+        for language, patterns in _CONTENT_PATTERNS.items()
+    }
+    best = max(scores.values())
+    leaders = [language for language, score in scores.items() if score == best]
+    if best < 2 or (len(leaders) > 1 and set(leaders) != {"javascript", "typescript"}):
+        return None
+    return leaders[0]
+
+
+def shebang_interpreter(content: str) -> str | None:
+    """Return the interpreter named by a shebang first line.
+
+    `env` indirections are followed, skipping its options (including `-S`) and
+    variable assignments.
+
+    Args:
+        content: File content, or just its first line
+
+    Returns:
+        Interpreter name without its directory (e.g. "python3"), or None if the
+        content does not start with a shebang
+    """
+    if not content.startswith("#!"):
+        return None
+    words = content[2:].partition("\n")[0].split()
+    if not words:
+        return None
+    interpreter = PurePath(words[0]).name
+    if interpreter == "env":
+        arguments = [word for word in words[1:] if not word.startswith("-") and "=" not in word]
+        if not arguments:
+            return None
+        interpreter = PurePath(arguments[0]).name
+    return interpreter
@@ -0,0 +1,263 @@
+"""Skeletons focused on changed lines, such as the lines a diff touches, or on a symbol.
+
+Reviewing a change needs the changed code in full and only the outline of the
+rest: items overlapping the focused lines keep their bodies, and everything else
+is lopped as usual. Studying one type needs nothing but that type: `lop_symbol`
+keeps the items related to a symbol and leaves the rest of the file out.
+"""
+
+from __future__ import annotations
+
+import re
+import subprocess
+from bisect import bisect_right
+from collections.abc import Sequence
+from pathlib import Path
+
+from loppers.errors import SymbolNotFoundError
+from loppers.loppers import (
+    LopperOptions,
+    get_extractor,
+    item_span,
+    line_break_of,
+    line_starts_of,
+    merge_ranges,
+)
+from loppers.symbol_model import CONTAINER_KINDS, Symbol, base_name, root_symbols, symbol_nodes
+
+# Hunk header of a unified diff, with the start lines and lengths of both sides
+_HUNK_HEADER = re.compile(r"@@ -\d+(?:,(\d+))? \+(\d+)(?:,(\d+))? @@")
+
+
+def lop_with_focus(
+    source: str,
+    language: str,
+    focus_ranges: Sequence[tuple[int, int]],
+    *,
+    options: LopperOptions | None = None,
+) -> str:
+    """Extract a skeleton keeping the items that overlap focused lines whole.
+
+    An item is focused when its span, including its attributes, decorators and doc
+    comments, overlaps one of the ranges. Members of classes, `impl` blocks, traits
+    and modules are focused apart, so a change inside one method keeps that method
+    whole and elides its siblings; a container is kept whole only when a focused
+    line is outside all of its members. Languages without symbol support get their
+    usual skeleton.
+
+    Args:
+        source: Source code to process
+        language: Programming language identifier
+        focus_ranges: Focused (first line, last line) ranges, 1-based and inclusive
+            (see `diff_line_ranges`)
+        options: Extraction options (defaults to `LopperOptions()`)
+
+    Returns:
+        Skeleton with the bodies of focused items kept
+
+    Raises:
+        UnsupportedLanguageError: If language is not supported
+    """
+    extractor = get_extractor(language, options)
+    if not focus_ranges or not extractor.config.symbol_query:
+        return extractor.extract(source)
+    encoded = source.encode()
+    tree = extractor.parse(encoded)
+    line_starts = line_starts_of(encoded)
+    pairs = symbol_nodes(extractor, tree, encoded)
+    spans = {id(symbol): item_span(node, encoded, line_starts) for symbol, node in pairs}
+    nested = {id(child) for symbol, _ in pairs for child in symbol.children}
+
+    def focused(symbol: Symbol) -> bool:
+        start, end = spans[id(symbol)]
+        first, last = bisect_right(line_starts, start), bisect_right(line_starts, end - 1)
+        return any(low <= last and first <= high for low, high in focus_ranges)
+
+    kept: list[tuple[int, int]] = []
+
+    def visit(symbol: Symbol) -> None:
+        if not focused(symbol):
+            return
+        members = [child for child in symbol.children if focused(child)]
+        if symbol.kind in CONTAINER_KINDS and members:
+            for member in members:
+                visit(member)
+        else:
+            kept.append(spans[id(symbol)])
+
+    for symbol, _ in pairs:
+        if id(symbol) not in nested:
+            visit(symbol)
+    return extractor.extract(source, tree=tree, keep=kept)
+
+
+def lop_symbol(
+    source: str,
+    language: str,
+    symbol: str,
+    *,
+    options: LopperOptions | None = None,
+) -> str:
+    """Extract the skeleton of a top-level symbol and of the items related to it.
+
+    The items kept are the top-level items named `symbol` (a Rust type with its
+    inherent and trait `impl` blocks, which are named after their self type; a
+    Python or TypeScript class with its methods), the Go methods of that type, and
+    the top-level functions mentioning it in their signature. Generic arguments
+    are ignored when matching names, so `Stack` matches `impl<T> Stack<T>`. These
+    items are lopped as usual and kept in source order with their doc comments and
+    attributes, a blank line apart; everything else in the file is left out.
+
+    Args:
+        source: Source code to process
+        language: Programming language identifier
+        symbol: Name of the top-level symbol
+        options: Extraction options (defaults to `LopperOptions()`)
+
+    Returns:
+        Skeleton of the items related to the symbol
+
+    Raises:
+        SymbolNotFoundError: If no top-level item is named `symbol`
+        UnsupportedLanguageError: If the language is not supported or has no symbol query
+    """
+    extractor = get_extractor(language, options)
+    encoded = source.encode()
+    tree = extractor.parse(encoded)
+    line_starts = line_starts_of(encoded)
+    pairs = symbol_nodes(extractor, tree, encoded)
+    nodes = {id(item): node for item, node in pairs}
+    roots = root_symbols(pairs)
+    if not any(base_name(root.name) == symbol for root in roots):
+        raise SymbolNotFoundError(symbol, list(dict.fromkeys(root.name for root in roots)))
+
+    mention = re.compile(rf"(?<![\w$]){re.escape(symbol)}(?![\w$])")
+
+    def related(root: Symbol) -> bool:
+        if base_name(root.name) == symbol:
+            return True
+        if root.parent is not None and base_name(root.parent) == symbol:
+            return True
+        return root.kind == "function" and mention.search(root.signature) is not None
+
+    spans = merge_ranges(
+        [item_span(nodes[id(root)], encoded, line_starts) for root in roots if related(root)]
+    )
+    # Everything around the items goes, but for a line break between two of them,
+    # which leaves a blank line as the items end with their own
+    line_break = line_break_of(encoded)
+    remove: list[tuple[int, int]] = []
+    position = 0
+    for start, end in spans:
+        cut_end = start
+        if position and encoded[position:start].endswith(line_break):
+            cut_end -= len(line_break)
+        if cut_end > position:
+            remove.append((position, cut_end))
+        position = end
+    if position < len(encoded):
+        remove.append((position, len(encoded)))
+    return extractor.extract(source, tree=tree, remove=remove)
+
+
+def diff_line_ranges(diff: str) -> dict[str, list[tuple[int, int]]]:
+    """Find the lines of each file that a unified diff adds or changes.
+
+    Lines are those of the new version of each file. Deleted lines that no added
+    lines replace focus the line above them (the first line when they delete the
+    start of the file). Files the diff deletes are left out.
+
+    Args:
+        diff: Unified diff text, such as the output of `git diff`
+
+    Returns:
+        Sorted, disjoint (first line, last line) ranges, 1-based and inclusive, by
+        file path as named in the diff (without git's `b/` prefix)
+    """
+    lines_by_path: dict[str, set[int]] = {}
+    changed: set[int] | None = None
+    old_left = new_left = new_line = 0
+    # Whether the last deleted lines are not (yet) replaced by added ones
+    deleting = False
+    for line in diff.splitlines():
+        marker = line[:1] if old_left > 0 or new_left > 0 else None
+        if deleting and marker not in ("-", "+", "\\"):
+            if changed is not None:
+                changed.add(max(new_line - 1, 1))
+            deleting = False
+        if marker is not None:
+            # Inside a hunk: every line is context, an addition or a deletion
+            if marker == "+":
+                if changed is not None:
+                    changed.add(new_line)
+                new_line, new_left, deleting = new_line + 1, new_left - 1, False
+            elif marker == "-":
+                old_left, deleting = old_left - 1, True
+            elif marker != "\\":
+                new_line, old_left, new_left = new_line + 1, old_left - 1, new_left - 1
+        elif line.startswith("+++ "):
+            path = _diff_path(line[4:])
+            changed = None if path is None else lines_by_path.setdefault(path, set())
+        elif match := _HUNK_HEADER.match(line):
+            old_length, new_start, new_length = match.groups()
+            old_left = 1 if old_length is None else int(old_length)
+            new_left = 1 if new_length is None else int(new_length)
+            # Hunks adding no lines start at the line above them
+            new_line = int(new_start) + (new_left == 0)
+    if deleting and changed is not None:
+        changed.add(max(new_line - 1, 1))
+    return {path: _line_ranges(lines) for path, lines in lines_by_path.items() if lines}
+
+
+def git_diff_line_ranges(path: str | Path, revision: str) -> dict[str, list[tuple[int, int]]]:
+    """Find the lines of the files under a path that changed since a git revision.
+
+    Args:
+        path: File or directory inside a git work tree
+        revision: Revision to compare the work tree with (`HEAD~1`, a branch, ...)
+
+    Returns:
+        Changed line ranges (see `diff_line_ranges`) by file path, relative to
+        `path` when it is a directory and to its directory when it is a file
+
+    Raises:
+        FileNotFoundError: If the path does not exist
+        ValueError: If git fails, e.g. outside a work tree or for an unknown revision
+    """
+    path = Path(path)
+    if not path.exists():
+        raise FileNotFoundError(f"Path not found: {path}")
+    directory, pathspec = (path, ".") if path.is_dir() else (path.parent, path.name)
+    command = ["git", "-C", str(directory), "diff", "--relative", "--no-color", "--no-ext-diff"]
+    try:
+        result = subprocess.run(
+            [*command, "-U0", revision, "--", pathspec],
+            capture_output=True,
+            text=True,
+            check=True,
+        )
+    except (OSError, subprocess.CalledProcessError) as e:
+        detail = getattr(e, "stderr", None) or str(e)
+        raise ValueError(f"git diff against {revision} failed: {detail.strip()}") from e
+    return diff_line_ranges(result.stdout)
+
+
+def _diff_path(header: str) -> str | None:
+    """Return the file path of a `+++` header, or None for a deleted file."""
+    path = header.split("\t", 1)[0].strip()
+    if path.startswith('"') and path.endswith('"'):
+        path = path[1:-1]
+    if path == "/dev/null":
+        return None
+    return path.removeprefix("b/")
+
+
+def _line_ranges(lines: set[int]) -> list[tuple[int, int]]:
+    """Group line numbers into sorted, disjoint ranges of consecutive lines."""
+    ranges: list[tuple[int, int]] = []
+    for line in sorted(lines):
+        if ranges and line == ranges[-1][1] + 1:
+            ranges[-1] = (ranges[-1][0], line)
+        else:
+            ranges.append((line, line))
+    return ranges
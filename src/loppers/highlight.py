@@ -0,0 +1,119 @@
+"""ANSI coloring of skeletons for terminals.
+
+Skeletons are parsed again as source of their language, and each token is colored
+by the class tree-sitter gives it: anonymous word tokens are keywords, nodes named
+as types are type names, and body placeholders (`// 6 lines`, Python's `...`)
+stand out from other comments. Coloring only adds escape sequences around tokens,
+so stripping them gives the skeleton back.
+"""
+
+from __future__ import annotations
+
+import re
+from collections.abc import Iterator
+
+from tree_sitter import Node
+
+from loppers.errors import LopError
+from loppers.loppers import get_extractor
+
+# Escape sequences starting each token class
+ANSI_COLORS: dict[str, str] = {
+    "keyword": "\x1b[35m",
+    "type": "\x1b[36m",
+    "string": "\x1b[32m",
+    "comment": "\x1b[2m",
+    "placeholder": "\x1b[33m",
+    "header": "\x1b[1m",
+}
+
+ANSI_RESET: str = "\x1b[0m"
+
+# Leaf node types naming types, beyond those ending in "_type" (`primitive_type`)
+_TYPE_NODE_TYPES: frozenset[str] = frozenset({"type_identifier", "sized_type_specifier"})
+
+# Comments standing for elided code, as placeholders render them
+_PLACEHOLDER_COMMENT = re.compile(r"^\W*(\d+ lines?\b|\.\.\.)")
+
+_KEYWORD = re.compile(r"[A-Za-z_]\w*")
+
+
+def highlight(skeleton: str, language: str) -> str:
+    """Color the tokens of a skeleton with ANSI escape sequences (see `ANSI_COLORS`).
+
+    Escape sequences never span a line break, so each line reads on its own, as
+    in a pager. Skeletons of languages without a parser, such as Markdown
+    documents, are returned unchanged.
+
+    Args:
+        skeleton: Skeleton text, as extracted
+        language: Programming language identifier of the skeleton
+
+    Returns:
+        The skeleton with escape sequences around its keywords, type names,
+        strings, comments and placeholders
+    """
+    try:
+        extractor = get_extractor(language)
+    except LopError:
+        return skeleton
+    source = skeleton.encode()
+    tree = extractor.parse(source)
+    parts: list[str] = []
+    position = 0
+    for node, token_class in _tokens(tree.root_node, source):
+        if node.start_byte < position:
+            continue
+        parts.append(source[position : node.start_byte].decode(errors="replace"))
+        text = source[node.start_byte : node.end_byte].decode(errors="replace")
+        parts.append(paint(text, token_class))
+        position = node.end_byte
+    parts.append(source[position:].decode(errors="replace"))
+    return "".join(parts)
+
+
+def paint(text: str, token_class: str) -> str:
+    """Wrap each line of a text in the escape sequences of a token class.
+
+    Args:
+        text: Text to color
+        token_class: Key of `ANSI_COLORS`
+
+    Returns:
+        The colored text
+    """
+    color = ANSI_COLORS[token_class]
+    return "\n".join(f"{color}{line}{ANSI_RESET}" if line else line for line in text.split("\n"))
+
+
+def _tokens(root: Node, source: bytes) -> Iterator[tuple[Node, str]]:
+    """Yield the tokens of a tree worth a color with their class, in source order."""
+    pending: list[Node] = [root]
+    while pending:
+        node = pending.pop()
+        token_class = _token_class(node, source)
+        if token_class is not None:
+            yield node, token_class
+        elif node.child_count:
+            pending.extend(reversed(node.children))
+
+
+def _token_class(node: Node, source: bytes) -> str | None:
+    """Classify a node as a token, or return None to classify its children instead."""
+    if "comment" in node.type:
+        text = source[node.start_byte : node.end_byte].decode(errors="replace")
+        return "placeholder" if _PLACEHOLDER_COMMENT.match(text) else "comment"
+    if node.type == "ellipsis":
+        return "placeholder"
+    if node.is_named and "string" in node.type:
+        return "string"
+    if node.child_count:
+        return None
+    if not node.is_named:
+        text = source[node.start_byte : node.end_byte].decode(errors="replace")
+        return "keyword" if _KEYWORD.fullmatch(text) else None
+    if node.type in _TYPE_NODE_TYPES or node.type.endswith("_type"):
+        return "type"
+    if node.parent is not None and node.parent.type == "type":
+        return "type"
+    return None
@@ -0,0 +1,263 @@
+"""Skeletons kept current across edits of a source, such as a file open in an editor.
+
+Reparsing a large file on every keystroke is wasteful: tree-sitter can reuse the
+unchanged parts of the previous syntax tree once it is told what was edited.
+"""
+
+from __future__ import annotations
+
+from collections import Counter
+from dataclasses import dataclass, field
+
+from tree_sitter import Tree
+
+from loppers.loppers import LopperOptions, SkeletonExtractor, get_extractor
+from loppers.symbol_model import Symbol, symbol_nodes
+
+# Kinds, names and traits of a symbol and its ancestors, from the top level down
+_SymbolPath = tuple[tuple[str, str, str | None], ...]
+
+# Identity of a symbol across edits: its path, and how many symbols with the same
+# path come before it
+_SymbolKey = tuple[_SymbolPath, int]
+
+
+@dataclass(frozen=True)
+class InputEdit:
+    """An edit of a source, in the terms of tree-sitter's `Tree.edit`.
+
+    Offsets are in bytes of the UTF-8 encoded source, and points are 0-based
+    (row, byte column) pairs.
+
+    Attributes:
+        start_byte: Offset of the start of the edit
+        old_end_byte: Offset of the end of the replaced text, in the old source
+        new_end_byte: Offset of the end of the inserted text, in the new source
+        start_point: Point of `start_byte`
+        old_end_point: Point of `old_end_byte`, in the old source
+        new_end_point: Point of `new_end_byte`, in the new source
+    """
+
+    start_byte: int
+    old_end_byte: int
+    new_end_byte: int
+    start_point: tuple[int, int]
+    old_end_point: tuple[int, int]
+    new_end_point: tuple[int, int]
+
+    @classmethod
+    def replacing(cls, source: str, start_byte: int, end_byte: int, text: str) -> InputEdit:
+        """Describe the replacement of a byte range of a source with new text.
+
+        Args:
+            source: Source before the edit
+            start_byte: Offset of the start of the replaced range
+            end_byte: Offset of the end of the replaced range
+            text: Text inserted in place of the range
+
+        Returns:
+            The edit
+
+        Raises:
+            ValueError: If the range is not within the source
+        """
+        encoded = source.encode()
+        if not 0 <= start_byte <= end_byte <= len(encoded):
+            msg = f"Invalid edit range {start_byte}..{end_byte} of a {len(encoded)}-byte source"
+            raise ValueError(msg)
+        start_point = _point(encoded, start_byte)
+        inserted = text.encode()
+        row, column = start_point
+        if b"\n" in inserted:
+            new_end_point = (row + inserted.count(b"\n"), len(inserted) - inserted.rfind(b"\n") - 1)
+        else:
+            new_end_point = (row, column + len(inserted))
+        return cls(
+            start_byte=start_byte,
+            old_end_byte=end_byte,
+            new_end_byte=start_byte + len(inserted),
+            start_point=start_point,
+            old_end_point=_point(encoded, end_byte),
+            new_end_point=new_end_point,
+        )
+
+
+@dataclass
+class SymbolChanges:
+    """How the symbols of a source changed with an edit.
+
+    Symbols are matched by their path (the kinds, names and traits of the symbol and
+    its ancestors) and, among symbols with the same path, by order.
+
+    Attributes:
+        added: Symbols of the new source without a match in the old one
+        removed: Symbols of the old source without a match in the new one
+        changed: Symbols of the new source whose definition text differs from that of
+            their match, such as a method whose body was edited and the `impl` block
+            holding it
+    """
+
+    added: list[Symbol] = field(default_factory=list)
+    removed: list[Symbol] = field(default_factory=list)
+    changed: list[Symbol] = field(default_factory=list)
+
+
+class IncrementalLopper:
+    """The skeleton of one source, re-extracted after each edit by reparsing incrementally.
+
+    The skeleton is rendered from the whole updated tree, so it is always the same
+    as a fresh extraction of the edited source; only parsing is incremental. An
+    instance holds the encoded source and its syntax tree, which grows linearly with
+    the source, for as long as it lives, along with the source and tree before the
+    last edit (for `symbol_changes`). Parsers and compiled queries are not held: they
+    come from `get_extractor`, shared by every source of a language and set of
+    options in the calling thread.
+
+    Attributes:
+        language: Language of the source
+        options: Extraction options
+        skeleton: Skeleton of the current source
+    """
+
+    def __init__(self, source: str, language: str, *, options: LopperOptions | None = None) -> None:
+        """Parse a source and extract its skeleton.
+
+        Args:
+            source: Source code to process
+            language: Programming language identifier
+            options: Extraction options (defaults to `LopperOptions()`)
+
+        Raises:
+            UnsupportedLanguageError: If language is not supported
+            ParseFailedError: If the source could not be parsed, or has syntax errors
+                and `on_error` is "fail"
+        """
+        self.language: str = language
+        self.options: LopperOptions = options or LopperOptions()
+        extractor = get_extractor(language, self.options)
+        self._source: bytes = source.encode()
+        self._tree: Tree = extractor.parse(self._source)
+        self._previous: tuple[bytes, Tree] | None = None
+        self.skeleton: str = extractor.extract(source, tree=self._tree)
+
+    @property
+    def source(self) -> str:
+        """Current source."""
+        return self._source.decode()
+
+    def edit(self, edit: InputEdit, new_source: str) -> str:
+        """Apply an edit of the source and return the new skeleton.
+
+        Args:
+            edit: Edit turning the current source into the new one
+            new_source: Source after the edit
+
+        Returns:
+            Skeleton of the new source
+
+        Raises:
+            ValueError: If the edit does not turn the current source into one of the
+                new source's length
+            ParseFailedError: If the new source could not be parsed, or has syntax
+                errors and `on_error` is "fail"; the previous source is kept
+        """
+        encoded = new_source.encode()
+        removed = edit.old_end_byte - edit.start_byte
+        inserted = edit.new_end_byte - edit.start_byte
+        in_range = 0 <= edit.start_byte <= edit.old_end_byte <= len(self._source)
+        if not in_range or inserted < 0 or len(encoded) != len(self._source) - removed + inserted:
+            raise ValueError("Edit does not match the current and new sources")
+
+        extractor = get_extractor(self.language, self.options)
+        # Edit a copy, so a failed parse leaves the current tree usable
+        old_tree = self._tree.copy()
+        old_tree.edit(
+            start_byte=edit.start_byte,
+            old_end_byte=edit.old_end_byte,
+            new_end_byte=edit.new_end_byte,
+            start_point=edit.start_point,
+            old_end_point=edit.old_end_point,
+            new_end_point=edit.new_end_point,
+        )
+        tree = extractor.parse(encoded, old_tree)
+        skeleton = extractor.extract(new_source, tree=tree)
+        self._previous = (self._source, self._tree)
+        self._source, self._tree, self.skeleton = encoded, tree, skeleton
+        return skeleton
+
+    def apply_edit(self, start_byte: int, end_byte: int, text: str) -> str:
+        """Replace a byte range of the source with new text and return the new skeleton.
+
+        Args:
+            start_byte: Offset of the start of the replaced range in the encoded source
+            end_byte: Offset of the end of the replaced range
+            text: Text inserted in place of the range
+
+        Returns:
+            Skeleton of the new source
+
+        Raises:
+            ValueError: If the range is not within the source, or splits a character
+            ParseFailedError: If the new source could not be parsed, or has syntax
+                errors and `on_error` is "fail"; the previous source is kept
+        """
+        edit = InputEdit.replacing(self.source, start_byte, end_byte, text)
+        try:
+            new_source = (
+                self._source[:start_byte].decode() + text + self._source[end_byte:].decode()
+            )
+        except UnicodeDecodeError as e:
+            raise ValueError(f"Edit range {start_byte}..{end_byte} splits a character") from e
+        return self.edit(edit, new_source)
+
+    def symbol_changes(self) -> SymbolChanges:
+        """Compare the symbols of the source before and after the last edit.
+
+        Returns:
+            Symbols added, removed and changed by the last edit, each in source order;
+            nothing before the first edit
+
+        Raises:
+            UnsupportedLanguageError: If the language has no symbol support
+        """
+        if self._previous is None:
+            return SymbolChanges()
+        extractor = get_extractor(self.language, self.options)
+        old = _keyed_symbols(extractor, self._previous[1], self._previous[0])
+        new = _keyed_symbols(extractor, self._tree, self._source)
+        return SymbolChanges(
+            added=[symbol for key, (symbol, _) in new.items() if key not in old],
+            removed=[symbol for key, (symbol, _) in old.items() if key not in new],
+            changed=[
+                symbol for key, (symbol, text) in new.items() if key in old and old[key][1] != text
+            ],
+        )
+
+
+def _keyed_symbols(
+    extractor: SkeletonExtractor, tree: Tree, source: bytes
+) -> dict[_SymbolKey, tuple[Symbol, bytes]]:
+    """Map the symbols of a tree, in source order, to themselves and their text."""
+    pairs = symbol_nodes(extractor, tree, source)
+    nested = {id(child) for symbol, _ in pairs for child in symbol.children}
+    nodes = {id(symbol): node for symbol, node in pairs}
+    seen: Counter[_SymbolPath] = Counter()
+    keyed: dict[_SymbolKey, tuple[Symbol, bytes]] = {}
+
+    def visit(symbol: Symbol, path: _SymbolPath) -> None:
+        path = (*path, (symbol.kind, symbol.name, symbol.trait))
+        node = nodes[id(symbol)]
+        keyed[path, seen[path]] = (symbol, source[node.start_byte : node.end_byte])
+        seen[path] += 1
+        for child in symbol.children:
+            visit(child, path)
+
+    for symbol, _ in pairs:
+        if id(symbol) not in nested:
+            visit(symbol, ())
+    return keyed
+
+
+def _point(source: bytes, offset: int) -> tuple[int, int]:
+    """Return the (row, byte column) point of an offset of encoded source."""
+    return source.count(b"\n", 0, offset), offset - (source.rfind(b"\n", 0, offset) + 1)
@@ -6,10 +6,51 @@ Compatible with tree-sitter >= 0.25
 
 from __future__ import annotations
 
-from dataclasses import dataclass
+import hashlib
+import json
+import re
+import threading
+from bisect import bisect_left, bisect_right
+from collections import Counter, OrderedDict
+from collections.abc import Callable, Iterator, Mapping, Sequence
+from dataclasses import dataclass, field, fields, replace
+from fnmatch import fnmatchcase
+from itertools import groupby
+from typing import Any, Literal
 
 import tree_sitter_language_pack
-from tree_sitter import Language, Node, Parser, Query, QueryCursor, Tree
+from tree_sitter import Language, Node, Parser, Query, QueryCursor, QueryError, Tree
+
+from loppers.errors import InvalidQueryError, ParseFailedError, UnsupportedLanguageError
+from loppers.symbol_model import (
+    IMPL_TARGET_KINDS,
+    Symbol,
+    base_name,
+    match_symbols,
+    root_symbols,
+    symbol_key,
+    symbol_nodes,
+)
+
+# Kind letters of ctags tag files by symbol kind, for languages that do not set their own
+DEFAULT_CTAGS_KINDS: dict[str, str] = {
+    "class": "c",
+    "constant": "C",
+    "constructor": "m",
+    "enum": "g",
+    "function": "f",
+    "impl": "c",
+    "interface": "t",
+    "macro": "d",
+    "method": "m",
+    "module": "n",
+    "static": "v",
+    "struct": "s",
+    "trait": "t",
+    "type": "t",
+    "union": "u",
+    "variable": "v",
+}
 
 
 @dataclass
@@ -19,10 +60,882 @@ class LanguageConfig:
     Attributes:
         name: Language identifier (e.g., "python", "javascript")
         body_query: Tree-sitter query to find function/method bodies to remove
+            (`@body`), and macro definitions whose rules are removed between their
+            delimiters (`@macro`)
+        doc_comment_query: Tree-sitter query capturing doc comments and docstrings
+            (`@doc`); in languages without a doc comment syntax, the comments above
+            a definition captured with it as `@item`, of which only those on the
+            lines directly above it document it
+        attribute_query: Tree-sitter query capturing attributes, annotations and decorators
+        symbol_query: Tree-sitter query capturing named definitions (`@definition.<kind>`,
+            `@name` and optionally `@body` when the body is not the node's `body` field)
+        placeholder: Template for elided-body placeholders; `{lines}` expands to the
+            elided line count (e.g., "4 lines")
+        type_body_query: Tree-sitter query capturing type bodies (`@body`: struct fields,
+            enum variants, interface members) and class-level fields (`@field`), elided
+            only when collapsing types
+        member_list_query: Tree-sitter query capturing the brace-delimited field lists
+            of structs and unions (`@fields`) and the variant lists of enums
+            (`@variants`), handled by `struct_fields` and `enum_variants`
+        trait_impl_query: Tree-sitter query capturing the item lists (`@body`) of
+            trait implementations and the traits they implement (`@trait`), handled
+            by `collapse_known_impls`
+        body_stub: Statement standing in for elided bodies when placeholders are off,
+            for languages where an empty body is a syntax error (Python's `...`)
+        stub_mode_body: Expression standing in for elided bodies with `stub_mode`,
+            type-checking whatever the function returns (Rust's `unimplemented!()`)
+        stub_mode_query: Tree-sitter query capturing the function bodies (`@body`)
+            cut from the end of their declarator (`@declarator`) with `stub_mode`,
+            as `prototype_query` (the default methods of Rust traits)
+        import_query: Tree-sitter query capturing import statements (`@import`) and,
+            when a statement imports several items, each of them (`@item`)
+        test_query: Tree-sitter query capturing test items (`@test`), or attributes
+            that make the item they precede a test when `_is_test_attribute` accepts
+            them (`@attr`)
+        prototype_query: Tree-sitter query capturing function bodies (`@body`) that
+            become prototypes, cut from the end of their declarator (`@declarator`)
+        literal_query: Tree-sitter query capturing string (`@string`) and numeric
+            (`@number`) literals, redacted when redacting literals, and array
+            (`@array`) and map (`@map`) literals, truncated with strings when longer
+            than `max_literal_len`
+        closure_query: Tree-sitter query capturing anonymous functions (`@closure`)
+            and their bodies (`@body`), listed as symbols when keeping closures
+        branch_query: Tree-sitter query capturing the branches of bodies
+            (`@branch`: conditionals, loops, match arms, exception handlers), counted
+            by `annotate_complexity`
+        alias_query: Tree-sitter query capturing type alias declarations (`@alias`,
+            with `name`, `type_parameters` and `type` or `value` fields), expanded
+            by `expand_aliases`
+        verbatim_query: Tree-sitter query capturing code kept verbatim, the bodies
+            it holds included, whose definitions are no symbols (`@verbatim`: the
+            class-level DSL blocks of Ruby, `has_many :items do ... end`)
+        line_comment: Token starting a line comment, used for import summaries and
+            test markers
+        path_separator: Separator between the names of the qualified names of
+            symbols (`outer::inner::run` in Rust, `Outer.Inner.run` in Python)
+        block_comment: Tokens opening and closing a comment that may end mid-line,
+            or None when comments run to the end of the line
+        ctags_kinds: Kind letters of the symbols in ctags tag files, by symbol kind
+            (kinds missing from it are tagged with their first letter)
+        grammar: Tree-sitter grammar of the language; None loads the grammar named
+            `name` from tree-sitter-language-pack (see `register_language` for
+            languages with grammars of their own)
     """
 
     name: str
     body_query: str
+    doc_comment_query: str | None = None
+    attribute_query: str | None = None
+    symbol_query: str | None = None
+    placeholder: str = "/* {lines} */"
+    type_body_query: str | None = None
+    member_list_query: str | None = None
+    trait_impl_query: str | None = None
+    body_stub: str | None = None
+    stub_mode_body: str | None = None
+    stub_mode_query: str | None = None
+    import_query: str | None = None
+    test_query: str | None = None
+    prototype_query: str | None = None
+    literal_query: str | None = None
+    closure_query: str | None = None
+    branch_query: str | None = None
+    alias_query: str | None = None
+    verbatim_query: str | None = None
+    line_comment: str = "//"
+    path_separator: str = "."
+    block_comment: tuple[str, str] | None = ("/*", "*/")
+    ctags_kinds: dict[str, str] = field(default_factory=lambda: dict(DEFAULT_CTAGS_KINDS))
+    grammar: Language | None = None
+
+
+@dataclass(frozen=True)
+class LopperOptions:
+    """Options controlling skeleton extraction.
+
+    Attributes:
+        keep_doc_comments: Keep doc comments (`///`, `/** */`, JSDoc), Python docstrings
+            and the comments directly above Bash functions
+        keep_attributes: Keep attributes (`#[derive]`), annotations and decorators.
+            `#[macro_export]` is always kept, as it makes a Rust macro public
+        max_attr_len: Replace the argument lists of kept attributes, annotations and
+            decorators longer than this many characters with `(...)`
+            (`#[derive(...)]`); shorter ones are kept verbatim, however many lines
+            they span
+        max_signature_len: Shorten the signatures of functions longer than this many
+            characters: a Rust `where` clause collapses to `where /* ... */`, then
+            the type parameter list to a count (`</* +12 more */>`), and then the
+            parameter list keeps the leading parameters that fit, followed by a
+            comment counting the others (`/* +47 more */`). Whole parameters are
+            cut, and the name and return type never, so a signature may stay longer
+            than the limit
+        max_body_lines: Keep bodies spanning at most this many source lines verbatim
+        body_placeholders: Replace elided bodies with a placeholder recording how many
+            source lines were removed (`{ /* 4 lines */ }`, `...  # 4 lines` in Python)
+        placeholder: Text replacing each elided body instead of the language default,
+            either a string (where `{lines}` expands to e.g. "4 lines" and `{count}` to
+            4) or a function of the elided line count. Brace-delimited bodies keep their
+            braces around it. Takes precedence over `body_placeholders`
+        placeholder_comment: Text of a comment replacing each elided body, written in
+            the language's placeholder syntax (`{ /* text */ }`, `...  # text` in
+            Python, `-- text` in Lua); `{lines}` and `{count}` expand as in
+            `placeholder`. Takes precedence over `body_placeholders`, and cannot be
+            combined with `placeholder`
+        body_braces: Placement of the placeholder of elided brace-delimited bodies:
+            "inline" puts it between the braces on the header line
+            (`fn f() { /* 4 lines */ }`) and "block" on its own line, one level
+            deeper, with the closing brace on the next line. Bodies elided without a
+            placeholder stay `{}`
+        annotate_sizes: End the line each elided function body leaves its header on
+            with a comment counting the lines of the original body, braces included
+            (`fn fibonacci(n: u32) -> u32 { // 6 lines`, `{ /* 6 lines */ } // 6 lines`
+            with `body_placeholders`, `...  # 4 lines` in Python); the comment is a
+            block comment when code follows the body on its line. Bodies kept by
+            `max_body_lines` or cut by `body_head_lines` are not annotated
+        annotate_complexity: Add the number of branches of each elided body to its
+            `annotate_sizes` comment (`// 6 lines, 1 branch`): conditionals, loops,
+            match arms and exception handlers, not counting those of functions nested
+            in the body. Languages without a branch query only get the line count
+        visibility: Least visibility of the items kept: "all" keeps every item; "crate"
+            drops private items (Rust items without `pub`, `_`-prefixed Python names,
+            lowercase Go names, items declared `private` and `#`-prefixed members) along
+            with their doc comments, attributes and nested items; "public" also drops
+            crate-visible items (`pub(crate)`, `pub(super)`, `internal`). Trait items are
+            implicitly public, and methods of an `impl` block are filtered one by one,
+            the `impl` blocks of dropped types going with them. Languages without symbol
+            support are not filtered.
+        prototypes: Turn elided C/C++ function definitions into prototypes ending in
+            `;` (constructor initializer lists go too), so a translation unit reads
+            like a header
+        stub_mode: Keep Rust skeletons compiling as a crate: elided function bodies
+            become `{ unimplemented!() }`, the default methods of traits become
+            declarations ending in `;` (so implementations relying on them no longer
+            compile) and macro rules are kept. Signatures with their generics,
+            lifetimes and where clauses, `use` items, type definitions and the
+            initializers of constants and statics are left untouched, so the options
+            changing them cannot be combined with it. Functions returning `impl
+            Trait` do not type-check, as `unimplemented!()` returns no value of the
+            trait. Languages without a stub body are not affected
+        keep_bodies: Patterns of items whose bodies are kept verbatim, nested items
+            included: names (`fibonacci`), qualified paths (`Calculator::add`, matching
+            the items of inherent and trait `impl` blocks alike) or globs (`test_*`),
+            as matched by `match_symbols`. Other options still apply inside kept
+            bodies (doc comments, literals)
+        max_depth: Keep items nested at most this deep, counting levels from 0 (0 keeps
+            top-level items only, 1 also the methods of their classes and `impl`
+            blocks, and so on), leaving a marker per run of hidden items
+            (`// 3 items hidden`) that counts the outermost ones. Nested modules and
+            classes count as levels, items dropped by `visibility` are not counted,
+            and items whose bodies are kept by `keep_bodies` keep their nested items.
+            Languages without symbol support are not limited
+        collapse_types: Elide type bodies (struct fields, enum variants, trait items,
+            interface members, Python class-level fields), keeping only the type headers
+        struct_fields: Handling of the fields of structs and unions (Rust, Go, C):
+            "keep" keeps them, "collapse" replaces each field list with a count
+            (`pub struct Config { /* 40 fields */ }`) and "public" drops the fields
+            that are not public (Rust fields without `pub`, `pub(crate)` ones too, and
+            unexported Go fields). Ignored when `collapse_types` elides the bodies
+        enum_variants: Handling of enum variants (Rust, TypeScript, C): "keep"
+            keeps them and "collapse" replaces each variant list with a count
+            (`enum Color { /* 3 variants */ }`)
+        collapse_known_impls: Collapse the Rust trait implementations made of
+            methods only to one line (`impl Display for Config { ... }`), as the
+            trait already declares their signatures. Implementations with associated
+            types or constants, or items of other kinds, keep their members, and so
+            do those holding an item `keep_bodies` keeps
+        collapse_impl_traits: Patterns of traits whose implementations are collapsed
+            whatever items they hold, with or without `collapse_known_impls`: names
+            (`Display`, also matching `fmt::Display` and `From<A>`), paths or globs
+        keep_impl_traits: Patterns of traits whose implementations are never
+            collapsed, matched like `collapse_impl_traits`
+        keep_macro_rules: Keep the rules of Rust `macro_rules!` definitions, elided
+            like function bodies by default. Macro invocations at item position
+            (`lazy_static! { ... }`, `thread_local! { ... }`) are always kept whole, as
+            they often declare statics
+        body_head_lines: Keep the leading statements of each elided body that fit in
+            this many lines and mark the rest as elided (`/* ... */`, `...` in Python).
+            Only whole statements are kept, so braces stay balanced
+        imports: Handling of import statements (Rust `use`, Python `import`, JS
+            `import` and `require`): "keep" keeps them, "drop" removes them, and
+            "collapse" replaces each group of imports not separated by a blank line
+            with a summary comment (`// 14 imports omitted`); a lone import is kept,
+            as its summary would be no shorter
+        keep_section_comments: Keep the section comments (`// ---- Parsing ----`,
+            `# SECTION: networking`, `// MARK: - Views`) directly above items dropped
+            by `visibility`, `strip_tests` or `max_depth`, which otherwise go with
+            the item like its doc comments; comments above them stay too. Comments
+            separated from the item below by a blank line are always kept
+        section_comment_pattern: Regular expression searched in the text of a
+            comment, delimiters included, telling section comments apart instead of
+            the default of banners (runs of three or more of `-=*#~_+/`) and comments
+            starting with `SECTION:`, `MARK:`, `#region` or `#endregion`
+        strip_tests: Remove test code, leaving a marker comment per run of it
+            (`// tests omitted (3 items)`): Rust items marked `#[test]` or `#[bench]`
+            or gated by `#[cfg(test)]` (also inside `all(...)`), Python `test_`
+            functions and `TestCase` subclasses, Go `Test`/`Benchmark`/`Fuzz`/`Example`
+            functions and top-level JS/TS `describe`/`it`/`test` calls
+        skip_tests: Leave the items `strip_tests` removes out of symbol outlines,
+            with the symbols nested in them (see `extract_symbols`), and the files of
+            JS/TS `__tests__` directories out of the tag files of directories.
+            Skeletons are not affected
+        empty_containers: Handling of the modules, `impl` blocks, classes, traits
+            and interfaces whose items `visibility` or `strip_tests` all dropped:
+            "keep" leaves their header and braces, "drop" removes them with their
+            doc comments and attributes, and "collapse" puts them on one line
+            (`impl Calculator { /* all items filtered */ }`). Fields, re-exports and
+            other statements left keep a container. A container whose items are
+            all such containers counts as emptied too
+        dedup_symbols: Collapse runs of consecutive sibling items repeating the
+            first one of the run into that item and a note counting the others
+            (`// +3 similar`), as generated code and platform variants repeat:
+            "off" keeps them all, "signature" groups items of the same kind whose
+            signatures only differ by their name, and "name" items of the same kind
+            and name. Items with nested items are never grouped, and imports are
+            not items: runs of `use` or `import` statements stay (see `imports`)
+        redact_literals: Replace string literals with a quoted ellipsis and numeric
+            literals with `0` in the kept code (default values, constant initializers,
+            kept bodies). Literals of doc comments, attributes and imports are kept
+        max_literal_len: Truncate array, map and string literals of the kept code
+            longer than this many bytes, such as the tables of generated files:
+            arrays and maps keep the leading elements that fit, followed by a comment
+            counting the elided bytes (`&[1, 2, /* 10240 bytes elided */]`), and
+            strings their leading text with the count inside the quotes. Literals of
+            doc comments, attributes, imports and items whose bodies are kept by
+            `keep_bodies` are left whole
+        expand_aliases: Experimental, best-effort: replace the references to type
+            aliases declared in the same source with their definitions, outside
+            bodies (`fn load() -> Result<Config>` becomes `fn load() ->
+            std::result::Result<Config, Error>` under `type Result<T> = ...`).
+            Aliases declared more than once, references with the wrong number of
+            type arguments and references to an alias within its own expansion are
+            left as they are. Rust, TypeScript and Go aliases are expanded
+        query: Tree-sitter query handling nodes the built-in queries do not, through
+            the names of its captures: `@keep.whole` keeps a node verbatim (its
+            bodies and nested items included), `@keep.signature` elides the `body`
+            field of a node and `@prune.body` elides a node as a body. Captures
+            prefixed with `_` are left for predicates. Checked when an extractor is
+            built (see `QUERY_CAPTURES`)
+        line_numbers: Prefix each line with a gutter holding the number of the source
+            line it comes from, right-aligned to the widest line number of the source
+        indent_width: Re-indent the skeleton with this many spaces per nesting level
+            of the symbol tree rather than keeping the source's indentation: each
+            item is indented by how many symbols enclose it, and the other lines of an
+            item by their levels of source indentation past its first line (columns
+            short of a level, such as the alignment of ` * ` comment lines, are
+            kept). Lines inside multi-line strings other than docstrings keep their
+            text. Languages without symbol support are re-indented by source levels
+        sort: Order of the items: "source" keeps the order of the source and
+            "alphabetical" sorts the items of each level of the symbol tree by name
+            (top-level items, then the methods of each class or `impl` block apart),
+            ties broken by position. Items move with their doc comments and
+            attributes, while the text between them (blank lines, loose comments,
+            statements) stays in place. Levels whose items share lines, and languages
+            without symbol support, keep the source order
+        on_error: Handling of syntax errors: "passthrough" keeps the regions that did
+            not parse verbatim, "drop" removes them (keeping the well-formed items they
+            contain), and "fail" raises `ParseFailedError`. Bodies containing errors are
+            never elided
+        notebook_format: Rendering of Jupyter notebooks: "text" flattens their cells
+            into one text, each under a `# %% [cell N]` line, and "json" writes the
+            cleaned notebook
+        language_options: Option values overriding these for the sources of some
+            languages, by language identifier (`{"python": {"imports": "drop"}}`),
+            such as the per-language tables of a config file (see `load_config`).
+            A mapping is accepted; it is kept as sorted pairs, so options stay
+            hashable. Options derived with `dataclasses.replace` keep the overrides
+    """
+
+    keep_doc_comments: bool = True
+    keep_attributes: bool = True
+    max_attr_len: int | None = None
+    max_signature_len: int | None = None
+    max_body_lines: int | None = None
+    body_placeholders: bool = False
+    placeholder: str | Callable[[int], str] | None = None
+    placeholder_comment: str | None = None
+    body_braces: Literal["inline", "block"] = "inline"
+    annotate_sizes: bool = False
+    annotate_complexity: bool = False
+    visibility: Literal["all", "crate", "public"] = "all"
+    keep_bodies: tuple[str, ...] = ()
+    max_depth: int | None = None
+    collapse_types: bool = False
+    struct_fields: Literal["keep", "collapse", "public"] = "keep"
+    enum_variants: Literal["keep", "collapse"] = "keep"
+    collapse_known_impls: bool = False
+    collapse_impl_traits: tuple[str, ...] = ()
+    keep_impl_traits: tuple[str, ...] = ()
+    keep_macro_rules: bool = False
+    prototypes: bool = False
+    stub_mode: bool = False
+    body_head_lines: int | None = None
+    imports: Literal["keep", "drop", "collapse"] = "keep"
+    keep_section_comments: bool = False
+    section_comment_pattern: str | None = None
+    strip_tests: bool = False
+    skip_tests: bool = False
+    empty_containers: Literal["keep", "drop", "collapse"] = "keep"
+    dedup_symbols: Literal["off", "signature", "name"] = "off"
+    redact_literals: bool = False
+    max_literal_len: int | None = None
+    expand_aliases: bool = False
+    query: str | None = None
+    line_numbers: bool = False
+    indent_width: int | None = None
+    sort: Literal["source", "alphabetical"] = "source"
+    on_error: Literal["passthrough", "drop", "fail"] = "passthrough"
+    notebook_format: Literal["text", "json"] = "text"
+    language_options: tuple[tuple[str, tuple[tuple[str, Any], ...]], ...] = ()
+
+    def __post_init__(self) -> None:
+        """Validate option values.
+
+        Raises:
+            ValueError: If an option has an invalid value
+        """
+        if self.visibility not in ("all", "crate", "public"):
+            msg = f"Invalid visibility '{self.visibility}'. Expected 'all', 'crate' or 'public'"
+            raise ValueError(msg)
+        for name in ("placeholder", "placeholder_comment"):
+            template = getattr(self, name)
+            if isinstance(template, str):
+                try:
+                    template.format(lines="1 line", count=1)
+                except (IndexError, KeyError, ValueError) as e:
+                    msg = f"Invalid {name} '{template}': {e!r}"
+                    raise ValueError(msg) from e
+        if self.section_comment_pattern is not None:
+            try:
+                re.compile(self.section_comment_pattern)
+            except re.error as e:
+                msg = f"Invalid section_comment_pattern '{self.section_comment_pattern}': {e}"
+                raise ValueError(msg) from e
+        if self.placeholder is not None and self.placeholder_comment is not None:
+            raise ValueError("placeholder and placeholder_comment cannot be combined")
+        for name in ("keep_bodies", "collapse_impl_traits", "keep_impl_traits"):
+            patterns = getattr(self, name)
+            if isinstance(patterns, str):
+                raise ValueError(f"{name} must be a sequence of patterns, got '{patterns}'")
+            # Lists are accepted too, but options must stay hashable
+            object.__setattr__(self, name, tuple(patterns))
+        if self.body_braces not in ("inline", "block"):
+            msg = f"Invalid body_braces '{self.body_braces}'. Expected 'inline' or 'block'"
+            raise ValueError(msg)
+        if self.annotate_complexity and not self.annotate_sizes:
+            raise ValueError("annotate_complexity requires annotate_sizes")
+        if self.max_depth is not None and self.max_depth < 0:
+            raise ValueError(f"max_depth must be non-negative, got {self.max_depth}")
+        if self.max_attr_len is not None and self.max_attr_len < 0:
+            raise ValueError(f"max_attr_len must be non-negative, got {self.max_attr_len}")
+        if self.max_signature_len is not None and self.max_signature_len < 0:
+            msg = f"max_signature_len must be non-negative, got {self.max_signature_len}"
+            raise ValueError(msg)
+        if self.max_literal_len is not None and self.max_literal_len < 0:
+            msg = f"max_literal_len must be non-negative, got {self.max_literal_len}"
+            raise ValueError(msg)
+        if self.body_head_lines is not None and self.body_head_lines < 0:
+            msg = f"body_head_lines must be non-negative, got {self.body_head_lines}"
+            raise ValueError(msg)
+        if self.indent_width is not None and self.indent_width < 1:
+            raise ValueError(f"indent_width must be positive, got {self.indent_width}")
+        if self.imports not in ("keep", "drop", "collapse"):
+            msg = f"Invalid imports '{self.imports}'. Expected 'keep', 'drop' or 'collapse'"
+            raise ValueError(msg)
+        if self.struct_fields not in ("keep", "collapse", "public"):
+            msg = (
+                f"Invalid struct_fields '{self.struct_fields}'. "
+                "Expected 'keep', 'collapse' or 'public'"
+            )
+            raise ValueError(msg)
+        if self.enum_variants not in ("keep", "collapse"):
+            msg = f"Invalid enum_variants '{self.enum_variants}'. Expected 'keep' or 'collapse'"
+            raise ValueError(msg)
+        if self.empty_containers not in ("keep", "drop", "collapse"):
+            msg = (
+                f"Invalid empty_containers '{self.empty_containers}'. "
+                "Expected 'keep', 'drop' or 'collapse'"
+            )
+            raise ValueError(msg)
+        if self.dedup_symbols not in ("off", "signature", "name"):
+            msg = (
+                f"Invalid dedup_symbols '{self.dedup_symbols}'. "
+                "Expected 'off', 'signature' or 'name'"
+            )
+            raise ValueError(msg)
+        if self.sort not in ("source", "alphabetical"):
+            msg = f"Invalid sort '{self.sort}'. Expected 'source' or 'alphabetical'"
+            raise ValueError(msg)
+        if self.on_error not in ("passthrough", "drop", "fail"):
+            msg = (
+                f"Invalid on_error '{self.on_error}'. Expected 'passthrough', 'drop' or 'fail'"
+            )
+            raise ValueError(msg)
+        if self.notebook_format not in ("text", "json"):
+            msg = f"Invalid notebook_format '{self.notebook_format}'. Expected 'text' or 'json'"
+            raise ValueError(msg)
+        if self.stub_mode:
+            conflicts: list[str] = [
+                name
+                for name, given in (
+                    ("collapse_types", self.collapse_types),
+                    ("placeholder", self.placeholder is not None),
+                    ("placeholder_comment", self.placeholder_comment is not None),
+                    ("body_head_lines", self.body_head_lines is not None),
+                    ("struct_fields", self.struct_fields != "keep"),
+                    ("enum_variants", self.enum_variants != "keep"),
+                    ("collapse_known_impls", self.collapse_known_impls),
+                    ("collapse_impl_traits", bool(self.collapse_impl_traits)),
+                    ("imports", self.imports != "keep"),
+                    ("redact_literals", self.redact_literals),
+                    ("max_literal_len", self.max_literal_len is not None),
+                )
+                if given
+            ]
+            if conflicts:
+                raise ValueError(f"stub_mode cannot be combined with {', '.join(conflicts)}")
+        languages: list[tuple[str, tuple[tuple[str, Any], ...]]] = []
+        for language, overrides in sorted(dict(self.language_options).items()):
+            if not isinstance(overrides, (Mapping, tuple)):
+                msg = f"Invalid language_options for '{language}': expected option values"
+                raise ValueError(msg)
+            values = dict(overrides)
+            if "language_options" in values:
+                raise ValueError(f"language_options for '{language}' cannot be nested")
+            # Checked through the options, which also turn lists into tuples
+            parsed = LopperOptions.from_dict(values)
+            replace(self, **values, language_options=())
+            normalized = [(name, getattr(parsed, name)) for name in sorted(values)]
+            languages.append((language, tuple(normalized)))
+        object.__setattr__(self, "language_options", tuple(languages))
+
+    def for_language(self, language: str) -> LopperOptions:
+        """Return the options applying to sources of a language (see `language_options`).
+
+        Args:
+            language: Language identifier
+
+        Returns:
+            These options with the overrides of the language, without any language
+            options; these options themselves when the language has no overrides
+        """
+        overrides = dict(self.language_options).get(language)
+        if overrides is None:
+            return self
+        return replace(self, **dict(overrides), language_options=())
+
+    def to_dict(self) -> dict[str, Any]:
+        """Convert the options to a dictionary of plain values, as read by `from_dict`.
+
+        Raises:
+            ValueError: If `placeholder` is a function, which has no plain value
+        """
+        if callable(self.placeholder):
+            raise ValueError("A placeholder function cannot be converted to a plain value")
+        data: dict[str, Any] = {option.name: getattr(self, option.name) for option in fields(self)}
+        for name in ("keep_bodies", "collapse_impl_traits", "keep_impl_traits"):
+            data[name] = list(getattr(self, name))
+        data["language_options"] = {
+            language: {
+                name: list(value) if isinstance(value, tuple) else value
+                for name, value in overrides
+            }
+            for language, overrides in self.language_options
+        }
+        return data
+
+    def fingerprint(self) -> str:
+        """Return a hash of the option values, equal for options extracting alike.
+
+        Raises:
+            ValueError: If `placeholder` is a function, which has no plain value
+        """
+        data = json.dumps(self.to_dict(), sort_keys=True)
+        return hashlib.sha256(data.encode()).hexdigest()
+
+    @classmethod
+    def builder(cls) -> LopperOptionsBuilder:
+        """Start building options with chainable setters (see `LopperOptionsBuilder`).
+
+        Returns:
+            A builder starting from the default options
+        """
+        return LopperOptionsBuilder()
+
+    @classmethod
+    def from_dict(cls, data: Mapping[str, Any]) -> LopperOptions:
+        """Build options from a dictionary, such as a parsed JSON or TOML config.
+
+        Missing options keep their defaults. Unknown options and values of the wrong
+        type are errors rather than being ignored, so a typo in a config is caught.
+
+        Args:
+            data: Option values by option name
+
+        Returns:
+            The options
+
+        Raises:
+            ValueError: If an option is unknown or has an invalid value
+        """
+        if not isinstance(data, Mapping):
+            raise ValueError(f"Invalid options: expected an object, got {data!r}")
+        names: list[str] = [option.name for option in fields(cls)]
+        unknown: list[str] = sorted(set(data) - set(names))
+        if unknown:
+            msg = f"Unknown options: {', '.join(unknown)}. Expected some of: {', '.join(names)}"
+            raise ValueError(msg)
+        values: dict[str, Any] = dict(data)
+        languages = values.pop("language_options", {})
+        if not isinstance(languages, Mapping):
+            raise ValueError(f"Invalid language_options {languages!r}: expected an object")
+        for name, value in values.items():
+            expected: type = _OPTION_TYPES.get(name) or type(getattr(_DEFAULT_OPTIONS, name))
+            if expected is tuple:
+                valid = isinstance(value, (list, tuple)) and all(
+                    isinstance(item, str) for item in value
+                )
+            else:
+                valid = isinstance(value, expected) and not (
+                    isinstance(value, bool) and expected is not bool
+                )
+            if not (valid or (value is None and name in _OPTION_TYPES)):
+                kind = _OPTION_TYPE_NAMES[expected]
+                raise ValueError(f"Invalid {name} {value!r}: expected {kind}")
+        return cls(**values, language_options=languages)
+
+
+# Value types of the options that default to None (the other options take the type of
+# their default)
+_OPTION_TYPES: dict[str, type] = {
+    "max_attr_len": int,
+    "max_signature_len": int,
+    "max_literal_len": int,
+    "max_body_lines": int,
+    "placeholder": str,
+    "placeholder_comment": str,
+    "max_depth": int,
+    "body_head_lines": int,
+    "indent_width": int,
+    "section_comment_pattern": str,
+    "query": str,
+}
+
+# Names of option value types in error messages
+_OPTION_TYPE_NAMES: dict[type, str] = {
+    bool: "a boolean",
+    int: "an integer",
+    str: "a string",
+    tuple: "a list of strings",
+}
+
+_DEFAULT_OPTIONS: LopperOptions = LopperOptions()
+
+
+class LopperOptionsBuilder:
+    """Builds `LopperOptions` one option at a time.
+
+    Every option has a setter of the same name taking its value and returning the
+    builder, so calls chain, and options added in later versions get theirs
+    without changes to calling code:
+
+        options = LopperOptions.builder().visibility("public").max_depth(2).build()
+
+    Values are checked when the options are built, as the constructor checks them.
+    """
+
+    def __init__(self, base: LopperOptions | None = None) -> None:
+        """Start from some options.
+
+        Args:
+            base: Options the values set override (defaults to `LopperOptions()`)
+        """
+        self._base: LopperOptions = base or _DEFAULT_OPTIONS
+        self._values: dict[str, Any] = {}
+        self._languages: dict[str, dict[str, Any]] = {
+            language: dict(overrides) for language, overrides in self._base.language_options
+        }
+
+    def __getattr__(self, name: str) -> Callable[[Any], LopperOptionsBuilder]:
+        """Return the setter of an option.
+
+        Raises:
+            AttributeError: If there is no option of that name
+        """
+        if name.startswith("_") or name not in _OPTION_NAMES:
+            raise AttributeError(f"LopperOptions has no option '{name}'")
+        return lambda value: self.set(**{name: value})
+
+    def set(self, **values: Any) -> LopperOptionsBuilder:
+        """Set options by name.
+
+        Args:
+            **values: Option values by option name
+
+        Returns:
+            The builder
+
+        Raises:
+            TypeError: If an option is unknown
+        """
+        unknown: list[str] = sorted(set(values) - _OPTION_NAMES)
+        if unknown:
+            raise TypeError(f"Unknown options: {', '.join(unknown)}")
+        if "language_options" in values:
+            self._languages = {
+                language: dict(overrides)
+                for language, overrides in dict(values.pop("language_options")).items()
+            }
+        self._values.update(values)
+        return self
+
+    def language(self, language: str, **values: Any) -> LopperOptionsBuilder:
+        """Set options overriding the others for the sources of a language.
+
+        Args:
+            language: Language identifier
+            **values: Option values by option name (see `language_options`)
+
+        Returns:
+            The builder
+        """
+        self._languages.setdefault(language, {}).update(values)
+        return self
+
+    def build(self) -> LopperOptions:
+        """Build the options.
+
+        Returns:
+            The options set, over the options the builder started from
+
+        Raises:
+            ValueError: If an option has an invalid value
+        """
+        return replace(self._base, **self._values, language_options=self._languages)
+
+
+# Names of the options, as taken by `LopperOptions` and its builder
+_OPTION_NAMES: frozenset[str] = frozenset(option.name for option in fields(LopperOptions))
+
+# Capture names interpreted in the query of `LopperOptions.query`
+QUERY_CAPTURES: frozenset[str] = frozenset({"keep.signature", "keep.whole", "prune.body"})
+
+# Symbol visibilities dropped unless every item is kept
+PRIVATE_VISIBILITIES: frozenset[str] = frozenset({"private", "fileprivate", "pub(self)"})
+
+# Symbol visibilities dropped when extracting only the public API (along with Rust's
+# `pub(in path)`)
+CRATE_VISIBILITIES: frozenset[str] = frozenset({"pub(crate)", "pub(super)", "internal"})
+
+# Nodes preceding an item that belong to it (doc comments, Rust attributes, decorators
+# of TypeScript class members)
+_LEADING_TRIVIA_TYPES: frozenset[str] = frozenset(
+    {"attribute_item", "decorator", "comment", "line_comment", "block_comment"}
+)
+
+# Bytes handed to tree-sitter per read when parsing a buffer other than `bytes`
+_READ_CHUNK_SIZE: int = 1 << 16
+
+# Comment nodes of the grammars
+_COMMENT_TYPES: frozenset[str] = frozenset({"comment", "line_comment", "block_comment"})
+
+# Section comments, once their delimiters are stripped: banners and section markers
+_SECTION_COMMENT: re.Pattern[str] = re.compile(
+    r"[-=*#~_+/]{3,}|^\s*(?:(?:section|mark)\s*:|#(?:end)?region\b)", re.IGNORECASE
+)
+
+# Region markers, matched on the whole comment as `#` is also Python's comment prefix
+_REGION_MARKER: re.Pattern[str] = re.compile(r"^#\s*(?:end)?region\b", re.IGNORECASE)
+
+# Nodes wrapping a definition that belong to its item (decorators, C++ templates)
+_WRAPPER_TYPES: frozenset[str] = frozenset({"decorated_definition", "template_declaration"})
+
+# Symbol kinds holding other items, dropped or collapsed by `empty_containers`
+_CONTAINER_KINDS: frozenset[str] = frozenset({"class", "impl", "interface", "module", "trait"})
+
+# Text of the placeholder of a collapsed empty container
+_EMPTIED_NOTE: str = "all items filtered"
+
+# Type parameters and definition of a type alias
+_Alias = tuple[list[str], Node]
+
+# Alias definitions parenthesized where expanded, as operators bind other types
+# tighter (`Handler | null` with `type Handler = () => void`)
+_GROUPED_TYPES: frozenset[str] = frozenset(
+    {"function_type", "constructor_type", "union_type", "intersection_type", "conditional_type"}
+)
+
+# Nodes naming a type or lifetime, which may refer to an alias or its parameters
+_ALIAS_NAME_TYPES: frozenset[str] = frozenset({"type_identifier", "lifetime"})
+
+# Parents of type names that are not references to a type of that name: the names
+# of generic types (handled with their arguments), qualified paths and declarations
+# of type parameters
+_NOT_REFERENCES: frozenset[str] = frozenset(
+    {
+        "generic_type",
+        "scoped_type_identifier",
+        "nested_type_identifier",
+        "qualified_type",
+        "type_parameters",
+        "type_parameter",
+        "constrained_type_parameter",
+        "optional_type_parameter",
+        "lifetime_parameter",
+    }
+)
+
+# Rust attributes marking tests (`test`, `bench`, `tokio::test(...)`) once `#[` and `]` are stripped
+_TEST_ATTRIBUTE: re.Pattern[str] = re.compile(
+    r"(?:\w+\s*::\s*)*(?:test|bench)\s*(?:\(.*\))?", re.DOTALL
+)
+
+# Text replacing redacted string literals (a quoted ellipsis)
+_REDACTED_STRING: bytes = '"\u2026"'.encode()
+
+# Suffixes of the node types of string parts that a truncation must not split
+_STRING_PART_SUFFIXES: tuple[str, ...] = ("interpolation", "substitution", "escape_sequence")
+
+# Nodes whose string literals are part of the declaration syntax (`extern "C"`)
+_SYNTAX_LITERAL_PARENTS: frozenset[str] = frozenset({"extern_modifier", "linkage_specification"})
+
+# Rust attribute making a macro public, kept along with the definition it exports
+_MACRO_EXPORT: re.Pattern[bytes] = re.compile(rb"#\s*\[\s*macro_export\b")
+
+# Tokens delimiting the rules of a Rust macro definition
+_MACRO_DELIMITER_TYPES: frozenset[str] = frozenset({"{", "}", "(", ")", "[", "]"})
+
+# Argument lists of attributes, annotations and decorators
+_ATTRIBUTE_ARGUMENT_TYPES: frozenset[str] = frozenset(
+    {
+        "token_tree",
+        "argument_list",
+        "arguments",
+        "annotation_argument_list",
+        "attribute_argument_list",
+        "value_arguments",
+    }
+)
+
+# Text replacing the argument lists of attributes longer than `max_attr_len`
+_ELIDED_ARGUMENTS: bytes = b"(...)"
+
+# Separates line numbers from the lines they number (a box-drawing vertical bar)
+GUTTER_SEPARATOR: str = "\u2502"
+
+# Nodes wrapping all statements of a body (Go blocks, Kotlin and Swift function bodies)
+_STATEMENT_LIST_TYPES: frozenset[str] = frozenset({"statement_list", "statements"})
+
+# Doc comment queries shared by C-family grammars
+_SLASH_STAR_DOC_QUERY = '((comment) @doc (#match? @doc "^/[*][*]"))'
+_TRIPLE_SLASH_DOC_QUERY = '((comment) @doc (#match? @doc "^(///|/[*][*])"))'
+
+# Body query shared by JavaScript-family grammars; method definitions cover class
+# methods, accessors and object-literal methods
+_JS_BODY_QUERY = (
+    "[(function_declaration body: (statement_block) @body) "
+    "(generator_function_declaration body: (statement_block) @body) "
+    "(arrow_function body: (_) @body) "
+    "(function_expression body: (statement_block) @body) "
+    "(generator_function body: (statement_block) @body) "
+    "(method_definition body: (statement_block) @body)]"
+)
+
+# Import query shared by JavaScript-family grammars: `import` statements (including
+# TypeScript's `import type`) and top-level `require` calls
+_JS_IMPORT_QUERY = (
+    "(program (import_statement) @import) "
+    "(program [(lexical_declaration (variable_declarator value: (call_expression "
+    "function: (identifier) @_fn))) (variable_declaration (variable_declarator "
+    'value: (call_expression function: (identifier) @_fn)))] @import (#eq? @_fn "require")) '
+    "(program (expression_statement (call_expression function: (identifier) @_fn)) @import "
+    '(#eq? @_fn "require"))'
+)
+
+# Test query shared by JavaScript-family grammars: top-level `describe`, `it` and
+# `test` calls, including `describe.skip(...)` and friends
+_JS_TEST_QUERY = (
+    "(program (expression_statement (call_expression function: "
+    "[(identifier) @_fn (member_expression object: (identifier) @_fn)])) @test "
+    '(#match? @_fn "^(describe|it|test)$"))'
+)
+
+# Literal query shared by JavaScript-family grammars
+_JS_LITERAL_QUERY = (
+    "[(string) (template_string)] @string (number) @number (array) @array (object) @map"
+)
+
+# Closure query shared by JavaScript-family grammars
+_JS_CLOSURE_QUERY = (
+    "[(arrow_function body: (_) @body) (function_expression body: (_) @body)] @closure"
+)
+
+# Branch query shared by JavaScript-family grammars
+_JS_BRANCH_QUERY = (
+    "[(if_statement) (for_statement) (for_in_statement) (while_statement) (do_statement) "
+    "(switch_case) (catch_clause) (ternary_expression)] @branch"
+)
+
+# Branch patterns shared by C-family grammars
+_C_BRANCH_PATTERNS = (
+    "(if_statement) (for_statement) (while_statement) (do_statement) (case_statement) "
+    "(conditional_expression) "
+)
+
+# Member list query shared by C-family grammars
+_C_MEMBER_LIST_QUERY = (
+    "[(struct_specifier body: (_) @fields) (union_specifier body: (_) @fields) "
+    "(enum_specifier body: (_) @variants)]"
+)
+
+# Prototype query shared by C-family grammars
+_C_PROTOTYPE_QUERY = (
+    "(function_definition declarator: (_) @declarator body: (compound_statement) @body)"
+)
+
+# Symbol queries shared by JavaScript-family grammars
+_JS_SYMBOL_PATTERNS = (
+    "(function_declaration name: (_) @name) @definition.function "
+    "(generator_function_declaration name: (_) @name) @definition.function "
+    "(class_declaration name: (_) @name) @definition.class "
+    "(method_definition name: (_) @name) @definition.method "
+    "(lexical_declaration (variable_declarator name: (identifier) @name "
+    "value: [(arrow_function body: (_) @body) (function_expression body: (_) @body)])) "
+    "@definition.function "
+    "(variable_declaration (variable_declarator name: (identifier) @name "
+    "value: [(arrow_function body: (_) @body) (function_expression body: (_) @body)])) "
+    "@definition.function "
+)
+# Class fields holding a function, which grammars name differently
+_JS_FIELD_SYMBOL_PATTERN = (
+    "(field_definition property: (_) @name "
+    "value: [(arrow_function body: (_) @body) (function_expression body: (_) @body)]) "
+    "@definition.function "
+)
+_TS_FIELD_SYMBOL_PATTERN = (
+    "(public_field_definition name: (_) @name "
+    "value: [(arrow_function body: (_) @body) (function_expression body: (_) @body)]) "
+    "@definition.function "
+)
+_TS_SYMBOL_PATTERNS = (
+    "(abstract_class_declaration name: (_) @name) @definition.class "
+    "(interface_declaration name: (_) @name) @definition.interface "
+    "(type_alias_declaration name: (_) @name) @definition.type "
+    "(enum_declaration name: (_) @name) @definition.enum "
+    "(function_signature name: (_) @name) @definition.function "
+    "(method_signature name: (_) @name) @definition.method "
+    "(abstract_method_signature name: (_) @name) @definition.method "
+)
+_C_SYMBOL_PATTERNS = (
+    "(function_definition declarator: (function_declarator declarator: (_) @name)) "
+    "@definition.function "
+    "(function_definition declarator: (pointer_declarator "
+    "declarator: (function_declarator declarator: (_) @name))) @definition.function "
+    "(declaration declarator: (function_declarator declarator: (_) @name)) "
+    "@definition.function "
+    "(struct_specifier name: (_) @name body: (_)) @definition.struct "
+    "(union_specifier name: (_) @name body: (_)) @definition.union "
+    "(enum_specifier name: (_) @name body: (_)) @definition.enum "
+)
 
 
 # Language-specific queries for finding bodies to remove
@@ -31,64 +944,288 @@ LANGUAGE_CONFIGS: dict[str, LanguageConfig] = {
     "python": LanguageConfig(
         name="python",
         body_query=("[(function_definition body: (block) @body)]"),
+        doc_comment_query=(
+            "[(module . (expression_statement (string)) @doc) "
+            "(class_definition body: (block . (expression_statement (string)) @doc)) "
+            "(function_definition body: (block . (expression_statement (string)) @doc))]"
+        ),
+        placeholder="...  # {lines}",
+        body_stub="...",
+        type_body_query=(
+            "(class_definition body: (block (expression_statement (assignment)) @field))"
+        ),
+        attribute_query="(decorator) @attr",
+        symbol_query=(
+            "[(class_definition name: (identifier) @name) @definition.class "
+            "(function_definition name: (identifier) @name) @definition.function]"
+        ),
+        import_query=(
+            "(module [(import_statement) (import_from_statement) (future_import_statement)] "
+            "@import)"
+        ),
+        line_comment="#",
+        test_query=(
+            '((function_definition name: (identifier) @_name) @test (#match? @_name "^test_")) '
+            "((class_definition superclasses: (argument_list [(identifier) (attribute)] @_base)) "
+            '@test (#match? @_base "TestCase$"))'
+        ),
+        literal_query=(
+            "[(string) @string (integer) @number (float) @number "
+            "[(list) (set) (tuple)] @array (dictionary) @map]"
+        ),
+        closure_query="(lambda body: (_) @body) @closure",
+        branch_query=(
+            "[(if_statement) (elif_clause) (for_statement) (while_statement) "
+            "(except_clause) (case_clause) (conditional_expression)] @branch"
+        ),
+        block_comment=None,
     ),
     "javascript": LanguageConfig(
         name="javascript",
-        body_query=(
-            "[(function_declaration body: (statement_block) @body) "
-            "(arrow_function body: (_) @body) "
-            "(function_expression body: (statement_block) @body) "
-            "(method_definition body: (statement_block) @body)]"
-        ),
+        body_query=_JS_BODY_QUERY,
+        doc_comment_query=_SLASH_STAR_DOC_QUERY,
+        attribute_query="(decorator) @attr",
+        symbol_query=f"[{_JS_SYMBOL_PATTERNS}{_JS_FIELD_SYMBOL_PATTERN}]",
+        import_query=_JS_IMPORT_QUERY,
+        test_query=_JS_TEST_QUERY,
+        literal_query=_JS_LITERAL_QUERY,
+        closure_query=_JS_CLOSURE_QUERY,
+        branch_query=_JS_BRANCH_QUERY,
     ),
     "typescript": LanguageConfig(
         name="typescript",
-        body_query=(
-            "[(function_declaration body: (statement_block) @body) "
-            "(arrow_function body: (_) @body) "
-            "(function_expression body: (statement_block) @body) "
-            "(method_definition body: (statement_block) @body)]"
+        body_query=_JS_BODY_QUERY,
+        doc_comment_query=_SLASH_STAR_DOC_QUERY,
+        attribute_query="(decorator) @attr",
+        symbol_query=f"[{_JS_SYMBOL_PATTERNS}{_TS_SYMBOL_PATTERNS}{_TS_FIELD_SYMBOL_PATTERN}]",
+        type_body_query=(
+            "[(interface_declaration body: (_) @body) (enum_declaration body: (_) @body)]"
         ),
+        member_list_query="(enum_declaration body: (_) @variants)",
+        import_query=_JS_IMPORT_QUERY,
+        test_query=_JS_TEST_QUERY,
+        literal_query=_JS_LITERAL_QUERY,
+        closure_query=_JS_CLOSURE_QUERY,
+        branch_query=_JS_BRANCH_QUERY,
+        alias_query="(type_alias_declaration) @alias",
     ),
     "tsx": LanguageConfig(
-        name="typescript",
-        body_query=(
-            "[(function_declaration body: (statement_block) @body) "
-            "(arrow_function body: (_) @body) "
-            "(function_expression body: (statement_block) @body) "
-            "(method_definition body: (statement_block) @body)]"
+        name="tsx",
+        body_query=_JS_BODY_QUERY,
+        doc_comment_query=_SLASH_STAR_DOC_QUERY,
+        attribute_query="(decorator) @attr",
+        symbol_query=f"[{_JS_SYMBOL_PATTERNS}{_TS_SYMBOL_PATTERNS}{_TS_FIELD_SYMBOL_PATTERN}]",
+        type_body_query=(
+            "[(interface_declaration body: (_) @body) (enum_declaration body: (_) @body)]"
         ),
+        member_list_query="(enum_declaration body: (_) @variants)",
+        import_query=_JS_IMPORT_QUERY,
+        test_query=_JS_TEST_QUERY,
+        literal_query=_JS_LITERAL_QUERY,
+        closure_query=_JS_CLOSURE_QUERY,
+        branch_query=_JS_BRANCH_QUERY,
+        alias_query="(type_alias_declaration) @alias",
     ),
     "java": LanguageConfig(
         name="java",
         body_query=(
             "[(method_declaration body: (block) @body) "
-            "(constructor_declaration (constructor_body) @body)]"
+            "(constructor_declaration (constructor_body) @body) "
+            "(compact_constructor_declaration body: (block) @body) "
+            "(static_initializer (block) @body) "
+            "(class_body (block) @body)]"
+        ),
+        doc_comment_query='((block_comment) @doc (#match? @doc "^/[*][*]"))',
+        attribute_query="(modifiers [(marker_annotation) (annotation)] @attr)",
+        symbol_query=(
+            "[(class_declaration name: (_) @name) @definition.class "
+            "(record_declaration name: (_) @name) @definition.class "
+            "(interface_declaration name: (_) @name) @definition.interface "
+            "(annotation_type_declaration name: (_) @name) @definition.interface "
+            "(enum_declaration name: (_) @name) @definition.enum "
+            "(method_declaration name: (_) @name) @definition.method "
+            "(constructor_declaration name: (_) @name) @definition.constructor]"
+        ),
+        import_query="(import_declaration) @import",
+        literal_query=(
+            "(string_literal) @string "
+            "[(decimal_integer_literal) (hex_integer_literal) (octal_integer_literal) "
+            "(binary_integer_literal) (decimal_floating_point_literal) "
+            "(hex_floating_point_literal)] @number "
+            "(array_initializer) @array"
+        ),
+        branch_query=(
+            "[(if_statement) (for_statement) (enhanced_for_statement) (while_statement) "
+            "(do_statement) (switch_label) (catch_clause) (ternary_expression)] @branch"
         ),
     ),
     "go": LanguageConfig(
         name="go",
         body_query=(
-            "[(function_declaration body: (block) @body) (method_declaration body: (block) @body)]"
+            "[(function_declaration body: (block) @body) "
+            "(method_declaration body: (block) @body) "
+            "(func_literal body: (block) @body)]"
+        ),
+        symbol_query=(
+            "[(function_declaration name: (_) @name) @definition.function "
+            "(method_declaration name: (_) @name) @definition.method "
+            "(type_spec name: (_) @name type: (struct_type (field_declaration_list) @body)) "
+            "@definition.struct "
+            "(type_spec name: (_) @name type: (interface_type) @body) @definition.interface "
+            "(type_spec name: (_) @name type: [(type_identifier) (qualified_type) "
+            "(generic_type) (function_type) (map_type) (slice_type) (array_type) (pointer_type) "
+            "(channel_type)]) "
+            "@definition.type "
+            "(type_alias name: (_) @name) @definition.type "
+            "(const_spec name: (_) @name) @definition.constant "
+            "(var_spec name: (_) @name) @definition.variable]"
+        ),
+        type_body_query=(
+            "[(struct_type (field_declaration_list) @body) (interface_type) @body]"
+        ),
+        member_list_query="(struct_type (field_declaration_list) @fields)",
+        import_query="(import_declaration) @import (import_spec) @item",
+        test_query=(
+            "(source_file (function_declaration name: (identifier) @_name) @test "
+            '(#match? @_name "^(Test|Benchmark|Fuzz|Example)([A-Z0-9_]|$)"))'
+        ),
+        literal_query=(
+            "[(interpreted_string_literal) (raw_string_literal)] @string "
+            "[(int_literal) (float_literal) (imaginary_literal)] @number "
+            "(literal_value) @array"
+        ),
+        closure_query="(func_literal body: (block) @body) @closure",
+        branch_query=(
+            "[(if_statement) (for_statement) (expression_case) (type_case) "
+            "(communication_case)] @branch"
         ),
+        alias_query="(type_alias) @alias",
     ),
     "rust": LanguageConfig(
         name="rust",
-        body_query=("[(function_item body: (block) @body)]"),
+        body_query="[(function_item body: (block) @body) (macro_definition) @macro]",
+        doc_comment_query=(
+            '[((line_comment) @doc (#match? @doc "^//[/!]")) '
+            '((block_comment) @doc (#match? @doc "^/[*][*!]"))]'
+        ),
+        attribute_query="[(attribute_item) (inner_attribute_item)] @attr",
+        symbol_query=(
+            "[(function_item name: (identifier) @name) @definition.function "
+            "(function_signature_item name: (identifier) @name) @definition.function "
+            "(struct_item name: (type_identifier) @name) @definition.struct "
+            "(enum_item name: (type_identifier) @name) @definition.enum "
+            "(union_item name: (type_identifier) @name) @definition.union "
+            "(trait_item name: (type_identifier) @name) @definition.trait "
+            "(impl_item type: (_) @name) @definition.impl "
+            "(mod_item name: (identifier) @name) @definition.module "
+            "(const_item name: (identifier) @name) @definition.constant "
+            "(static_item name: (identifier) @name) @definition.static "
+            "(type_item name: (type_identifier) @name) @definition.type "
+            '(macro_definition name: (identifier) @name ["{" "(" "["] @body) @definition.macro]'
+        ),
+        type_body_query=(
+            "[(struct_item body: (field_declaration_list) @body) "
+            "(union_item body: (field_declaration_list) @body) "
+            "(enum_item body: (enum_variant_list) @body) "
+            "(trait_item body: (declaration_list) @body)]"
+        ),
+        member_list_query=(
+            "[(struct_item body: (field_declaration_list) @fields) "
+            "(union_item body: (field_declaration_list) @fields) "
+            "(enum_item body: (enum_variant_list) @variants)]"
+        ),
+        trait_impl_query="(impl_item trait: (_) @trait body: (declaration_list) @body)",
+        stub_mode_body="unimplemented!()",
+        import_query="[(use_declaration) (extern_crate_declaration)] @import",
+        test_query='((attribute_item) @attr (#match? @attr "test|bench"))',
+        # Default methods of traits, cut after their return type or where clause
+        stub_mode_query=(
+            "(trait_item body: (declaration_list "
+            "(function_item (_) @declarator . body: (block) @body)))"
+        ),
+        literal_query=(
+            "[(string_literal) (raw_string_literal)] @string "
+            "[(integer_literal) (float_literal)] @number "
+            "(array_expression) @array"
+        ),
+        closure_query="(closure_expression body: (_) @body) @closure",
+        branch_query=(
+            "[(if_expression) (match_arm) (for_expression) (while_expression) "
+            "(loop_expression)] @branch"
+        ),
+        # Associated types of traits and impls are not aliases of the module
+        alias_query=(
+            "[(source_file (type_item) @alias) "
+            "(mod_item body: (declaration_list (type_item) @alias))]"
+        ),
+        path_separator="::",
+        # Type aliases are "T", leaving "t" to traits
+        ctags_kinds={**DEFAULT_CTAGS_KINDS, "type": "T"},
     ),
     "cpp": LanguageConfig(
         name="cpp",
-        body_query=("[(function_definition body: (compound_statement) @body)]"),
+        body_query=(
+            "[(function_definition body: (compound_statement) @body) "
+            "(lambda_expression body: (compound_statement) @body)]"
+        ),
+        doc_comment_query=_TRIPLE_SLASH_DOC_QUERY,
+        symbol_query=(
+            f"[{_C_SYMBOL_PATTERNS}"
+            "(function_definition declarator: (reference_declarator "
+            "(function_declarator declarator: (_) @name))) @definition.function "
+            "(class_specifier name: (_) @name body: (_)) @definition.class "
+            "(namespace_definition name: (_) @name) @definition.module "
+            "(field_declaration declarator: (function_declarator declarator: (_) @name)) "
+            "@definition.function]"
+        ),
+        import_query="[(preproc_include) (using_declaration)] @import",
+        literal_query=(
+            "[(string_literal) (raw_string_literal)] @string (number_literal) @number "
+            "(initializer_list) @array"
+        ),
+        prototype_query=_C_PROTOTYPE_QUERY,
+        branch_query=f"[{_C_BRANCH_PATTERNS}(for_range_loop) (catch_clause)] @branch",
+        path_separator="::",
     ),
     "c": LanguageConfig(
         name="c",
         body_query=("[(function_definition body: (compound_statement) @body)]"),
+        doc_comment_query=_TRIPLE_SLASH_DOC_QUERY,
+        symbol_query=f"[{_C_SYMBOL_PATTERNS}]",
+        type_body_query=(
+            "[(struct_specifier body: (_) @body) (union_specifier body: (_) @body) "
+            "(enum_specifier body: (_) @body)]"
+        ),
+        member_list_query=_C_MEMBER_LIST_QUERY,
+        import_query="(preproc_include) @import",
+        literal_query=(
+            "(string_literal) @string (number_literal) @number (initializer_list) @array"
+        ),
+        prototype_query=_C_PROTOTYPE_QUERY,
+        branch_query=f"[{_C_BRANCH_PATTERNS}] @branch",
     ),
     "csharp": LanguageConfig(
         name="csharp",
         body_query=(
             "[(method_declaration body: (block) @body) (accessor_declaration body: (block) @body)]"
         ),
+        doc_comment_query=_TRIPLE_SLASH_DOC_QUERY,
+        attribute_query="(attribute_list) @attr",
+        symbol_query=(
+            "[(class_declaration name: (_) @name) @definition.class "
+            "(interface_declaration name: (_) @name) @definition.interface "
+            "(struct_declaration name: (_) @name) @definition.struct "
+            "(enum_declaration name: (_) @name) @definition.enum "
+            "(method_declaration name: (_) @name) @definition.method "
+            "(constructor_declaration name: (_) @name) @definition.constructor]"
+        ),
+        import_query="(using_directive) @import",
+        literal_query=(
+            "[(string_literal) (verbatim_string_literal) (raw_string_literal) "
+            "(interpolated_string_expression)] @string "
+            "[(integer_literal) (real_literal)] @number "
+            "(initializer_expression) @array"
+        ),
     ),
     "ruby": LanguageConfig(
         name="ruby",
@@ -96,10 +1233,54 @@ LANGUAGE_CONFIGS: dict[str, LanguageConfig] = {
             "[(method body: (body_statement) @body) "
             "(singleton_method body: (body_statement) @body)]"
         ),
+        symbol_query=(
+            "[(class name: (_) @name) @definition.class "
+            "(module name: (_) @name) @definition.module "
+            "(method name: (_) @name) @definition.method "
+            "(singleton_method name: (_) @name) @definition.method]"
+        ),
+        placeholder="# {lines}",
+        import_query=(
+            "(program (call !receiver method: (identifier) @_method) @import "
+            '(#match? @_method "^require(_relative)?$"))'
+        ),
+        line_comment="#",
+        literal_query="(string) @string [(integer) (float)] @number (array) @array (hash) @map",
+        # Methods defined in the blocks of class-level calls belong to what the call
+        # builds (an association extension, a concern's class methods)
+        verbatim_query=(
+            "[(class body: (body_statement (call block: [(do_block) (block)]) @verbatim)) "
+            "(module body: (body_statement (call block: [(do_block) (block)]) @verbatim))]"
+        ),
+        path_separator="::",
+        block_comment=None,
     ),
     "php": LanguageConfig(
         name="php",
-        body_query=("[(method_declaration body: (compound_statement) @body)]"),
+        body_query=(
+            "[(function_definition body: (compound_statement) @body) "
+            "(method_declaration body: (compound_statement) @body)]"
+        ),
+        doc_comment_query=_SLASH_STAR_DOC_QUERY,
+        attribute_query="(attribute_list) @attr",
+        symbol_query=(
+            "[(class_declaration name: (_) @name) @definition.class "
+            "(interface_declaration name: (_) @name) @definition.interface "
+            "(trait_declaration name: (_) @name) @definition.trait "
+            "(namespace_definition name: (_) @name) @definition.module "
+            "(function_definition name: (_) @name) @definition.function "
+            "(method_declaration name: (_) @name) @definition.method]"
+        ),
+        import_query=(
+            "[(namespace_use_declaration) @import "
+            "(expression_statement [(require_expression) (require_once_expression) "
+            "(include_expression) (include_once_expression)]) @import]"
+        ),
+        literal_query=(
+            "[(string) (encapsed_string)] @string [(integer) (float)] @number "
+            "(array_creation_expression) @array"
+        ),
+        path_separator="::",
     ),
     "kotlin": LanguageConfig(
         name="kotlin",
@@ -108,18 +1289,51 @@ LANGUAGE_CONFIGS: dict[str, LanguageConfig] = {
             "(getter (function_body) @body) "
             "(setter (function_body) @body)]"
         ),
+        doc_comment_query='((multiline_comment) @doc (#match? @doc "^/[*][*]"))',
+        attribute_query="(annotation) @attr",
+        symbol_query=(
+            "[(class_declaration (type_identifier) @name "
+            "[(class_body) (enum_class_body)]? @body) @definition.class "
+            "(object_declaration (type_identifier) @name (class_body)? @body) "
+            "@definition.class "
+            "(function_declaration (simple_identifier) @name (function_body)? @body) "
+            "@definition.function]"
+        ),
+        import_query="(import_header) @import",
     ),
     "swift": LanguageConfig(
         name="swift",
         body_query=("[(function_declaration (function_body) @body)]"),
+        doc_comment_query=(
+            '[((comment) @doc (#match? @doc "^///")) '
+            '((multiline_comment) @doc (#match? @doc "^/[*][*]"))]'
+        ),
+        attribute_query="(attribute) @attr",
+        import_query="(import_declaration) @import",
+    ),
+    "bash": LanguageConfig(
+        name="bash",
+        body_query="(function_definition body: (compound_statement) @body)",
+        symbol_query="(function_definition name: (_) @name) @definition.function",
+        doc_comment_query=(
+            '((comment)+ @doc . (function_definition) @item (#not-match? @doc "^#!"))'
+        ),
+        # `:` is the shell's no-op command, keeping `{ : 3 lines; }` valid
+        placeholder=": {lines};",
+        line_comment="#",
+        block_comment=None,
     ),
     "lua": LanguageConfig(
         name="lua",
         body_query=("[(function_declaration (block) @body)]"),
+        placeholder="-- {lines}",
+        line_comment="--",
+        block_comment=("--[[", "]]"),
     ),
     "scala": LanguageConfig(
         name="scala",
         body_query=("[(function_definition (block) @body)]"),
+        import_query="(import_declaration) @import",
     ),
     "groovy": LanguageConfig(
         name="groovy",
@@ -132,6 +1346,176 @@ LANGUAGE_CONFIGS: dict[str, LanguageConfig] = {
 }
 
 
+@dataclass(frozen=True)
+class ParseDiagnostic:
+    """A region of the source that did not parse.
+
+    Attributes:
+        kind: "error" for unparsed source (a tree-sitter ERROR node), "missing" for a
+            token tree-sitter assumed to recover (an empty range)
+        message: Description, naming the missing token if any
+        start_byte: Byte offset of the region in the UTF-8 encoded source
+        end_byte: Byte offset just past the end of the region
+        start_line: First line of the region (1-based)
+        end_line: Last line of the region (1-based, inclusive)
+    """
+
+    kind: Literal["error", "missing"]
+    message: str
+    start_byte: int
+    end_byte: int
+    start_line: int
+    end_line: int
+
+    @classmethod
+    def from_node(cls, node: Node) -> ParseDiagnostic:
+        """Describe an ERROR or MISSING node.
+
+        Args:
+            node: ERROR or MISSING node
+
+        Returns:
+            Diagnostic covering the node
+        """
+        kind: Literal["error", "missing"] = "missing" if node.is_missing else "error"
+        message = f"missing `{node.type}`" if node.is_missing else "syntax error"
+        return cls(
+            kind,
+            message,
+            node.start_byte,
+            node.end_byte,
+            node.start_point[0] + 1,
+            node.end_point[0] + 1,
+        )
+
+
+@dataclass(frozen=True)
+class SourceMap:
+    """Mapping between the lines of a skeleton and the source lines they come from.
+
+    Line numbers are 1-based, and ranges include their last line.
+
+    Attributes:
+        lines: Source line range of each skeleton line, in order; a line holding a
+            placeholder spans the lines it replaces
+        elided: Source line ranges without a skeleton line of their own (removed body
+            lines, dropped items), in order
+    """
+
+    lines: list[tuple[int, int]] = field(default_factory=list)
+    elided: list[tuple[int, int]] = field(default_factory=list)
+
+    def original_line(self, output_line: int) -> int:
+        """Return the first source line of a skeleton line.
+
+        Args:
+            output_line: Skeleton line number
+
+        Returns:
+            Source line number
+
+        Raises:
+            IndexError: If the skeleton has no such line
+        """
+        if not 1 <= output_line <= len(self.lines):
+            raise IndexError(f"Skeleton has no line {output_line}")
+        return self.lines[output_line - 1][0]
+
+    def output_line(self, original_line: int) -> int | None:
+        """Return the skeleton line a source line ended up on.
+
+        Args:
+            original_line: Source line number
+
+        Returns:
+            Skeleton line number, or None if the source line was elided
+        """
+        index: int = bisect_right([start for start, _ in self.lines], original_line) - 1
+        if index >= 0 and original_line <= self.lines[index][1]:
+            return index + 1
+        return None
+
+    @classmethod
+    def from_segments(
+        cls,
+        segments: Sequence[tuple[bytes, int, bool]],
+        placeholder_ends: dict[int, int],
+        line_starts: list[int],
+        line_count: int,
+    ) -> SourceMap:
+        """Build the source map of a skeleton from its segments.
+
+        Args:
+            segments: Segments from `_kept_segments`
+            placeholder_ends: End byte of the range each placeholder replaces, by start;
+                text replacing no range, such as indentation, maps to the line of its
+                offset
+            line_starts: Byte offset at which each line starts
+            line_count: Number of lines of the source
+
+        Returns:
+            Source map of the skeleton
+        """
+        lines: list[tuple[int, int]] = []
+        current: tuple[int, int] | None = None
+        for text, offset, is_source in segments:
+            index: int = 0
+            while index < len(text):
+                newline: int = text.find(b"\n", index)
+                piece_end: int = newline + 1 if newline >= 0 else len(text)
+                if is_source:
+                    first = bisect_right(line_starts, offset + index)
+                    last = bisect_right(line_starts, offset + piece_end - 1)
+                else:
+                    first = bisect_right(line_starts, offset)
+                    end: int = placeholder_ends.get(offset, offset + 1)
+                    last = bisect_right(line_starts, max(offset, end - 1))
+                if current is not None:
+                    first, last = min(current[0], first), max(current[1], last)
+                current = (first, last)
+                if newline < 0:
+                    break
+                lines.append(current)
+                current = None
+                index = piece_end
+        if current is not None:
+            lines.append(current)
+
+        elided: list[tuple[int, int]] = []
+        next_line: int = 1
+        # Sorted, as the `sort` option moves lines out of source order
+        for first, last in [*sorted(lines), (line_count + 1, line_count + 1)]:
+            if first > next_line:
+                elided.append((next_line, first - 1))
+            next_line = max(next_line, last + 1)
+        return cls(lines, elided)
+
+
+@dataclass(frozen=True)
+class ExtractionResult:
+    """A skeleton together with the syntax errors found while extracting it.
+
+    Attributes:
+        skeleton: Extracted skeleton
+        diagnostics: Regions of the source that did not parse, in source order
+        source_map: Source lines of each skeleton line
+    """
+
+    skeleton: str
+    diagnostics: list[ParseDiagnostic] = field(default_factory=list)
+    source_map: SourceMap = field(default_factory=SourceMap)
+
+    @property
+    def syntax_errors(self) -> int:
+        """Number of regions that did not parse."""
+        return len(self.diagnostics)
+
+    @property
+    def has_errors(self) -> bool:
+        """Whether the source had syntax errors."""
+        return bool(self.diagnostics)
+
+
 class SkeletonExtractor:
     """Extract source skeletons using tree-sitter queries.
 
@@ -139,112 +1523,2703 @@ class SkeletonExtractor:
     while preserving signatures, class definitions, and docstrings (for Python).
     """
 
-    def __init__(self, language: str) -> None:
+    def __init__(self, language: str, options: LopperOptions | None = None) -> None:
         """Initialize extractor for a language.
 
         Args:
             language: Programming language (python, javascript, java, etc.)
+            options: Extraction options (defaults to `LopperOptions()`)
 
         Raises:
-            ValueError: If language not supported
+            UnsupportedLanguageError: If language not supported
+            InvalidQueryError: If the query of the options is malformed or uses
+                unknown captures
         """
         if language not in LANGUAGE_CONFIGS:
             supported = ", ".join(LANGUAGE_CONFIGS.keys())
             msg = f"Language '{language}' not supported. Supported: {supported}"
-            raise ValueError(msg)
+            raise UnsupportedLanguageError(msg, language=language)
 
         self.language: str = language
         self.config: LanguageConfig = LANGUAGE_CONFIGS[language]
+        self.options: LopperOptions = (options or LopperOptions()).for_language(language)
 
         # Load language with tree-sitter >= 0.25 API
-        self.lang: Language = tree_sitter_language_pack.get_language(language)  # type: ignore
+        self.lang: Language = (
+            self.config.grammar or tree_sitter_language_pack.get_language(language)  # type: ignore
+        )
 
         self.parser: Parser = Parser()
         self.parser.language = self.lang
 
-    def extract(self, source_code: str) -> str:
-        """Extract skeleton from source code.
+        # Compiled queries, keyed by query source
+        self._queries: dict[str, Query] = {}
+        if self.options.query is not None:
+            self._check_query(self.options.query)
 
-        Parses the source code and removes function/method bodies while
-        preserving signatures, class definitions, and docstrings (Python only).
+    def parse(self, source_code: str | bytes, old_tree: Tree | None = None) -> Tree:
+        """Parse source code into a tree-sitter tree.
+
+        Passing the previous tree of an edited source (after `Tree.edit`) lets
+        tree-sitter reparse incrementally, reusing the unchanged parts.
 
         Args:
-            source_code: Source code to process
+            source_code: Source code to parse, as text or UTF-8 bytes (any buffer
+                supporting slicing, such as a memory map, is read in chunks)
+            old_tree: Previous tree of the source, already edited to match it
 
         Returns:
-            Skeleton with function implementations removed
+            Parsed syntax tree
+
+        Raises:
+            ParseFailedError: If tree-sitter produced no tree (e.g. parsing timed out)
         """
-        tree: Tree = self.parser.parse(source_code.encode())
-        lines: list[str] = source_code.splitlines(keepends=True)
+        source = source_code.encode() if isinstance(source_code, str) else source_code
+        reader = source if isinstance(source, bytes) else _chunk_reader(source)
+        if old_tree is None:
+            tree = self.parser.parse(reader)
+        else:
+            tree = self.parser.parse(reader, old_tree)
+        if tree is None:
+            raise ParseFailedError(f"Could not parse {self.language} source")
+        return tree
 
-        # Find all function bodies to remove
-        query: Query = Query(self.lang, self.config.body_query)
-        cursor: QueryCursor = QueryCursor(query)
-        captures = cursor.captures(tree.root_node)
+    def verbatim_spans(self, tree: Tree) -> list[tuple[int, int]]:
+        """Find the code of a tree kept verbatim (see `verbatim_query`).
 
-        # Collect line ranges to remove
-        lines_to_remove: set[int] = set()
-        for capture_name, node_list in captures.items():
-            for node in node_list:
-                start_line: int = node.start_point[0]
-                end_line: int = node.end_point[0]
-
-                # For Python, preserve docstrings (first string in body)
-                skip_start: int = start_line
-                if self.language == "python" and node.child_count > 0:
-                    first_child: Node = node.child(0)  # type: ignore
-                    if (
-                        first_child.type == "expression_statement"
-                        and first_child.child_count > 0
-                        and first_child.child(0).type == "string"  # type: ignore
-                    ):
-                        # Skip the docstring lines when removing
-                        skip_start = first_child.end_point[0] + 1
-
-                # For languages with braces, skip the opening brace line if it contains the declaration
-                # (Ruby uses def...end, so body capture doesn't include the def line)
-                # (Lua uses def...end, so body capture doesn't include the def line)
-                if self.language not in ("python", "ruby", "lua") and start_line < end_line:
-                    skip_start = start_line + 1
-
-                # For single-line bodies (like Lua), include the line in removal
-                if start_line == end_line:
-                    end_inclusive = end_line + 1
-                # For multi-line bodies:
-                # - Python/Ruby/Lua: remove everything including end_line
-                # - Other languages: preserve closing brace (don't include end_line)
-                else:
-                    end_inclusive = (
-                        end_line + 1 if self.language in ("python", "ruby", "lua") else end_line
-                    )
+        Args:
+            tree: Parsed syntax tree
+
+        Returns:
+            Sorted, disjoint byte ranges of the code kept verbatim
+        """
+        if not self.config.verbatim_query:
+            return []
+        cursor: QueryCursor = QueryCursor(self.query(self.config.verbatim_query))
+        nodes: list[Node] = cursor.captures(tree.root_node).get("verbatim", [])
+        return merge_ranges([(node.start_byte, node.end_byte) for node in nodes])
+
+    def query(self, query_source: str) -> Query:
+        """Compile a query for this language, reusing earlier compilations.
+
+        Args:
+            query_source: Tree-sitter query source
+
+        Returns:
+            Compiled query
+        """
+        query = self._queries.get(query_source)
+        if query is None:
+            query = self._queries[query_source] = Query(self.lang, query_source)
+        return query
 
-                for line_num in range(skip_start, end_inclusive):
-                    lines_to_remove.add(line_num)
+    def _check_query(self, query_source: str) -> None:
+        """Compile a user-supplied query, checking that its captures are interpreted.
 
-        # Build skeleton by keeping non-body lines
-        skeleton: list[str] = []
-        for i, line in enumerate(lines):
-            if i not in lines_to_remove:
-                skeleton.append(line)
+        Args:
+            query_source: Tree-sitter query source
+
+        Raises:
+            InvalidQueryError: If the query is malformed or uses unknown captures
+        """
+        try:
+            query = self.query(query_source)
+        except QueryError as e:
+            raise InvalidQueryError(f"Invalid {self.language} query: {e}") from e
+        names = {query.capture_name(index) for index in range(query.capture_count)}
+        unknown = sorted(
+            name for name in names if name not in QUERY_CAPTURES and not name.startswith("_")
+        )
+        if unknown:
+            captures = ", ".join(f"@{name}" for name in unknown)
+            expected = ", ".join(f"@{name}" for name in sorted(QUERY_CAPTURES))
+            msg = (
+                f"Unknown captures in {self.language} query: {captures}. Expected {expected}"
+                " (or `_`-prefixed captures for predicates)"
+            )
+            raise InvalidQueryError(msg)
 
-        return "".join(skeleton).rstrip()
+    def extract(
+        self,
+        source_code: str,
+        *,
+        tree: Tree | None = None,
+        remove: Sequence[tuple[int, int]] = (),
+        keep: Sequence[tuple[int, int]] = (),
+    ) -> str:
+        """Extract skeleton from source code.
+
+        Parses the source code and removes function/method bodies while
+        preserving signatures, class definitions, and docstrings (Python only).
+
+        Args:
+            source_code: Source code to process
+            tree: Tree already parsed from the source (e.g. incrementally after an edit)
+            remove: Additional byte ranges of the encoded source to drop entirely,
+                such as whole items
+            keep: Additional byte ranges of the encoded source whose bodies are kept
+                verbatim, such as whole items
+
+        Returns:
+            Skeleton with function implementations removed
+        """
+        chunks = self.iter_extract(source_code.encode(), tree=tree, remove=remove, keep=keep)
+        return b"".join(chunks).decode().rstrip()
+
+    def extract_result(
+        self,
+        source_code: str,
+        *,
+        tree: Tree | None = None,
+        remove: Sequence[tuple[int, int]] = (),
+        keep: Sequence[tuple[int, int]] = (),
+    ) -> ExtractionResult:
+        """Extract skeleton from source code, reporting its syntax errors.
+
+        Args:
+            source_code: Source code to process
+            tree: Tree already parsed from the source
+            remove: Additional byte ranges of the encoded source to drop entirely
+            keep: Additional byte ranges of the encoded source whose bodies are kept
+
+        Returns:
+            Skeleton with the regions of the source that did not parse, and the source
+            lines of each skeleton line
+
+        Raises:
+            ParseFailedError: If the source has syntax errors and `on_error` is "fail"
+        """
+        if tree is None:
+            tree = self.parse(source_code)
+        source = source_code.encode()
+        line_starts, placeholders, pending = self.segments(
+            source, tree=tree, remove=remove, keep=keep
+        )
+        segments = list(pending)
+        rendered = self.render_segments(iter(segments), source, line_starts)
+        skeleton = b"".join(rendered).decode().rstrip()
+        diagnostics = [
+            ParseDiagnostic.from_node(node) for node in syntax_error_nodes(tree.root_node)
+        ]
+        source_map = SourceMap.from_segments(
+            segments,
+            {start: end for start, end, _ in placeholders},
+            line_starts,
+            count_lines(source, line_starts),
+        )
+        return ExtractionResult(skeleton, diagnostics, source_map)
+
+    def iter_extract(
+        self,
+        source: bytes,
+        *,
+        tree: Tree | None = None,
+        remove: Sequence[tuple[int, int]] = (),
+        keep: Sequence[tuple[int, int]] = (),
+    ) -> Iterator[bytes]:
+        """Extract the skeleton of encoded source code piece by piece.
+
+        The skeleton is never assembled in memory, so `source` can be a memory map
+        of a large file. Trailing whitespace of the skeleton is dropped.
+
+        Args:
+            source: UTF-8 encoded source code (or a buffer of it supporting slicing)
+            tree: Tree already parsed from the source
+            remove: Additional byte ranges of the source to drop entirely
+            keep: Additional byte ranges of the source whose bodies are kept
+
+        Yields:
+            Consecutive UTF-8 encoded pieces of the skeleton
+
+        Raises:
+            ParseFailedError: If the source has syntax errors and `on_error` is "fail"
+        """
+        line_starts, _, segments = self.segments(source, tree=tree, remove=remove, keep=keep)
+        yield from self.render_segments(segments, source, line_starts)
+
+    def segments(
+        self,
+        source: bytes,
+        *,
+        tree: Tree | None = None,
+        remove: Sequence[tuple[int, int]] = (),
+        keep: Sequence[tuple[int, int]] = (),
+    ) -> tuple[
+        list[int], list[tuple[int, int, bytes]], Iterator[tuple[bytes, int, bool]]
+    ]:
+        """Compute the segments of the skeleton of encoded source code.
+
+        Args:
+            source: UTF-8 encoded source code (or a buffer of it supporting slicing)
+            tree: Tree already parsed from the source
+            remove: Additional byte ranges of the source to drop entirely
+            keep: Additional byte ranges of the source whose bodies are kept
+
+        Returns:
+            The byte offset at which each line starts, the placeholders in the
+            skeleton, and the skeleton segments (see `_kept_segments`) without
+            trailing whitespace, re-indented when `indent_width` is set
+
+        Raises:
+            ParseFailedError: If the source has syntax errors and `on_error` is "fail"
+        """
+        if tree is None:
+            tree = self.parse(source)
+        errors: list[Node] = syntax_error_nodes(tree.root_node)
+        if errors and self.options.on_error == "fail":
+            raise ParseFailedError(f"Found {len(errors)} syntax errors in {self.language} source")
+        line_starts: list[int] = line_starts_of(source)
+
+        # Find all function bodies (and type bodies when collapsing types) to remove
+        body_queries: list[str | None] = [
+            self.config.body_query,
+            self.config.type_body_query if self.options.collapse_types else None,
+        ]
+
+        # Collect byte ranges to remove, with the placeholder text of elided bodies
+        removals: list[tuple[int, int]] = []
+        placeholders: list[tuple[int, int, bytes]] = []
+        prototyped: set[int] = set()
+        kept_bodies: list[tuple[int, int]] = []
+        if self.options.keep_bodies and self.config.symbol_query:
+            kept_bodies = self._kept_body_spans(tree, source)
+        if self.config.verbatim_query:
+            kept_bodies = merge_ranges([*kept_bodies, *self.verbatim_spans(tree)])
+        # Nodes marked by the user query: kept whole, or elided as bodies
+        marked: dict[str, list[Node]] = {}
+        if self.options.query:
+            marked = QueryCursor(self.query(self.options.query)).captures(tree.root_node)
+            whole: list[Node] = marked.get("keep.whole", [])
+            kept_bodies = merge_ranges(
+                [*kept_bodies, *((node.start_byte, node.end_byte) for node in whole)]
+            )
+        if keep:
+            kept_bodies = merge_ranges([*kept_bodies, *keep])
+        prototype_query: str | None = (
+            self.config.stub_mode_query
+            if self._stubs_bodies()
+            else self.config.prototype_query if self.options.prototypes else None
+        )
+        if prototype_query:
+            prototyped = self._collect_prototypes(
+                tree, prototype_query, removals, placeholders, kept_bodies
+            )
+        body_nodes: list[tuple[str, list[Node]]] = [
+            ("body", marked.get("prune.body", [])),
+            (
+                "body",
+                [
+                    body
+                    for node in marked.get("keep.signature", [])
+                    if (body := node.child_by_field_name("body")) is not None
+                ],
+            ),
+        ]
+        for body_query in filter(None, body_queries):
+            cursor: QueryCursor = QueryCursor(self.query(body_query))
+            for capture_name, node_list in cursor.captures(tree.root_node).items():
+                if capture_name == "field":
+                    # Class-level fields are dropped as whole statements
+                    removals.extend(_node_span(node, source, line_starts) for node in node_list)
+                    continue
+                body_nodes.append((capture_name, node_list))
+        for capture_name, node_list in body_nodes:
+            node_list = [
+                node
+                for node in node_list
+                if node.start_byte not in prototyped
+                and not is_inside(kept_bodies, node.start_byte, node.end_byte)
+            ]
+            if capture_name == "macro":
+                if not (self.options.keep_macro_rules or self._stubs_bodies()):
+                    self._collect_rule_removals(
+                        node_list, source, line_starts, removals, placeholders
+                    )
+            else:
+                self._collect_body_removals(node_list, source, line_starts, removals, placeholders)
+
+        # Collapse the field and variant lists of types, or drop their private fields
+        if (
+            self.config.member_list_query
+            and not self.options.collapse_types
+            and (self.options.struct_fields != "keep" or self.options.enum_variants != "keep")
+        ):
+            member_spans: list[tuple[int, int, bytes]] = self._member_spans(
+                tree, source, line_starts, kept_bodies
+            )
+            placeholders.extend(span for span in member_spans if span[2])
+            removals.extend(span[:2] for span in member_spans)
+
+        # Collapse trait implementations the trait definition already describes
+        if self.config.trait_impl_query and (
+            self.options.collapse_known_impls or self.options.collapse_impl_traits
+        ):
+            collapsed: list[tuple[int, int, bytes]] = self._collapsed_impl_spans(
+                tree, source, kept_bodies
+            )
+            placeholders.extend(collapsed)
+            removals.extend(span[:2] for span in collapsed)
+
+        # Drop doc comments, docstrings and attributes when not kept
+        dropped: list[list[Node]] = []
+        if not self.options.keep_doc_comments:
+            dropped.append(self._doc_nodes(tree))
+        if not self.options.keep_attributes and self.config.attribute_query:
+            attr_cursor: QueryCursor = QueryCursor(self.query(self.config.attribute_query))
+            dropped.extend(attr_cursor.captures(tree.root_node).values())
+        for node_list in dropped:
+            removals.extend(
+                _node_span(node, source, line_starts)
+                for node in node_list
+                if not _MACRO_EXPORT.match(source[node.start_byte : node.end_byte])
+            )
+
+        # Elide the argument lists of long attributes
+        if (
+            self.options.keep_attributes
+            and self.options.max_attr_len is not None
+            and self.config.attribute_query
+        ):
+            elided_arguments: list[tuple[int, int, bytes]] = _outside(
+                self._attribute_argument_spans(tree, source), merge_ranges(removals)
+            )
+            placeholders.extend(elided_arguments)
+            removals.extend(span[:2] for span in elided_arguments)
+
+        # Drop import statements, or replace each group of them with a summary
+        if self.options.imports != "keep" and self.config.import_query:
+            for start, end, summary in self._import_spans(tree, source, line_starts):
+                removals.append((start, end))
+                if summary:
+                    placeholders.append((start, end, summary))
+
+        # Drop private items when only the public API is wanted
+        items: list[tuple[int, int]] = list(remove)
+        if self.options.visibility != "all" and self.config.symbol_query:
+            items.extend(self._private_item_spans(tree, source, line_starts))
+        if errors and self.options.on_error == "drop":
+            items.extend(self._error_spans(errors, tree, source, line_starts))
+        removals.extend(items)
+        filtered: list[tuple[int, int]] = list(items)
+        # Bodies left without statements get a stub where an empty one is invalid
+        if items and self.config.body_stub and self.config.symbol_query:
+            placeholders.extend(self._emptied_body_stubs(tree, source, merge_ranges(items)))
+
+        # Drop test code, leaving a marker in place of each run of it
+        if self.options.strip_tests and self.config.test_query:
+            test_spans: list[tuple[int, int, bytes]] = self._test_spans(tree, source, line_starts)
+            placeholders = _outside(placeholders, merge_ranges([span[:2] for span in test_spans]))
+            placeholders.extend(span for span in test_spans if span[2])
+            removals.extend(span[:2] for span in test_spans)
+            filtered.extend(span[:2] for span in test_spans)
+
+        # Drop or collapse the containers whose items were all dropped above
+        if self.options.empty_containers != "keep" and filtered and self.config.symbol_query:
+            emptied: list[tuple[int, int, bytes]] = self._empty_container_spans(
+                tree, source, line_starts, merge_ranges(filtered)
+            )
+            placeholders = _outside(placeholders, merge_ranges([span[:2] for span in emptied]))
+            placeholders.extend(span for span in emptied if span[2])
+            removals.extend(span[:2] for span in emptied)
+            items.extend(span[:2] for span in emptied if not span[2])
+
+        # Drop items nested deeper than the depth limit, leaving a marker per run
+        if self.options.max_depth is not None and self.config.symbol_query:
+            deep_spans: list[tuple[int, int, bytes]] = self._deep_item_spans(
+                tree, source, line_starts, merge_ranges(removals), kept_bodies
+            )
+            placeholders = _outside(placeholders, merge_ranges([span[:2] for span in deep_spans]))
+            placeholders.extend(span for span in deep_spans if span[2])
+            removals.extend(span[:2] for span in deep_spans)
+
+        # Collapse the runs of items repeating the item before them
+        if self.options.dedup_symbols != "off" and self.config.symbol_query:
+            duplicates: list[tuple[int, int, bytes]] = self._duplicate_spans(
+                tree, source, line_starts, merge_ranges(removals)
+            )
+            placeholders = _outside(placeholders, merge_ranges([span[:2] for span in duplicates]))
+            placeholders.extend(span for span in duplicates if span[2])
+            removals.extend(span[:2] for span in duplicates)
+
+        # Truncate long literals of the code that is kept
+        if self.options.max_literal_len is not None and self.config.literal_query:
+            truncations: list[tuple[int, int, bytes]] = self._literal_truncations(
+                tree, source, line_starts, merge_ranges(removals), kept_bodies
+            )
+            placeholders.extend(truncations)
+            removals.extend(span[:2] for span in truncations)
+
+        # Redact the literals of the code that is kept
+        if self.options.redact_literals and self.config.literal_query:
+            redactions: list[tuple[int, int, bytes]] = self._literal_redactions(
+                tree, merge_ranges(removals)
+            )
+            placeholders.extend(redactions)
+            removals.extend(span[:2] for span in redactions)
+
+        # Shorten the signatures longer than the limit
+        if self.options.max_signature_len is not None and self.config.symbol_query:
+            merged_removals: list[tuple[int, int]] = merge_ranges(removals)
+            cuts: list[tuple[int, int, bytes]] = [
+                span
+                for span in self._signature_cuts(tree, source)
+                if not _overlaps(merged_removals, span[0], span[1])
+            ]
+            placeholders.extend(cuts)
+            removals.extend(span[:2] for span in cuts)
+
+        # Substitute the definitions of local type aliases for their references
+        if self.options.expand_aliases and self.config.alias_query:
+            merged: list[tuple[int, int]] = merge_ranges(removals)
+            expansions: list[tuple[int, int, bytes]] = [
+                span
+                for span in self._alias_expansions(tree, source)
+                if not _overlaps(merged, span[0], span[1])
+            ]
+            placeholders.extend(expansions)
+            removals.extend(span[:2] for span in expansions)
+
+        # Build skeleton by keeping the bytes between removed ranges; bodies nested in
+        # another elided body are covered by the outer body's placeholder, and bodies
+        # of dropped items get none
+        kept: list[tuple[int, int, bytes]] = _outermost(_outside(placeholders, merge_ranges(items)))
+        segments = _kept_segments(source, merge_ranges(removals), kept)
+        if self.options.sort == "alphabetical" and self.config.symbol_query:
+            segments = _reordered(segments, self._sorted_ranges(tree, source, line_starts))
+        segments = _right_trimmed(segments)
+        if self.options.indent_width is not None:
+            levels: list[tuple[int, int] | None] = self._line_levels(tree, source, line_starts)
+            segments = _reindented(
+                segments,
+                line_starts,
+                levels,
+                _indent_unit(source, line_starts),
+                self.options.indent_width,
+            )
+        return line_starts, kept, segments
+
+    def _sorted_ranges(
+        self, tree: Tree, source: bytes, line_starts: list[int]
+    ) -> list[tuple[int, int]]:
+        """Order the byte ranges of a source so the items of each level come sorted by name.
+
+        Args:
+            tree: Parsed syntax tree
+            source: Encoded source code
+            line_starts: Byte offset at which each line starts
+
+        Returns:
+            Byte ranges covering the source and one byte past its end (where
+            placeholders at the very end sit), in skeleton order
+        """
+        pairs = symbol_nodes(self, tree, source)
+        spans: dict[int, tuple[int, int]] = {}
+        for symbol, node in pairs:
+            start, end = item_span(node, source, line_starts)
+            # The line break ending an item stays in place, as the last item may lack one
+            spans[id(symbol)] = (start, start + len(source[start:end].rstrip(b"\r\n")))
+        nested: set[int] = {id(child) for symbol, _ in pairs for child in symbol.children}
+
+        def ordered(start: int, end: int, symbols: list[Symbol]) -> Iterator[tuple[int, int]]:
+            items = sorted(
+                (
+                    (spans[id(symbol)], symbol)
+                    for symbol in symbols
+                    if start <= spans[id(symbol)][0] and spans[id(symbol)][1] <= end
+                ),
+                key=lambda item: item[0],
+            )
+            ranked = items
+            if all(left[0][1] <= right[0][0] for left, right in zip(items, items[1:])):
+                ranked = sorted(items, key=lambda item: symbol_key(item[1]))
+            position: int = start
+            for (slot, _), ((low, high), symbol) in zip(items, ranked):
+                yield position, slot[0]
+                yield from ordered(low, high, symbol.children)
+                position = slot[1]
+            yield position, end
+
+        roots: list[Symbol] = [symbol for symbol, _ in pairs if id(symbol) not in nested]
+        return list(ordered(0, len(source) + 1, roots))
+
+    def render_segments(
+        self, segments: Iterator[tuple[bytes, int, bool]], source: bytes, line_starts: list[int]
+    ) -> Iterator[bytes]:
+        """Render skeleton segments, numbering their lines when asked to."""
+        if not self.options.line_numbers:
+            for text, _, _ in segments:
+                yield text
+            return
+        yield from _numbered(segments, line_starts, len(str(count_lines(source, line_starts))))
+
+    def _collect_body_removals(
+        self,
+        nodes: list[Node],
+        source: bytes,
+        line_starts: list[int],
+        removals: list[tuple[int, int]],
+        placeholders: list[tuple[int, int, bytes]],
+    ) -> None:
+        """Add the removal ranges (and placeholders) of captured body nodes.
+
+        Args:
+            nodes: Captured body nodes
+            source: Encoded source code
+            line_starts: Byte offset at which each line starts
+            removals: Removal ranges to extend
+            placeholders: Placeholder ranges and texts to extend
+        """
+        max_body_lines: int | None = self.options.max_body_lines
+        for node in nodes:
+            # Keep short bodies, measured on the original source span
+            body_lines: int = node.end_point[0] - node.start_point[0] + 1
+            if max_body_lines is not None and body_lines <= max_body_lines:
+                continue
+            # A body with syntax errors may have swallowed the code after it
+            if node.has_error:
+                continue
+            span = self._removal_span(node, source, line_starts)
+            if span is None:
+                continue
+            if self.options.body_head_lines:
+                head_cut = self._head_cut(node, span, source, line_starts)
+                if head_cut is not None:
+                    placeholders.append(head_cut)
+                    removals.append(head_cut[:2])
+                continue
+            text: bytes = b""
+            placed: bool = bool(self._marks_bodies() or self.config.body_stub)
+            if placed:
+                span, text = self._placeholder(node, span, source, line_starts)
+            if self.options.annotate_sizes and source[node.start_byte : node.end_byte].strip(
+                b"{} \t\r\n"
+            ):
+                note: str = self._size_note(node, nodes)
+                span, text = self._annotated(node, span, text, note, source, line_starts)
+                placed = True
+            if placed:
+                placeholders.append((*span, text))
+            removals.append(span)
+
+    def _collect_rule_removals(
+        self,
+        nodes: list[Node],
+        source: bytes,
+        line_starts: list[int],
+        removals: list[tuple[int, int]],
+        placeholders: list[tuple[int, int, bytes]],
+    ) -> None:
+        """Add the removal ranges (and placeholders) of the rules of macro definitions.
+
+        The rules are elided like a brace-delimited body, between the delimiters of
+        the definition (`macro_rules! name {` and `}`).
+
+        Args:
+            nodes: Captured macro definition nodes
+            source: Encoded source code
+            line_starts: Byte offset at which each line starts
+            removals: Removal ranges to extend
+            placeholders: Placeholder ranges and texts to extend
+        """
+        max_body_lines: int | None = self.options.max_body_lines
+        for node in nodes:
+            delimiters: list[Node] = [
+                child for child in node.children if child.type in _MACRO_DELIMITER_TYPES
+            ]
+            if node.has_error or len(delimiters) < 2:
+                continue
+            opening, closing = delimiters[0], delimiters[-1]
+            body_lines: int = closing.end_point[0] - opening.start_point[0] + 1
+            if max_body_lines is not None and body_lines <= max_body_lines:
+                continue
+            inner: tuple[int, int] = (opening.end_byte, closing.start_byte)
+            if not source[inner[0] : inner[1]].strip():
+                continue
+            marker: str = self._placeholder_text(body_lines)
+            if marker or body_lines == 1:
+                placeholders.append((*inner, f" {marker} ".encode() if marker else b""))
+                removals.append(inner)
+            else:
+                # Keep the delimiter lines, like the braces of an elided function body
+                first_line: int = opening.start_point[0] + 1
+                removals.append((line_starts[first_line], line_starts[closing.start_point[0]]))
+
+    def _collect_prototypes(
+        self,
+        tree: Tree,
+        query: str,
+        removals: list[tuple[int, int]],
+        placeholders: list[tuple[int, int, bytes]],
+        kept_bodies: list[tuple[int, int]],
+    ) -> set[int]:
+        """Add the removals turning elided function definitions into prototypes.
+
+        Everything after the declarator (initializer lists and the body) is replaced
+        by `;`. Bodies kept by `max_body_lines` or `keep_bodies` and bodies with syntax
+        errors stay.
+
+        Args:
+            tree: Parsed syntax tree
+            query: Query capturing the bodies and declarators (see `prototype_query`)
+            removals: Removal ranges to extend
+            placeholders: Placeholder ranges and texts to extend
+            kept_bodies: Sorted, disjoint byte ranges of the items whose bodies are kept
+
+        Returns:
+            Start bytes of the bodies replaced
+        """
+        max_body_lines: int | None = self.options.max_body_lines
+        cursor: QueryCursor = QueryCursor(self.query(query))
+        prototyped: set[int] = set()
+        for _, match in cursor.matches(tree.root_node):
+            body: Node = match["body"][0]
+            body_lines: int = body.end_point[0] - body.start_point[0] + 1
+            if (
+                (max_body_lines is not None and body_lines <= max_body_lines)
+                or body.has_error
+                or is_inside(kept_bodies, body.start_byte, body.end_byte)
+            ):
+                continue
+            span: tuple[int, int] = (match["declarator"][0].end_byte, body.end_byte)
+            removals.append(span)
+            placeholders.append((*span, b";"))
+            prototyped.add(body.start_byte)
+        return prototyped
+
+    def _removal_span(
+        self, node: Node, source: bytes, line_starts: list[int]
+    ) -> tuple[int, int] | None:
+        """Compute the byte range to remove for a captured body node.
+
+        Args:
+            node: Captured body node
+            source: Encoded source code
+            line_starts: Byte offset at which each line starts
+
+        Returns:
+            Half-open byte range to remove, or None if nothing should be removed
+        """
+        start_line: int = node.start_point[0]
+        end_line: int = node.end_point[0]
+
+        # For Python, preserve docstrings (first string in body)
+        skip_start: int = start_line
+        if self.language == "python" and self.options.keep_doc_comments and node.child_count > 0:
+            first_child: Node = node.child(0)  # type: ignore
+            if (
+                first_child.type == "expression_statement"
+                and first_child.child_count > 0
+                and first_child.child(0).type == "string"  # type: ignore
+            ):
+                # Skip the docstring lines when removing
+                skip_start = first_child.end_point[0] + 1
+                if skip_start > end_line:
+                    return None
+
+        # Single-line bodies sharing a line with their header (`def f(): return 1`,
+        # `fn new() -> Self { Self {} }`) are cut out of the line so the header survives
+        if start_line == end_line and skip_start == start_line:
+            header = source[line_starts[start_line] : node.start_byte]
+            if header.strip():
+                text: bytes = source[node.start_byte : node.end_byte]
+                if (
+                    not (self._marks_bodies() or self.config.body_stub)
+                    and self.language not in ("python", "ruby", "lua")
+                    and text.startswith(b"{")
+                    and text.endswith(b"}")
+                ):
+                    # Without a placeholder the braces stay, so the definition still
+                    # has a body (`fn one() -> i32 {}`)
+                    inner = (node.start_byte + 1, node.end_byte - 1)
+                    return inner if source[inner[0] : inner[1]].strip() else None
+                return len(header.rstrip()) + line_starts[start_line], node.end_byte
+
+        # For languages with braces, skip the opening brace line if it contains the declaration
+        # (Ruby uses def...end, so body capture doesn't include the def line)
+        # (Lua uses def...end, so body capture doesn't include the def line)
+        if self.language not in ("python", "ruby", "lua") and start_line < end_line:
+            skip_start = start_line + 1
+
+        # For single-line bodies on their own line, remove the whole line
+        if start_line == end_line:
+            end_exclusive = end_line + 1
+        # For multi-line bodies:
+        # - Python/Ruby/Lua: remove everything including end_line
+        # - Other languages: preserve closing brace (don't include end_line)
+        else:
+            end_exclusive = end_line + 1 if self.language in ("python", "ruby", "lua") else end_line
+
+        if skip_start >= end_exclusive:
+            return None
+        return line_starts[skip_start], _line_offset(line_starts, end_exclusive, len(source))
+
+    def _head_cut(
+        self, node: Node, span: tuple[int, int], source: bytes, line_starts: list[int]
+    ) -> tuple[int, int, bytes] | None:
+        """Compute the removal of a body past its first `body_head_lines` lines.
+
+        Leading statements are kept while they end within the allowed lines; a
+        statement crossing the limit and everything after it is replaced by a marker
+        line, indented like that statement.
+
+        Args:
+            node: Captured body node
+            span: Byte range `_removal_span` computed for the node
+            source: Encoded source code
+            line_starts: Byte offset at which each line starts
+
+        Returns:
+            Byte range to remove and its marker text, or None if the whole body fits
+        """
+        head_lines: int = self.options.body_head_lines or 0
+        start, end = span
+        first_line: int = bisect_right(line_starts, start) - 1
+        statements: list[Node] = node.named_children
+        while len(statements) == 1 and statements[0].type in _STATEMENT_LIST_TYPES:
+            statements = statements[0].named_children
+
+        cut: int = start
+        elided: Node | None = None
+        for statement in statements:
+            if statement.start_byte < start:
+                continue
+            if statement.end_point[0] - first_line + 1 > head_lines:
+                elided = statement
+                break
+            cut = _line_offset(line_starts, statement.end_point[0] + 1, len(source))
+        if elided is not None and elided.start_byte < cut:
+            # The elided statement starts on a kept line: elide that whole line too
+            cut = max(start, line_starts[elided.start_point[0]])
+        if cut >= end:
+            return None
+
+        cut_line: int = bisect_right(line_starts, cut) - 1
+        elided_lines: int = bisect_right(line_starts, end - 1) - cut_line
+        if self._marks_bodies():
+            marker: str = self._placeholder_text(elided_lines)
+        else:
+            marker = self.config.body_stub or self.config.placeholder.format(lines="...")
+        indent: bytes = _indentation(source[cut:end])
+        if elided is not None:
+            elided_line_start: int = line_starts[elided.start_point[0]]
+            if not source[elided_line_start : elided.start_byte].strip():
+                indent = source[elided_line_start : elided.start_byte]
+        return cut, end, indent + marker.encode() + line_break_of(source)
+
+    def _kept_body_spans(self, tree: Tree, source: bytes) -> list[tuple[int, int]]:
+        """Compute the byte ranges of the items matching a `keep_bodies` pattern.
+
+        Args:
+            tree: Parsed syntax tree
+            source: Encoded source code
+
+        Returns:
+            Sorted, disjoint byte ranges of the matching definitions
+        """
+        pairs = symbol_nodes(self, tree, source)
+        nodes: dict[int, Node] = {id(symbol): node for symbol, node in pairs}
+        roots: list[Symbol] = root_symbols(pairs)
+        return merge_ranges(
+            [
+                (nodes[id(symbol)].start_byte, nodes[id(symbol)].end_byte)
+                for pattern in self.options.keep_bodies
+                for symbol in match_symbols(roots, pattern)
+            ]
+        )
+
+    def _private_item_spans(
+        self, tree: Tree, source: bytes, line_starts: list[int]
+    ) -> list[tuple[int, int]]:
+        """Compute the byte ranges of items below the kept visibility, with their trivia.
+
+        Args:
+            tree: Parsed syntax tree
+            source: Encoded source code
+            line_starts: Byte offset at which each line starts
+
+        Returns:
+            Byte ranges covering each dropped item
+        """
+        pairs = symbol_nodes(self, tree, source)
+        nodes: dict[int, Node] = {id(symbol): node for symbol, node in pairs}
+        spans: list[tuple[int, int]] = [
+            self._dropped_span(node, source, line_starts)
+            for symbol, node in pairs
+            if is_hidden(symbol.visibility, self.options.visibility)
+        ]
+        # The `impl` blocks of a dropped type go with it
+        for siblings in [root_symbols(pairs), *(symbol.children for symbol, _ in pairs)]:
+            dropped_types: set[str] = {
+                symbol.name
+                for symbol in siblings
+                if symbol.kind in IMPL_TARGET_KINDS
+                and is_hidden(symbol.visibility, self.options.visibility)
+            }
+            spans.extend(
+                self._dropped_span(nodes[id(symbol)], source, line_starts)
+                for symbol in siblings
+                if symbol.kind == "impl" and base_name(symbol.name) in dropped_types
+            )
+        return spans
+
+    def _dropped_span(self, node: Node, source: bytes, line_starts: list[int]) -> tuple[int, int]:
+        """Return the byte range removed with a dropped item.
+
+        The item goes with the trivia above it (see `item_span`), only up to the
+        last section comment among them when keeping section comments.
+        """
+        start, end = item_span(node, source, line_starts)
+        if not self.options.keep_section_comments:
+            return start, end
+        if node.parent is not None and node.parent.type in _WRAPPER_TYPES:
+            node = node.parent
+        sibling: Node | None = node.prev_named_sibling
+        while sibling is not None and sibling.start_byte >= start:
+            if sibling.type in _COMMENT_TYPES and self._is_section_comment(sibling, source):
+                next_line: int = bisect_right(line_starts, sibling.end_byte - 1)
+                offset: int = _line_offset(line_starts, next_line, len(source))
+                return (offset, end) if offset <= node.start_byte else (start, end)
+            sibling = sibling.prev_named_sibling
+        return start, end
+
+    def _emptied_body_stubs(
+        self, tree: Tree, source: bytes, dropped: list[tuple[int, int]]
+    ) -> list[tuple[int, int, bytes]]:
+        """Compute the stubs of the container bodies whose statements were all dropped.
+
+        An empty body is a syntax error in languages with a `body_stub`, so a class
+        whose members were all dropped keeps the stub (`...`) under its header.
+
+        Args:
+            tree: Parsed syntax tree
+            source: Encoded source code
+            dropped: Sorted, disjoint byte ranges of the items dropped
+
+        Returns:
+            Empty ranges at the line break ending each header, with the stub line
+            put after it
+        """
+        stub: bytes = (self.config.body_stub or "").encode()
+        stubs: list[tuple[int, int, bytes]] = []
+        for symbol, node in symbol_nodes(self, tree, source):
+            body: Node | None = node.child_by_field_name("body")
+            if (
+                symbol.kind not in _CONTAINER_KINDS
+                or body is None
+                or is_inside(dropped, node.start_byte, node.end_byte)
+            ):
+                continue
+            if not _is_emptied(body, dropped):
+                continue
+            header: Node | None = body.prev_sibling
+            newline: int = source.find(b"\n", header.end_byte if header else node.start_byte)
+            first: Node = next(
+                child for child in body.named_children if child.type not in _COMMENT_TYPES
+            )
+            if newline < 0 or newline >= first.start_byte:
+                continue
+            indent: bytes = source[first.start_byte - first.start_point[1] : first.start_byte]
+            stubs.append((newline, newline, indent + stub + line_break_of(source)))
+        return stubs
+
+    def _is_section_comment(self, node: Node, source: bytes) -> bool:
+        """Tell whether a comment is a section comment (see `section_comment_pattern`).
+
+        Doc comments document the item below them, so are never section comments,
+        however they look (`/// ----`).
+        """
+        if self._is_doc_comment(node):
+            return False
+        text: str = source[node.start_byte : node.end_byte].decode(errors="replace")
+        if self.options.section_comment_pattern is not None:
+            return re.search(self.options.section_comment_pattern, text) is not None
+        if _REGION_MARKER.search(text.strip()) is not None:
+            return True
+        content: str = text.strip().removeprefix(self.config.line_comment)
+        if self.config.block_comment is not None:
+            opening, closing = self.config.block_comment
+            content = content.removeprefix(opening).removesuffix(closing)
+        return _SECTION_COMMENT.search(content) is not None
+
+    def _is_doc_comment(self, node: Node) -> bool:
+        """Tell whether a comment is a doc comment on its own (see `doc_comment_query`)."""
+        if not self.config.doc_comment_query:
+            return False
+        cursor: QueryCursor = QueryCursor(self.query(self.config.doc_comment_query))
+        return any(
+            doc.start_byte == node.start_byte and doc.end_byte == node.end_byte
+            for doc in cursor.captures(node).get("doc", [])
+        )
+
+    def _member_spans(
+        self,
+        tree: Tree,
+        source: bytes,
+        line_starts: list[int],
+        kept_bodies: list[tuple[int, int]],
+    ) -> list[tuple[int, int, bytes]]:
+        """Compute the byte ranges of collapsed member lists and dropped private fields.
+
+        A collapsed list keeps its braces around a count of its members
+        (`{ /* 3 variants */ }`), not counting comments and attributes; empty lists
+        are kept as they are. A dropped field goes with its doc comments, attributes
+        and the separator after it.
+
+        Args:
+            tree: Parsed syntax tree
+            source: Encoded source code
+            line_starts: Byte offset at which each line starts
+            kept_bodies: Sorted, disjoint byte ranges of the items whose bodies are kept
+
+        Returns:
+            Byte ranges to remove, with the count placeholders of collapsed lists
+        """
+        cursor: QueryCursor = QueryCursor(self.query(self.config.member_list_query or ""))
+        modes: dict[str, str] = {
+            "fields": self.options.struct_fields,
+            "variants": self.options.enum_variants,
+        }
+        spans: list[tuple[int, int, bytes]] = []
+        for capture_name, node_list in cursor.captures(tree.root_node).items():
+            mode: str = modes.get(capture_name, "keep")
+            for node in node_list:
+                text: bytes = source[node.start_byte : node.end_byte]
+                if (
+                    node.has_error
+                    or not (text.startswith(b"{") and text.endswith(b"}"))
+                    or is_inside(kept_bodies, node.start_byte, node.end_byte)
+                ):
+                    continue
+                members: list[Node] = [
+                    child
+                    for child in node.named_children
+                    if child.type not in _LEADING_TRIVIA_TYPES
+                ]
+                if mode == "collapse" and members:
+                    noun: str = capture_name if len(members) != 1 else capture_name[:-1]
+                    marker: str = self.config.placeholder.format(lines=f"{len(members)} {noun}")
+                    spans.append((node.start_byte + 1, node.end_byte - 1, f" {marker} ".encode()))
+                elif mode == "public":
+                    for member in members:
+                        if not _is_private_field(member, source, self.language):
+                            continue
+                        end: int = member.end_byte
+                        separator: Node | None = member.next_sibling
+                        if separator is not None and separator.type in (",", ";"):
+                            end = separator.end_byte
+                        start: int = item_span(member, source, line_starts)[0]
+                        spans.append((*_range_span(start, end, source, line_starts), b""))
+        return spans
+
+    def _collapsed_impl_spans(
+        self, tree: Tree, source: bytes, kept_bodies: list[tuple[int, int]]
+    ) -> list[tuple[int, int, bytes]]:
+        """Compute the byte ranges of the item lists of collapsed trait implementations.
+
+        A collapsed list keeps its braces around `...`. Empty lists are kept as they
+        are, and so are those of implementations holding a body that is kept.
+
+        Args:
+            tree: Parsed syntax tree
+            source: Encoded source code
+            kept_bodies: Sorted, disjoint byte ranges of the items whose bodies are kept
+
+        Returns:
+            Byte ranges to remove, with the placeholders of collapsed lists
+        """
+        cursor: QueryCursor = QueryCursor(self.query(self.config.trait_impl_query or ""))
+        spans: list[tuple[int, int, bytes]] = []
+        for _, match in cursor.matches(tree.root_node):
+            trait_node, body = match["trait"][0], match["body"][0]
+            text: bytes = source[body.start_byte : body.end_byte]
+            if (
+                body.has_error
+                or not (text.startswith(b"{") and text.endswith(b"}"))
+                or _overlaps(kept_bodies, body.start_byte, body.end_byte)
+            ):
+                continue
+            members: list[Node] = [
+                child for child in body.named_children if child.type not in _LEADING_TRIVIA_TYPES
+            ]
+            trait: str = source[trait_node.start_byte : trait_node.end_byte].decode(
+                errors="replace"
+            )
+            if not members or _matches_trait(trait, self.options.keep_impl_traits):
+                continue
+            if _matches_trait(trait, self.options.collapse_impl_traits) or (
+                self.options.collapse_known_impls
+                and all(member.type == "function_item" for member in members)
+            ):
+                spans.append((body.start_byte + 1, body.end_byte - 1, b" ... "))
+        return spans
+
+    def _empty_container_spans(
+        self,
+        tree: Tree,
+        source: bytes,
+        line_starts: list[int],
+        filtered: list[tuple[int, int]],
+    ) -> list[tuple[int, int, bytes]]:
+        """Compute the removals of the containers left empty (see `empty_containers`).
+
+        A container is empty when it has items and each of them was filtered out or
+        is an empty container itself, with nothing else left in its body but
+        comments and a docstring: fields, re-exports and other statements keep it.
+        Only the outermost empty containers are removed or collapsed.
+
+        Args:
+            tree: Parsed syntax tree
+            source: Encoded source code
+            line_starts: Byte offset at which each line starts
+            filtered: Sorted, disjoint byte ranges of the items filtered out
+
+        Returns:
+            Byte ranges of the dropped containers with empty texts, or of the
+            collapsed bodies with their placeholder texts
+        """
+        pairs: list[tuple[Symbol, Node]] = symbol_nodes(self, tree, source)
+        nodes: dict[int, Node] = {id(symbol): node for symbol, node in pairs}
+        empty: set[int] = set()
+        emptied: list[tuple[int, int]] = []
+        spans: list[tuple[int, int, bytes]] = []
+        # Nested symbols follow their parents, so they are classified first
+        for symbol, node in reversed(pairs):
+            if (
+                symbol.kind not in _CONTAINER_KINDS
+                or not symbol.children
+                or is_inside(filtered, node.start_byte, node.end_byte)
+            ):
+                continue
+            if not all(
+                id(child) in empty
+                or is_inside(filtered, nodes[id(child)].start_byte, nodes[id(child)].end_byte)
+                for child in symbol.children
+            ):
+                continue
+            body: Node | None = node.child_by_field_name("body")
+            if body is not None:
+                removed: list[tuple[int, int]] = [*filtered, *emptied]
+                first: Node | None = body.named_children[0] if body.named_children else None
+                if (
+                    first is not None
+                    and first.type == "expression_statement"
+                    and first.named_child_count == 1
+                    and first.named_children[0].type == "string"
+                ):
+                    # A docstring documents the container, whatever is left of it
+                    removed.append((first.start_byte, first.end_byte))
+                if not _is_emptied(body, merge_ranges(removed)):
+                    continue
+            empty.add(id(symbol))
+            item: Node = node.parent if node.parent and node.parent.type in _WRAPPER_TYPES else node
+            emptied.append((item.start_byte, item.end_byte))
+            if self.options.empty_containers == "drop":
+                spans.append((*self._dropped_span(node, source, line_starts), b""))
+            elif (collapsed := self._collapsed_container(node, source)) is not None:
+                spans.append(collapsed)
+        return _outermost(spans)
+
+    def _collapsed_container(self, node: Node, source: bytes) -> tuple[int, int, bytes] | None:
+        """Compute the removal putting an empty container on one line.
+
+        The contents of a body between braces are replaced by a comment
+        (`{ /* all items filtered */ }`); other bodies are replaced, from the end
+        of the header, by the language's placeholder (`: ...  # all items filtered`).
+        """
+        body: Node | None = node.child_by_field_name("body")
+        if body is None:
+            return None
+        marker: str = self.config.placeholder.format(lines=_EMPTIED_NOTE)
+        text: bytes = source[body.start_byte : body.end_byte]
+        if text.startswith(b"{") and text.endswith(b"}"):
+            return body.start_byte + 1, body.end_byte - 1, f" {marker} ".encode()
+        header: Node | None = body.prev_sibling
+        start: int = header.end_byte if header is not None else body.start_byte
+        # The line break of the body's last line goes too, as filtered items take theirs
+        newline: int = source.find(b"\n", body.end_byte)
+        if newline < 0:
+            return start, body.end_byte, f" {marker}".encode()
+        return start, newline + 1, f" {marker}".encode() + line_break_of(source)
+
+    def _duplicate_spans(
+        self,
+        tree: Tree,
+        source: bytes,
+        line_starts: list[int],
+        removed: list[tuple[int, int]],
+    ) -> list[tuple[int, int, bytes]]:
+        """Compute the removals of the items repeating the one before them (see `dedup_symbols`).
+
+        Each run of consecutive sibling items sharing a grouping key (their kind
+        and name, or their kind and signature with the name blanked out) keeps its
+        first item, followed by a marker counting the others (`// +3 similar`).
+        Items inside removed code are skipped, so a dropped private item does not
+        break a run. Imports are no symbols, so runs of them are left to `imports`.
+
+        Args:
+            tree: Parsed syntax tree
+            source: Encoded source code
+            line_starts: Byte offset at which each line starts
+            removed: Sorted, disjoint byte ranges already removed from the skeleton
+
+        Returns:
+            Byte ranges covering the repeated items, the first one after each kept
+            item with its marker text
+        """
+        pairs: list[tuple[Symbol, Node]] = symbol_nodes(self, tree, source)
+        nodes: dict[int, Node] = {id(symbol): node for symbol, node in pairs}
+        nested: set[int] = {id(child) for symbol, _ in pairs for child in symbol.children}
+        levels: list[list[Symbol]] = [[symbol for symbol, _ in pairs if id(symbol) not in nested]]
+        levels.extend(symbol.children for symbol, _ in pairs if symbol.children)
+
+        def grouping_key(symbol: Symbol) -> str | None:
+            if symbol.children:
+                return None
+            if self.options.dedup_symbols == "name":
+                return f"{symbol.kind} {symbol.name}"
+            # Whitespace differences do not count, and the name is blanked out
+            signature: str = " ".join(symbol.signature.split())
+            name: re.Pattern[str] = re.compile(rf"(?<!\w){re.escape(symbol.name)}(?!\w)")
+            return f"{symbol.kind} {name.sub('_', signature, count=1)}"
+
+        spans: list[tuple[int, int, bytes]] = []
+        for siblings in levels:
+            runs: list[list[Symbol]] = []
+            previous: str | None = None
+            for symbol in siblings:
+                node: Node = nodes[id(symbol)]
+                if is_inside(removed, node.start_byte, node.end_byte):
+                    continue
+                key: str | None = grouping_key(symbol)
+                if key is not None and key == previous:
+                    runs[-1].append(symbol)
+                else:
+                    runs.append([symbol])
+                previous = key
+            for run in runs:
+                if len(run) < 2:
+                    continue
+                repeated: list[tuple[int, int]] = [
+                    self._dropped_span(nodes[id(symbol)], source, line_starts) for symbol in run[1:]
+                ]
+                marker: str = f"{self.config.line_comment} +{len(repeated)} similar"
+                for index, group in enumerate(_runs(repeated, source)):
+                    start, end = group[0][0], group[-1][1]
+                    if index or not _owns_lines(source, start, end):
+                        spans.append((start, end, b""))
+                        continue
+                    indent: bytes = _indentation(source[start:end])
+                    spans.append((start, end, indent + marker.encode() + line_break_of(source)))
+        return spans
+
+    def _deep_item_spans(
+        self,
+        tree: Tree,
+        source: bytes,
+        line_starts: list[int],
+        removed: list[tuple[int, int]],
+        kept_bodies: list[tuple[int, int]],
+    ) -> list[tuple[int, int, bytes]]:
+        """Compute the byte ranges of items nested too deep and the markers replacing them.
+
+        Hidden items only separated by whitespace are replaced by a single marker
+        counting them (`// 3 items hidden`), following the stub of a body it leaves
+        without statements where an empty one is invalid (`...  # 1 item hidden`).
+        Items inside removed code (such as functions defined in an elided body or
+        dropped private items) are not counted, and items inside kept bodies are
+        never hidden.
+
+        Args:
+            tree: Parsed syntax tree
+            source: Encoded source code
+            line_starts: Byte offset at which each line starts
+            removed: Sorted, disjoint byte ranges already removed from the skeleton
+            kept_bodies: Sorted, disjoint byte ranges of the items whose bodies are kept
+
+        Returns:
+            Byte ranges covering each run of hidden items, with their marker texts
+        """
+        max_depth: int = self.options.max_depth or 0
+        depths: dict[int, int] = {}
+        hidden: list[tuple[int, int, bytes]] = []
+        hidden_nodes: dict[int, Node] = {}
+        for symbol, node in symbol_nodes(self, tree, source):
+            depth: int = depths.get(id(symbol), 0)
+            for child in symbol.children:
+                depths[id(child)] = depth + 1
+            if depth == max_depth + 1 and not is_inside(
+                kept_bodies, node.start_byte, node.end_byte
+            ):
+                span: tuple[int, int] = self._dropped_span(node, source, line_starts)
+                hidden.append((*span, b""))
+                hidden_nodes[span[0]] = node
+
+        spans: list[tuple[int, int, bytes]] = []
+        outermost: list[tuple[int, int]] = [span[:2] for span in _outside(hidden, removed)]
+        for group in _runs(outermost, source):
+            start, end = group[0][0], group[-1][1]
+            if not _owns_lines(source, start, end):
+                spans.append((start, end, b""))
+                continue
+            note: str = f"{len(group)} item{'s' if len(group) != 1 else ''} hidden"
+            marker: str = f"{self.config.line_comment} {note}"
+            # A body left without statements needs its stub where an empty one is invalid
+            item: Node = hidden_nodes[start]
+            if item.parent is not None and item.parent.type in _WRAPPER_TYPES:
+                item = item.parent
+            if (
+                self.config.body_stub
+                and item.parent is not None
+                and _is_emptied(item.parent, merge_ranges([*removed, (start, end)]))
+            ):
+                marker = self.config.placeholder.format(lines=note)
+            indent: bytes = _indentation(source[start:end])
+            spans.append((start, end, indent + marker.encode() + line_break_of(source)))
+        return spans
+
+    def _line_levels(
+        self, tree: Tree, source: bytes, line_starts: list[int]
+    ) -> list[tuple[int, int] | None]:
+        """Compute the nesting level of each source line, for re-indenting the skeleton.
+
+        Each line belongs to the innermost symbol whose item (doc comments and
+        attributes included) spans it; lines outside every symbol belong to none.
+
+        Args:
+            tree: Parsed syntax tree
+            source: Encoded source code
+            line_starts: Byte offset at which each line starts
+
+        Returns:
+            For each line, the depth of its symbol in the symbol tree and the
+            indentation width of the symbol's first line ((0, 0) outside every
+            symbol), or None for lines inside multi-line strings, kept verbatim
+        """
+        levels: list[tuple[int, int] | None] = [(0, 0)] * len(line_starts)
+        if self.config.symbol_query:
+            depths: dict[int, int] = {}
+            # Parents come before their children, which override them
+            for symbol, node in symbol_nodes(self, tree, source):
+                depth: int = depths.get(id(symbol), 0)
+                for child in symbol.children:
+                    depths[id(child)] = depth + 1
+                start, end = item_span(node, source, line_starts)
+                first: int = bisect_right(line_starts, start) - 1
+                last: int = bisect_right(line_starts, max(start, end - 1)) - 1
+                width: int = len(_indentation(source[line_starts[first] : end]))
+                levels[first : last + 1] = [(depth, width)] * (last - first + 1)
+
+        if self.config.literal_query:
+            docs: list[tuple[int, int]] = merge_ranges(
+                [(node.start_byte, node.end_byte) for node in self._doc_nodes(tree)]
+            )
+            cursor: QueryCursor = QueryCursor(self.query(self.config.literal_query))
+            for node in cursor.captures(tree.root_node).get("string", []):
+                first_line, last_line = node.start_point[0], node.end_point[0]
+                if last_line > first_line and not is_inside(docs, node.start_byte, node.end_byte):
+                    levels[first_line + 1 : last_line + 1] = [None] * (last_line - first_line)
+        return levels
+
+    def _import_spans(
+        self, tree: Tree, source: bytes, line_starts: list[int]
+    ) -> list[tuple[int, int, bytes]]:
+        """Compute the byte ranges of import groups and the summaries replacing them.
+
+        Imports only separated by a line break form a group; a blank line or any other
+        code starts a new one. Summaries are empty unless imports are collapsed, when
+        groups importing a single item are kept as they are.
+
+        Args:
+            tree: Parsed syntax tree
+            source: Encoded source code
+            line_starts: Byte offset at which each line starts
+
+        Returns:
+            Byte ranges covering each group of imports, with their summary texts
+        """
+        cursor: QueryCursor = QueryCursor(self.query(self.config.import_query or ""))
+        captures: dict[str, list[Node]] = cursor.captures(tree.root_node)
+        items: list[int] = sorted(node.start_byte for node in captures.get("item", []))
+        # Some grammars include the trailing newline in `#include` lines
+        statements: list[tuple[int, int]] = [
+            (node.start_byte, node.end_byte - _trailing_whitespace(source, node))
+            for node in captures.get("import", [])
+        ]
+
+        spans: list[tuple[int, int, bytes]] = []
+        for group in _runs(statements, source, max_line_breaks=1):
+            start, end = _range_span(group[0][0], group[-1][1], source, line_starts)
+            if self.options.imports == "drop":
+                spans.append((start, end, b""))
+                continue
+            if not _owns_lines(source, start, end):
+                # A comment would swallow the code sharing the group's line
+                continue
+            count: int = bisect_left(items, end) - bisect_left(items, start) or len(group)
+            if count < 2:
+                continue
+            summary: str = (
+                f"{self.config.line_comment} {count} import{'s' if count != 1 else ''} omitted"
+            )
+            indent: bytes = source[start : group[0][0]]
+            spans.append((start, end, indent + summary.encode() + line_break_of(source)))
+        return spans
+
+    def test_nodes(self, tree: Tree, source: bytes) -> list[Node]:
+        """Find the test items of a source.
+
+        Test items are the nodes the test query captures as `@test`, and the items
+        an attribute it captures as `@attr` is attached to when the attribute marks
+        a test (`#[test]`, `#[bench]`, `#[cfg(test)]`; see `_is_test_attribute`).
+        Attributes are read from the syntax tree, so mentions of `test` in code and
+        comments do not count.
+
+        Args:
+            tree: Parsed syntax tree
+            source: Encoded source code
+
+        Returns:
+            Test item nodes, possibly nested in one another; none for languages
+            without a test query
+        """
+        if not self.config.test_query:
+            return []
+        cursor: QueryCursor = QueryCursor(self.query(self.config.test_query))
+        captures: dict[str, list[Node]] = cursor.captures(tree.root_node)
+        tests: list[Node] = list(captures.get("test", []))
+        for attribute in captures.get("attr", []):
+            if not _is_test_attribute(source[attribute.start_byte : attribute.end_byte]):
+                continue
+            item: Node | None = attribute.next_named_sibling
+            while item is not None and item.type in _LEADING_TRIVIA_TYPES:
+                item = item.next_named_sibling
+            if item is not None:
+                tests.append(item)
+        return tests
+
+    def _test_spans(
+        self, tree: Tree, source: bytes, line_starts: list[int]
+    ) -> list[tuple[int, int, bytes]]:
+        """Compute the byte ranges of test code and the markers replacing them.
+
+        Test items only separated by whitespace are replaced by a single marker
+        counting them (`// tests omitted (3 items)`).
+
+        Args:
+            tree: Parsed syntax tree
+            source: Encoded source code
+            line_starts: Byte offset at which each line starts
+
+        Returns:
+            Byte ranges covering each run of test items, with their marker texts
+        """
+        tests: list[Node] = self.test_nodes(tree, source)
+
+        # Items nested in another test item are removed along with it
+        outermost: list[tuple[int, int]] = []
+        for start, end in sorted(
+            {self._dropped_span(node, source, line_starts) for node in tests},
+            key=lambda span: (span[0], -span[1]),
+        ):
+            if not outermost or start >= outermost[-1][1]:
+                outermost.append((start, end))
+
+        spans: list[tuple[int, int, bytes]] = []
+        for group in _runs(outermost, source):
+            start, end = group[0][0], group[-1][1]
+            if not _owns_lines(source, start, end):
+                spans.append((start, end, b""))
+                continue
+            marker: str = (
+                f"{self.config.line_comment} tests omitted "
+                f"({len(group)} item{'s' if len(group) != 1 else ''})"
+            )
+            indent: bytes = _indentation(source[start:end])
+            spans.append((start, end, indent + marker.encode() + line_break_of(source)))
+        return spans
+
+    def _attribute_argument_spans(
+        self, tree: Tree, source: bytes
+    ) -> list[tuple[int, int, bytes]]:
+        """Compute the argument lists elided from attributes longer than `max_attr_len`.
+
+        Args:
+            tree: Parsed syntax tree
+            source: Encoded source code
+
+        Returns:
+            Byte ranges of the outermost argument lists of long attributes, with the
+            text replacing them
+        """
+        max_attr_len: int = self.options.max_attr_len or 0
+        cursor: QueryCursor = QueryCursor(self.query(self.config.attribute_query or ""))
+        spans: list[tuple[int, int, bytes]] = []
+        for node_list in cursor.captures(tree.root_node).values():
+            for attribute in node_list:
+                text: bytes = source[attribute.start_byte : attribute.end_byte]
+                if len(text.decode(errors="replace").rstrip()) <= max_attr_len:
+                    continue
+                pending: list[Node] = [attribute]
+                while pending:
+                    node = pending.pop()
+                    if node.type in _ATTRIBUTE_ARGUMENT_TYPES:
+                        spans.append((node.start_byte, node.end_byte, _ELIDED_ARGUMENTS))
+                    else:
+                        pending.extend(node.children)
+        return _outermost(spans)
+
+    def _doc_nodes(self, tree: Tree) -> list[Node]:
+        """Find the doc comments and docstrings of a tree (see `doc_comment_query`).
+
+        Comments captured along with the `@item` they are above only document it
+        when on the lines directly above it, as `item_span` attaches them, so a
+        comment a blank line away from a function is not taken for its docs.
+
+        Args:
+            tree: Parsed syntax tree
+
+        Returns:
+            Doc comment and docstring nodes, in no particular order
+        """
+        if not self.config.doc_comment_query:
+            return []
+        cursor: QueryCursor = QueryCursor(self.query(self.config.doc_comment_query))
+        docs: dict[int, Node] = {}
+        for _, captures in cursor.matches(tree.root_node):
+            items: list[Node] = captures.get("item", [])
+            if not items:
+                nodes: list[Node] = [node for node_list in captures.values() for node in node_list]
+                docs.update((node.start_byte, node) for node in nodes)
+                continue
+            line: int = items[0].start_point[0]
+            for node in sorted(captures.get("doc", []), key=lambda node: -node.start_byte):
+                if node.end_point[0] < line - 1:
+                    break
+                docs[node.start_byte] = node
+                line = node.start_point[0]
+        return list(docs.values())
+
+    def _literal_exempt_ranges(
+        self, tree: Tree, removed: list[tuple[int, int]]
+    ) -> list[tuple[int, int]]:
+        """Compute the ranges whose literals are left alone: removed code, doc comments,
+        attributes and imports.
+
+        Args:
+            tree: Parsed syntax tree
+            removed: Sorted, disjoint byte ranges removed from the skeleton
+
+        Returns:
+            Sorted, disjoint byte ranges
+        """
+        kept_queries: list[str | None] = [self.config.attribute_query, self.config.import_query]
+        kept: list[tuple[int, int]] = list(removed)
+        kept.extend((node.start_byte, node.end_byte) for node in self._doc_nodes(tree))
+        for kept_query in filter(None, kept_queries):
+            kept_cursor: QueryCursor = QueryCursor(self.query(kept_query))
+            for node_list in kept_cursor.captures(tree.root_node).values():
+                kept.extend((node.start_byte, node.end_byte) for node in node_list)
+        return merge_ranges(kept)
+
+    def _literal_truncations(
+        self,
+        tree: Tree,
+        source: bytes,
+        line_starts: list[int],
+        removed: list[tuple[int, int]],
+        kept_bodies: list[tuple[int, int]],
+    ) -> list[tuple[int, int, bytes]]:
+        """Compute the tails of long literals to elide and the markers replacing them.
+
+        Literals of removed code, doc comments, attributes, imports and items whose
+        bodies are kept are left whole, as are literals whose grammar does not
+        expose their delimiters. Cuts fall between elements, so the functions of
+        the literals are kept or dropped whole.
+
+        Args:
+            tree: Parsed syntax tree
+            source: Encoded source code
+            line_starts: Byte offset at which each line starts
+            removed: Sorted, disjoint byte ranges removed from the skeleton
+            kept_bodies: Sorted, disjoint byte ranges of the items whose bodies are kept
+
+        Returns:
+            Byte ranges of the outermost elided tails with their markers
+        """
+        limit: int = self.options.max_literal_len or 0
+        exempt: list[tuple[int, int]] = merge_ranges(
+            [*self._literal_exempt_ranges(tree, removed), *kept_bodies]
+        )
+        cursor: QueryCursor = QueryCursor(self.query(self.config.literal_query or ""))
+        captures: dict[str, list[Node]] = cursor.captures(tree.root_node)
+        truncations: list[tuple[int, int, bytes]] = []
+        for capture_name in ("array", "map", "string"):
+            for node in captures.get(capture_name, []):
+                if node.end_byte - node.start_byte <= limit or node.child_count < 2:
+                    continue
+                if node.parent is not None and node.parent.type in _SYNTAX_LITERAL_PARENTS:
+                    continue
+                if _overlaps(exempt, node.start_byte, node.end_byte):
+                    continue
+                if capture_name == "string":
+                    truncation = _string_truncation(node, source, limit)
+                else:
+                    truncation = self._collection_truncation(node, source, line_starts, limit)
+                if truncation is not None:
+                    truncations.append(truncation)
+        return _outermost(truncations)
+
+    def _collection_truncation(
+        self, node: Node, source: bytes, line_starts: list[int], limit: int
+    ) -> tuple[int, int, bytes] | None:
+        """Compute the elided tail of a long array or map literal and its marker.
+
+        The leading elements (and the separators after them) ending within `limit`
+        bytes of the literal's start are kept, up to its closing delimiter.
+        """
+        opening, closing = node.children[0], node.children[-1]
+        if opening.is_named or closing.is_named:
+            return None
+        cut: int = opening.end_byte
+        for child in node.children[1:-1]:
+            if child.end_byte - node.start_byte > limit:
+                break
+            cut = child.end_byte
+        elided: int = closing.start_byte - cut
+        if elided <= 0:
+            return None
+        note: str = f"{elided} bytes elided"
+        block_comment = self.config.block_comment
+        if block_comment is not None:
+            separator: str = " " if cut > opening.end_byte else ""
+            comment: str = f"{block_comment[0]} {note} {block_comment[1]}"
+            return cut, closing.start_byte, f"{separator}{comment}".encode()
+        # The comment runs to the end of the line, so the closing delimiter gets its own
+        indent: bytes = _indentation(source[line_starts[node.start_point[0]] : node.start_byte])
+        marker: bytes = f"  {self.config.line_comment} {note}".encode() + line_break_of(source)
+        return cut, closing.start_byte, marker + indent
+
+    def _literal_redactions(
+        self, tree: Tree, removed: list[tuple[int, int]]
+    ) -> list[tuple[int, int, bytes]]:
+        """Compute the literals to redact and the text replacing each.
+
+        Literals in removed code, doc comments, attributes and imports, and literals
+        that are part of a declaration's syntax (`extern "C"`), are left alone.
+
+        Args:
+            tree: Parsed syntax tree
+            removed: Sorted, disjoint byte ranges removed from the skeleton
+
+        Returns:
+            Byte ranges of the outermost redacted literals with their replacements
+        """
+        kept: list[tuple[int, int]] = self._literal_exempt_ranges(tree, removed)
+        cursor: QueryCursor = QueryCursor(self.query(self.config.literal_query or ""))
+        captures: dict[str, list[Node]] = cursor.captures(tree.root_node)
+        redactions: list[tuple[int, int, bytes]] = []
+        for capture_name, replacement in (("string", _REDACTED_STRING), ("number", b"0")):
+            for node in captures.get(capture_name, []):
+                if node.parent is not None and node.parent.type in _SYNTAX_LITERAL_PARENTS:
+                    continue
+                if not _overlaps(kept, node.start_byte, node.end_byte):
+                    redactions.append((node.start_byte, node.end_byte, replacement))
+        return _outermost(redactions)
+
+    def _signature_cuts(self, tree: Tree, source: bytes) -> list[tuple[int, int, bytes]]:
+        """Compute the cuts shortening signatures longer than `max_signature_len`.
+
+        Where clauses collapse first, then type parameter lists (`</* +2 more */>`),
+        then trailing parameters are cut until the signature fits.
+
+        Args:
+            tree: Parsed syntax tree
+            source: Encoded source code
+
+        Returns:
+            Byte ranges of the collapsed `where` clauses and type parameter lists
+            and of the cut parameters, with the comments replacing them
+        """
+        limit: int = self.options.max_signature_len or 0
+        cuts: list[tuple[int, int, bytes]] = []
+        for symbol, node in symbol_nodes(self, tree, source):
+            length: int = len(symbol.signature)
+            end: int = symbol.signature_end_byte
+            if length <= limit:
+                continue
+            if self.config.block_comment is not None:
+                opening, closing = self.config.block_comment
+                for clause in node.children:
+                    if clause.type == "where_clause" and clause.end_byte <= end:
+                        text: str = f"where {opening} ... {closing}"
+                        length -= len(_text(source, clause)) - len(text)
+                        cuts.append((clause.start_byte, clause.end_byte, text.encode()))
+                generics: Node | None = node.child_by_field_name("type_parameters")
+                if length > limit and generics is not None and generics.end_byte <= end:
+                    generic_items: list[Node] = [
+                        child
+                        for child in generics.named_children
+                        if child.type not in _COMMENT_TYPES
+                    ]
+                    inner: tuple[int, int] = (generics.start_byte + 1, generics.end_byte - 1)
+                    if generic_items:
+                        more: str = self._more_parameters(len(generic_items), separator=False)
+                        length -= len(source[slice(*inner)].decode(errors="replace")) - len(more)
+                        cuts.append((*inner, more.encode()))
+            parameters: Node | None = _parameter_list(node)
+            if length <= limit or parameters is None or parameters.end_byte > end:
+                continue
+            items: list[Node] = [
+                child for child in parameters.named_children if child.type not in _COMMENT_TYPES
+            ]
+            if not items:
+                continue
+            delimiters: list[Node] = [child for child in parameters.children if not child.is_named]
+            list_start: int = delimiters[0].end_byte if delimiters else parameters.start_byte
+            list_end: int = delimiters[-1].start_byte if delimiters else parameters.end_byte
+            # Keep as many leading parameters as fit, but cut at least one
+            for kept in range(len(items) - 1, -1, -1):
+                start: int = items[kept - 1].end_byte if kept else list_start
+                marker: str = self._more_parameters(len(items) - kept, separator=kept > 0)
+                cut_length: int = len(source[start:list_end].decode(errors="replace"))
+                if length - cut_length + len(marker) <= limit or kept == 0:
+                    cuts.append((start, list_end, marker.encode()))
+                    break
+        return cuts
+
+    def _more_parameters(self, count: int, *, separator: bool) -> str:
+        """Render the comment standing for the parameters cut from a signature."""
+        comma: str = ", " if separator else ""
+        if self.config.block_comment is not None:
+            opening, closing = self.config.block_comment
+            return f"{comma}{opening} +{count} more {closing}"
+        # A line comment ends the line, so the closing delimiter goes on the next one
+        return f"{comma.rstrip()}  {self.config.line_comment} +{count} more\n"
+
+    def _alias_expansions(self, tree: Tree, source: bytes) -> list[tuple[int, int, bytes]]:
+        """Compute the references to local type aliases and their expansions.
+
+        References inside bodies and alias declarations are left alone, as are
+        qualified paths (`std::result::Result`), the names of definitions and the
+        names an item binds as type parameters (`T` in `fn get<T>(x: T)`, with an
+        alias `T` elsewhere).
+
+        Args:
+            tree: Parsed syntax tree
+            source: Encoded source code
+
+        Returns:
+            Byte ranges of the outermost expanded references with their expansions
+        """
+        cursor: QueryCursor = QueryCursor(self.query(self.config.alias_query or ""))
+        declarations: list[Node] = cursor.captures(tree.root_node).get("alias", [])
+        aliases: dict[str, _Alias | None] = {}
+        for declaration in declarations:
+            name: Node | None = declaration.child_by_field_name("name")
+            value: Node | None = declaration.child_by_field_name("type")
+            value = value or declaration.child_by_field_name("value")
+            if name is None or value is None:
+                continue
+            key: str = _text(source, name)
+            parameters: list[str] | None = _parameter_names(
+                declaration.child_by_field_name("type_parameters"), source
+            )
+            # An alias declared twice (in two modules, say) is ambiguous
+            resolved: bool = key not in aliases and parameters is not None
+            aliases[key] = (parameters or [], value) if resolved else None
+
+        skipped: list[tuple[int, int]] = [(node.start_byte, node.end_byte) for node in declarations]
+        body_cursor: QueryCursor = QueryCursor(self.query(self.config.body_query))
+        for node_list in body_cursor.captures(tree.root_node).values():
+            skipped.extend((node.start_byte, node.end_byte) for node in node_list)
+        skipped = merge_ranges(skipped)
+
+        expansions: list[tuple[int, int, bytes]] = []
+        # Nodes to visit, with the aliases visible in them
+        pending: list[tuple[Node, Mapping[str, _Alias | None]]] = [(tree.root_node, aliases)]
+        while pending:
+            node, visible = pending.pop()
+            if is_inside(skipped, node.start_byte, node.end_byte):
+                continue
+            if _alias_reference(node, source) is not None:
+                text: str = _expanded(node, source, visible, frozenset(), {})
+                if text != _text(source, node):
+                    if "\n" in text:
+                        text = " ".join(text.split())
+                    expansions.append((node.start_byte, node.end_byte, text.encode()))
+                    continue
+            bound: set[str] = _bound_type_names(node, source) & visible.keys()
+            if bound:
+                # Type parameters shadow the aliases of the same name in their item
+                visible = {**visible, **dict.fromkeys(bound)}
+            pending.extend((child, visible) for child in node.children)
+        return _outermost(expansions)
+
+    def _error_spans(
+        self, errors: list[Node], tree: Tree, source: bytes, line_starts: list[int]
+    ) -> list[tuple[int, int]]:
+        """Compute the byte ranges dropped for syntax errors.
+
+        Definitions and comments inside an ERROR node parsed fine and are kept; the
+        tokens between them are dropped.
+
+        Args:
+            errors: Outermost ERROR and MISSING nodes
+            tree: Parsed syntax tree
+            source: Encoded source code
+            line_starts: Byte offset at which each line starts
+
+        Returns:
+            Byte ranges covering the unparsed tokens
+        """
+        definitions: set[tuple[int, int]] = set()
+        if self.config.symbol_query:
+            definitions = {
+                (node.start_byte, node.end_byte) for _, node in symbol_nodes(self, tree, source)
+            }
+
+        def kept(node: Node) -> bool:
+            return (
+                node.type in _LEADING_TRIVIA_TYPES
+                or (node.start_byte, node.end_byte) in definitions
+            )
+
+        spans: list[tuple[int, int]] = []
+        pending: list[Node] = [node for node in errors if node.is_error]
+        while pending:
+            children: list[Node] = pending.pop().children
+            for child in children:
+                if child.is_error:
+                    pending.append(child)
+                elif kept(child):
+                    pending.extend(node for node in syntax_error_nodes(child) if node.is_error)
+            for dropped, run in groupby(children, key=lambda n: not (n.is_error or kept(n))):
+                if dropped:
+                    nodes = list(run)
+                    spans.append(
+                        _range_span(nodes[0].start_byte, nodes[-1].end_byte, source, line_starts)
+                    )
+        return spans
+
+    def _placeholder(
+        self, node: Node, span: tuple[int, int], source: bytes, line_starts: list[int]
+    ) -> tuple[tuple[int, int], bytes]:
+        """Compute the byte range and placeholder text replacing an elided body.
+
+        Brace-delimited bodies keep their braces around the placeholder
+        (`{ /* 4 lines */ }`, or on its own line with `body_braces` set to "block"),
+        and empty ones collapse to `{}`. Other bodies get the
+        placeholder on its own indented line, or after the header when they share it.
+        Without placeholders, the language's body stub (if any) is used instead.
+        Line counts include the lines of the braces, so a body on a single line
+        counts as one.
+
+        Args:
+            node: Captured body node
+            span: Byte range `_removal_span` computed for the node
+            source: Encoded source code
+            line_starts: Byte offset at which each line starts
+
+        Returns:
+            Byte range to remove and the placeholder text to put in its place
+        """
+        text: bytes = source[node.start_byte : node.end_byte]
+        if (
+            self.language not in ("python", "ruby", "lua")
+            and text.startswith(b"{")
+            and text.endswith(b"}")
+        ):
+            inner: tuple[int, int] = (node.start_byte + 1, node.end_byte - 1)
+            if not source[inner[0] : inner[1]].strip():
+                return inner, b""
+            body_lines: int = node.end_point[0] - node.start_point[0] + 1
+            marker: str = self._placeholder_text(body_lines)
+            if not marker:
+                return inner, b""
+            if self.options.body_braces == "block":
+                return inner, self._block_placeholder(node, marker, source, line_starts)
+            return inner, f" {marker} ".encode()
+
+        start, end = span
+        first_line: int = bisect_right(line_starts, start) - 1
+        if not self._marks_bodies() and first_line > node.start_point[0]:
+            # The kept leading part of the body (a docstring) is a valid body already
+            return span, b""
+        elided_lines: int = node.end_point[0] - max(first_line, node.start_point[0]) + 1
+        placeholder: str = self._placeholder_text(elided_lines)
+        if not placeholder:
+            return span, b""
+        if start != line_starts[first_line]:
+            return span, f" {placeholder}".encode()
+
+        # Indent like the first elided statement (comments may be indented arbitrarily)
+        indent: bytes = _indentation(source[start:end])
+        for child in node.named_children:
+            if child.start_byte >= start and child.type != "comment":
+                child_line_start: int = line_starts[child.start_point[0]]
+                if not source[child_line_start : child.start_byte].strip():
+                    indent = source[child_line_start : child.start_byte]
+                break
+        return span, indent + placeholder.encode() + line_break_of(source)
+
+    def _size_note(self, node: Node, bodies: list[Node]) -> str:
+        """Render the size annotation of an elided body: its lines, and its branches.
+
+        Args:
+            node: Captured body node
+            bodies: All captured body nodes, whose branches count toward their own
+                body only
+
+        Returns:
+            Text of the annotation, e.g. "6 lines, 1 branch"
+        """
+        lines: int = node.end_point[0] - node.start_point[0] + 1
+        note: str = f"{lines} line{'s' if lines != 1 else ''}"
+        if self.options.annotate_complexity and self.config.branch_query:
+            nested: list[tuple[int, int]] = merge_ranges(
+                [
+                    (body.start_byte, body.end_byte)
+                    for body in bodies
+                    if node.start_byte <= body.start_byte
+                    and body.end_byte <= node.end_byte
+                    and (body.start_byte, body.end_byte) != (node.start_byte, node.end_byte)
+                ]
+            )
+            cursor: QueryCursor = QueryCursor(self.query(self.config.branch_query))
+            branches: int = sum(
+                not is_inside(nested, branch.start_byte, branch.end_byte)
+                for branch in cursor.captures(node).get("branch", [])
+            )
+            note += f", {branches} branch{'es' if branches != 1 else ''}"
+        return note
+
+    def _annotated(
+        self,
+        node: Node,
+        span: tuple[int, int],
+        text: bytes,
+        note: str,
+        source: bytes,
+        line_starts: list[int],
+    ) -> tuple[tuple[int, int], bytes]:
+        """Add a size annotation to the removal of an elided body.
+
+        The annotation ends the line the body's placeholder leaves its header on
+        (`{ // 6 lines` when the body is removed down to its closing brace, after
+        the closing brace of a placeholder between braces, after a placeholder on
+        its own line), and is written as a block comment when code follows on that
+        line; without block comments it is left out there.
+
+        Args:
+            node: Captured body node
+            span: Byte range removed for the body
+            text: Placeholder text replacing the range, possibly empty
+            note: Text of the annotation (see `_size_note`)
+            source: Encoded source code
+            line_starts: Byte offset at which each line starts
+
+        Returns:
+            Byte range to remove and the text to put in its place
+        """
+        start, end = span
+        line_break: bytes = line_break_of(source)
+        # PEP 8 puts two spaces before inline comments
+        gap: str = "  " if self.language == "python" else " "
+        comment: bytes = f"{gap}{self.config.line_comment} {note}".encode()
+        if text.startswith(line_break):
+            # A placeholder laid out on lines of its own, after the opening brace
+            return span, comment + text
+        if text.endswith(line_break):
+            return span, text[: -len(line_break)] + comment + line_break
+        if (
+            not text
+            and line_starts[bisect_right(line_starts, start) - 1] == start
+            and start > node.start_byte
+            and source[start - len(line_break) : start] == line_break
+        ):
+            # Whole lines removed: the comment goes at the end of the last line kept
+            return (start - len(line_break), end), comment + line_break
+        newline: int = source.find(b"\n", end)
+        line_end: int = len(source) if newline < 0 else newline
+        tail_end: int = node.end_byte if end < node.end_byte <= line_end else end
+        if not source[tail_end:line_end].strip():
+            return (start, tail_end), text + source[end:tail_end] + comment
+        if self.config.block_comment is not None:
+            opening, closing = self.config.block_comment
+            return span, text + f" {opening} {note} {closing}".encode()
+        return span, text
+
+    def _block_placeholder(
+        self, node: Node, marker: str, source: bytes, line_starts: list[int]
+    ) -> bytes:
+        """Lay out the placeholder of a brace-delimited body on its own line.
+
+        The placeholder is indented like the first line of the body, or one level
+        (four spaces, or a tab in tab-indented code) past the line of the opening
+        brace when the body starts on that line, and the closing brace goes on a
+        line of its own, indented like the opening brace's line.
+        """
+        line_start: int = line_starts[node.start_point[0]]
+        outer: bytes = _indentation(source[line_start : node.start_byte + 1])
+        inner_text: bytes = source[node.start_byte + 1 : node.end_byte - 1]
+        if inner_text.lstrip(b" \t").startswith((b"\n", b"\r\n")):
+            inner: bytes = _indentation(inner_text)
+        else:
+            inner = outer + (b"\t" if outer.startswith(b"\t") else b"    ")
+        line_break: bytes = line_break_of(source)
+        return line_break + inner + marker.encode() + line_break + outer
+
+    def _marks_bodies(self) -> bool:
+        """Tell whether elided bodies are replaced with a placeholder of the options."""
+        return (
+            self.options.body_placeholders
+            or self.options.placeholder is not None
+            or self.options.placeholder_comment is not None
+            or self._stubs_bodies()
+        )
+
+    def _stubs_bodies(self) -> bool:
+        """Tell whether `stub_mode` applies, the language having a stub body."""
+        return self.options.stub_mode and self.config.stub_mode_body is not None
+
+    def _placeholder_text(self, lines: int) -> str:
+        """Render the placeholder for a number of elided lines."""
+        if self._stubs_bodies():
+            return self.config.stub_mode_body or ""
+        custom = self.options.placeholder
+        line_count: str = f"{lines} line{'s' if lines != 1 else ''}"
+        if callable(custom):
+            return custom(lines)
+        if custom is not None:
+            return custom.format(lines=line_count, count=lines)
+        comment = self.options.placeholder_comment
+        if comment is not None:
+            text: str = comment.format(lines=line_count, count=lines)
+            return self.config.placeholder.format(lines=text)
+        if not self.options.body_placeholders:
+            return self.config.body_stub or ""
+        return self.config.placeholder.format(lines=line_count)
+
+
+def is_hidden(visibility: str | None, level: Literal["all", "crate", "public"]) -> bool:
+    """Tell whether items of a visibility are dropped when keeping a visibility level.
+
+    Args:
+        visibility: Declared visibility of the item (see `Symbol.visibility`)
+        level: Least visibility of the items kept (see `LopperOptions.visibility`)
+
+    Returns:
+        True if the item is dropped
+    """
+    if visibility in PRIVATE_VISIBILITIES:
+        return level != "all"
+    if visibility in CRATE_VISIBILITIES or (visibility or "").startswith("pub(in"):
+        return level == "public"
+    return False
+
+
+def _kept_segments(
+    source: bytes,
+    removals: list[tuple[int, int]],
+    placeholders: list[tuple[int, int, bytes]],
+) -> Iterator[tuple[bytes, int, bool]]:
+    """Yield the kept source between removed ranges, and the placeholders replacing them.
+
+    Args:
+        source: Encoded source code
+        removals: Sorted, disjoint byte ranges to remove
+        placeholders: Sorted, disjoint placeholder ranges and texts
+
+    Yields:
+        (text, source offset, whether the text is source) triples; placeholders
+        carry the offset of the range they replace
+    """
+    pending = iter(placeholders)
+    placeholder = next(pending, None)
+    position: int = 0
+    for start, end in [*removals, (len(source), len(source))]:
+        yield source[position:start], position, True
+        while placeholder is not None and placeholder[0] < end:
+            yield placeholder[2], placeholder[0], False
+            placeholder = next(pending, None)
+        position = end
+
+
+def _reordered(
+    segments: Iterator[tuple[bytes, int, bool]], ranges: list[tuple[int, int]]
+) -> Iterator[tuple[bytes, int, bool]]:
+    """Yield the parts of skeleton segments coming from each source range in turn.
+
+    Args:
+        segments: Segments in source order (see `_kept_segments`)
+        ranges: Disjoint byte ranges covering the source, in the order wanted
+
+    Yields:
+        The segments, source ones cut at range boundaries, with placeholders going
+        with the range their offset is in
+    """
+    pieces: list[tuple[bytes, int, bool]] = [piece for piece in segments if piece[0]]
+    offsets: list[int] = [offset for _, offset, _ in pieces]
+    for start, end in ranges:
+        # The last source segment starting before the range may run into it
+        index: int = bisect_left(offsets, start)
+        while index > 0 and not pieces[index - 1][2]:
+            index -= 1
+        index = max(index - 1, 0)
+        while index < len(pieces) and pieces[index][1] < end:
+            text, offset, is_source = pieces[index]
+            if is_source:
+                low, high = max(start, offset), min(end, offset + len(text))
+                if low < high:
+                    yield text[low - offset : high - offset], low, True
+            elif offset >= start:
+                yield text, offset, False
+            index += 1
+
+
+def _right_trimmed(
+    segments: Iterator[tuple[bytes, int, bool]],
+) -> Iterator[tuple[bytes, int, bool]]:
+    """Drop trailing whitespace, holding whitespace back until something follows it."""
+    held: list[tuple[bytes, int, bool]] = []
+    for text, offset, is_source in segments:
+        kept: bytes = text.rstrip()
+        if kept:
+            yield from held
+            held = []
+            yield kept, offset, is_source
+            text = text[len(kept) :]
+            offset += len(kept) if is_source else 0
+        if text:
+            held.append((text, offset, is_source))
+
+
+def _numbered(
+    segments: Iterator[tuple[bytes, int, bool]], line_starts: list[int], width: int
+) -> Iterator[bytes]:
+    """Prefix each line with the number of the source line it starts on.
+
+    Args:
+        segments: Segments from `_kept_segments`
+        line_starts: Byte offset at which each line starts
+        width: Width to right-align line numbers to
+
+    Yields:
+        Segment texts with line number gutters inserted
+    """
+    at_line_start: bool = True
+    for text, offset, is_source in segments:
+        pieces: list[bytes] = []
+        index: int = 0
+        while index < len(text):
+            if at_line_start:
+                number: int = bisect_right(line_starts, offset + index if is_source else offset)
+                gutter: str = f"{number:>{width}}{GUTTER_SEPARATOR}"
+                blank: bool = text.startswith((b"\n", b"\r\n"), index)
+                pieces.append(gutter.encode() if blank else f"{gutter} ".encode())
+                at_line_start = False
+            newline: int = text.find(b"\n", index)
+            if newline < 0:
+                pieces.append(text[index:])
+                break
+            pieces.append(text[index : newline + 1])
+            index = newline + 1
+            at_line_start = True
+        yield b"".join(pieces)
+
+
+def _reindented(
+    segments: Iterator[tuple[bytes, int, bool]],
+    line_starts: list[int],
+    levels: list[tuple[int, int] | None],
+    unit: int,
+    width: int,
+) -> Iterator[tuple[bytes, int, bool]]:
+    """Replace the indentation of each line with one of `width` spaces per nesting level.
+
+    A line starting `extra` columns past the first line of its symbol is indented
+    `extra // unit` levels deeper than the symbol, followed by the rest of the
+    columns. Whitespace of blank lines is dropped.
+
+    Args:
+        segments: Segments from `_kept_segments`
+        line_starts: Byte offset at which each line starts
+        levels: Nesting level of each source line (see `_line_levels`)
+        unit: Width of one level of the source's indentation
+        width: Number of spaces per nesting level
+
+    Yields:
+        The segments with their leading whitespace replaced by indentation segments,
+        which are not source and carry the offset of the text they indent
+    """
+    leading: bytes | None = b""
+    for text, offset, is_source in segments:
+        index: int = 0
+        while index < len(text):
+            if leading is not None:
+                content: int = index
+                while content < len(text) and text[content] in b" \t":
+                    content += 1
+                leading += text[index:content]
+                index = content
+                if index == len(text):
+                    break
+                if text[index] not in b"\r\n":
+                    position: int = offset + index if is_source else offset
+                    level = levels[bisect_right(line_starts, position) - 1]
+                    if level is None:
+                        indent: bytes = leading
+                    else:
+                        depth, base = level
+                        extra: int = max(len(leading) - base, 0)
+                        indent = b" " * (width * (depth + extra // unit) + extra % unit)
+                    if indent:
+                        yield indent, position, False
+                leading = None
+            newline: int = text.find(b"\n", index)
+            end: int = newline + 1 if newline >= 0 else len(text)
+            yield text[index:end], offset + index if is_source else offset, is_source
+            if newline >= 0:
+                leading = b""
+            index = end
+
+
+def _indent_unit(source: bytes, line_starts: list[int]) -> int:
+    """Return the width of one level of a source's indentation.
+
+    The width is the most common increase of indentation from a line to the next
+    non-blank one, counting a tab as one column; 4 when no line is indented.
+    """
+    increases: Counter[int] = Counter()
+    previous: int = 0
+    for start, end in zip(line_starts, [*line_starts[1:], len(source)]):
+        line: bytes = source[start:end]
+        if not line.strip():
+            continue
+        current: int = len(line) - len(line.lstrip(b" \t"))
+        if current > previous:
+            increases[current - previous] += 1
+        previous = current
+    return increases.most_common(1)[0][0] if increases else 4
+
+
+def _matches_trait(trait: str, patterns: Sequence[str]) -> bool:
+    """Tell whether a trait matches one of some patterns, by path or by name.
+
+    Generic arguments are left out, so `From<A>` matches `From` and `fmt::Display`
+    matches both `fmt::Display` and `Display`.
+    """
+    path: str = "".join(trait.split("<", 1)[0].split())
+    name: str = path.rsplit("::", 1)[-1]
+    return any(fnmatchcase(path, pattern) or fnmatchcase(name, pattern) for pattern in patterns)
+
+
+def _chunk_reader(buffer: bytes) -> Callable[[int, object], bytes]:
+    """Return a tree-sitter read callback serving a buffer in chunks."""
+
+    def read(offset: int, _point: object) -> bytes:
+        return bytes(buffer[offset : offset + _READ_CHUNK_SIZE])
+
+    return read
+
+
+def _runs(
+    ranges: list[tuple[int, int]], source: bytes, *, max_line_breaks: int | None = None
+) -> list[list[tuple[int, int]]]:
+    """Group byte ranges separated only by whitespace, in source order.
+
+    Args:
+        ranges: Disjoint byte ranges
+        source: Encoded source code
+        max_line_breaks: Most line breaks allowed between ranges of a group (no limit
+            if None), so that a blank line can start a new group
+
+    Returns:
+        Groups of consecutive ranges
+    """
+    groups: list[list[tuple[int, int]]] = []
+    for start, end in sorted(ranges):
+        gap: bytes = source[groups[-1][-1][1] : start] if groups else b""
+        if (
+            groups
+            and not gap.strip()
+            and (max_line_breaks is None or gap.count(b"\n") <= max_line_breaks)
+        ):
+            groups[-1].append((start, end))
+        else:
+            groups.append([(start, end)])
+    return groups
+
+
+def is_inside(ranges: list[tuple[int, int]], start: int, end: int) -> bool:
+    """Tell whether a byte range lies inside one of the given sorted, disjoint ranges."""
+    index: int = bisect_right(ranges, (start, float("inf"))) - 1
+    return index >= 0 and end <= ranges[index][1]
+
+
+def _overlaps(ranges: list[tuple[int, int]], start: int, end: int) -> bool:
+    """Tell whether a byte range overlaps any of the given sorted, disjoint ranges."""
+    index: int = bisect_left(ranges, (end,)) - 1
+    return index >= 0 and ranges[index][1] > start
+
+
+def _trailing_whitespace(source: bytes, node: Node) -> int:
+    """Return the number of whitespace bytes ending a node."""
+    text: bytes = source[node.start_byte : node.end_byte]
+    return len(text) - len(text.rstrip())
+
+
+def _is_private_field(node: Node, source: bytes, language: str) -> bool:
+    """Tell whether a struct field is dropped when keeping only public fields.
+
+    Rust fields are public when declared `pub` (not `pub(crate)` and the like), and Go
+    fields when exported; fields of other languages are all public.
+    """
+    if language == "rust":
+        modifier: Node | None = next(
+            (child for child in node.named_children if child.type == "visibility_modifier"),
+            None,
+        )
+        if modifier is None:
+            return True
+        declared: bytes = source[modifier.start_byte : modifier.end_byte]
+        return is_hidden(re.sub(r"\s+", "", declared.decode()), "public")
+    if language == "go":
+        names: list[Node] = node.children_by_field_name("name")
+        # Embedded fields are named after their type (`*pkg.Reader` is `Reader`)
+        named: Node | None = names[0] if names else node.child_by_field_name("type")
+        if named is None:
+            return False
+        name: str = source[named.start_byte : named.end_byte].decode(errors="replace")
+        return not name.rpartition(".")[2].lstrip("*").partition("[")[0][:1].isupper()
+    return False
+
+
+def _is_emptied(body: Node, removed: list[tuple[int, int]]) -> bool:
+    """Tell whether removed ranges cover every statement of a body, comments aside."""
+    statements: list[Node] = [
+        child for child in body.named_children if child.type not in _COMMENT_TYPES
+    ]
+    return bool(statements) and all(
+        is_inside(removed, child.start_byte, child.end_byte) for child in statements
+    )
+
+
+def _owns_lines(source: bytes, start: int, end: int) -> bool:
+    """Tell whether a byte range covers whole lines, sharing them with no other code."""
+    return source[start - 1 : start] in (b"", b"\n") and (
+        end == len(source) or source[end - 1 : end] == b"\n"
+    )
+
+
+def _is_test_attribute(text: bytes) -> bool:
+    """Tell whether a Rust attribute marks a test or compiles its item only for tests.
+
+    Test attributes are `#[test]` and `#[bench]`, and path-qualified ones such as
+    `#[tokio::test]`.
+    A `cfg` predicate gates on tests when it cannot hold outside of them:
+    `#[cfg(test)]` and `#[cfg(all(test, feature = "x"))]` do, `#[cfg(not(test))]` and
+    `#[cfg(any(test, feature = "x"))]` do not.
+    """
+    attribute: str = text.decode(errors="replace").strip()
+    attribute = attribute.removeprefix("#[").removesuffix("]").strip()
+    if _TEST_ATTRIBUTE.fullmatch(attribute):
+        return True
+    arguments: str = attribute.removeprefix("cfg").strip()
+    if arguments == attribute or not (arguments.startswith("(") and arguments.endswith(")")):
+        return False
+    return _requires_test(arguments[1:-1])
+
+
+def _requires_test(predicate: str) -> bool:
+    """Tell whether a Rust `cfg` predicate only holds when compiling tests."""
+    name, _, rest = predicate.strip().partition("(")
+    if not rest:
+        return name.strip() == "test"
+    arguments: list[str] = _split_arguments(rest.rstrip().removesuffix(")"))
+    if name.strip() == "all":
+        return any(_requires_test(argument) for argument in arguments)
+    if name.strip() == "any":
+        return bool(arguments) and all(_requires_test(argument) for argument in arguments)
+    return False
+
+
+def _split_arguments(text: str) -> list[str]:
+    """Split a comma-separated argument list at its top-level commas."""
+    arguments: list[str] = []
+    current: list[str] = []
+    depth: int = 0
+    quoted: bool = False
+    for char in text:
+        if char == '"':
+            quoted = not quoted
+        elif not quoted and char in "()":
+            depth += 1 if char == "(" else -1
+        elif not quoted and char == "," and depth == 0:
+            arguments.append("".join(current))
+            current = []
+            continue
+        current.append(char)
+    arguments.append("".join(current))
+    return [argument for argument in arguments if argument.strip()]
+
+
+def _indentation(text: bytes) -> bytes:
+    """Return the leading whitespace of the first non-blank line of text."""
+    for line in text.splitlines():
+        if line.strip():
+            return line[: len(line) - len(line.lstrip())]
+    return b""
+
+
+def _string_truncation(node: Node, source: bytes, limit: int) -> tuple[int, int, bytes] | None:
+    """Compute the elided tail of a long string literal and the note replacing it.
+
+    The text within `limit` bytes of the literal's start is kept, cut before any
+    interpolation or escape sequence it would split, and the note counting the
+    elided bytes goes inside the quotes, where no comment could.
+    """
+    opening, closing = node.children[0], node.children[-1]
+    cut: int = max(min(node.start_byte + limit, closing.start_byte), opening.end_byte)
+    inner: Node | None = node.descendant_for_byte_range(cut, cut)
+    while inner is not None and inner != node:
+        if inner.start_byte < cut and inner.type.endswith(_STRING_PART_SUFFIXES):
+            cut = inner.start_byte
+        inner = inner.parent
+    # Keep whole UTF-8 characters, and no backslash escaping the closing quote
+    while cut > opening.end_byte and source[cut] & 0xC0 == 0x80:
+        cut -= 1
+    escapes: int = len(source[opening.end_byte : cut]) - len(
+        source[opening.end_byte : cut].rstrip(b"\\")
+    )
+    cut -= escapes % 2
+    elided: int = closing.start_byte - cut
+    if elided <= 0:
+        return None
+    return cut, closing.start_byte, f"... ({elided} bytes elided)".encode()
+
+
+def _text(source: bytes, node: Node) -> str:
+    """Return the source text of a node."""
+    return source[node.start_byte : node.end_byte].decode(errors="replace")
+
+
+def _parameter_names(parameters: Node | None, source: bytes) -> list[str] | None:
+    """Return the names of the type parameters of an alias, or None if one has no name."""
+    names: list[str] = []
+    for child in parameters.named_children if parameters is not None else []:
+        if child.type in _COMMENT_TYPES:
+            continue
+        name: Node | None = (
+            child
+            if child.type in _ALIAS_NAME_TYPES
+            else child.child_by_field_name("name") or child.child_by_field_name("left")
+        )
+        if name is None:
+            return None
+        names.append(_text(source, name))
+    return names
+
+
+def _bound_type_names(node: Node, source: bytes) -> set[str]:
+    """Return the names of the type parameters an item declares (`T` of `fn get<T>`)."""
+    parameters: Node | None = node.child_by_field_name("type_parameters")
+    names: set[str] = set()
+    for child in parameters.named_children if parameters is not None else []:
+        name: Node | None = (
+            child
+            if child.type in _ALIAS_NAME_TYPES
+            else child.child_by_field_name("name") or child.child_by_field_name("left")
+        )
+        if name is not None:
+            names.add(_text(source, name))
+    return names
+
+
+def _parameter_list(node: Node) -> Node | None:
+    """Return the parameter list of a function definition, following C declarators."""
+    current: Node | None = node
+    while current is not None:
+        parameters: Node | None = current.child_by_field_name("parameters")
+        if parameters is not None:
+            return parameters
+        current = current.child_by_field_name("declarator")
+    return None
+
+
+def _alias_reference(node: Node, source: bytes) -> tuple[str, list[Node]] | None:
+    """Return the name and type arguments a node refers to a type by, if it does.
+
+    Generic types (`Result<T>`) and bare type names count; qualified paths, names
+    of definitions and type parameter declarations do not.
+    """
+    if node.type == "generic_type":
+        name: Node | None = node.child_by_field_name("type") or node.child_by_field_name("name")
+        if name is None or name.type != "type_identifier":
+            return None
+        arguments: Node | None = node.child_by_field_name("type_arguments")
+        children: list[Node] = arguments.named_children if arguments is not None else []
+        return _text(source, name), [
+            child for child in children if child.type not in _COMMENT_TYPES
+        ]
+    parent: Node | None = node.parent
+    if node.type not in _ALIAS_NAME_TYPES or parent is None or parent.type in _NOT_REFERENCES:
+        return None
+    named: Node | None = parent.child_by_field_name("name")
+    if named is not None and named.start_byte == node.start_byte:
+        return None
+    return _text(source, node), []
+
+
+def _expanded(
+    node: Node,
+    source: bytes,
+    aliases: Mapping[str, _Alias | None],
+    active: frozenset[str],
+    bindings: Mapping[str, str],
+) -> str:
+    """Render a type with its references to aliases replaced by their definitions.
+
+    Args:
+        node: Type node
+        source: Encoded source code
+        aliases: Type parameters and definition of each alias by name; None for
+            aliases that cannot be resolved
+        active: Aliases being expanded, left as they are when referenced again
+        bindings: Types given for the parameters of the alias being expanded
+
+    Returns:
+        Text of the type
+    """
+    reference: tuple[str, list[Node]] | None = _alias_reference(node, source)
+    if reference is not None:
+        name, arguments = reference
+        if not arguments and name in bindings:
+            return bindings[name]
+        alias: _Alias | None = None if name in bindings else aliases.get(name)
+        if alias is not None and name not in active and len(arguments) == len(alias[0]):
+            values: list[str] = [
+                _expanded(argument, source, aliases, active, bindings) for argument in arguments
+            ]
+            parameters: dict[str, str] = dict(zip(alias[0], values))
+            text: str = _expanded(alias[1], source, aliases, active | {name}, parameters)
+            return f"({text})" if alias[1].type in _GROUPED_TYPES else text
+    parts: list[str] = []
+    position: int = node.start_byte
+    for child in node.children:
+        parts.append(source[position : child.start_byte].decode(errors="replace"))
+        parts.append(_expanded(child, source, aliases, active, bindings))
+        position = child.end_byte
+    parts.append(source[position : node.end_byte].decode(errors="replace"))
+    return "".join(parts)
+
+
+def _outside(
+    spans: list[tuple[int, int, bytes]], ranges: list[tuple[int, int]]
+) -> list[tuple[int, int, bytes]]:
+    """Drop spans lying inside any of the given sorted, disjoint ranges."""
+    starts: list[int] = [start for start, _ in ranges]
+    kept: list[tuple[int, int, bytes]] = []
+    for span in spans:
+        index: int = bisect_right(starts, span[0]) - 1
+        if index < 0 or span[1] > ranges[index][1]:
+            kept.append(span)
+    return kept
+
+
+def _outermost(spans: list[tuple[int, int, bytes]]) -> list[tuple[int, int, bytes]]:
+    """Drop spans contained in another span, returning the rest sorted by position."""
+    outermost: list[tuple[int, int, bytes]] = []
+    for span in sorted(spans, key=lambda span: (span[0], -span[1])):
+        if not outermost or span[0] >= outermost[-1][1]:
+            outermost.append(span)
+    return outermost
+
+
+def syntax_error_nodes(node: Node) -> list[Node]:
+    """Return the outermost ERROR and MISSING nodes under a node, in source order."""
+    errors: list[Node] = []
+    pending: list[Node] = [node]
+    while pending:
+        current = pending.pop()
+        if current.is_error or current.is_missing:
+            errors.append(current)
+        elif current.has_error:
+            pending.extend(reversed(current.children))
+    return errors
+
+
+def line_starts_of(source: bytes) -> list[int]:
+    """Return the byte offset at which each line of the source starts.
+
+    Lines are split on newline bytes only, matching tree-sitter's row numbering.
+
+    Args:
+        source: Encoded source code
+
+    Returns:
+        List where index ``i`` holds the byte offset of line ``i``
+    """
+    starts: list[int] = [0]
+    position: int = source.find(b"\n")
+    while position != -1:
+        starts.append(position + 1)
+        position = source.find(b"\n", position + 1)
+    return starts
+
+
+def _node_span(node: Node, source: bytes, line_starts: list[int]) -> tuple[int, int]:
+    """Return the byte range covering a node, widened to whole lines when it owns them.
+
+    Args:
+        node: Node to cover
+        source: Encoded source code
+        line_starts: Byte offset at which each line starts
+
+    Returns:
+        Half-open byte range of the node, including its lines if nothing else is on them
+    """
+    return _range_span(node.start_byte, node.end_byte, source, line_starts)
+
+
+def item_span(node: Node, source: bytes, line_starts: list[int]) -> tuple[int, int]:
+    """Return the byte range of an item with its decorators, attributes and doc comments.
+
+    Decorators and C++ template headers wrap the definition node and are included too.
+
+    Args:
+        node: Definition node of the item
+        source: Encoded source code
+        line_starts: Byte offset at which each line starts
+
+    Returns:
+        Half-open byte range of the item and the trivia directly above it
+    """
+    if node.parent is not None and node.parent.type in _WRAPPER_TYPES:
+        node = node.parent
+    first: Node = node
+    sibling: Node | None = node.prev_named_sibling
+    while (
+        sibling is not None
+        and sibling.type in _LEADING_TRIVIA_TYPES
+        and sibling.end_point[0] >= first.start_point[0] - 1
+    ):
+        first = sibling
+        sibling = sibling.prev_named_sibling
+    return _range_span(first.start_byte, node.end_byte, source, line_starts)
+
+
+def _range_span(
+    start: int, end: int, source: bytes, line_starts: list[int]
+) -> tuple[int, int]:
+    """Return a byte range widened to whole lines when nothing else is on them.
+
+    Args:
+        start: Start byte of the range
+        end: End byte of the range (exclusive)
+        source: Encoded source code
+        line_starts: Byte offset at which each line starts
+
+    Returns:
+        Half-open byte range, including its lines if nothing else is on them
+    """
+    # Some grammars include the trailing newline in comments and decorators
+    end = start + len(source[start:end].rstrip())
+    line_start: int = line_starts[bisect_right(line_starts, start) - 1]
+    next_line: int = bisect_right(line_starts, max(start, end - 1))
+    line_end: int = _line_offset(line_starts, next_line, len(source))
+    if source[line_start:start].strip() or source[end:line_end].strip():
+        # Shares its line with other code: take trailing spaces along to avoid gaps
+        while source[end : end + 1] in (b" ", b"\t"):
+            end += 1
+        return start, end
+    return line_start, line_end
+
+
+def line_break_of(source: bytes) -> bytes:
+    """Return the line break of a source: CRLF if its first line ends with one, else LF."""
+    newline: int = source.find(b"\n")
+    return b"\r\n" if newline > 0 and source[newline - 1 : newline] == b"\r" else b"\n"
+
+
+def count_lines(source: bytes, line_starts: list[int]) -> int:
+    """Return the number of lines of the source, not counting an empty last line."""
+    return len(line_starts) - 1 if source[-1:] == b"\n" else len(line_starts)
+
+
+def _line_offset(line_starts: list[int], line: int, source_length: int) -> int:
+    """Return the byte offset of a line start, clamped to the end of the source."""
+    return line_starts[line] if line < len(line_starts) else source_length
+
+
+def merge_ranges(ranges: list[tuple[int, int]]) -> list[tuple[int, int]]:
+    """Merge overlapping half-open byte ranges into sorted, disjoint ranges."""
+    merged: list[tuple[int, int]] = []
+    for start, end in sorted(ranges):
+        if merged and start <= merged[-1][1]:
+            merged[-1] = (merged[-1][0], max(merged[-1][1], end))
+        else:
+            merged.append((start, end))
+    return merged
+
+
+# Per-thread extractors keyed by (language, options); parsers are not thread-safe
+_extractor_pool = threading.local()
+
+# Number of extractors kept per thread, so that a long-running server answering
+# requests with ever new options does not hold a parser for each of them
+_EXTRACTOR_POOL_SIZE: int = 32
+
+
+def get_extractor(language: str, options: LopperOptions | None = None) -> SkeletonExtractor:
+    """Return a reusable extractor for a language, local to the calling thread.
+
+    Building a parser and compiling the queries of a language is far more
+    expensive than parsing a typical file, so extractors are cached per thread
+    and reused across calls. Each thread keeps the 32 most recently used
+    extractors, dropping the others.
+
+    Args:
+        language: Programming language
+        options: Extraction options (defaults to `LopperOptions()`)
+
+    Returns:
+        Extractor owned by the calling thread
+
+    Raises:
+        UnsupportedLanguageError: If language not supported
+    """
+    key = (language, options or LopperOptions())
+    extractors: OrderedDict[tuple[str, LopperOptions], SkeletonExtractor] | None = getattr(
+        _extractor_pool, "extractors", None
+    )
+    if extractors is None:
+        extractors = _extractor_pool.extractors = OrderedDict()
+    extractor = extractors.get(key)
+    if extractor is None:
+        extractor = extractors[key] = SkeletonExtractor(*key)
+        if len(extractors) > _EXTRACTOR_POOL_SIZE:
+            extractors.popitem(last=False)
+    else:
+        extractors.move_to_end(key)
+    return extractor
 
 
-def extract(source_code: str, language: str) -> str:
+def extract(source_code: str, language: str, options: LopperOptions | None = None) -> str:
     """Extract skeleton from source code.
 
-    Convenience function that creates an extractor and extracts the skeleton
-    in one call.
+    Convenience function that fetches a cached extractor (see `get_extractor`)
+    and extracts the skeleton in one call.
 
     Args:
         source_code: Source code to process
         language: Programming language
+        options: Extraction options (defaults to `LopperOptions()`)
 
     Returns:
         Skeleton with implementations removed
 
     Raises:
-        ValueError: If language not supported
+        UnsupportedLanguageError: If language not supported
     """
-    extractor: SkeletonExtractor = SkeletonExtractor(language)
-    return extractor.extract(source_code)
+    return get_extractor(language, options).extract(source_code)
@@ -0,0 +1,150 @@
+"""Skeletons of Jupyter notebooks, lopping the source of each code cell.
+
+A notebook is JSON: outputs, execution counts and the rest of its metadata would
+outweigh the code it holds. Its code cells are lopped in the notebook's kernel
+language, while Markdown and raw cells are kept as they are.
+"""
+
+from __future__ import annotations
+
+import json
+import re
+from typing import Any
+
+from loppers.documents import EmbeddedCode, lop_regions, named_language
+from loppers.errors import InvalidNotebookError
+from loppers.loppers import LopperOptions
+
+# Language identifier of Jupyter notebooks
+NOTEBOOK_LANGUAGE: str = "notebook"
+
+# Language of the code cells of notebooks whose metadata names none
+DEFAULT_NOTEBOOK_LANGUAGE: str = "python"
+
+# IPython lines that are not Python: magics (`%matplotlib`), shell escapes
+# (`!pip install`), help (`?len`) and their assignments (`files = !ls`)
+_MAGIC_LINE: re.Pattern[str] = re.compile(r"[ \t]*(?:[%!?]|[\w.]+[ \t]*=[ \t]*[%!])")
+
+
+def lop_notebook(source: str, *, options: LopperOptions | None = None) -> str:
+    """Extract the skeleton of a Jupyter notebook.
+
+    Code cells are lopped in the language of `metadata.kernelspec.language` (or
+    `metadata.language_info.name`, Python when neither is set), and are kept as
+    they are when it is not supported. In Python notebooks, IPython magic and shell
+    lines are kept verbatim around the lopped code, and cells starting with a cell
+    magic (`%%bash`) are kept whole. Outputs and execution counts are dropped.
+
+    Args:
+        source: Notebook JSON (nbformat 4)
+        options: Extraction options applied to every code cell (defaults to
+            `LopperOptions()`); `notebook_format` selects the rendering: "text"
+            puts each cell under a `# %% [cell N]` line (`# %% [cell N] markdown`
+            for Markdown cells), "json" writes the cleaned notebook
+
+    Returns:
+        Notebook skeleton
+
+    Raises:
+        InvalidNotebookError: If the notebook is not valid nbformat 4 JSON
+        ParseFailedError: If a cell has syntax errors and `on_error` is "fail"
+    """
+    options = options or LopperOptions()
+    notebook = _read_notebook(source)
+    language = _kernel_language(notebook)
+    texts: list[str] = [
+        _lopped_cell(_cell_source(cell), language, options)
+        if cell["cell_type"] == "code"
+        else _cell_source(cell)
+        for cell in notebook["cells"]
+    ]
+
+    if options.notebook_format == "json":
+        cells: list[dict[str, Any]] = []
+        for cell, text in zip(notebook["cells"], texts):
+            cleaned = dict(cell)
+            cleaned["source"] = (
+                text.splitlines(keepends=True) if isinstance(cell["source"], list) else text
+            )
+            if cell["cell_type"] == "code":
+                cleaned["outputs"], cleaned["execution_count"] = [], None
+            cells.append(cleaned)
+        return json.dumps({**notebook, "cells": cells}, indent=1, ensure_ascii=False)
+
+    sections: list[str] = []
+    for index, (cell, text) in enumerate(zip(notebook["cells"], texts), start=1):
+        kind = "" if cell["cell_type"] == "code" else f" {cell['cell_type']}"
+        sections.append(f"# %% [cell {index}]{kind}\n{text}".rstrip())
+    return "\n\n".join(sections)
+
+
+def _read_notebook(source: str) -> dict[str, Any]:
+    """Parse a notebook, checking the structure its cells are read through."""
+    try:
+        notebook = json.loads(source)
+    except json.JSONDecodeError as e:
+        raise InvalidNotebookError(f"Invalid notebook: {e}") from e
+    if not isinstance(notebook, dict) or not isinstance(notebook.get("cells"), list):
+        raise InvalidNotebookError("Invalid notebook: expected an object with a 'cells' array")
+    for index, cell in enumerate(notebook["cells"], start=1):
+        if not (
+            isinstance(cell, dict)
+            and isinstance(cell.get("cell_type"), str)
+            and (
+                isinstance(cell.get("source"), str)
+                or (
+                    isinstance(cell.get("source"), list)
+                    and all(isinstance(line, str) for line in cell["source"])
+                )
+            )
+        ):
+            msg = f"Invalid notebook: cell {index} needs a 'cell_type' and a text 'source'"
+            raise InvalidNotebookError(msg)
+    return notebook
+
+
+def _kernel_language(notebook: dict[str, Any]) -> str | None:
+    """Return the supported language of a notebook's code cells, if any."""
+    metadata = notebook.get("metadata")
+    metadata = metadata if isinstance(metadata, dict) else {}
+    for key, field in (("kernelspec", "language"), ("language_info", "name")):
+        section = metadata.get(key)
+        if isinstance(section, dict) and isinstance(section.get(field), str) and section[field]:
+            return named_language(section[field])
+    return DEFAULT_NOTEBOOK_LANGUAGE
+
+
+def _cell_source(cell: dict[str, Any]) -> str:
+    """Return the source of a cell, stored as one string or a list of lines."""
+    source = cell["source"]
+    return source if isinstance(source, str) else "".join(source)
+
+
+def _lopped_cell(source: str, language: str | None, options: LopperOptions) -> str:
+    """Lop the source of a code cell, keeping the IPython syntax of Python cells as is."""
+    if language is None:
+        return source
+    regions: list[EmbeddedCode] = [EmbeddedCode(0, len(source), language)]
+    if language == "python":
+        if source.lstrip().startswith("%%"):
+            return source
+        regions = _python_regions(source)
+    return lop_regions(source, regions, options=options).skeleton
+
+
+def _python_regions(source: str) -> list[EmbeddedCode]:
+    """Find the runs of Python lines between the IPython lines of a cell."""
+    regions: list[EmbeddedCode] = []
+    start: int | None = None
+    offset: int = 0
+    for line in source.splitlines(keepends=True):
+        if _MAGIC_LINE.match(line):
+            if start is not None:
+                regions.append(EmbeddedCode(start, offset, "python"))
+                start = None
+        elif start is None:
+            start = offset
+        offset += len(line)
+    if start is not None:
+        regions.append(EmbeddedCode(start, offset, "python"))
+    return regions
@@ -0,0 +1,150 @@
+"""Outlines of symbols grouped by kind and sorted by name.
+
+Documentation snapshots need a rendering that does not follow source order, so
+moving an item around a file leaves it unchanged: symbols are grouped by kind
+(types, traits, `impl` blocks, free functions, then the rest), sorted by name
+within each group, and rendered one signature per line, nested symbols indented
+under their parent.
+"""
+
+from __future__ import annotations
+
+from collections.abc import Sequence
+from dataclasses import dataclass
+
+from loppers.symbol_model import IMPL_TARGET_KINDS, Symbol, base_name
+
+# Symbol kinds of each group of an outline, in order; other kinds come last
+OUTLINE_GROUPS: tuple[frozenset[str], ...] = (
+    frozenset({"class", "struct", "enum", "union", "type"}),
+    frozenset({"trait", "interface"}),
+    frozenset({"impl"}),
+    frozenset({"function", "method", "constructor"}),
+)
+
+# Ways of listing the items of trait `impl` blocks
+IMPL_ITEM_STYLES: tuple[str, ...] = ("names", "signatures", "none")
+
+
+@dataclass(frozen=True)
+class OutlineOptions:
+    """Options controlling outline rendering.
+
+    Attributes:
+        indent: Text indenting each level of nesting
+        merge_impls: List the items of inherent `impl` blocks, and Go methods, under
+            the type they belong to when it is defined alongside them, leaving the
+            blocks themselves out
+        impl_items: How trait `impl` blocks list their items: "names" references
+            them after the block's signature (`impl Arithmetic for Calculator { add,
+            multiply }`), since the trait outlines their signatures; "signatures"
+            nests their signatures under the block; "none" leaves them out
+    """
+
+    indent: str = "    "
+    merge_impls: bool = True
+    impl_items: str = "names"
+
+    def __post_init__(self) -> None:
+        """Validate the options.
+
+        Raises:
+            ValueError: If `impl_items` is not one of `IMPL_ITEM_STYLES`
+        """
+        if self.impl_items not in IMPL_ITEM_STYLES:
+            choices = ", ".join(IMPL_ITEM_STYLES)
+            raise ValueError(f"impl_items must be one of {choices}, got {self.impl_items!r}")
+
+
+def render_outline(symbols: Sequence[Symbol], *, options: OutlineOptions | None = None) -> str:
+    """Render symbols as an outline grouped by kind and sorted by name.
+
+    Top-level groups are separated by a blank line; nested symbols are grouped and
+    sorted the same way under their parent. Signatures are rendered on one line,
+    with runs of whitespace squashed. Same-named symbols are ordered by the trait
+    they implement, their signature and finally their position, so rendering the
+    same symbols always gives the same text.
+
+    Args:
+        symbols: Top-level symbols, as `extract_symbols` returns them (without
+            `group_impls`, which `merge_impls` supersedes)
+        options: Rendering options (defaults to `OutlineOptions()`)
+
+    Returns:
+        The outline, one symbol per line
+    """
+    options = options or OutlineOptions()
+    lines: list[str] = []
+    for group in _grouped(symbols, None, options):
+        if lines:
+            lines.append("")
+        for symbol, members in group:
+            _render(symbol, members, 0, options, lines)
+    return "\n".join(lines)
+
+
+def _grouped(
+    symbols: Sequence[Symbol], enclosing: str | None, options: OutlineOptions
+) -> list[list[tuple[Symbol, list[Symbol]]]]:
+    """Group sibling symbols by kind and sort each group, pairing each with its members.
+
+    Members are a symbol's children, followed by the items merged into it.
+    """
+    merged: dict[int, list[Symbol]] = {}
+    moved: set[int] = set()
+    if options.merge_impls:
+        types: dict[str, Symbol] = {}
+        for symbol in symbols:
+            if symbol.kind in IMPL_TARGET_KINDS:
+                types.setdefault(symbol.name, symbol)
+        for symbol in symbols:
+            if symbol.kind == "impl" and symbol.trait is None:
+                target, items = types.get(base_name(symbol.name)), symbol.children
+            elif symbol.parent is not None and symbol.parent != enclosing:
+                # Go methods are declared apart from the type they belong to
+                target, items = types.get(symbol.parent), [symbol]
+            else:
+                continue
+            if target is not None:
+                merged.setdefault(id(target), []).extend(items)
+                moved.add(id(symbol))
+
+    groups: list[list[tuple[Symbol, list[Symbol]]]] = [[] for _ in range(len(OUTLINE_GROUPS) + 1)]
+    for symbol in symbols:
+        if id(symbol) in moved:
+            continue
+        index = next(
+            (i for i, kinds in enumerate(OUTLINE_GROUPS) if symbol.kind in kinds),
+            len(OUTLINE_GROUPS),
+        )
+        groups[index].append((symbol, [*symbol.children, *merged.get(id(symbol), [])]))
+    for group in groups:
+        group.sort(key=lambda pair: _sort_key(pair[0]))
+    return [group for group in groups if group]
+
+
+def _render(
+    symbol: Symbol, members: list[Symbol], depth: int, options: OutlineOptions, lines: list[str]
+) -> None:
+    """Render a symbol's line, then those of its members one level deeper."""
+    line = " ".join((symbol.signature or symbol.name).split())
+    is_trait_impl = symbol.kind == "impl" and symbol.trait is not None
+    if is_trait_impl and options.impl_items != "signatures":
+        if options.impl_items == "names" and members:
+            names = sorted({member.name for member in members}, key=_name_key)
+            line = f"{line} {{ {', '.join(names)} }}"
+        members = []
+    lines.append(f"{options.indent * depth}{line}")
+    for group in _grouped(members, symbol.name, options):
+        for member, nested in group:
+            _render(member, nested, depth + 1, options, lines)
+
+
+def _sort_key(symbol: Symbol) -> tuple[tuple[str, str], str, str, int]:
+    """Return the key ordering symbols alphabetically, with ties broken for stability."""
+    return _name_key(symbol.name), symbol.trait or "", symbol.signature, symbol.start_byte
+
+
+def _name_key(name: str) -> tuple[str, str]:
+    """Return the key ordering names alphabetically regardless of case, then by case."""
+    return name.casefold(), name
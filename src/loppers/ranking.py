@@ -0,0 +1,124 @@
+"""Importance ranking of symbols by how often they are referenced.
+
+Symbols whose names appear as identifiers across many files are the ones the rest
+of a project depends on, so they are worth keeping in a repo map first.
+"""
+
+from __future__ import annotations
+
+from collections import Counter
+from collections.abc import Sequence
+from dataclasses import dataclass
+from pathlib import Path
+
+from tree_sitter import Node
+
+from loppers.budget import read_source
+from loppers.loppers import LANGUAGE_CONFIGS, get_extractor
+from loppers.symbol_model import Symbol, base_name, symbol_nodes
+
+# Node types holding identifiers in the supported grammars
+IDENTIFIER_TYPES: frozenset[str] = frozenset(
+    {
+        "identifier",
+        "type_identifier",
+        "field_identifier",
+        "property_identifier",
+        "shorthand_property_identifier",
+        "simple_identifier",
+        "constant",
+        "name",
+    }
+)
+
+# Symbol kinds that name another definition rather than define an API of their own
+_UNRANKED_KINDS: frozenset[str] = frozenset({"impl"})
+
+
+@dataclass(frozen=True)
+class RankedSymbol:
+    """A symbol with the references to it found in a set of files.
+
+    References are matched by name, so symbols sharing a name share their counts.
+
+    Attributes:
+        path: Path of the file defining the symbol, as given
+        symbol: Ranked symbol
+        references: Number of identifiers naming the symbol, its definitions excluded
+        files: Number of files other than the defining one that name the symbol
+    """
+
+    path: str
+    symbol: Symbol
+    references: int
+    files: int
+
+
+def rank_symbols(
+    files: Sequence[str | Path], *, root: str | Path | None = None
+) -> list[RankedSymbol]:
+    """Rank the symbols of a set of files by how often they are referenced.
+
+    Every identifier of every file counts as a reference to the symbols with that
+    name, except the identifiers naming each definition. Symbols are ordered by
+    references, then by the number of other files referencing them, then by
+    position. Files in languages without symbol support contribute references only,
+    and files in unsupported languages are ignored.
+
+    Args:
+        files: Paths of the files to rank the symbols of
+        root: Directory the paths are relative to (defaults to the working directory)
+
+    Returns:
+        Ranked symbols, most referenced first (`impl` blocks left out)
+
+    Raises:
+        FileNotFoundError: If a file does not exist
+        InvalidUtf8Error: If a file is not valid UTF-8
+    """
+    root_path = Path(root) if root is not None else Path()
+    defined: list[tuple[str, Symbol]] = []
+    identifiers: dict[str, Counter[str]] = {}
+    for path in map(str, files):
+        source, language = read_source(root_path / path)
+        if language is None or language not in LANGUAGE_CONFIGS:
+            continue
+        extractor = get_extractor(language)
+        source_bytes = source.encode()
+        tree = extractor.parse(source_bytes)
+        identifiers[path] = _identifiers(tree.root_node)
+        if extractor.config.symbol_query:
+            defined.extend(
+                (path, symbol) for symbol, _ in symbol_nodes(extractor, tree, source_bytes)
+            )
+
+    # Each definition names itself once
+    definitions: Counter[str] = Counter(
+        base_name(symbol.name) for _, symbol in defined if symbol.kind not in _UNRANKED_KINDS
+    )
+    totals: Counter[str] = sum(identifiers.values(), Counter())
+    ranked: list[RankedSymbol] = []
+    for path, symbol in defined:
+        if symbol.kind in _UNRANKED_KINDS:
+            continue
+        name = base_name(symbol.name)
+        referencing = sum(
+            1 for other, names in identifiers.items() if other != path and names[name]
+        )
+        references = max(totals[name] - definitions[name], 0)
+        ranked.append(RankedSymbol(path, symbol, references, referencing))
+    ranked.sort(key=lambda item: (-item.references, -item.files))
+    return ranked
+
+
+def _identifiers(root: Node) -> Counter[str]:
+    """Count the identifiers of a syntax tree by name."""
+    counts: Counter[str] = Counter()
+    pending: list[Node] = [root]
+    while pending:
+        node = pending.pop()
+        if node.type in IDENTIFIER_TYPES and node.text:
+            counts[node.text.decode(errors="replace")] += 1
+        else:
+            pending.extend(node.children)
+    return counts
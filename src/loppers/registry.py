@@ -0,0 +1,84 @@
+"""Registration of languages defined outside loppers, with grammars of their own.
+
+The built-in languages are `LanguageConfig` entries of `LANGUAGE_CONFIGS`, with
+grammars from tree-sitter-language-pack. A registered language takes the same
+path: once its config is in `LANGUAGE_CONFIGS` and its extensions are mapped, every
+option, directory walk and API handles it like a built-in one.
+"""
+
+from __future__ import annotations
+
+from collections.abc import Sequence
+from dataclasses import fields
+
+from tree_sitter import QueryError
+
+from loppers.documents import DOCUMENT_LANGUAGES
+from loppers.errors import InvalidQueryError
+from loppers.extensions import (
+    EXTENSION_TO_LANGUAGE,
+    FILENAME_TO_LANGUAGE,
+    INTERPRETER_TO_LANGUAGE,
+)
+from loppers.loppers import LANGUAGE_CONFIGS, LanguageConfig, SkeletonExtractor
+from loppers.notebooks import NOTEBOOK_LANGUAGE
+
+
+def register_language(
+    config: LanguageConfig,
+    *,
+    extensions: Sequence[str] = (),
+    file_names: Sequence[str] = (),
+    interpreters: Sequence[str] = (),
+) -> None:
+    """Add a language, detected by file extension, file name or shebang interpreter.
+
+    The queries of the config are compiled against its grammar first, so a query
+    written for another grammar fails here rather than on the first file. Extensions,
+    file names and interpreters already mapped to another language are taken over.
+    Register languages before extracting from other threads; worker processes of
+    `get_skeletons_parallel` see them only when forked from the registering process.
+
+    Args:
+        config: Language definition; its `grammar` is required unless `name` is a
+            grammar of tree-sitter-language-pack
+        extensions: File extensions of the language (with or without dot)
+        file_names: Well-known file names of the language (`BUILD`)
+        interpreters: Shebang interpreters running the language, without version
+            suffixes
+
+    Raises:
+        ValueError: If a language of the same name is already registered
+        InvalidQueryError: If a query of the config does not compile for its grammar
+        LookupError: If the config has no grammar and tree-sitter-language-pack has
+            none of its name
+    """
+    if config.name in (*LANGUAGE_CONFIGS, *DOCUMENT_LANGUAGES, NOTEBOOK_LANGUAGE):
+        raise ValueError(f"Language '{config.name}' is already registered")
+    LANGUAGE_CONFIGS[config.name] = config
+    try:
+        _check_queries(SkeletonExtractor(config.name))
+    except Exception:
+        del LANGUAGE_CONFIGS[config.name]
+        raise
+
+    for extension in extensions:
+        dotted = extension if extension.startswith(".") else f".{extension}"
+        EXTENSION_TO_LANGUAGE[dotted.lower()] = config.name
+    for file_name in file_names:
+        FILENAME_TO_LANGUAGE[file_name] = config.name
+    for interpreter in interpreters:
+        INTERPRETER_TO_LANGUAGE[interpreter] = config.name
+
+
+def _check_queries(extractor: SkeletonExtractor) -> None:
+    """Compile every query of an extractor's language config."""
+    for option in fields(LanguageConfig):
+        query_source = getattr(extractor.config, option.name)
+        if not option.name.endswith("_query") or query_source is None:
+            continue
+        try:
+            extractor.query(query_source)
+        except QueryError as e:
+            msg = f"Invalid {option.name} of {extractor.language}: {e}"
+            raise InvalidQueryError(msg) from e
@@ -0,0 +1,310 @@
+"""Extraction from asynchronous code, and a server answering JSON-lines requests.
+
+Services lopping sources on request must not block their event loop on parsing,
+and building the parser of a language per request costs more than parsing most
+files: an `AsyncLopper` runs extractions on a fixed pool of worker threads, each
+reusing its own extractors (see `get_extractor`). `serve` keeps one in front of
+a stream of newline-delimited JSON requests, so editor plugins and other
+processes can talk to a single warm instance:
+
+    {"id": 1, "source": "fn main() { run(); }", "language": "rust"}
+    {"id": 2, "path": "src/lib.rs", "options": {"visibility": "public"}}
+
+Each request is answered by a line holding its `id` and either the `skeleton`
+or an `error`, in the order the extractions complete.
+"""
+
+from __future__ import annotations
+
+import asyncio
+import json
+import os
+from collections.abc import AsyncIterator, Callable, Iterable, Mapping
+from concurrent.futures import ThreadPoolExecutor
+from dataclasses import dataclass, replace
+from functools import partial
+from pathlib import Path
+from types import TracebackType
+from typing import Any, TextIO, TypeVar
+
+from loppers.loppers import LopperOptions
+from loppers.source_utils import extract_skeleton, get_skeleton
+
+_T = TypeVar("_T")
+
+
+@dataclass(frozen=True)
+class LopResult:
+    """Outcome of extracting the skeleton of one source of a batch.
+
+    Attributes:
+        index: Position of the source in the batch
+        skeleton: Extracted skeleton, or None if extraction failed
+        error: Error message if extraction failed, otherwise None
+        exception: Exception raised by the extraction, if it failed
+    """
+
+    index: int
+    skeleton: str | None = None
+    error: str | None = None
+    exception: Exception | None = None
+
+    @property
+    def ok(self) -> bool:
+        """Whether the skeleton was extracted."""
+        return self.error is None
+
+
+class AsyncLopper:
+    """Extracts skeletons from asynchronous code on a pool of worker threads.
+
+    Each worker thread keeps one extractor per language and options (see
+    `get_extractor`), so parsers are built once per worker rather than once per
+    call. At most `max_workers` extractions run at once; further calls wait
+    for a free worker. Use it as an async context manager, or call `aclose`, to
+    shut the pool down once the extractions already started complete.
+    """
+
+    def __init__(
+        self, *, max_workers: int | None = None, options: LopperOptions | None = None
+    ) -> None:
+        """Start the worker pool.
+
+        Args:
+            max_workers: Number of worker threads (defaults to the CPU count)
+            options: Extraction options of the calls passing none (defaults to
+                `LopperOptions()`)
+
+        Raises:
+            ValueError: If `max_workers` is not positive
+        """
+        if max_workers is not None and max_workers < 1:
+            raise ValueError(f"max_workers must be positive, got {max_workers}")
+        self.max_workers: int = max_workers or os.cpu_count() or 1
+        self.options: LopperOptions = options or LopperOptions()
+        self._executor = ThreadPoolExecutor(self.max_workers, thread_name_prefix="loppers")
+        self._closed = False
+
+    async def __aenter__(self) -> AsyncLopper:
+        """Return the lopper itself."""
+        return self
+
+    async def __aexit__(
+        self,
+        exc_type: type[BaseException] | None,
+        exc: BaseException | None,
+        traceback: TracebackType | None,
+    ) -> None:
+        """Shut the lopper down (see `aclose`)."""
+        await self.aclose()
+
+    @property
+    def closed(self) -> bool:
+        """Whether the lopper was shut down."""
+        return self._closed
+
+    async def lop(
+        self, source: str, language: str, *, options: LopperOptions | None = None
+    ) -> str:
+        """Extract the skeleton of a source (see `extract_skeleton`).
+
+        Args:
+            source: Source code to process
+            language: Programming language identifier
+            options: Extraction options (defaults to those of the lopper)
+
+        Returns:
+            Skeleton with function implementations removed
+
+        Raises:
+            RuntimeError: If the lopper is shut down
+            UnsupportedLanguageError: If language is not supported
+            ParseFailedError: If the source has syntax errors and `on_error` is "fail"
+        """
+        return await self._run(extract_skeleton, source, language, options=options or self.options)
+
+    async def lop_file(
+        self,
+        path: str | Path,
+        *,
+        language: str | None = None,
+        options: LopperOptions | None = None,
+    ) -> str:
+        """Read a file and extract its skeleton, both on a worker thread.
+
+        Args:
+            path: Path of the file
+            language: Programming language identifier; detected from the file
+                when None (see `detect_file_language`)
+            options: Extraction options (defaults to those of the lopper)
+
+        Returns:
+            Skeleton with function implementations removed
+
+        Raises:
+            RuntimeError: If the lopper is shut down
+            FileNotFoundError: If the file does not exist
+            UnsupportedLanguageError: If the language is not supported
+            ParseFailedError: If the file has syntax errors and `on_error` is "fail"
+        """
+        return await self._run(_lop_file, Path(path), language, options or self.options)
+
+    async def lop_many(
+        self,
+        sources: Iterable[tuple[str, str]],
+        *,
+        options: LopperOptions | None = None,
+    ) -> AsyncIterator[LopResult]:
+        """Extract the skeletons of many sources, yielding each as soon as it completes.
+
+        A failure on one source is reported in its result and does not abort the
+        batch. Extractions not started yet are cancelled when the iteration
+        stops early.
+
+        Args:
+            sources: (source, language) pairs
+            options: Extraction options (defaults to those of the lopper)
+
+        Yields:
+            One result per source, in completion order; `index` tells its source
+        """
+        tasks = [
+            asyncio.ensure_future(self._result(index, source, language, options))
+            for index, (source, language) in enumerate(sources)
+        ]
+        try:
+            for task in asyncio.as_completed(tasks):
+                yield await task
+        finally:
+            for task in tasks:
+                task.cancel()
+
+    async def aclose(self) -> None:
+        """Shut the lopper down once the extractions already submitted complete.
+
+        Calls made after it starts raise RuntimeError. Closing twice does nothing.
+        """
+        if self._closed:
+            return
+        self._closed = True
+        await asyncio.get_running_loop().run_in_executor(None, self._executor.shutdown)
+
+    async def _result(
+        self, index: int, source: str, language: str, options: LopperOptions | None
+    ) -> LopResult:
+        """Extract one source of a batch, capturing any failure in the result."""
+        try:
+            return LopResult(index, skeleton=await self.lop(source, language, options=options))
+        except Exception as e:
+            return LopResult(index, error=f"{type(e).__name__}: {e}", exception=e)
+
+    async def _run(self, function: Callable[..., _T], *args: Any, **kwargs: Any) -> _T:
+        """Run a function on a worker thread."""
+        if self._closed:
+            raise RuntimeError("AsyncLopper is shut down")
+        loop = asyncio.get_running_loop()
+        return await loop.run_in_executor(self._executor, partial(function, *args, **kwargs))
+
+
+def serve(
+    input: TextIO,
+    output: TextIO,
+    *,
+    max_workers: int | None = None,
+    options: LopperOptions | None = None,
+) -> None:
+    """Answer newline-delimited JSON extraction requests until the input ends.
+
+    Each line of the input is a JSON object:
+
+    - `source` and `language`: a source to lop, or `path`: a file to read and
+      lop, as `language` when given and else as detected from the file
+    - `options` (optional): option values over `options`, named as in
+      `LopperOptions.from_dict`
+    - `id` (optional): any JSON value, echoed back in the response
+
+    Each request is answered by one line of JSON holding its `id` and either
+    `skeleton` or `error` (an object with the exception's `type` name and its
+    `message`), flushed as soon as the extraction completes, so responses may
+    come out of order. Blank lines are skipped, and a line that is not a valid
+    request is answered with an error. Reading stops while all workers are busy;
+    at the end of the input, the requests read so far are answered before it
+    returns.
+
+    Args:
+        input: Stream of requests, one per line
+        output: Stream the responses are written to
+        max_workers: Number of worker threads (defaults to the CPU count)
+        options: Extraction options the requests start from (defaults to
+            `LopperOptions()`)
+    """
+    asyncio.run(_serve(input, output, max_workers=max_workers, options=options))
+
+
+async def _serve(
+    input: TextIO,
+    output: TextIO,
+    *,
+    max_workers: int | None,
+    options: LopperOptions | None,
+) -> None:
+    """Serve requests from a stream until it ends (see `serve`)."""
+    loop = asyncio.get_running_loop()
+    async with AsyncLopper(max_workers=max_workers, options=options) as lopper:
+        slots = asyncio.Semaphore(lopper.max_workers)
+        pending: set[asyncio.Task[None]] = set()
+
+        async def answer(line: str) -> None:
+            try:
+                response = await _response(lopper, line)
+                output.write(json.dumps(response) + "\n")
+                output.flush()
+            finally:
+                slots.release()
+
+        while line := await loop.run_in_executor(None, input.readline):
+            if not line.strip():
+                continue
+            await slots.acquire()
+            task = asyncio.ensure_future(answer(line))
+            pending.add(task)
+            task.add_done_callback(pending.discard)
+        await asyncio.gather(*pending)
+
+
+async def _response(lopper: AsyncLopper, line: str) -> dict[str, Any]:
+    """Answer one request line, reporting any failure as an error."""
+    request_id: Any = None
+    try:
+        request = json.loads(line)
+        if not isinstance(request, dict):
+            raise ValueError("A request must be a JSON object")
+        request_id = request.get("id")
+        options = request.get("options") or {}
+        if not isinstance(options, Mapping):
+            raise ValueError("options must be an object of option values")
+        # Only the request's options are converted: a base placeholder function has no plain value
+        requested = LopperOptions.from_dict(options)
+        merged = replace(lopper.options, **{name: getattr(requested, name) for name in options})
+        language = request.get("language")
+        if "source" in request:
+            if not isinstance(language, str):
+                raise ValueError("A request with a source must name its language")
+            skeleton = await lopper.lop(str(request["source"]), language, options=merged)
+        elif "path" in request:
+            skeleton = await lopper.lop_file(request["path"], language=language, options=merged)
+        else:
+            raise ValueError("A request must have a source or a path")
+    except Exception as e:
+        error = {"type": type(e).__name__, "message": str(e)}
+        return {"id": request_id, "error": error}
+    return {"id": request_id, "skeleton": skeleton}
+
+
+def _lop_file(path: Path, language: str | None, options: LopperOptions) -> str:
+    """Read a file and extract its skeleton, as a language or as detected from the file."""
+    if language is None:
+        return get_skeleton(path, options=options)
+    if not path.is_file():
+        raise FileNotFoundError(f"File not found: {path}")
+    return extract_skeleton(path.read_text(encoding="utf-8"), language, options=options)
@@ -0,0 +1,131 @@
+"""Keyword-argument shortcuts for calling loppers from Python tooling.
+
+Each shortcut takes the extraction options as keyword arguments rather than a
+`LopperOptions`, and returns plain strings and dicts, so a script needs no other
+import:
+
+    skeleton = lop(source, "rust", visibility="public", imports="collapse")
+    skeletons = lop_dir("src", max_workers=None)  # path -> skeleton
+"""
+
+from __future__ import annotations
+
+from pathlib import Path
+from typing import Any
+
+from loppers.documents import DOCUMENT_LANGUAGES
+from loppers.loppers import LANGUAGE_CONFIGS, LopperOptions
+from loppers.notebooks import NOTEBOOK_LANGUAGE
+from loppers.source_utils import extract_skeleton, get_skeleton, get_skeletons
+from loppers.symbols import extract_symbols
+
+
+def lop(source: str, language: str, **options: Any) -> str:
+    """Extract the skeleton of source code (see `extract_skeleton`).
+
+    Args:
+        source: Source code to process
+        language: Programming language identifier
+        **options: Extraction options (see `LopperOptions`)
+
+    Returns:
+        Skeleton with function implementations removed
+
+    Raises:
+        TypeError: If an option is unknown or has an invalid value
+        UnsupportedLanguageError: If the language is not supported
+    """
+    return extract_skeleton(source, language, options=_options(options))
+
+
+def lop_file(path: str | Path, **options: Any) -> str:
+    """Read a file and extract its skeleton, detecting its language (see `get_skeleton`).
+
+    Args:
+        path: Path of the file
+        **options: Extraction options (see `LopperOptions`)
+
+    Returns:
+        Skeleton with function implementations removed
+
+    Raises:
+        TypeError: If an option is unknown or has an invalid value
+        FileNotFoundError: If the file does not exist
+        UnsupportedLanguageError: If the file's language is not supported
+    """
+    return get_skeleton(path, options=_options(options))
+
+
+def lop_dir(path: str | Path, *, max_workers: int | None = 1, **options: Any) -> dict[str, str]:
+    """Extract the skeletons of every supported file under a directory (see `get_skeletons`).
+
+    Args:
+        path: Root directory path
+        max_workers: Number of worker processes (default 1, in-process; None for the
+            CPU count)
+        **options: Extraction options (see `LopperOptions`)
+
+    Returns:
+        Skeleton of each processed file by path relative to the directory, in path
+        order; skipped and failed files are left out
+
+    Raises:
+        TypeError: If an option is unknown or has an invalid value
+        FileNotFoundError: If the directory does not exist
+        NotADirectoryError: If the path is not a directory
+    """
+    report = get_skeletons(path, options=_options(options), max_workers=max_workers)
+    return dict(report.skeletons)
+
+
+def lop_symbols(
+    source: str,
+    language: str,
+    *,
+    group_impls: bool = False,
+    keep_closures: bool = False,
+    **options: Any,
+) -> list[dict[str, Any]]:
+    """Extract the symbol outline of source code as dicts (see `extract_symbols`).
+
+    Args:
+        source: Source code to process
+        language: Programming language identifier
+        group_impls: List the items of Rust `impl` blocks as children of the type
+            they implement (see `extract_symbols`)
+        keep_closures: List closures, lambdas and other anonymous functions as
+            "closure" symbols (see `extract_symbols`)
+        **options: Extraction options (see `LopperOptions`), such as `skip_tests`
+
+    Returns:
+        Top-level symbols in the JSON symbol schema (see `Symbol.to_dict`)
+
+    Raises:
+        TypeError: If an option is unknown or has an invalid value
+        UnsupportedLanguageError: If the language is not supported
+    """
+    symbols = extract_symbols(
+        source,
+        language,
+        group_impls=group_impls,
+        keep_closures=keep_closures,
+        options=_options(options),
+    )
+    return [symbol.to_dict() for symbol in symbols]
+
+
+def supported_languages() -> list[str]:
+    """List the language identifiers `lop` accepts, registered languages included.
+
+    Returns:
+        Sorted language identifiers, documents and notebooks included
+    """
+    return sorted({*LANGUAGE_CONFIGS, *DOCUMENT_LANGUAGES, NOTEBOOK_LANGUAGE})
+
+
+def _options(options: dict[str, Any]) -> LopperOptions:
+    """Build extraction options from keyword arguments, rejecting bad ones with TypeError."""
+    try:
+        return LopperOptions.from_dict(options)
+    except ValueError as e:
+        raise TypeError(str(e)) from None
@@ -1,19 +1,34 @@
 from __future__ import annotations
 
+import codecs
 import contextlib
+import itertools
+import mmap
+import os
+import subprocess
 from collections import defaultdict
-from collections.abc import Iterable, Sequence
-from pathlib import Path
+from collections.abc import Iterable, Iterator, Sequence
+from concurrent.futures import ProcessPoolExecutor
+from dataclasses import dataclass, field, replace
+from pathlib import Path, PurePath
+from typing import BinaryIO
 
 from binaryornot.check import is_binary
 from pathspec import PathSpec
 
-from loppers.extensions import get_language
+from loppers.cache import LopCache
+from loppers.documents import DOCUMENT_LANGUAGES, extract_document
+from loppers.errors import InvalidUtf8Error, LopError, UnsupportedLanguageError
+from loppers.extensions import detect_language
 from loppers.ignore_patterns import DEFAULT_IGNORE_PATTERNS
-from loppers.loppers import SkeletonExtractor
+from loppers.loppers import ExtractionResult, LopperOptions, ParseDiagnostic, get_extractor
+from loppers.notebooks import NOTEBOOK_LANGUAGE, lop_notebook
 
 Tree = defaultdict[str, "Tree"]
 
+# Longest first line read when looking for a shebang
+_SHEBANG_MAX_LENGTH: int = 256
+
 
 def format_file_size(size_bytes: int) -> str:
     """Format file size in bytes to human-friendly format.
@@ -108,6 +123,242 @@ def tree_as_str(
     return "\n".join(lines)
 
 
+IGNORE_FILE_NAMES: Sequence[str] = (".gitignore", ".ignore")
+
+
+def read_ignore_file(path: Path) -> list[str]:
+    """Read the patterns of a gitignore-style file, skipping blanks and comments."""
+    with contextlib.suppress(OSError, UnicodeDecodeError):
+        return [
+            line.strip()
+            for line in path.read_text(encoding="utf-8").splitlines()
+            if line.strip() and not line.lstrip().startswith("#")
+        ]
+    return []
+
+
+def global_ignore_patterns(root: str | Path) -> list[str]:
+    """Collect git's exclude patterns that don't live in the worktree.
+
+    These are the repository's `.git/info/exclude` and the user's global excludes
+    file (`core.excludesFile`, defaulting to `$XDG_CONFIG_HOME/git/ignore`).
+
+    Args:
+        root: Repository root directory
+
+    Returns:
+        Patterns relative to root
+    """
+    patterns = read_ignore_file(Path(root) / ".git" / "info" / "exclude")
+
+    excludes_file: str = ""
+    with contextlib.suppress(OSError, subprocess.SubprocessError):
+        excludes_file = subprocess.run(
+            ["git", "config", "--path", "--get", "core.excludesFile"],
+            cwd=root,
+            capture_output=True,
+            text=True,
+            timeout=5,
+            check=False,
+        ).stdout.strip()
+    if not excludes_file:
+        config_home = os.environ.get("XDG_CONFIG_HOME") or Path.home() / ".config"
+        excludes_file = str(Path(config_home) / "git" / "ignore")
+    patterns.extend(read_ignore_file(Path(excludes_file).expanduser()))
+    return patterns
+
+
+def build_ignore_spec(
+    root: str | Path,
+    *,
+    ignore_patterns: Sequence[str] | None = None,
+    use_default_ignore: bool = True,
+    respect_gitignore: bool = True,
+) -> PathSpec | None:
+    """Build the combined ignore spec used for file discovery.
+
+    Ignore files in subdirectories are not included; `walk_files` applies them
+    as it descends.
+
+    Args:
+        root: Root directory path
+        ignore_patterns: Additional gitignore-style patterns to ignore
+        use_default_ignore: Apply built-in ignore patterns (node_modules, .git, etc.)
+        respect_gitignore: Merge patterns from `.gitignore` and `.ignore` files in the
+            root and from git's global excludes
+
+    Returns:
+        Compiled spec, or None when there is nothing to ignore
+    """
+    root_path = Path(root)
+    patterns: list[str] = []
+    if use_default_ignore:
+        patterns.extend(DEFAULT_IGNORE_PATTERNS)
+    if respect_gitignore:
+        patterns.extend(global_ignore_patterns(root_path))
+        for ignore_file_name in IGNORE_FILE_NAMES:
+            patterns.extend(read_ignore_file(root_path / ignore_file_name))
+    if ignore_patterns:
+        patterns.extend(ignore_patterns)
+    return PathSpec.from_lines("gitwildmatch", patterns) if patterns else None
+
+
+def matches_pattern(path: str | PurePath, patterns: Sequence[str]) -> bool:
+    """Tell whether a relative path matches one of some glob patterns.
+
+    Patterns use gitignore syntax: `*.rs` matches at any depth, `src/**/*.rs` only
+    under `src` at the root, and `**/tests/**` anything inside a `tests` directory.
+
+    Args:
+        path: Path relative to the root of a walk
+        patterns: Glob patterns
+
+    Returns:
+        True if any pattern matches the path
+    """
+    if not patterns:
+        return False
+    return PathSpec.from_lines("gitwildmatch", patterns).match_file(PurePath(path).as_posix())
+
+
+def walk_files(
+    root: str | Path,
+    *,
+    spec: PathSpec | None = None,
+    recursive: bool = True,
+    respect_gitignore: bool = False,
+    include: Sequence[str] | None = None,
+    exclude: Sequence[str] | None = None,
+) -> Iterator[str]:
+    """Yield files under a root directory that are not ignored.
+
+    Symlinked directories are followed, but each real directory is visited at most
+    once, so symlink cycles cannot cause infinite recursion.
+
+    Args:
+        root: Root directory path
+        spec: Ignore spec matched against paths relative to root
+        recursive: Recursively traverse directories (default True)
+        respect_gitignore: Also apply `.gitignore` and `.ignore` files found in
+            subdirectories, relative to their own directory
+        include: Glob patterns of the files to yield, relative to root (see
+            `matches_pattern`); files matching any of them are yielded, all files
+            when None or empty
+        exclude: Glob patterns of files not to yield, winning over `include`
+
+    Yields:
+        File paths relative to root (POSIX separators), in sorted traversal order
+    """
+    root_path = Path(root)
+    visited: set[Path] = set()
+    include_spec = PathSpec.from_lines("gitwildmatch", include) if include else None
+    exclude_spec = PathSpec.from_lines("gitwildmatch", exclude) if exclude else None
+
+    def is_selected(relative_str: str) -> bool:
+        if include_spec is not None and not include_spec.match_file(relative_str):
+            return False
+        return exclude_spec is None or not exclude_spec.match_file(relative_str)
+
+    def is_ignored(relative_str: str, is_dir: bool, scopes: list[tuple[str, PathSpec]]) -> bool:
+        for prefix, scope_spec in scopes:
+            if not relative_str.startswith(prefix):
+                continue
+            scoped_path = relative_str[len(prefix) :]
+            if scope_spec.match_file(scoped_path) or (
+                is_dir and scope_spec.match_file(f"{scoped_path}/")
+            ):
+                return True
+        return False
+
+    def walk(current: Path, scopes: list[tuple[str, PathSpec]]) -> Iterator[str]:
+        real_path = current.resolve()
+        if real_path in visited:
+            return
+        visited.add(real_path)
+
+        if respect_gitignore and current != root_path:
+            nested_patterns = [
+                pattern
+                for ignore_file_name in IGNORE_FILE_NAMES
+                for pattern in read_ignore_file(current / ignore_file_name)
+            ]
+            if nested_patterns:
+                prefix = f"{current.relative_to(root_path).as_posix()}/"
+                scopes = [*scopes, (prefix, PathSpec.from_lines("gitwildmatch", nested_patterns))]
+
+        for entry in sorted(current.iterdir()):
+            relative_str = entry.relative_to(root_path).as_posix()
+            if entry.is_dir():
+                if recursive and not is_ignored(relative_str, True, scopes):
+                    yield from walk(entry, scopes)
+            elif (
+                entry.is_file()
+                and not is_ignored(relative_str, False, scopes)
+                and is_selected(relative_str)
+            ):
+                yield relative_str
+
+    yield from walk(root_path, [("", spec)] if spec else [])
+
+
+def walk_dir(
+    root: str | Path,
+    *,
+    recursive: bool = True,
+    ignore_patterns: Sequence[str] | None = None,
+    use_default_ignore: bool = True,
+    respect_gitignore: bool = True,
+    include: Sequence[str] | None = None,
+    exclude: Sequence[str] | None = None,
+) -> Iterator[str]:
+    """Yield the source files under a root directory that loppers can extract.
+
+    Only files in a supported language (by extension, file name or shebang, see
+    `detect_file_language`) are yielded. Paths
+    ignored by `.gitignore`/`.ignore` files (at any depth), git's global excludes,
+    the built-in patterns or `ignore_patterns` are never visited.
+
+    Args:
+        root: Root directory path
+        recursive: Recursively traverse directories (default True)
+        ignore_patterns: Additional gitignore-style patterns to ignore
+        use_default_ignore: Apply built-in ignore patterns (node_modules, .git, etc.)
+        respect_gitignore: Respect ignore files and git's global excludes when True
+        include: Glob patterns of the files to yield, relative to root (see
+            `matches_pattern`); all files when None
+        exclude: Glob patterns of files to leave out, winning over `include`
+
+    Yields:
+        Source file paths relative to root (POSIX separators), in sorted order
+
+    Raises:
+        FileNotFoundError: If root does not exist
+        NotADirectoryError: If root is not a directory
+    """
+    root_path = Path(root)
+    if not root_path.exists():
+        raise FileNotFoundError(f"Root not found: {root_path}")
+    if not root_path.is_dir():
+        raise NotADirectoryError(f"Expected a directory at: {root_path}")
+
+    spec = build_ignore_spec(
+        root_path,
+        ignore_patterns=ignore_patterns,
+        use_default_ignore=use_default_ignore,
+        respect_gitignore=respect_gitignore,
+    )
+    for relative_str in walk_files(
+        root_path,
+        spec=spec,
+        recursive=recursive,
+        respect_gitignore=respect_gitignore,
+        include=include,
+        exclude=exclude,
+    ):
+        if detect_file_language(root_path / relative_str):
+            yield relative_str
+
+
 def describe_repository(
     root: str | Path,
     *,
@@ -121,7 +372,8 @@ def describe_repository(
         root: Path to the repository root directory.
         ignore_patterns: Additional gitignore-style patterns to ignore.
         use_default_ignore: Whether to apply the built-in ignore patterns.
-        respect_gitignore: Merge patterns from a `.gitignore` file in the root when True.
+        respect_gitignore: Merge patterns from `.gitignore`/`.ignore` files in the root and
+            from git's global excludes when True.
 
     Returns:
         A tuple where the first element is a string containing the hierarchical
@@ -138,21 +390,12 @@ def describe_repository(
     if not root_path.is_dir():
         raise NotADirectoryError(f"Expected a directory at: {root_path}")
 
-    patterns: list[str] = []
-    if use_default_ignore:
-        patterns.extend(DEFAULT_IGNORE_PATTERNS)
-    if respect_gitignore:
-        gitignore_path = root_path / ".gitignore"
-        if gitignore_path.exists():
-            gitignore_lines = [
-                line.strip()
-                for line in gitignore_path.read_text(encoding="utf-8").splitlines()
-                if line.strip() and not line.lstrip().startswith("#")
-            ]
-            patterns.extend(gitignore_lines)
-    if ignore_patterns:
-        patterns.extend(ignore_patterns)
-    spec = PathSpec.from_lines("gitwildmatch", patterns) if patterns else None
+    spec = build_ignore_spec(
+        root_path,
+        ignore_patterns=ignore_patterns,
+        use_default_ignore=use_default_ignore,
+        respect_gitignore=respect_gitignore,
+    )
 
     tree_lines: list[str] = []
     file_list: list[str] = []
@@ -185,7 +428,37 @@ def describe_repository(
     return file_tree, file_list
 
 
-def extract_skeleton(source: str, language: str) -> str:
+def detect_file_language(file_path: Path | str) -> str | None:
+    """Detect the language of a file on disk.
+
+    Like `detect_language`, but reads the first line of the file for a shebang
+    when neither its extension nor its name is known.
+
+    Args:
+        file_path: Path to the file
+
+    Returns:
+        Language identifier, or None if not detected
+    """
+    path = Path(file_path)
+    language = detect_language(path)
+    if language is None:
+        try:
+            with path.open("rb") as file:
+                head = file.readline(_SHEBANG_MAX_LENGTH)
+        except OSError:
+            return None
+        if head.startswith(b"#!"):
+            language = detect_language(path, head.decode(errors="replace"))
+    return language
+
+
+def extract_skeleton(
+    source: str,
+    language: str,
+    *,
+    options: LopperOptions | None = None,
+) -> str:
     """Extract skeleton from source code.
 
     Lower-level function that extracts skeleton for a given language.
@@ -193,55 +466,470 @@ def extract_skeleton(source: str, language: str) -> str:
     Args:
         source: Source code to process
         language: Programming language identifier
+        options: Extraction options (defaults to `LopperOptions()`)
 
     Returns:
         Skeleton with function implementations removed
 
     Raises:
-        ValueError: If language is not supported
+        UnsupportedLanguageError: If language is not supported
+        InvalidNotebookError: If a notebook (language "notebook") is malformed
+    """
+    if language == NOTEBOOK_LANGUAGE:
+        return lop_notebook(source, options=options)
+    if language in DOCUMENT_LANGUAGES:
+        return extract_document(source, language, options=options).skeleton
+    return get_extractor(language, options).extract(source)
+
+
+def skeletonize_with_query(
+    source: str,
+    language: str,
+    query: str,
+    *,
+    options: LopperOptions | None = None,
+) -> str:
+    """Extract a skeleton, also handling the nodes marked by a tree-sitter query.
+
+    The query's captures say what to do with the nodes it matches, on top of the
+    built-in handling: `@keep.whole` keeps a node verbatim, `@keep.signature`
+    elides its `body` field and `@prune.body` elides the node itself as a body.
+    For example `(function_item body: (block) @prune.body)` elides Rust function
+    bodies, and `((function_item name: (identifier) @_name) @keep.whole
+    (#eq? @_name "main"))` keeps `main` whole.
+
+    Args:
+        source: Source code to process
+        language: Programming language identifier
+        query: Tree-sitter query source
+        options: Extraction options (defaults to `LopperOptions()`), whose own
+            `query` is replaced
+
+    Returns:
+        Skeleton with function implementations removed
+
+    Raises:
+        UnsupportedLanguageError: If language is not supported
+        InvalidQueryError: If the query is malformed or uses captures other than
+            `@keep.signature`, `@keep.whole`, `@prune.body` and `_`-prefixed ones
+    """
+    options = replace(options or LopperOptions(), query=query)
+    return get_extractor(language, options).extract(source)
+
+
+def extract_skeleton_result(
+    source: str,
+    language: str,
+    *,
+    options: LopperOptions | None = None,
+) -> ExtractionResult:
+    """Extract skeleton from source code, reporting the regions that did not parse.
+
+    Args:
+        source: Source code to process
+        language: Programming language identifier
+        options: Extraction options (defaults to `LopperOptions()`)
+
+    Returns:
+        Skeleton with the regions of the source that did not parse, and the source
+        lines of each skeleton line; notebooks, whose cells are not lines of their
+        JSON source, report neither
+
+    Raises:
+        UnsupportedLanguageError: If language is not supported
+        InvalidNotebookError: If a notebook (language "notebook") is malformed
+        ParseFailedError: If the source has syntax errors and `on_error` is "fail"
     """
-    extractor = SkeletonExtractor(language)
-    return extractor.extract(source)
+    if language == NOTEBOOK_LANGUAGE:
+        return ExtractionResult(lop_notebook(source, options=options))
+    if language in DOCUMENT_LANGUAGES:
+        return extract_document(source, language, options=options)
+    return get_extractor(language, options).extract_result(source)
 
 
-def get_skeleton(file_path: Path | str, *, add_header: bool = False) -> str:
-    """Extract skeleton from a file by auto-detecting language from extension.
+def get_skeleton(
+    file_path: Path | str,
+    *,
+    add_header: bool = False,
+    options: LopperOptions | None = None,
+) -> str:
+    """Extract skeleton from a file by auto-detecting language from extension or shebang.
 
     Args:
         file_path: Path to the file
         add_header: If True, prepend "--- <filepath>" header to skeleton
+        options: Extraction options (defaults to `LopperOptions()`)
 
     Returns:
         Skeleton with function implementations removed, optionally with header
 
     Raises:
         FileNotFoundError: If file does not exist
-        ValueError: If file language is not supported
+        UnsupportedLanguageError: If file language is not supported
+        InvalidUtf8Error: If the file is not valid UTF-8
+        ParseFailedError: If the file could not be parsed
     """
     path = Path(file_path)
+    skeleton = _extract_file(path, options).skeleton
 
+    # Add header if requested
+    if add_header:
+        skeleton = f"--- {path}\n{skeleton}"
+
+    return skeleton
+
+
+def _extract_file(
+    path: Path, options: LopperOptions | None, cache: LopCache | None = None
+) -> ExtractionResult:
+    """Extract the skeleton of a file, reporting its syntax errors.
+
+    With a cache, the result of an unchanged file is read from it instead, and any
+    other result is stored in it.
+    """
     if not path.is_file():
         raise FileNotFoundError(f"File not found: {path}")
 
-    # Auto-detect language from extension
-    language = get_language(str(path.suffix))
+    # Auto-detect language from extension, file name or shebang
+    language = detect_file_language(path)
     if not language:
-        raise ValueError(f"Unsupported file type: {path.suffix}")
+        raise _unsupported_file(path)
 
     # Read file content
     try:
         content = path.read_text(encoding="utf-8")
     except UnicodeDecodeError as e:
-        raise ValueError(f"Could not read file as text: {e}")
+        raise InvalidUtf8Error(f"Could not read file as text: {e}", path=path) from e
+
+    if cache is not None and (cached := cache.get(content, language, options)) is not None:
+        return cached
 
     # Extract skeleton
-    skeleton = extract_skeleton(content, language)
+    try:
+        result = extract_skeleton_result(content, language, options=options)
+    except LopError as e:
+        e.path = e.path or path
+        raise
+    if cache is not None:
+        cache.put(content, language, result, options)
+    return result
 
-    # Add header if requested
-    if add_header:
-        skeleton = f"--- {path}\n{skeleton}"
 
-    return skeleton
+def stream_skeleton(
+    file_path: Path | str,
+    *,
+    options: LopperOptions | None = None,
+) -> Iterator[str]:
+    """Extract the skeleton of a file piece by piece without reading it into memory.
+
+    The file is memory-mapped, so only the pages tree-sitter and the skeleton need
+    are loaded, and the skeleton is produced in pieces as it is built. Use it for
+    very large (e.g. generated) files; the pieces join to `get_skeleton`'s output.
+    Documents and notebooks, whose code is lopped region by region or cell by cell,
+    are read whole instead and yield their skeleton as one piece.
+
+    Args:
+        file_path: Path to the file
+        options: Extraction options (defaults to `LopperOptions()`)
+
+    Returns:
+        Iterator over consecutive pieces of the skeleton
+
+    Raises:
+        FileNotFoundError: If file does not exist
+        UnsupportedLanguageError: If file language is not supported
+        InvalidUtf8Error: While iterating, if the file is not valid UTF-8
+    """
+    path = Path(file_path)
+    if not path.is_file():
+        raise FileNotFoundError(f"File not found: {path}")
+    language = detect_file_language(path)
+    if not language:
+        raise _unsupported_file(path)
+    if language in DOCUMENT_LANGUAGES or language == NOTEBOOK_LANGUAGE:
+
+        def whole() -> Iterator[str]:
+            if skeleton := get_skeleton(path, options=options):
+                yield skeleton
+
+        return whole()
+    extractor = get_extractor(language, options)
+
+    def pieces() -> Iterator[str]:
+        if path.stat().st_size == 0:
+            return
+        with path.open("rb") as file, mmap.mmap(file.fileno(), 0, access=mmap.ACCESS_READ) as data:
+            try:
+                yield from _decode_pieces(extractor.iter_extract(data))  # type: ignore[arg-type]
+            except LopError as e:
+                e.path = e.path or path
+                raise
+
+    return pieces()
+
+
+def stream_skeleton_reader(
+    reader: BinaryIO,
+    language: str,
+    *,
+    options: LopperOptions | None = None,
+    chunk_size: int = 1 << 16,
+) -> Iterator[str]:
+    """Extract the skeleton of source read from a binary stream, piece by piece.
+
+    The stream is read in chunks into a single buffer (without a decoded copy of
+    the source), and the skeleton is produced in pieces instead of as one string.
+
+    Args:
+        reader: Binary stream of UTF-8 encoded source code
+        language: Programming language identifier
+        options: Extraction options (defaults to `LopperOptions()`)
+        chunk_size: Number of bytes read at a time
+
+    Returns:
+        Iterator over consecutive pieces of the skeleton
+
+    Raises:
+        UnsupportedLanguageError: If language is not supported
+        InvalidUtf8Error: While iterating, if the source is not valid UTF-8
+    """
+    extractor = get_extractor(language, options)
+
+    def pieces() -> Iterator[str]:
+        buffer = bytearray()
+        while chunk := reader.read(chunk_size):
+            buffer += chunk
+        yield from _decode_pieces(extractor.iter_extract(buffer))  # type: ignore[arg-type]
+
+    return pieces()
+
+
+def _decode_pieces(pieces: Iterable[bytes]) -> Iterator[str]:
+    """Decode UTF-8 pieces that may split multi-byte characters."""
+    decoder = codecs.getincrementaldecoder("utf-8")()
+    try:
+        for piece in pieces:
+            if text := decoder.decode(piece):
+                yield text
+        if text := decoder.decode(b"", final=True):
+            yield text
+    except UnicodeDecodeError as e:
+        raise InvalidUtf8Error(f"Could not read file as text: {e}") from e
+
+
+def _unsupported_file(path: Path) -> UnsupportedLanguageError:
+    """Build the error for a file whose language could not be detected."""
+    return UnsupportedLanguageError(
+        f"Unsupported file type: {path.suffix or path.name}",
+        path=path,
+        extension=path.suffix or None,
+    )
+
+
+@dataclass
+class SkeletonReport:
+    """Skeletons extracted from a directory, with a summary of what was left out.
+
+    Attributes:
+        skeletons: (relative path, skeleton) pairs of processed files, in path order
+        skipped: Relative paths of binary files and files in unsupported languages
+        failed: (relative path, error message) pairs of files that could not be processed
+        syntax_errors: (relative path, error count) pairs of processed files that did not
+            parse cleanly
+    """
+
+    skeletons: list[tuple[str, str]] = field(default_factory=list)
+    skipped: list[str] = field(default_factory=list)
+    failed: list[tuple[str, str]] = field(default_factory=list)
+    syntax_errors: list[tuple[str, int]] = field(default_factory=list)
+
+    @property
+    def processed(self) -> int:
+        """Number of files whose skeleton was extracted."""
+        return len(self.skeletons)
+
+
+def get_skeletons(
+    root: str | Path,
+    *,
+    recursive: bool = True,
+    ignore_patterns: Sequence[str] | None = None,
+    use_default_ignore: bool = True,
+    respect_gitignore: bool = True,
+    include: Sequence[str] | None = None,
+    exclude: Sequence[str] | None = None,
+    options: LopperOptions | None = None,
+    max_workers: int | None = 1,
+    cache: LopCache | None = None,
+) -> SkeletonReport:
+    """Extract skeletons from every supported source file under a root directory.
+
+    Files are selected by the language registry (`EXTENSION_TO_LANGUAGE`,
+    `FILENAME_TO_LANGUAGE` and shebang interpreters), so newly
+    supported languages are picked up automatically. Binary files and files in
+    unsupported languages are skipped, and files that fail to process are recorded
+    instead of aborting the run. With several workers, files are processed in
+    parallel (see `get_skeletons_parallel`); the report is identical either way.
+
+    Args:
+        root: Root directory path
+        recursive: Recursively traverse directories (default True)
+        ignore_patterns: Additional gitignore-style patterns to ignore
+        use_default_ignore: Apply built-in ignore patterns (node_modules, .git, etc.)
+        respect_gitignore: Respect .gitignore/.ignore files and git's global excludes when True
+        include: Glob patterns of the files to process, relative to root (see
+            `matches_pattern`); all files when None. Files left out by `include` or
+            `exclude` are not reported as skipped
+        exclude: Glob patterns of files to leave out, winning over `include`
+        options: Extraction options (defaults to `LopperOptions()`)
+        max_workers: Number of worker processes (default 1, in-process; None for the
+            CPU count)
+        cache: Cache reusing the skeletons of files unchanged since an earlier run
+            (see `LopCache`)
+
+    Returns:
+        Report with the extracted skeletons and the skipped and failed files
+
+    Raises:
+        FileNotFoundError: If root does not exist
+        NotADirectoryError: If root is not a directory
+    """
+    root_path = Path(root)
+    if not root_path.exists():
+        raise FileNotFoundError(f"Root not found: {root_path}")
+    if not root_path.is_dir():
+        raise NotADirectoryError(f"Expected a directory at: {root_path}")
+
+    spec = build_ignore_spec(
+        root_path,
+        ignore_patterns=ignore_patterns,
+        use_default_ignore=use_default_ignore,
+        respect_gitignore=respect_gitignore,
+    )
+
+    files = walk_files(
+        root_path,
+        spec=spec,
+        recursive=recursive,
+        respect_gitignore=respect_gitignore,
+        include=include,
+        exclude=exclude,
+    )
+    report = SkeletonReport()
+    sources: list[str] = []
+    for relative_str in sorted(files):
+        full_path = root_path / relative_str
+        if not detect_file_language(full_path) or is_binary(str(full_path)):
+            report.skipped.append(relative_str)
+        else:
+            sources.append(relative_str)
+
+    results = get_skeletons_parallel(
+        [root_path / relative_str for relative_str in sources],
+        options=options,
+        max_workers=max_workers,
+        cache=cache,
+    )
+    for relative_str, result in zip(sources, results):
+        if result.skeleton is not None:
+            report.skeletons.append((relative_str, result.skeleton))
+            if result.syntax_errors:
+                report.syntax_errors.append((relative_str, result.syntax_errors))
+        elif result.skipped:
+            report.skipped.append(relative_str)
+        else:
+            report.failed.append((relative_str, result.error or "unknown error"))
+    return report
+
+
+@dataclass
+class SkeletonResult:
+    """Outcome of extracting the skeleton of a single file.
+
+    Attributes:
+        path: File path, as given
+        skeleton: Extracted skeleton, or None if extraction failed
+        error: Error message if extraction failed, otherwise None
+        exception: Exception raised by the extraction, if it failed
+        diagnostics: Regions of the file that did not parse, in source order
+    """
+
+    path: str
+    skeleton: str | None = None
+    error: str | None = None
+    exception: Exception | None = None
+    diagnostics: list[ParseDiagnostic] = field(default_factory=list)
+
+    @property
+    def ok(self) -> bool:
+        """Whether the skeleton was extracted."""
+        return self.error is None
+
+    @property
+    def syntax_errors(self) -> int:
+        """Number of regions of the file that did not parse."""
+        return len(self.diagnostics)
+
+    @property
+    def skipped(self) -> bool:
+        """Whether the file was left out because its language is not supported."""
+        return isinstance(self.exception, UnsupportedLanguageError)
+
+
+def _skeleton_result(
+    path: str, options: LopperOptions | None, cache: LopCache | None = None
+) -> SkeletonResult:
+    """Extract one file's skeleton, capturing any failure in the result."""
+    try:
+        result = _extract_file(Path(path), options, cache)
+        return SkeletonResult(path, skeleton=result.skeleton, diagnostics=result.diagnostics)
+    except Exception as e:
+        return SkeletonResult(path, error=f"{type(e).__name__}: {e}", exception=e)
+
+
+def get_skeletons_parallel(
+    file_paths: Sequence[str | Path],
+    *,
+    options: LopperOptions | None = None,
+    max_workers: int | None = None,
+    cache: LopCache | None = None,
+) -> list[SkeletonResult]:
+    """Extract skeletons from many files in parallel worker processes.
+
+    Each worker reuses one extractor per language (see `get_extractor`) instead of
+    building a parser per file. A failure on one file is reported in its result and
+    does not abort the batch.
+
+    Args:
+        file_paths: Paths of the files to process
+        options: Extraction options (defaults to `LopperOptions()`)
+        max_workers: Number of worker processes (defaults to the CPU count)
+        cache: Cache reusing the skeletons of files unchanged since an earlier run
+            (see `LopCache`); worker processes share its directory, but count their
+            hits and misses apart
+
+    Returns:
+        One result per input path, in input order regardless of completion order
+    """
+    paths = [str(path) for path in file_paths]
+    if not paths:
+        return []
+
+    workers = max_workers or os.cpu_count() or 1
+    if workers == 1:
+        return [_skeleton_result(path, options, cache) for path in paths]
+
+    chunksize = max(1, len(paths) // (workers * 4))
+    with ProcessPoolExecutor(max_workers=workers) as executor:
+        return list(
+            executor.map(
+                _skeleton_result,
+                paths,
+                itertools.repeat(options),
+                itertools.repeat(cache),
+                chunksize=chunksize,
+            )
+        )
 
 
 def find_files(
@@ -251,6 +939,8 @@ def find_files(
     ignore_patterns: Sequence[str] | None = None,
     use_default_ignore: bool = True,
     respect_gitignore: bool = True,
+    include: Sequence[str] | None = None,
+    exclude: Sequence[str] | None = None,
 ) -> list[str]:
     """Collect all non-binary text files from a root directory.
 
@@ -259,7 +949,10 @@ def find_files(
         recursive: Recursively traverse directories (default True)
         ignore_patterns: Additional gitignore-style patterns to ignore
         use_default_ignore: Apply built-in ignore patterns (node_modules, .git, etc.)
-        respect_gitignore: Respect .gitignore file in root when True
+        respect_gitignore: Respect .gitignore/.ignore files and git's global excludes when True
+        include: Glob patterns of the files to collect, relative to root (see
+            `matches_pattern`); all files when None
+        exclude: Glob patterns of files to leave out, winning over `include`
 
     Returns:
         List of file paths relative to root (respects ignore patterns and binary detection)
@@ -274,53 +967,25 @@ def find_files(
     if not root_path.is_dir():
         raise NotADirectoryError(f"Expected a directory at: {root_path}")
 
-    patterns: list[str] = []
-    if use_default_ignore:
-        patterns.extend(DEFAULT_IGNORE_PATTERNS)
-    if respect_gitignore:
-        gitignore_path = root_path / ".gitignore"
-        if gitignore_path.exists():
-            gitignore_lines = [
-                line.strip()
-                for line in gitignore_path.read_text(encoding="utf-8").splitlines()
-                if line.strip() and not line.lstrip().startswith("#")
-            ]
-            patterns.extend(gitignore_lines)
-    if ignore_patterns:
-        patterns.extend(ignore_patterns)
-    spec = PathSpec.from_lines("gitwildmatch", patterns) if patterns else None
-
-    files_to_process: list[str] = []
-
-    if not recursive:
-        # Non-recursive: just get immediate files
-        for entry in sorted(root_path.iterdir()):
-            if entry.is_file():
-                relative_str = entry.relative_to(root_path).as_posix()
-                if spec and spec.match_file(relative_str):
-                    continue
-                if not is_binary(str(entry)):
-                    files_to_process.append(relative_str)
-    else:
-        # Recursive: collect all files
-        def walk(current: Path) -> None:
-            for entry in sorted(current.iterdir()):
-                relative_path = entry.relative_to(root_path)
-                relative_str = relative_path.as_posix()
-                if spec:
-                    if entry.is_dir():
-                        if spec.match_file(f"{relative_str}/") or spec.match_file(relative_str):
-                            continue
-                    else:
-                        if spec.match_file(relative_str):
-                            continue
-                if entry.is_dir():
-                    walk(entry)
-                else:
-                    if not is_binary(str(entry)):
-                        files_to_process.append(relative_str)
+    spec = build_ignore_spec(
+        root_path,
+        ignore_patterns=ignore_patterns,
+        use_default_ignore=use_default_ignore,
+        respect_gitignore=respect_gitignore,
+    )
 
-        walk(root_path)
+    files_to_process: list[str] = [
+        relative_str
+        for relative_str in walk_files(
+            root_path,
+            spec=spec,
+            recursive=recursive,
+            respect_gitignore=respect_gitignore,
+            include=include,
+            exclude=exclude,
+        )
+        if not is_binary(str(root_path / relative_str))
+    ]
 
     return sorted(files_to_process)
 
@@ -332,6 +997,8 @@ def get_tree(
     ignore_patterns: Sequence[str] | None = None,
     use_default_ignore: bool = True,
     respect_gitignore: bool = True,
+    include: Sequence[str] | None = None,
+    exclude: Sequence[str] | None = None,
     collapse_single_dirs: bool = False,
     show_sizes: bool = False,
 ) -> str:
@@ -342,7 +1009,9 @@ def get_tree(
         recursive: Recursively traverse directories (default True)
         ignore_patterns: Additional gitignore-style patterns to ignore
         use_default_ignore: Apply built-in ignore patterns (node_modules, .git, etc.)
-        respect_gitignore: Respect .gitignore file in root when True
+        respect_gitignore: Respect .gitignore/.ignore files and git's global excludes when True
+        include: Glob patterns of the files to show (see `find_files`)
+        exclude: Glob patterns of files to leave out, winning over `include`
         collapse_single_dirs: Collapse directories with single children (default False)
         show_sizes: Show file sizes in human-friendly format (default False)
 
@@ -365,6 +1034,8 @@ def get_tree(
         ignore_patterns=ignore_patterns,
         use_default_ignore=use_default_ignore,
         respect_gitignore=respect_gitignore,
+        include=include,
+        exclude=exclude,
     )
 
     # Collect file sizes if requested
@@ -390,6 +1061,7 @@ def concatenate_files(
     *,
     extract: bool = True,
     ignore_not_found: bool = False,
+    options: LopperOptions | None = None,
 ) -> str:
     """Concatenate files with optional skeleton extraction.
 
@@ -398,6 +1070,7 @@ def concatenate_files(
         file_paths: List of file paths (relative to root) to concatenate
         extract: Extract skeletons from code files (default True)
         ignore_not_found: Ignore files that cannot be found or processed (default False)
+        options: Extraction options used when extracting skeletons
 
     Returns:
         Concatenated content of all files with headers for each file
@@ -428,13 +1101,10 @@ def concatenate_files(
             if extract:
                 # Try to extract skeleton, fall back to original for unsupported types
                 try:
-                    content = get_skeleton(full_file_path, add_header=False)
-                except ValueError as e:
+                    content = get_skeleton(full_file_path, add_header=False, options=options)
+                except UnsupportedLanguageError:
                     # File type not supported for extraction, include as-is
-                    if "Unsupported file type" in str(e):
-                        content = full_file_path.read_text(encoding="utf-8")
-                    else:
-                        raise
+                    content = full_file_path.read_text(encoding="utf-8")
             else:
                 # Include original file content
                 content = full_file_path.read_text(encoding="utf-8")
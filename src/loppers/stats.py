@@ -0,0 +1,438 @@
+"""Compression statistics of skeletons.
+
+Statistics compare a source file with its skeleton (bytes, lines and estimated
+tokens), count the items the source defines and those that lost code to the
+skeleton, so the savings of lopping can be measured per file and over a whole
+directory, and a directory taken as a census of its code per language.
+"""
+
+from __future__ import annotations
+
+import io
+from collections import Counter
+from collections.abc import Sequence
+from dataclasses import asdict, dataclass, field
+from pathlib import Path
+from typing import IO
+
+from binaryornot.check import is_binary
+
+from loppers.budget import TokenEstimator, estimate_tokens, read_source
+from loppers.cache import LopCache
+from loppers.loppers import (
+    LANGUAGE_CONFIGS,
+    ExtractionResult,
+    LopperOptions,
+    ParseDiagnostic,
+    SourceMap,
+    count_lines,
+    get_extractor,
+    item_span,
+    syntax_error_nodes,
+)
+from loppers.source_utils import stream_skeleton
+from loppers.symbol_model import symbol_nodes
+
+# Bytes continuing a multi-byte UTF-8 character, left out when counting characters
+_CONTINUATION_BYTES: bytes = bytes(range(0x80, 0xC0))
+
+# Number of bytes of a file read at a time when measuring it
+_MEASURE_CHUNK_SIZE: int = 1 << 20
+
+
+@dataclass(frozen=True)
+class LopStats:
+    """Sizes of a source and its skeleton, with the items that lost code.
+
+    Statistics add up with `+`, and `LopStats()` is the empty total.
+
+    Attributes:
+        original_bytes: Size of the UTF-8 encoded source
+        original_lines: Number of lines of the source
+        original_tokens: Estimated token count of the source
+        output_bytes: Size of the UTF-8 encoded skeleton
+        output_lines: Number of lines of the skeleton
+        output_tokens: Estimated token count of the skeleton
+        elided_lines: Number of source lines without a skeleton line of their own
+            (elided body lines, dropped items)
+        symbols: Number of items the source defines, nested ones included, by symbol
+            kind (e.g. {"function": 4, "impl": 1, "method": 6, "struct": 1}); empty
+            for languages without symbol support
+        elided: Number of items that lost code (elided bodies and dropped items), by
+            symbol kind (e.g. {"function": 3, "method": 2}); empty for languages
+            without symbol support
+    """
+
+    original_bytes: int = 0
+    original_lines: int = 0
+    original_tokens: int = 0
+    output_bytes: int = 0
+    output_lines: int = 0
+    output_tokens: int = 0
+    elided_lines: int = 0
+    symbols: dict[str, int] = field(default_factory=dict)
+    elided: dict[str, int] = field(default_factory=dict)
+
+    @property
+    def saved_tokens(self) -> int:
+        """Estimated number of tokens the skeleton saves."""
+        return self.original_tokens - self.output_tokens
+
+    @property
+    def ratio(self) -> float:
+        """Estimated token count of the skeleton relative to the source (1.0 if empty)."""
+        return self.output_tokens / self.original_tokens if self.original_tokens else 1.0
+
+    def __add__(self, other: LopStats) -> LopStats:
+        """Sum two statistics, merging their item counts."""
+        return LopStats(
+            self.original_bytes + other.original_bytes,
+            self.original_lines + other.original_lines,
+            self.original_tokens + other.original_tokens,
+            self.output_bytes + other.output_bytes,
+            self.output_lines + other.output_lines,
+            self.output_tokens + other.output_tokens,
+            self.elided_lines + other.elided_lines,
+            dict(sorted((Counter(self.symbols) + Counter(other.symbols)).items())),
+            dict(sorted((Counter(self.elided) + Counter(other.elided)).items())),
+        )
+
+
+def lop_stats(
+    source_code: str,
+    language: str,
+    *,
+    options: LopperOptions | None = None,
+    estimator: TokenEstimator = estimate_tokens,
+) -> LopStats:
+    """Extract a skeleton and measure it against its source.
+
+    An item counts as elided when code of its own (outside its nested items) is
+    missing from the skeleton: an elided body, a collapsed type body or the whole
+    item when it is dropped.
+
+    Args:
+        source_code: Source code to process
+        language: Programming language identifier
+        options: Extraction options (defaults to LopperOptions())
+        estimator: Token estimator (defaults to a characters / 4 heuristic)
+
+    Returns:
+        Statistics of the source and its skeleton
+
+    Raises:
+        UnsupportedLanguageError: If the language is not supported
+        ParseFailedError: If the source has syntax errors and `on_error` is "fail"
+    """
+    return _measure(source_code, language, options=options, estimator=estimator)[1]
+
+
+def lop_to_writer(
+    file_path: Path | str,
+    writer: IO[str] | IO[bytes],
+    *,
+    options: LopperOptions | None = None,
+) -> LopStats:
+    """Extract the skeleton of a file straight into a writer, measuring it on the way.
+
+    For files too large to hold as text, such as generated sources of hundreds of
+    megabytes: the file is memory-mapped and the skeleton written piece by piece as
+    it is built (see `stream_skeleton`), so neither the decoded source nor the
+    skeleton is ever held in memory. What remains is the syntax tree tree-sitter
+    builds of the whole file, outside the Python heap, and the offsets of its lines
+    and elided ranges, which take about 40 bytes per line of the file.
+
+    Sizes, lines and tokens are counted from the pieces as they pass: tokens are
+    estimated as one per four characters (see `estimate_tokens`) and lines by their
+    line feeds. Elided lines and items are not counted, as that needs the whole
+    skeleton; use `lop_stats` for them.
+
+    Args:
+        file_path: Path to the file
+        writer: Text or binary stream the skeleton is written to, UTF-8 encoded when
+            binary
+        options: Extraction options (defaults to `LopperOptions()`)
+
+    Returns:
+        Statistics of the file and its skeleton, without `elided_lines`, `symbols`
+        and `elided`
+
+    Raises:
+        FileNotFoundError: If file does not exist
+        UnsupportedLanguageError: If file language is not supported
+        InvalidUtf8Error: If the file is not valid UTF-8; the skeleton may be
+            partially written
+    """
+    text = isinstance(writer, io.TextIOBase)
+    output_bytes = output_chars = output_breaks = 0
+    for piece in stream_skeleton(file_path, options=options):
+        encoded = piece.encode()
+        writer.write(piece if text else encoded)  # type: ignore[arg-type]
+        output_bytes += len(encoded)
+        output_chars += len(piece)
+        output_breaks += piece.count("\n")
+
+    original_bytes = original_chars = original_breaks = 0
+    last = b""
+    with Path(file_path).open("rb") as file:
+        while chunk := file.read(_MEASURE_CHUNK_SIZE):
+            original_bytes += len(chunk)
+            original_chars += len(chunk.translate(None, _CONTINUATION_BYTES))
+            original_breaks += chunk.count(b"\n")
+            last = chunk[-1:]
+    return LopStats(
+        original_bytes=original_bytes,
+        original_lines=original_breaks + (last not in (b"", b"\n")),
+        original_tokens=-(-original_chars // 4),
+        output_bytes=output_bytes,
+        output_lines=output_breaks + (output_bytes > 0),
+        output_tokens=-(-output_chars // 4),
+    )
+
+
+def _measure(
+    source_code: str,
+    language: str,
+    *,
+    options: LopperOptions | None,
+    estimator: TokenEstimator,
+) -> tuple[ExtractionResult, LopStats]:
+    """Extract a skeleton and measure it, returning the result with its statistics."""
+    extractor = get_extractor(language, options)
+    source = source_code.encode()
+    tree = extractor.parse(source)
+    line_starts, placeholders, pending = extractor.segments(source, tree=tree)
+    segments = list(pending)
+    rendered = extractor.render_segments(iter(segments), source, line_starts)
+    skeleton = b"".join(rendered).decode().rstrip()
+    diagnostics = [ParseDiagnostic.from_node(node) for node in syntax_error_nodes(tree.root_node)]
+    source_map = SourceMap.from_segments(
+        segments,
+        {start: end for start, end, _ in placeholders},
+        line_starts,
+        count_lines(source, line_starts),
+    )
+
+    symbols: Counter[str] = Counter()
+    elided: Counter[str] = Counter()
+    if extractor.config.symbol_query:
+        removed = _removed_ranges(segments, source)
+        pairs = symbol_nodes(extractor, tree, source)
+        spans = {id(symbol): item_span(node, source, line_starts) for symbol, node in pairs}
+        for symbol, _ in pairs:
+            symbols[symbol.kind] += 1
+            nested = sorted(spans[id(child)] for child in symbol.children)
+            start, end = spans[id(symbol)]
+            if any(
+                _has_code(source, max(start, low), min(end, high), nested)
+                for low, high in removed
+                if low < end and high > start
+            ):
+                elided[symbol.kind] += 1
+
+    stats = LopStats(
+        original_bytes=len(source),
+        original_lines=len(source_code.splitlines()),
+        original_tokens=estimator(source_code),
+        output_bytes=len(skeleton.encode()),
+        output_lines=len(skeleton.splitlines()),
+        output_tokens=estimator(skeleton),
+        elided_lines=sum(last - first + 1 for first, last in source_map.elided),
+        symbols=dict(sorted(symbols.items())),
+        elided=dict(sorted(elided.items())),
+    )
+    return ExtractionResult(skeleton, diagnostics, source_map), stats
+
+
+def _cached_stats(
+    source: str,
+    language: str,
+    options: LopperOptions | None,
+    estimator: TokenEstimator,
+    cache: LopCache | None,
+) -> LopStats:
+    """Measure a source, reading its statistics from the cache when they are there."""
+    # Other estimators have no stable fingerprint, like placeholder functions
+    if cache is None or estimator is not estimate_tokens:
+        return lop_stats(source, language, options=options, estimator=estimator)
+    if (cached := cache.get_stats(source, language, options)) is not None:
+        return LopStats(**cached)
+    result, stats = _measure(source, language, options=options, estimator=estimator)
+    cache.put(source, language, result, options, stats=asdict(stats))
+    return stats
+
+
+def _removed_ranges(
+    segments: Sequence[tuple[bytes, int, bool]], source: bytes
+) -> list[tuple[int, int]]:
+    """Return the byte ranges of code missing from a skeleton's segments."""
+    ranges: list[tuple[int, int]] = []
+    position = 0
+    for text, offset, is_source in [*segments, (b"", len(source), True)]:
+        if not is_source:
+            continue
+        if source[position:offset].strip():
+            ranges.append((position, offset))
+        position = max(position, offset + len(text))
+    return ranges
+
+
+def _has_code(source: bytes, start: int, end: int, nested: list[tuple[int, int]]) -> bool:
+    """Tell whether a byte range holds code outside the nested item spans."""
+    position = start
+    for low, high in [*nested, (end, end)]:
+        if low > position and source[position : min(low, end)].strip():
+            return True
+        position = max(position, high)
+        if position >= end:
+            return False
+    return False
+
+
+@dataclass(frozen=True)
+class StatsReport:
+    """Statistics of several files, with the files that could not be measured.
+
+    Attributes:
+        files: (path, statistics) pairs of measured files, in input order
+        skipped: Paths of binary files and files in unsupported languages
+        failed: (path, error message) pairs of files that could not be processed
+        languages: Language of each measured file, by path
+    """
+
+    files: list[tuple[str, LopStats]] = field(default_factory=list)
+    skipped: list[str] = field(default_factory=list)
+    failed: list[tuple[str, str]] = field(default_factory=list)
+    languages: dict[str, str] = field(default_factory=dict)
+
+    @property
+    def total(self) -> LopStats:
+        """Statistics summed over the measured files."""
+        return sum((stats for _, stats in self.files), LopStats())
+
+    @property
+    def by_language(self) -> dict[str, tuple[int, LopStats]]:
+        """Number of measured files and their summed statistics, by language.
+
+        Files of unknown language are counted under "unknown".
+        """
+        totals: dict[str, tuple[int, LopStats]] = {}
+        for path, stats in self.files:
+            language = self.languages.get(path, "unknown")
+            count, total = totals.get(language, (0, LopStats()))
+            totals[language] = (count + 1, total + stats)
+        return dict(sorted(totals.items()))
+
+    def census(self) -> str:
+        """Render the lines and items of the measured files as a table, one row per language.
+
+        Items are counted by symbol kind, nested ones included, and a total row
+        follows when there are several languages.
+        """
+        rows: list[tuple[str, int, LopStats]] = [
+            (language, count, stats) for language, (count, stats) in self.by_language.items()
+        ]
+        if len(rows) > 1:
+            rows.append(("total", len(self.files), self.total))
+        header = ("language", "files", "lines", "elided", "items")
+        cells: list[tuple[str, ...]] = [header]
+        for language, count, stats in rows:
+            items = ", ".join(f"{kind}: {number}" for kind, number in stats.symbols.items())
+            cells.append(
+                (
+                    language,
+                    str(count),
+                    str(stats.original_lines),
+                    str(stats.elided_lines),
+                    items or "-",
+                )
+            )
+        return _aligned(cells, left=(0, 4))
+
+    def table(self) -> str:
+        """Render the statistics as a table, one row per file and a total row.
+
+        Skipped and failed files are listed below the table.
+        """
+        rows: list[tuple[str, LopStats]] = list(self.files)
+        if len(rows) > 1:
+            rows.append(("total", self.total))
+        header = ("path", "lines", "bytes", "tokens", "saved", "elided")
+        cells: list[tuple[str, ...]] = [header]
+        for path, stats in rows:
+            elided = ", ".join(f"{kind}: {count}" for kind, count in stats.elided.items())
+            cells.append(
+                (
+                    path,
+                    f"{stats.original_lines} -> {stats.output_lines}",
+                    f"{stats.original_bytes} -> {stats.output_bytes}",
+                    f"{stats.original_tokens} -> {stats.output_tokens}",
+                    f"{1 - stats.ratio:.0%}",
+                    elided or "-",
+                )
+            )
+        lines = [_aligned(cells, left=(0, 5))]
+        if self.skipped:
+            lines.append(f"skipped: {', '.join(self.skipped)}")
+        lines.extend(f"failed: {path} ({error})" for path, error in self.failed)
+        return "\n".join(lines)
+
+
+def _aligned(cells: list[tuple[str, ...]], *, left: tuple[int, ...]) -> str:
+    """Render rows of cells as columns, left-aligning the given columns."""
+    widths = [max(len(row[column]) for row in cells) for column in range(len(cells[0]))]
+    return "\n".join(
+        "  ".join(
+            cell.ljust(width) if column in left else cell.rjust(width)
+            for column, (cell, width) in enumerate(zip(row, widths))
+        ).rstrip()
+        for row in cells
+    )
+
+
+def collect_stats(
+    files: Sequence[str | Path],
+    *,
+    root: str | Path | None = None,
+    options: LopperOptions | None = None,
+    estimator: TokenEstimator = estimate_tokens,
+    cache: LopCache | None = None,
+) -> StatsReport:
+    """Measure the skeletons of several files.
+
+    Binary files, host documents (Markdown, HTML, Vue and Svelte files), notebooks
+    and files in unsupported languages are skipped, and files that fail to process
+    are recorded instead of aborting the run, so the total only covers the measured
+    files.
+
+    Args:
+        files: Paths of the files to measure
+        root: Directory the paths are relative to (defaults to the working directory)
+        options: Extraction options (defaults to LopperOptions())
+        estimator: Token estimator (defaults to a characters / 4 heuristic)
+        cache: Cache reusing the statistics of files unchanged since an earlier run
+            (see `LopCache`), and storing the others with their skeletons; only the
+            default estimator's statistics are cached
+
+    Returns:
+        Report with the statistics of each measured file
+    """
+    root_path = Path(root) if root is not None else Path()
+    report = StatsReport()
+    for path in files:
+        full_path = root_path / path
+        try:
+            if full_path.is_file() and is_binary(str(full_path)):
+                report.skipped.append(str(path))
+                continue
+            source, language = read_source(full_path)
+            if language not in LANGUAGE_CONFIGS:
+                report.skipped.append(str(path))
+                continue
+            stats = _cached_stats(source, language, options, estimator, cache)
+        except Exception as e:
+            report.failed.append((str(path), f"{type(e).__name__}: {e}"))
+        else:
+            report.files.append((str(path), stats))
+            report.languages[str(path)] = language
+    return report
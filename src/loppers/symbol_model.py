@@ -0,0 +1,478 @@
+"""The symbol model: `Symbol` trees and their collection from syntax trees.
+
+The extractor filters, orders and budgets items by their symbols, so this module
+sits below it and only uses the extractor it is handed.
+"""
+
+from __future__ import annotations
+
+import re
+from collections.abc import Mapping, Sequence
+from dataclasses import asdict, dataclass, field
+from fnmatch import fnmatchcase
+from typing import TYPE_CHECKING, Any
+
+from tree_sitter import Node, QueryCursor, Tree
+
+from loppers.errors import UnsupportedLanguageError
+
+if TYPE_CHECKING:
+    from loppers.loppers import SkeletonExtractor
+
+# Symbol kinds whose nested functions are reported as methods
+CONTAINER_KINDS: frozenset[str] = frozenset(
+    {"class", "impl", "trait", "interface", "struct", "enum", "module"}
+)
+
+# Modules that only scope their items, whose functions stay functions (Rust `mod`,
+# C++ and PHP namespaces)
+_NAMESPACE_TYPES: frozenset[str] = frozenset({"mod_item", "namespace_definition"})
+
+# Symbol kinds of the types `impl` blocks are grouped under
+IMPL_TARGET_KINDS: frozenset[str] = frozenset({"struct", "enum", "union"})
+
+# Visibility keywords recognized in signatures of languages with access modifiers
+_VISIBILITY_KEYWORD = re.compile(r"\b(public|private|protected|internal|fileprivate|open)\b")
+_RUST_VISIBILITY = re.compile(r"pub(\s*\([^)]*\))?")
+
+# Nodes between a Rust item and the attributes above it
+_RUST_TRIVIA_TYPES: frozenset[str] = frozenset({"attribute_item", "line_comment", "block_comment"})
+
+# Qualifier keywords reported as symbol flags
+_QUALIFIERS: frozenset[str] = frozenset({"async", "unsafe", "const", "extern"})
+
+# Nodes grouping the qualifiers of a definition (Rust function modifiers, C/C++ storage
+# classes, C# and Kotlin modifiers)
+_QUALIFIER_GROUP_TYPES: frozenset[str] = frozenset(
+    {"function_modifiers", "extern_modifier", "storage_class_specifier", "modifiers", "modifier"}
+)
+
+# Blocks whose items are all declared extern (`extern "C" { ... }`)
+_EXTERN_BLOCK_TYPES: frozenset[str] = frozenset({"foreign_mod_item", "linkage_specification"})
+
+# Name of the symbols of closures, lambdas and anonymous functions
+CLOSURE_NAME: str = "<closure>"
+
+# Nodes naming the anonymous function they hold, which is reported as a named symbol
+_NAMING_PARENT_TYPES: frozenset[str] = frozenset(
+    {"variable_declarator", "field_definition", "public_field_definition"}
+)
+
+
+@dataclass
+class Symbol:
+    """A named definition extracted from source code.
+
+    Symbols form a tree: items defined inside another item (methods in a class or
+    `impl` block, a function inside a function) are listed in its `children`, and
+    name it as their `parent`. Anonymous items such as closures, lambdas and
+    anonymous functions are only reported when asked for (see `extract_symbols`),
+    as "closure" symbols named `<closure>`.
+
+    Attributes:
+        kind: Symbol kind (e.g., "function", "method", "class", "struct", "impl")
+        name: Symbol name (the implemented type for Rust `impl` blocks)
+        signature: Definition text up to (excluding) its body
+        visibility: Declared visibility (e.g., "pub", "pub(crate)", "private", "public",
+            "protected"), derived from the language's convention when implicit; None
+            when the language has no notion of it or it does not apply
+        parent: Name of the enclosing symbol, or None for top-level symbols; Go
+            methods name their receiver's type
+        qualified_name: Names of the enclosing symbols and this one, joined by the
+            language's path separator and without generic arguments
+            (`outer::inner::run` for a function in nested Rust modules,
+            `Calculator::add` for an `impl` item, `Square.Area` for a Go method), to
+            tell same-named symbols of different modules apart
+        trait: Trait implemented by a Rust `impl` block, set on the block and its
+            items (e.g. "Arithmetic" for `impl Arithmetic for Calculator`), so items
+            grouped under their type (see `extract_symbols`) keep their origin
+        is_async: Declared `async`
+        is_unsafe: Declared `unsafe` (Rust functions, impl blocks and traits)
+        is_const: Declared `const` (Rust `const fn` and constants)
+        is_extern: Declared `extern`, or inside an `extern` block
+        start_line: First line of the definition (1-based)
+        end_line: Last line of the definition (1-based, inclusive)
+        start_column: Column of the first byte of the definition (0-based, in bytes)
+        end_column: Column just past the last byte of the definition (0-based, in bytes)
+        start_byte: Byte offset of the definition in the UTF-8 encoded source, as
+            tree-sitter reports it; the signature starts there too
+        end_byte: Byte offset just past the end of the definition
+        signature_end_byte: Byte offset just past the end of the signature, so
+            `start_byte..signature_end_byte` is the range of `signature` in the source
+        children: Symbols nested inside this definition, in source order
+    """
+
+    kind: str
+    name: str
+    signature: str
+    start_line: int
+    end_line: int
+    visibility: str | None = None
+    parent: str | None = None
+    qualified_name: str = ""
+    trait: str | None = None
+    is_async: bool = False
+    is_unsafe: bool = False
+    is_const: bool = False
+    is_extern: bool = False
+    start_column: int = 0
+    end_column: int = 0
+    start_byte: int = 0
+    end_byte: int = 0
+    signature_end_byte: int = 0
+    children: list[Symbol] = field(default_factory=list)
+
+    def to_dict(self) -> dict[str, Any]:
+        """Convert the symbol and its children to plain dictionaries."""
+        return asdict(self)
+
+    @classmethod
+    def from_dict(cls, data: Mapping[str, Any]) -> Symbol:
+        """Build a symbol and its children from the dictionaries of `to_dict`.
+
+        Args:
+            data: Symbol fields, with children as nested dictionaries
+
+        Returns:
+            The symbol, equal to the one the dictionaries were made from
+
+        Raises:
+            ValueError: If a field is missing, unknown or not a dictionary
+        """
+        if not isinstance(data, Mapping):
+            raise ValueError(f"Invalid symbol: expected an object, got {data!r}")
+        fields = dict(data)
+        children = [cls.from_dict(child) for child in fields.pop("children", [])]
+        try:
+            return cls(**fields, children=children)
+        except TypeError as e:
+            raise ValueError(f"Invalid symbol: {e}") from e
+
+
+def symbol_nodes(
+    extractor: SkeletonExtractor, tree: Tree, source: bytes, *, closures: bool = False
+) -> list[tuple[Symbol, Node]]:
+    """Collect the symbols of a parsed tree together with their definition nodes.
+
+    Args:
+        extractor: Extractor of the tree's language (must have a symbol query)
+        tree: Parsed syntax tree
+        source: Encoded source code the tree was parsed from
+        closures: Also collect the anonymous functions of the language's closure
+            query, as "closure" symbols
+
+    Returns:
+        (symbol, definition node) pairs of all symbols, nested ones included, in
+        source order; symbols are already nested into their parents' children
+
+    Raises:
+        UnsupportedLanguageError: If the language has no symbol query
+    """
+    language = extractor.language
+    if not extractor.config.symbol_query:
+        msg = f"Symbol extraction not supported for language '{language}'"
+        raise UnsupportedLanguageError(msg, language=language)
+    cursor = QueryCursor(extractor.query(extractor.config.symbol_query))
+    verbatim = extractor.verbatim_spans(tree)
+
+    # Collect one entry per definition node
+    found: dict[tuple[int, int], tuple[Symbol, Node]] = {}
+    for _, match in cursor.matches(tree.root_node):
+        for capture_name, nodes in match.items():
+            if not capture_name.startswith("definition."):
+                continue
+            node = nodes[0]
+            key = (node.start_byte, node.end_byte)
+            if key in found or "name" not in match or _within(verbatim, key):
+                continue
+            body = match["body"][0] if "body" in match else None
+            qualifiers = _qualifiers(node, match["name"][0])
+            symbol = Symbol(
+                kind=capture_name.removeprefix("definition."),
+                name=_node_text(match["name"][0], source),
+                signature=signature_text(node, source, body=body),
+                start_line=node.start_point[0] + 1,
+                end_line=node.end_point[0] + 1,
+                is_async="async" in qualifiers,
+                is_unsafe="unsafe" in qualifiers,
+                is_const="const" in qualifiers,
+                is_extern="extern" in qualifiers,
+                start_column=node.start_point[1],
+                end_column=node.end_point[1],
+                start_byte=node.start_byte,
+                end_byte=node.end_byte,
+                signature_end_byte=_signature_end(node, source, body=body),
+            )
+            found[key] = (symbol, node)
+    if closures and extractor.config.closure_query:
+        closure_cursor = QueryCursor(extractor.query(extractor.config.closure_query))
+        for _, match in closure_cursor.matches(tree.root_node):
+            node = match["closure"][0]
+            key = (node.start_byte, node.end_byte)
+            named = node.parent is not None and node.parent.type in _NAMING_PARENT_TYPES
+            if key in found or named:
+                continue
+            body = match["body"][0]
+            closure = Symbol(
+                kind="closure",
+                name=CLOSURE_NAME,
+                signature=signature_text(node, source, body=body),
+                start_line=node.start_point[0] + 1,
+                end_line=node.end_point[0] + 1,
+                start_column=node.start_point[1],
+                end_column=node.end_point[1],
+                start_byte=node.start_byte,
+                end_byte=node.end_byte,
+                signature_end_byte=_signature_end(node, source, body=body),
+            )
+            found[key] = (closure, node)
+
+    # Nest symbols by containment of their definition nodes
+    ordered: list[tuple[Symbol, Node]] = []
+    stack: list[tuple[int, Symbol, Node]] = []
+    for (start, end), (symbol, node) in sorted(
+        found.items(), key=lambda item: (item[0][0], -item[0][1])
+    ):
+        while stack and stack[-1][0] <= start:
+            stack.pop()
+        parent, parent_node = (stack[-1][1], stack[-1][2]) if stack else (None, None)
+        if parent is not None:
+            if (
+                symbol.kind == "function"
+                and parent.kind in CONTAINER_KINDS
+                and parent_node is not None
+                and parent_node.type not in _NAMESPACE_TYPES
+            ):
+                symbol.kind = "method"
+            symbol.parent = parent.name
+            if parent.kind == "impl":
+                symbol.trait = parent.trait
+            parent.children.append(symbol)
+        trait = node.child_by_field_name("trait") if symbol.kind == "impl" else None
+        if trait is not None:
+            symbol.trait = _node_text(trait, source)
+        if node.type == "method_declaration" and language == "go":
+            # Go methods are declared apart from their type; attribute them to it
+            symbol.parent = _receiver_type(node, source)
+        # Closures keep their `<closure>` name, which is no generic argument
+        name = base_name(symbol.name) or symbol.name
+        if parent is not None:
+            name = f"{parent.qualified_name}{extractor.config.path_separator}{name}"
+        elif symbol.parent:
+            name = f"{base_name(symbol.parent)}{extractor.config.path_separator}{name}"
+        symbol.qualified_name = name
+        symbol.visibility = _visibility(symbol, language, parent, parent_node, node)
+        ordered.append((symbol, node))
+        stack.append((end, symbol, node))
+    return ordered
+
+
+def match_symbols(symbols: Sequence[Symbol], pattern: str) -> list[Symbol]:
+    """Find the symbols matching a name, a qualified path or a glob, nested ones included.
+
+    Patterns are paths whose segments are separated by `::` or `.`, matched against
+    the end of each symbol's path: `fibonacci` matches every symbol with that name,
+    and `Calculator::add` the `add` items of both the inherent and the trait `impl`
+    blocks of `Calculator` (`<Calculator as Arithmetic>::add` only those of the
+    trait's). Segments may hold glob wildcards (`test_*`). Matching is case-sensitive
+    and ignores generic arguments.
+
+    Args:
+        symbols: Symbol outline to search
+        pattern: Name, qualified path or glob pattern
+
+    Returns:
+        Matching symbols in source order, nested ones included
+    """
+    segments = path_segments(pattern)
+    matches: list[Symbol] = []
+
+    def visit(symbol: Symbol, path: list[tuple[str, str | None]]) -> None:
+        path = [*path, (symbol.name, symbol.trait)]
+        tail = path[-len(segments) :]
+        if len(tail) == len(segments) and all(
+            fnmatchcase(base_name(name), expected)
+            and (trait_name is None or squash_whitespace(trait or "") == trait_name)
+            for (name, trait), (expected, trait_name) in zip(tail, segments)
+        ):
+            matches.append(symbol)
+        for child in symbol.children:
+            visit(child, path)
+
+    if segments:
+        for symbol in symbols:
+            # Go methods are top-level, but named after their receiver's type
+            visit(symbol, [(symbol.parent, None)] if symbol.parent else [])
+    return matches
+
+
+def symbol_key(symbol: Symbol) -> tuple[str, str, int]:
+    """Return the key ordering symbols alphabetically, with ties broken by position."""
+    return symbol.name.casefold(), symbol.name, symbol.start_byte
+
+
+def signature_text(node: Node, source: bytes, *, body: Node | None = None) -> str:
+    """Return the text of a definition up to its body.
+
+    This is the signature `extract_symbols` reports, for a node parsed by the
+    caller: for a function or method, the text from its start through its
+    parameter list and return type. Declarations without a body, such as trait
+    methods ending in `;`, give their whole text without the `;`.
+
+    Args:
+        node: Definition node
+        source: Encoded source code the node was parsed from
+        body: Body node; defaults to the node's `body` field
+
+    Returns:
+        Definition text without its body and trailing `:`/`;` punctuation
+    """
+    return source[node.start_byte : _signature_end(node, source, body=body)].decode(
+        errors="replace"
+    )
+
+
+def _signature_end(node: Node, source: bytes, *, body: Node | None = None) -> int:
+    """Return the byte offset just past the signature of a definition (see `signature_text`).
+
+    The signature ends where the body node of the syntax tree starts, so braces and
+    arrows in a return type (`-> impl Fn() -> i32`, `-> [u8; { N + 1 }]`) are kept.
+    """
+    if body is None:
+        body = node.child_by_field_name("body")
+    end = body.start_byte if body is not None else node.end_byte
+    return node.start_byte + len(source[node.start_byte : end].rstrip().rstrip(b";:").rstrip())
+
+
+def root_symbols(pairs: Sequence[tuple[Symbol, Node]]) -> list[Symbol]:
+    """Return the symbols not nested in another one, in source order.
+
+    Go methods name their receiver's type as parent, yet are top-level symbols.
+    """
+    nested = {id(child) for symbol, _ in pairs for child in symbol.children}
+    return [symbol for symbol, _ in pairs if id(symbol) not in nested]
+
+
+def _receiver_type(node: Node, source: bytes) -> str | None:
+    """Return the base type name of a Go method's receiver (`Stack` for `(s *Stack[T])`)."""
+    receiver = node.child_by_field_name("receiver")
+    parameter = receiver.named_children[0] if receiver and receiver.named_children else None
+    receiver_type = parameter.child_by_field_name("type") if parameter else None
+    pending: list[Node] = [receiver_type] if receiver_type else []
+    while pending:
+        current = pending.pop()
+        if current.type == "type_identifier":
+            return _node_text(current, source)
+        pending.extend(reversed(current.named_children))
+    return None
+
+
+def _qualifiers(node: Node, name: Node) -> set[str]:
+    """Collect the qualifier keywords written before the name of a definition."""
+    found: set[str] = set()
+    pending: list[Node] = [child for child in node.children if child.end_byte <= name.start_byte]
+    while pending:
+        child = pending.pop()
+        if child.type in _QUALIFIERS:
+            found.add(child.type)
+        elif child.type in _QUALIFIER_GROUP_TYPES:
+            pending.extend(child.children)
+    ancestor: Node | None = node.parent
+    while ancestor is not None:
+        if ancestor.type in _EXTERN_BLOCK_TYPES:
+            found.add("extern")
+        ancestor = ancestor.parent
+    return found
+
+
+def _visibility(
+    symbol: Symbol, language: str, parent: Symbol | None, parent_node: Node | None, node: Node
+) -> str | None:
+    """Determine the visibility of a symbol from its signature and language rules."""
+    if symbol.kind == "closure":
+        # Closures are values, not declarations
+        return None
+    if language == "rust":
+        if symbol.kind == "impl":
+            return None
+        if symbol.kind == "macro":
+            # `macro_rules!` macros are textually scoped unless exported from the crate
+            return "pub" if _is_exported_macro(node) else "private"
+        declared = _RUST_VISIBILITY.match(symbol.signature)
+        if declared:
+            return re.sub(r"\s+", "", declared.group(0))
+        # Trait items and trait impl items are as visible as the trait itself
+        if parent is not None and (
+            parent.kind == "trait"
+            or (
+                parent.kind == "impl"
+                and parent_node is not None
+                and parent_node.child_by_field_name("trait") is not None
+            )
+        ):
+            return "pub"
+        return "private"
+    if language == "go":
+        return "public" if symbol.name[:1].isupper() else "private"
+    if language == "python":
+        is_dunder = symbol.name.startswith("__") and symbol.name.endswith("__")
+        return "private" if symbol.name.startswith("_") and not is_dunder else "public"
+
+    # ECMAScript private members (`#secret`)
+    if symbol.name.startswith("#"):
+        return "private"
+    # Access modifiers written before the name (Java, C#, PHP, Kotlin, Swift, TypeScript)
+    name_index = symbol.signature.find(symbol.name)
+    header = symbol.signature[:name_index] if name_index >= 0 else symbol.signature
+    declared = _VISIBILITY_KEYWORD.search(header)
+    return declared.group(1) if declared else None
+
+
+def _is_exported_macro(node: Node) -> bool:
+    """Tell whether a Rust macro definition is marked `#[macro_export]`."""
+    sibling: Node | None = node.prev_named_sibling
+    while sibling is not None and sibling.type in _RUST_TRIVIA_TYPES:
+        if sibling.type == "attribute_item" and sibling.text and b"macro_export" in sibling.text:
+            return True
+        sibling = sibling.prev_named_sibling
+    return False
+
+
+def path_segments(path: str) -> list[tuple[str, str | None]]:
+    """Split a qualified path into (name, trait) segments."""
+    segments: list[tuple[str, str | None]] = []
+    rest = path.strip()
+    if rest.startswith("<"):
+        # `<Type as Trait>` head, whose parts may have generic arguments of their own
+        depth = 0
+        for index, char in enumerate(rest):
+            depth += {"<": 1, ">": -1}.get(char, 0)
+            if depth == 0:
+                break
+        else:
+            return []
+        type_name, _, trait = " ".join(rest[1:index].split()).partition(" as ")
+        segments.append((base_name(type_name), squash_whitespace(trait) or None))
+        rest = rest[index + 1 :].removeprefix("::")
+    segments.extend((base_name(name), None) for name in re.split(r"::|\.", rest) if name)
+    return segments if all(name for name, _ in segments) else []
+
+
+def squash_whitespace(text: str) -> str:
+    """Return text without whitespace, for comparing type expressions."""
+    return re.sub(r"\s+", "", text)
+
+
+def base_name(name: str) -> str:
+    """Return a symbol name without its generic arguments."""
+    return name.partition("<")[0].strip()
+
+
+def _within(ranges: list[tuple[int, int]], span: tuple[int, int]) -> bool:
+    """Tell whether a byte range lies inside one of the given ranges."""
+    return any(start <= span[0] and span[1] <= end for start, end in ranges)
+
+
+def _node_text(node: Node, source: bytes) -> str:
+    """Return the source text of a node."""
+    return source[node.start_byte : node.end_byte].decode(errors="replace")
@@ -0,0 +1,295 @@
+"""Structured symbol outline extraction.
+
+Symbols describe the named definitions of a source file (classes, functions,
+methods, traits, ...) as a tree mirroring their nesting in the source. The model
+itself lives in `loppers.symbol_model`; this module extracts outlines from source
+code and searches, sorts and serializes them.
+"""
+
+from __future__ import annotations
+
+import hashlib
+import json
+from collections.abc import Iterator, Sequence
+from dataclasses import dataclass, replace
+from pathlib import PurePath
+
+from loppers.loppers import (
+    LopperOptions,
+    get_extractor,
+    is_inside,
+    item_span,
+    line_starts_of,
+    merge_ranges,
+)
+from loppers.symbol_model import (
+    IMPL_TARGET_KINDS,
+    Symbol,
+    base_name,
+    path_segments,
+    root_symbols,
+    squash_whitespace,
+    symbol_key,
+    symbol_nodes,
+)
+
+@dataclass
+class ItemSource:
+    """The complete source of one item.
+
+    Attributes:
+        symbol: Symbol of the item
+        text: Source text of the item, with its doc comments, attributes and
+            decorators, on whole lines
+        start_line: First line of the text (1-based)
+        end_line: Last line of the text (1-based, inclusive)
+    """
+
+    symbol: Symbol
+    text: str
+    start_line: int
+    end_line: int
+
+
+def extract_symbols(
+    source: str,
+    language: str,
+    *,
+    group_impls: bool = False,
+    keep_closures: bool = False,
+    options: LopperOptions | None = None,
+) -> list[Symbol]:
+    """Extract the symbol outline of source code.
+
+    Args:
+        source: Source code to process
+        language: Programming language identifier
+        group_impls: List the items of Rust `impl` blocks, inherent and trait ones,
+            as children of the type they implement, after its own children; their
+            `trait` tells which trait each comes from. Blocks implementing a type not
+            defined in the same scope (or in this source) stay standalone
+        keep_closures: List closures, lambdas, arrow functions and other anonymous
+            functions as "closure" symbols, children of the function they are
+            written in, with their parameters as signature (`|x|`, `(a, b) =>`).
+            Anonymous functions assigned to a variable or field are still reported
+            under that name. Languages without a closure query list none
+        options: Extraction options; `skip_tests` leaves test items out, with the
+            symbols nested in them: Rust items marked `#[test]` or `#[bench]` or
+            compiled only for tests (`#[cfg(test)] mod tests`), Python `test_`
+            functions and `TestCase` classes, Go `Test`, `Benchmark`, `Fuzz` and
+            `Example` functions (the items `strip_tests` removes from skeletons).
+            Languages without a test query keep every symbol
+
+    Returns:
+        Top-level symbols in source order, with nested symbols as children
+
+    Raises:
+        UnsupportedLanguageError: If the language is not supported or has no symbol query
+    """
+    extractor = get_extractor(language, options)
+    source_bytes = source.encode()
+    tree = extractor.parse(source)
+    pairs = symbol_nodes(extractor, tree, source_bytes, closures=keep_closures)
+    if extractor.options.skip_tests:
+        tests = merge_ranges(
+            [(node.start_byte, node.end_byte) for node in extractor.test_nodes(tree, source_bytes)]
+        )
+        dropped = {
+            id(symbol)
+            for symbol, node in pairs
+            if is_inside(tests, node.start_byte, node.end_byte)
+        }
+        pairs = [(symbol, node) for symbol, node in pairs if id(symbol) not in dropped]
+        for symbol, _ in pairs:
+            symbol.children = [child for child in symbol.children if id(child) not in dropped]
+    symbols = root_symbols(pairs)
+    return _grouped_impls(symbols) if group_impls else symbols
+
+
+def is_test_file(path: str | PurePath) -> bool:
+    """Tell whether a file holds tests only, as those of JS/TS `__tests__` directories.
+
+    Args:
+        path: Path of the file
+
+    Returns:
+        True if a directory of the path is named `__tests__`
+    """
+    return "__tests__" in PurePath(path).parts[:-1]
+
+
+def extract_item(source: str, language: str, path: str) -> ItemSource | None:
+    """Extract the full, unpruned source of the item at a qualified path.
+
+    Path segments are separated by `::` or `.` (`Calculator::process`,
+    `Widget.render`) and resolved from the top-level symbols down. Rust trait
+    implementations are selected with `<Type as Trait>::item`; an unqualified type
+    prefers its inherent `impl` items. Generic arguments are ignored when matching
+    names, and the first match in source order wins.
+
+    Args:
+        source: Source code to search
+        language: Programming language identifier
+        path: Qualified path of the item
+
+    Returns:
+        Source of the item, or None if the path does not resolve
+
+    Raises:
+        UnsupportedLanguageError: If the language is not supported or has no symbol query
+    """
+    segments = path_segments(path)
+    if not segments:
+        return None
+    extractor = get_extractor(language)
+    source_bytes = source.encode()
+    pairs = symbol_nodes(extractor, extractor.parse(source_bytes), source_bytes)
+    nodes = {id(symbol): node for symbol, node in pairs}
+
+    candidates: list[Symbol] = root_symbols(pairs)
+    for index, (name, trait) in enumerate(segments):
+        if index > 0:
+            candidates = [child for symbol in candidates for child in symbol.children]
+        candidates = [
+            symbol
+            for symbol in candidates
+            if base_name(symbol.name) == name
+            and (trait is None or squash_whitespace(symbol.trait or "") == trait)
+        ]
+        # Inherent items shadow trait items, as in Rust's own path resolution
+        candidates.sort(key=lambda symbol: symbol.trait is not None)
+    if not candidates:
+        return None
+
+    symbol = candidates[0]
+    line_starts = line_starts_of(source_bytes)
+    start, end = item_span(nodes[id(symbol)], source_bytes, line_starts)
+    text = source_bytes[start:end].decode(errors="replace").rstrip("\r\n")
+    start_line = source_bytes.count(b"\n", 0, start) + 1
+    return ItemSource(symbol, text, start_line, start_line + text.count("\n"))
+
+
+def find_symbols(symbols: Sequence[Symbol], name: str) -> list[Symbol]:
+    """Find the symbols with a given name or qualified name, nested ones included.
+
+    Args:
+        symbols: Symbol outline to search (e.g. from `extract_symbols`)
+        name: Exact symbol name, or exact qualified name (`outer::inner::run`)
+
+    Returns:
+        Matching symbols in source order; use their `qualified_name`, `trait` and
+        line span to tell same-named symbols apart
+    """
+    return [
+        symbol
+        for symbol in iter_symbols(symbols)
+        if name in (symbol.name, symbol.qualified_name)
+    ]
+
+
+def find_matching_symbols(symbols: Sequence[Symbol], pattern: str) -> list[Symbol]:
+    """Find the symbols whose name contains a pattern, ignoring case.
+
+    Args:
+        symbols: Symbol outline to search
+        pattern: Substring to look for in symbol names
+
+    Returns:
+        Matching symbols in source order, nested ones included
+    """
+    folded = pattern.casefold()
+    return [symbol for symbol in iter_symbols(symbols) if folded in symbol.name.casefold()]
+
+
+def iter_symbols(symbols: Sequence[Symbol]) -> Iterator[Symbol]:
+    """Iterate over symbols and their nested symbols, depth-first in source order.
+
+    Args:
+        symbols: Symbol outline to walk
+
+    Yields:
+        Each symbol, followed by its children
+    """
+    for symbol in symbols:
+        yield symbol
+        yield from iter_symbols(symbol.children)
+
+
+def sort_symbols(symbols: Sequence[Symbol]) -> list[Symbol]:
+    """Sort symbols alphabetically by name, and the children of each in turn.
+
+    Names are compared regardless of case, then by case; same-named symbols keep
+    their source order. The symbols are copied rather than sorted in place.
+
+    Args:
+        symbols: Symbol outline to sort
+
+    Returns:
+        Copies of the symbols in alphabetical order, with their children sorted
+    """
+    return sorted(
+        (replace(symbol, children=sort_symbols(symbol.children)) for symbol in symbols),
+        key=symbol_key,
+    )
+
+
+def symbols_to_json(symbols: Sequence[Symbol], *, indent: int | None = 2) -> str:
+    """Serialize symbols to JSON.
+
+    Args:
+        symbols: Symbols to serialize
+        indent: JSON indentation (None for compact output)
+
+    Returns:
+        JSON array of symbol objects
+    """
+    return json.dumps([symbol.to_dict() for symbol in symbols], indent=indent)
+
+
+def symbols_from_json(text: str) -> list[Symbol]:
+    """Deserialize symbols serialized by `symbols_to_json`.
+
+    Args:
+        text: JSON array of symbol objects
+
+    Returns:
+        Symbols equal to the serialized ones, with their children
+
+    Raises:
+        ValueError: If the text is not a JSON array of symbol objects
+    """
+    data = json.loads(text)
+    if not isinstance(data, list):
+        raise ValueError("Invalid symbols: expected a JSON array")
+    return [Symbol.from_dict(item) for item in data]
+
+
+def source_hash(source: str) -> str:
+    """Hash source code, to tell whether symbols cached for it are still current.
+
+    Args:
+        source: Source code the symbols were extracted from
+
+    Returns:
+        Hexadecimal SHA-256 digest of the UTF-8 encoded source
+    """
+    return hashlib.sha256(source.encode()).hexdigest()
+
+
+def _grouped_impls(symbols: list[Symbol]) -> list[Symbol]:
+    """Move the items of `impl` blocks under the types they implement, scope by scope."""
+    types: dict[str, Symbol] = {}
+    for symbol in symbols:
+        if symbol.kind in IMPL_TARGET_KINDS:
+            types.setdefault(symbol.name, symbol)
+    grouped: list[Symbol] = []
+    for symbol in symbols:
+        symbol.children = _grouped_impls(symbol.children)
+        target = types.get(base_name(symbol.name)) if symbol.kind == "impl" else None
+        if target is None:
+            grouped.append(symbol)
+            continue
+        for child in symbol.children:
+            child.parent = target.name
+        target.children.extend(symbol.children)
+    return grouped
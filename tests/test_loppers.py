@@ -2,14 +2,134 @@
 
 from __future__ import annotations
 
+import argparse
+import ast
+import asyncio
+import contextlib
+import dataclasses
+import io
+import json
+import os
+import pickle
+import random
+import re
+import shutil
+import subprocess
 import tempfile
+import textwrap
+import threading
+import time
+import tracemalloc
 import unittest
 from pathlib import Path
+from typing import ClassVar
+from unittest import mock
 
+import tree_sitter_language_pack
 from binaryornot.check import is_binary
 
-from loppers import concatenate_files, extract_skeleton, find_files, get_skeleton
-from loppers.loppers import SkeletonExtractor
+from loppers import (
+    ANSI_COLORS,
+    EXTENSION_TO_LANGUAGE,
+    AsyncLopper,
+    BudgetReport,
+    ConfigError,
+    EmbeddedCode,
+    ExtractionResult,
+    IncrementalLopper,
+    InputEdit,
+    InvalidNotebookError,
+    InvalidQueryError,
+    InvalidUtf8Error,
+    LanguageConfig,
+    LopCache,
+    LopError,
+    LopResult,
+    LoppedFile,
+    LopperConfig,
+    LopperOptions,
+    LopperOptionsBuilder,
+    OutlineOptions,
+    ParseFailedError,
+    Symbol,
+    SymbolChanges,
+    SymbolNotFoundError,
+    UnsupportedLanguageError,
+    collect_stats,
+    concatenate_files,
+    detect_file_language,
+    detect_language,
+    diff_line_ranges,
+    estimate_tokens,
+    extract_item,
+    extract_skeleton,
+    extract_skeleton_result,
+    extract_symbols,
+    extract_with_regions,
+    find_config,
+    find_files,
+    find_matching_symbols,
+    find_symbols,
+    fit_files_to_budget,
+    fit_to_budget,
+    get_language,
+    get_skeleton,
+    get_skeletons,
+    get_skeletons_parallel,
+    highlight,
+    iter_symbols,
+    load_config,
+    lop,
+    lop_dir,
+    lop_file,
+    lop_notebook,
+    lop_stats,
+    lop_symbol,
+    lop_symbols,
+    lop_to_writer,
+    lop_with_focus,
+    markdown_code_blocks,
+    matches_pattern,
+    rank_symbols,
+    read_config,
+    register_language,
+    render_bundle,
+    render_ctags,
+    render_outline,
+    render_repo_map,
+    script_blocks,
+    serve,
+    signature_text,
+    skeletonize_diff,
+    skeletonize_with_query,
+    sniff_language,
+    sort_symbols,
+    source_hash,
+    stream_skeleton,
+    stream_skeleton_reader,
+    supported_languages,
+    symbols_from_json,
+    symbols_to_json,
+    walk_dir,
+)
+from loppers.cli import main, use_color
+from loppers.loppers import LANGUAGE_CONFIGS, SkeletonExtractor, get_extractor
+from loppers.symbols import is_test_file
+
+SAMPLE_RS: str = (Path(__file__).parent.parent / "examples" / "sample.rs").read_text()
+GENERICS_RS: str = (Path(__file__).parent.parent / "examples" / "generics.rs").read_text()
+MACROS_RS: str = """/// Builds a map.
+#[macro_export]
+macro_rules! hashmap {
+    ($($key:expr => $value:expr),* $(,)?) => {{
+        let mut map = ::std::collections::HashMap::new();
+        $(map.insert($key, $value);)*
+        map
+    }};
+}
+
+macro_rules! square { ($x:expr) => { $x * $x }; }
+"""
 
 
 class TestSkeletonExtractor(unittest.TestCase):
@@ -44,6 +164,270 @@ def process(
         self.assertIn("def process", skeleton)
         self.assertNotIn("result = {}", skeleton)
 
+    def test_python_decorators_and_nested_classes(self) -> None:
+        """Test Python decorators and class members keep their place and indentation."""
+        code: str = '''
+@functools.lru_cache(maxsize=None)
+def cached(x):
+    return x * 2
+
+
+class Service:
+    """A service."""
+
+    @property
+    def name(self) -> str:
+        return self._name
+
+    class Config:
+        def load(self):
+            return {}
+'''
+        skeleton: str = extract_skeleton(code, "python")
+        self.assertIn("@functools.lru_cache(maxsize=None)\ndef cached(x):", skeleton)
+        self.assertIn("    @property\n    def name(self) -> str:", skeleton)
+        self.assertIn("    class Config:\n        def load(self):", skeleton)
+        self.assertNotIn("return x * 2", skeleton)
+        self.assertNotIn("return self._name", skeleton)
+        self.assertNotIn("return {}", skeleton)
+
+    def test_rust_generic_signatures_complete(self) -> None:
+        """Test that generic parameters, lifetimes and where-clauses are kept verbatim."""
+        self.assertEqual(
+            extract_skeleton(GENERICS_RS, "rust"),
+            "//! Generic containers.\n"
+            "\n"
+            "use std::fmt::Debug;\n"
+            "\n"
+            "/// Returns the largest item.\n"
+            "pub fn largest<'a, T: PartialOrd + 'a>(items: &'a [T]) -> Option<&'a T> {\n"
+            "}\n"
+            "\n"
+            "pub fn describe<K, V, const N: usize>(pairs: [(K, V); N]) -> String\n"
+            "where\n"
+            "    K: Debug + Ord,\n"
+            "    V: Debug + Clone + Default,\n"
+            "{\n"
+            "}\n"
+            "\n"
+            "pub struct Cache<K, V>\n"
+            "where\n"
+            "    K: Eq + std::hash::Hash,\n"
+            "{\n"
+            "    entries: Vec<(K, V)>,\n"
+            "}\n"
+            "\n"
+            "impl<K, V> Cache<K, V>\n"
+            "where\n"
+            "    K: Eq + std::hash::Hash + Clone,\n"
+            "    V: Clone,\n"
+            "{\n"
+            "    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V>\n"
+            "    where\n"
+            "        K: std::borrow::Borrow<Q>,\n"
+            "        Q: Eq,\n"
+            "    {\n"
+            "    }\n"
+            "}\n"
+            "\n"
+            "pub trait Visitor<'de> {\n"
+            "    fn visit<T>(&mut self, value: T) -> Result<(), String>\n"
+            "    where\n"
+            "        T: Debug + 'de;\n"
+            "}",
+        )
+
+    def test_python_single_line_function(self) -> None:
+        """Test that a body on the same line as its def keeps the signature."""
+        code: str = "def double(x): return x * 2\n\ndef triple(x): return x * 3\n"
+        skeleton: str = extract_skeleton(code, "python")
+        self.assertIn("def double(x):", skeleton)
+        self.assertIn("def triple(x):", skeleton)
+        self.assertNotIn("return", skeleton)
+
+    def test_python_skeleton_is_valid_python(self) -> None:
+        """Test that elided Python bodies become `...` and the skeleton still parses."""
+        code: str = '''"""Module docstring."""
+
+import asyncio
+
+
+class Worker:
+    """A worker."""
+
+    retries: int = 3
+
+    @staticmethod
+    def create() -> "Worker":
+        return Worker()
+
+    @property
+    def busy(self) -> bool:
+        """Whether the worker is busy."""
+        return False
+
+    async def run(self, job):
+        # Run the job
+        await asyncio.sleep(0)
+
+
+def outer(x):
+    def inner(y):
+        return y + 1
+    return inner(x)
+'''
+        skeleton: str = extract_skeleton(code, "python")
+        ast.parse(skeleton)
+        self.assertTrue(skeleton.startswith('"""Module docstring."""\n\nimport asyncio'))
+        self.assertIn("    retries: int = 3", skeleton)
+        self.assertIn('    @staticmethod\n    def create() -> "Worker":\n        ...\n', skeleton)
+        self.assertIn(
+            '    def busy(self) -> bool:\n        """Whether the worker is busy."""\n\n', skeleton
+        )
+        self.assertIn("    async def run(self, job):\n        ...\n", skeleton)
+        self.assertTrue(skeleton.endswith("def outer(x):\n    ..."))
+        self.assertNotIn("inner", skeleton)
+
+    def test_python_sample_is_valid_python(self) -> None:
+        """Test that the skeleton of the Python sample parses as Python."""
+        sample = (Path(__file__).parent.parent / "examples" / "sample.py").read_text()
+        ast.parse(extract_skeleton(sample, "python"))
+
+    def test_typescript_declarations_kept(self) -> None:
+        """Test that TypeScript type declarations and imports survive while bodies go."""
+        code: str = """import { api } from "./api";
+
+export interface User {
+    id: string;
+    name?: string;
+}
+
+export type Id = string | number;
+
+enum Role {
+    Admin,
+    Guest,
+}
+
+export const fetchUser = async (id: Id): Promise<User> => {
+    return api.get(id);
+};
+
+const handlers = {
+    onSave(user: User) {
+        api.save(user);
+    },
+};
+
+function* ids() {
+    yield 1;
+}
+"""
+        skeleton: str = extract_skeleton(code, "typescript")
+        self.assertIn('import { api } from "./api";', skeleton)
+        self.assertIn("export interface User {\n    id: string;\n    name?: string;\n}", skeleton)
+        self.assertIn("export type Id = string | number;", skeleton)
+        self.assertIn("enum Role {\n    Admin,\n    Guest,\n}", skeleton)
+        self.assertIn("export const fetchUser = async (id: Id): Promise<User> => {", skeleton)
+        self.assertIn("    onSave(user: User) {", skeleton)
+        self.assertIn("function* ids() {", skeleton)
+        self.assertNotIn("api.get", skeleton)
+        self.assertNotIn("api.save", skeleton)
+        self.assertNotIn("yield", skeleton)
+
+    def test_tsx_component(self) -> None:
+        """Test that a TSX component loses its JSX while its props interface is intact."""
+        code: str = """interface ButtonProps {
+    label: string;
+    onClick: () => void;
+}
+
+export const Button = ({ label, onClick }: ButtonProps) => {
+    return (
+        <button className="btn" onClick={onClick}>
+            {label}
+        </button>
+    );
+};
+"""
+        self.assertEqual(
+            [get_language(ext) for ext in (".ts", ".tsx", ".mts", ".cts")],
+            ["typescript", "tsx", "typescript", "typescript"],
+        )
+        skeleton: str = extract_skeleton(code, "tsx")
+        self.assertIn(
+            "interface ButtonProps {\n    label: string;\n    onClick: () => void;\n}", skeleton
+        )
+        self.assertIn("export const Button = ({ label, onClick }: ButtonProps) => {", skeleton)
+        self.assertNotIn("<button", skeleton)
+        self.assertNotIn("{label}", skeleton)
+
+    def test_go_declarations_kept(self) -> None:
+        """Test that Go declarations survive while function and closure bodies go."""
+        code: str = """package shapes
+
+import (
+    "fmt"
+    "strings"
+)
+
+const (
+    Small Size = iota
+    Large
+)
+
+type Shape interface {
+    fmt.Stringer
+    Area() float64
+}
+
+type Calculator struct {
+    value int
+}
+
+var double = func(x int) int {
+    return x * 2
+}
+
+func (c *Calculator) Add(x int) int {
+    c.value += x
+    return c.value
+}
+
+func Map[K comparable, V any](m map[K]V, f func(V) V) map[K]V {
+    out := make(map[K]V, len(m))
+    for k, v := range m {
+        out[k] = f(strings.TrimSpace(v))
+    }
+    return out
+}
+"""
+        skeleton: str = extract_skeleton(code, "go")
+        self.assertTrue(skeleton.startswith('package shapes\n\nimport (\n    "fmt"\n'))
+        self.assertIn("const (\n    Small Size = iota\n    Large\n)", skeleton)
+        self.assertIn("type Shape interface {\n    fmt.Stringer\n    Area() float64\n}", skeleton)
+        self.assertIn("type Calculator struct {\n    value int\n}", skeleton)
+        self.assertIn("var double = func(x int) int {", skeleton)
+        self.assertIn("func (c *Calculator) Add(x int) int {", skeleton)
+        self.assertIn(
+            "func Map[K comparable, V any](m map[K]V, f func(V) V) map[K]V {", skeleton
+        )
+        self.assertNotIn("x * 2", skeleton)
+        self.assertNotIn("c.value +=", skeleton)
+        self.assertNotIn("range", skeleton)
+
+        symbols = {symbol.name: symbol for symbol in extract_symbols(code, "go")}
+        self.assertEqual(symbols["Small"].kind, "constant")
+        self.assertEqual(symbols["Add"].signature, "func (c *Calculator) Add(x int) int")
+        self.assertEqual(
+            symbols["Map"].signature,
+            "func Map[K comparable, V any](m map[K]V, f func(V) V) map[K]V",
+        )
+
+    def test_go_test_file_detected(self) -> None:
+        """Test that Go test files are detected as Go."""
+        self.assertEqual(get_language(Path("calc_test.go").suffix), "go")
+
     def test_language_not_supported(self) -> None:
         """Test unsupported language error."""
         with self.assertRaises(ValueError):
@@ -92,12 +476,43 @@ const greet = (name) => {
 };
 
 const concise = (x) => x * 2;
+
+export const load = async (url) => {
+    const response = await fetch(url);
+    return response.json();
+};
+
+class Button {
+    onClick = (event) => {
+        event.preventDefault();
+    };
+}
 '''
         skeleton: str = extract_skeleton(code, "javascript")
         self.assertIn("const add = (a, b) =>", skeleton)
         self.assertIn("const greet = (name) =>", skeleton)
+        self.assertIn("export const load = async (url) => {", skeleton)
+        self.assertIn("    onClick = (event) => {", skeleton)
         self.assertNotIn("const result", skeleton)
         self.assertNotIn("console.log", skeleton)
+        self.assertNotIn("fetch", skeleton)
+        self.assertNotIn("preventDefault", skeleton)
+
+        symbols = extract_symbols(code, "javascript")
+        self.assertEqual(
+            [(symbol.kind, symbol.name) for symbol in symbols],
+            [
+                ("function", "add"),
+                ("function", "greet"),
+                ("function", "concise"),
+                ("function", "load"),
+                ("class", "Button"),
+            ],
+        )
+        self.assertEqual(symbols[3].signature, "const load = async (url) =>")
+        self.assertEqual(
+            [(child.kind, child.name) for child in symbols[4].children], [("method", "onClick")]
+        )
 
     def test_java_constructor(self) -> None:
         """Test Java constructor extraction."""
@@ -161,6 +576,51 @@ public class Example {
         self.assertIn("public void test()", skeleton)
         self.assertNotIn("System.out.println", skeleton)
 
+    def test_java_declarations_and_anonymous_classes(self) -> None:
+        """Test that Java keeps declarations while nested and anonymous bodies go."""
+        code: str = """@Service
+public class Registry<T extends Comparable<T>> {
+    @Inject
+    private final Map<String, T> items = new HashMap<>();
+
+    private final Runnable cleanup = new Runnable() {
+        @Override
+        public void run() {
+            items.clear();
+        }
+    };
+
+    static {
+        System.loadLibrary("registry");
+    }
+
+    public T load(String key) throws IOException, NotFoundException {
+        return items.get(key);
+    }
+
+    public static class Entry {
+        public String describe() {
+            return "entry";
+        }
+    }
+}
+"""
+        skeleton: str = extract_skeleton(code, "java")
+        self.assertIn("@Service\npublic class Registry<T extends Comparable<T>> {", skeleton)
+        self.assertIn(
+            "    @Inject\n    private final Map<String, T> items = new HashMap<>();", skeleton
+        )
+        self.assertIn("        @Override\n        public void run() {", skeleton)
+        self.assertIn("    static {", skeleton)
+        self.assertIn(
+            "    public T load(String key) throws IOException, NotFoundException {", skeleton
+        )
+        self.assertIn(
+            "    public static class Entry {\n        public String describe() {", skeleton
+        )
+        for body in ("items.clear()", "loadLibrary", "items.get", '"entry"'):
+            self.assertNotIn(body, skeleton)
+
     def test_csharp_properties_and_lambdas(self) -> None:
         """Test C# properties and lambda expressions."""
         code: str = '''
@@ -199,6 +659,58 @@ fn main() {
         self.assertIn("fn main()", skeleton)
         # Closures should have bodies removed
 
+    def test_rust_macro_rules(self) -> None:
+        """Test that macro definitions keep their header and lose their rules."""
+        skeleton = extract_skeleton(MACROS_RS, "rust")
+        self.assertEqual(
+            skeleton,
+            "/// Builds a map.\n#[macro_export]\nmacro_rules! hashmap {\n}\n\n"
+            "macro_rules! square {}",
+        )
+        options = LopperOptions(body_placeholders=True)
+        skeleton = extract_skeleton(MACROS_RS, "rust", options=options)
+        self.assertIn("macro_rules! hashmap { /* 7 lines */ }", skeleton)
+        self.assertIn("macro_rules! square { /* 1 line */ }", skeleton)
+
+    def test_rust_macro_rules_kept(self) -> None:
+        """Test keeping the rules of a macro, and its export attribute in any case."""
+        code = (
+            "#[macro_export]\n"
+            "macro_rules! maximum {\n"
+            "    ($x:expr) => { $x };\n"
+            "    ($x:expr, $($rest:expr),+) => {{\n"
+            "        let rest = maximum!($($rest),+);\n"
+            "        if $x > rest { $x } else { rest }\n"
+            "    }};\n"
+            "}\n"
+        )
+        options = LopperOptions(keep_attributes=False)
+        self.assertEqual(
+            extract_skeleton(code, "rust", options=options),
+            "#[macro_export]\nmacro_rules! maximum {\n}",
+        )
+        options = LopperOptions(keep_macro_rules=True, body_placeholders=True)
+        self.assertEqual(extract_skeleton(code, "rust", options=options), code.rstrip())
+
+    def test_rust_item_macro_invocations(self) -> None:
+        """Test that item-position macro invocations keep the statics they declare."""
+        invocations = (
+            "lazy_static! {\n"
+            "    /// The global registry.\n"
+            "    pub static ref REGISTRY: Mutex<Vec<u32>> = Mutex::new(Vec::new());\n"
+            "}\n"
+            "\n"
+            "thread_local! {\n"
+            "    static DEPTH: Cell<u32> = Cell::new(0);\n"
+            "}\n"
+        )
+        code = invocations + "\npub fn depth() -> u32 {\n    DEPTH.with(|d| d.get())\n}\n"
+        options = LopperOptions(visibility="public", body_placeholders=True)
+        self.assertEqual(
+            extract_skeleton(code, "rust", options=options),
+            invocations + "\npub fn depth() -> u32 { /* 3 lines */ }",
+        )
+
     def test_cpp_lambdas(self) -> None:
         """Test C++ lambda expressions."""
         code: str = '''
@@ -213,6 +725,67 @@ void process() {
         skeleton: str = extract_skeleton(code, "cpp")
         self.assertIn("void process()", skeleton)
 
+    def test_cpp_header_like_skeleton(self) -> None:
+        """Test that C++ keeps declarations and templates while definitions lose bodies."""
+        code: str = """#include <vector>
+
+using std::vector;
+
+template <typename T>
+T max_of(const vector<T>& values) {
+    T best = values[0];
+    for (const T& v : values) {
+        if (v > best) best = v;
+    }
+    return best;
+}
+
+class Stack {
+public:
+    Stack() : size_(0) {
+        data_.reserve(16);
+    }
+    ~Stack() {
+        data_.clear();
+    }
+    void push(int value) {
+        data_.push_back(value);
+        ++size_;
+    }
+
+private:
+    std::vector<int> data_;
+    int size_;
+};
+
+int Stack::top() const {
+    return data_.back();
+}
+"""
+        skeleton: str = extract_skeleton(code, "cpp")
+        self.assertIn("#include <vector>\n\nusing std::vector;", skeleton)
+        self.assertIn("template <typename T>\nT max_of(const vector<T>& values) {", skeleton)
+        self.assertIn("    Stack() : size_(0) {", skeleton)
+        self.assertIn("    ~Stack() {", skeleton)
+        self.assertIn("    void push(int value) {", skeleton)
+        self.assertIn("private:\n    std::vector<int> data_;\n    int size_;\n};", skeleton)
+        self.assertIn("int Stack::top() const {", skeleton)
+        for body in ("return best", "reserve", "clear()", "push_back", "back()"):
+            self.assertNotIn(body, skeleton)
+
+        symbols = extract_symbols(code, "cpp")
+        self.assertEqual([symbol.name for symbol in symbols], ["max_of", "Stack", "Stack::top"])
+        self.assertEqual(
+            [(child.kind, child.name) for child in symbols[1].children],
+            [("method", "Stack"), ("method", "~Stack"), ("method", "push")],
+        )
+
+    def test_cpp_extensions(self) -> None:
+        """Test that C++ sources and ambiguous `.h` headers use the C++ grammar."""
+        for extension in (".cc", ".cpp", ".cxx", ".hpp", ".h"):
+            self.assertEqual(get_language(extension), "cpp")
+        self.assertEqual(get_language(".c"), "c")
+
     def test_ruby_methods_and_blocks(self) -> None:
         """Test Ruby methods and blocks."""
         code: str = '''
@@ -258,6 +831,114 @@ class Service {
         self.assertIn("public function process", skeleton)
         self.assertNotIn("return $item * 2", skeleton)
 
+    def test_ruby_nested_modules(self) -> None:
+        """Test that Ruby modules, classes and their class-level declarations stay.
+
+        The blocks of class-level calls are kept verbatim, their methods not being
+        methods of the class.
+        """
+        code = (
+            "module Shop\n"
+            '  VERSION = "1.0"\n'
+            "\n"
+            "  class Order < ApplicationRecord\n"
+            "    attr_accessor :total\n"
+            "    attr_reader :id\n"
+            "\n"
+            "    has_many :items do\n"
+            "      def pending\n"
+            '        where(state: "pending")\n'
+            "      end\n"
+            "    end\n"
+            "\n"
+            "    def checkout(card)\n"
+            "      charge(card)\n"
+            "      save\n"
+            "    end\n"
+            "  end\n"
+            "end\n"
+        )
+        self.assertEqual(
+            extract_skeleton(code, "ruby"),
+            "module Shop\n"
+            '  VERSION = "1.0"\n'
+            "\n"
+            "  class Order < ApplicationRecord\n"
+            "    attr_accessor :total\n"
+            "    attr_reader :id\n"
+            "\n"
+            "    has_many :items do\n"
+            "      def pending\n"
+            '        where(state: "pending")\n'
+            "      end\n"
+            "    end\n"
+            "\n"
+            "    def checkout(card)\n"
+            "    end\n"
+            "  end\n"
+            "end",
+        )
+        self.assertEqual(
+            [symbol.qualified_name for symbol in iter_symbols(extract_symbols(code, "ruby"))],
+            ["Shop", "Shop::Order", "Shop::Order::checkout"],
+        )
+
+    def test_php_namespaces_and_attributes(self) -> None:
+        """Test that PHP functions collapse while declarations and attributes stay."""
+        code = (
+            "<?php\n"
+            "namespace App\\Controller;\n"
+            "\n"
+            "use App\\Entity\\Order;\n"
+            "\n"
+            "function helper(int $x): int {\n"
+            "    return $x * 2;\n"
+            "}\n"
+            "\n"
+            "#[Route('/orders')]\n"
+            "class OrderController {\n"
+            "    private readonly OrderRepository $orders;\n"
+            "    public ?string $name = null;\n"
+            "\n"
+            "    #[Route('/{id}', methods: ['GET'])]\n"
+            "    public function show(int $id): Order {\n"
+            "        return $this->orders->find($id);\n"
+            "    }\n"
+            "}\n"
+        )
+        self.assertEqual(
+            extract_skeleton(code, "php"),
+            "<?php\n"
+            "namespace App\\Controller;\n"
+            "\n"
+            "use App\\Entity\\Order;\n"
+            "\n"
+            "function helper(int $x): int {\n"
+            "}\n"
+            "\n"
+            "#[Route('/orders')]\n"
+            "class OrderController {\n"
+            "    private readonly OrderRepository $orders;\n"
+            "    public ?string $name = null;\n"
+            "\n"
+            "    #[Route('/{id}', methods: ['GET'])]\n"
+            "    public function show(int $id): Order {\n"
+            "    }\n"
+            "}",
+        )
+        nested = (
+            "<?php\n"
+            "namespace App {\n"
+            "    class Kernel {\n"
+            "        public function boot() {}\n"
+            "    }\n"
+            "}\n"
+        )
+        self.assertEqual(
+            [symbol.qualified_name for symbol in iter_symbols(extract_symbols(nested, "php"))],
+            ["App", "App::Kernel", "App::Kernel::boot"],
+        )
+
     def test_swift_functions_and_methods(self) -> None:
         """Test Swift function and method extraction."""
         code: str = '''
@@ -397,176 +1078,3959 @@ class User {
         self.assertNotIn('return "Hello, $name"', skeleton)
         self.assertNotIn("throw IllegalArgumentException", skeleton)
 
+    def test_kotlin_classes_and_expression_bodies(self) -> None:
+        """Test Kotlin data classes, companion objects and expression-bodied functions."""
+        code: str = """@Serializable
+data class Point(val x: Int, val y: Int) {
+    fun norm() = x * x + y * y
 
-class TestBinaryFileDetection(unittest.TestCase):
-    """Test binary file detection."""
+    companion object {
+        fun origin(): Point {
+            return Point(0, 0)
+        }
+    }
+}
+"""
+        skeleton: str = extract_skeleton(code, "kotlin")
+        self.assertIn("@Serializable\ndata class Point(val x: Int, val y: Int) {", skeleton)
+        self.assertIn("    fun norm()\n", skeleton)
+        self.assertIn("    companion object {\n        fun origin(): Point {", skeleton)
+        self.assertNotIn("x * x", skeleton)
+        self.assertNotIn("Point(0, 0)", skeleton)
 
-    def test_text_file_not_binary(self) -> None:
-        """Test that text files are not detected as binary."""
-        with tempfile.NamedTemporaryFile(mode="w", suffix=".txt", delete=False) as f:
-            f.write("Hello, this is a text file\n")
-            f.flush()
-            path = Path(f.name)
-            try:
-                self.assertFalse(is_binary(str(path)))
-            finally:
-                path.unlink()
+        options = LopperOptions(body_placeholders=True)
+        skeleton = extract_skeleton(code, "kotlin", options=options)
+        self.assertIn("    fun norm() /* 1 line */\n", skeleton)
 
-    def test_binary_extension_detected(self) -> None:
-        """Test that files with known binary extensions are detected."""
-        # binaryornot detects based on known extensions and null bytes
-        # Test with a known binary extension + null byte content
-        with tempfile.NamedTemporaryFile(suffix=".pyc", delete=False, mode="wb") as f:
-            f.write(b"Python compiled\x00binary\x00content")
-            f.flush()
-            path = Path(f.name)
-            try:
-                self.assertTrue(is_binary(str(path)))
-            finally:
-                path.unlink()
+        symbols = extract_symbols(code, "kotlin")
+        self.assertTrue(symbols[0].signature.endswith("data class Point(val x: Int, val y: Int)"))
+        self.assertEqual(
+            [(child.kind, child.name) for child in symbols[0].children],
+            [("method", "norm"), ("method", "origin")],
+        )
 
-    def test_null_byte_detection(self) -> None:
-        """Test that files with null bytes are detected as binary."""
-        with tempfile.NamedTemporaryFile(mode="wb", suffix=".txt", delete=False) as f:
-            # Files with null bytes fail UTF-8 decoding
-            f.write(b"Some text\x00with null bytes")
-            f.flush()
-            path = Path(f.name)
-            try:
-                self.assertTrue(is_binary(str(path)))
-            finally:
-                path.unlink()
 
-    def test_python_file_not_binary(self) -> None:
-        """Test that Python files are not detected as binary."""
-        with tempfile.NamedTemporaryFile(mode="w", suffix=".py", delete=False) as f:
-            f.write("def hello():\n    print('hello')\n")
-            f.flush()
-            path = Path(f.name)
-            try:
-                self.assertFalse(is_binary(str(path)))
-            finally:
-                path.unlink()
+class TestEncodings(unittest.TestCase):
+    """Test that non-ASCII text and CRLF line breaks come through unchanged."""
 
-    def test_empty_file_not_binary(self) -> None:
-        """Test that empty files are not detected as binary."""
-        with tempfile.NamedTemporaryFile(delete=False) as f:
-            f.flush()
-            path = Path(f.name)
-            try:
-                self.assertFalse(is_binary(str(path)))
-            finally:
-                path.unlink()
+    def test_emoji_doc_comment(self) -> None:
+        """Test that a doc comment with emoji survives above an elided function."""
+        code = "/// Adds \U0001f389 numbers.\npub fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n"
+        options = LopperOptions(body_placeholders=True)
+        self.assertEqual(
+            extract_skeleton(code, "rust", options=options),
+            "/// Adds \U0001f389 numbers.\npub fn add(a: i32, b: i32) -> i32 { /* 3 lines */ }",
+        )
 
-    def test_image_extension_detected(self) -> None:
-        """Test that binary image files with null bytes are detected."""
-        # binaryornot detects binary content via null bytes
-        test_cases = [
-            (".jpg", b"\xFF\xD8\xFF\xE0\x00image"),  # JPEG with null byte
-            (".png", b"\x89PNG\r\n\x1a\n\x00data"),  # PNG with null byte
-            (".gif", b"GIF89a\x00binary"),  # GIF with null byte
-        ]
-        for ext, content in test_cases:
-            with tempfile.NamedTemporaryFile(suffix=ext, delete=False, mode="wb") as f:
-                f.write(content)
-                f.flush()
-                path = Path(f.name)
-                try:
-                    self.assertTrue(is_binary(str(path)), f"{ext} should be detected as binary")
-                finally:
-                    path.unlink()
+    def test_cjk_literal_in_kept_body(self) -> None:
+        """Test that a short body holding CJK text is kept intact next to an elided one."""
+        code = (
+            "fn greet() -> &'static str { \"\u4f60\u597d\uff0c\u4e16\u754c\" }\n"
+            "fn count() -> u32 {\n    let \u5024 = 1;\n    \u5024\n}\n"
+        )
+        options = LopperOptions(max_body_lines=1, body_placeholders=True)
+        self.assertEqual(
+            extract_skeleton(code, "rust", options=options),
+            "fn greet() -> &'static str { \"\u4f60\u597d\uff0c\u4e16\u754c\" }\n"
+            "fn count() -> u32 { /* 4 lines */ }",
+        )
 
+    def test_multibyte_before_elision(self) -> None:
+        """Test that cuts after multibyte names fall on the byte offsets of the body."""
+        code = "fn gr\u00f6\u00dfe() -> u32 {\n    let \u00df = 1;\n    \u00df\n}\n"
+        options = LopperOptions(body_placeholders=True)
+        self.assertEqual(
+            extract_skeleton(code, "rust", options=options),
+            "fn gr\u00f6\u00dfe() -> u32 { /* 4 lines */ }",
+        )
+        code = 'def gr\u00fc\u00dfe():\n    """Gr\u00fc\u00df \U0001f44b."""\n    return "\u2713"\n'
+        self.assertEqual(
+            extract_skeleton(code, "python"),
+            'def gr\u00fc\u00dfe():\n    """Gr\u00fc\u00df \U0001f44b."""',
+        )
 
-class TestFindFilesWithMixedTypes(unittest.TestCase):
-    """Test find_files returns all non-binary text files."""
+    def test_crlf_line_breaks(self) -> None:
+        """Test that CRLF line breaks are kept, and used for the lines loppers adds."""
+        code = (
+            "use std::fmt;\r\nuse std::io;\r\n\r\n"
+            "/// Doc.\r\npub fn f() -> u32 {\r\n    1\r\n}\r\n"
+        )
+        skeleton = extract_skeleton(code, "rust", options=LopperOptions(imports="collapse"))
+        self.assertEqual(
+            skeleton, "// 2 imports omitted\r\n\r\n/// Doc.\r\npub fn f() -> u32 {\r\n}"
+        )
 
-    def test_find_files_includes_non_code_files(self) -> None:
-        """Test that find_files includes markdown, json, yaml, etc."""
-        with tempfile.TemporaryDirectory() as tmpdir:
-            tmppath = Path(tmpdir)
 
-            # Create various file types
-            (tmppath / "readme.md").write_text("# Test\n")
-            (tmppath / "config.json").write_text('{"key": "value"}\n')
-            (tmppath / "data.yaml").write_text("key: value\n")
-            (tmppath / "script.py").write_text("def hello():\n    pass\n")
+class TestOptionsConfig(unittest.TestCase):
+    """Test reading options from plain dictionaries such as parsed configs."""
 
-            # Find files
-            files = find_files(tmppath, recursive=False)
+    def test_round_trip(self) -> None:
+        """Test that options survive a JSON round trip, defaults included."""
+        options = LopperOptions(max_body_lines=3, keep_bodies=("main",), visibility="crate")
+        data = json.loads(json.dumps(options.to_dict()))
+        self.assertEqual(LopperOptions.from_dict(data), options)
+        self.assertEqual(LopperOptions.from_dict({}), LopperOptions())
+        self.assertEqual(LopperOptions.from_dict({"max_depth": None}), LopperOptions())
 
-            # All text files should be included
-            self.assertIn("readme.md", files)
-            self.assertIn("config.json", files)
-            self.assertIn("data.yaml", files)
-            self.assertIn("script.py", files)
+    def test_language_options(self) -> None:
+        """Test that per-language overrides survive a JSON round trip and apply by language."""
+        options = LopperOptions(
+            max_body_lines=3,
+            keep_bodies=("main",),
+            visibility="crate",
+            language_options={"python": {"imports": "drop", "keep_bodies": ["run"]}},
+        )
+        data = json.loads(json.dumps(options.to_dict()))
+        self.assertEqual(LopperOptions.from_dict(data), options)
+        self.assertEqual(
+            options.for_language("python"),
+            LopperOptions(
+                max_body_lines=3, keep_bodies=("run",), visibility="crate", imports="drop"
+            ),
+        )
+        self.assertIs(options.for_language("rust"), options)
+        derived = dataclasses.replace(options, max_body_lines=5)
+        self.assertEqual(derived.for_language("python").max_body_lines, 5)
 
-    def test_find_files_excludes_binary_files(self) -> None:
-        """Test that find_files excludes binary files."""
-        with tempfile.TemporaryDirectory() as tmpdir:
-            tmppath = Path(tmpdir)
+    def test_builder(self) -> None:
+        """Test that the builder sets options by name, over the defaults or given options."""
+        options = (
+            LopperOptions.builder()
+            .visibility("public")
+            .max_depth(2)
+            .keep_bodies(["main"])
+            .language("python", imports="drop")
+            .build()
+        )
+        self.assertEqual(
+            options,
+            LopperOptions(
+                visibility="public",
+                max_depth=2,
+                keep_bodies=("main",),
+                language_options={"python": {"imports": "drop"}},
+            ),
+        )
+        self.assertEqual(LopperOptions.builder().build(), LopperOptions())
+        derived = (
+            LopperOptionsBuilder(options)
+            .set(max_depth=None)
+            .language("go", sort="alphabetical")
+            .build()
+        )
+        self.assertIsNone(derived.max_depth)
+        self.assertEqual(derived.for_language("python").imports, "drop")
+        self.assertEqual(derived.for_language("go").sort, "alphabetical")
 
-            # Create text and binary files
-            (tmppath / "text.txt").write_text("text content\n")
-            (tmppath / "binary.bin").write_bytes(b"\x00\x01\x02\x03")
+        with self.assertRaises(AttributeError):
+            LopperOptions.builder().max_bodylines(3)
+        with self.assertRaises(TypeError):
+            LopperOptions.builder().set(max_bodylines=3)
+        with self.assertRaises(ValueError):
+            LopperOptions.builder().visibility("secret").build()
 
-            files = find_files(tmppath, recursive=False)
+    def test_invalid_configs(self) -> None:
+        """Test that unknown options and values of the wrong type are rejected."""
+        for data in (
+            {"max_bodylines": 3},
+            {"max_body_lines": True},
+            {"keep_attributes": 1},
+            {"keep_bodies": "main"},
+            {"visibility": "secret"},
+            {"placeholder": "x", "placeholder_comment": "y"},
+            {"language_options": {"python": {"imports": "all"}}},
+            {"placeholder": "x", "language_options": {"python": {"placeholder_comment": "y"}}},
+        ):
+            with self.subTest(data=data), self.assertRaises(ValueError):
+                LopperOptions.from_dict(data)
+        with self.assertRaisesRegex(ValueError, "Unknown options: max_bodylines"):
+            LopperOptions.from_dict({"max_bodylines": 3})
+        with self.assertRaises(ValueError):
+            LopperOptions(placeholder=str).to_dict()
 
-            self.assertIn("text.txt", files)
-            self.assertNotIn("binary.bin", files)
+    def test_config_discovery(self) -> None:
+        """Test that the config file nearest above a path is found and merged."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir)
+            nested = root / "crates" / "core" / "src"
+            nested.mkdir(parents=True)
+            (nested / "lib.py").write_text("def main():\n    pass\n")
+            (root / "loppers.toml").write_text(
+                'strip_tests = true\nimports = "collapse"\n\n'
+                '[lang.python]\nimports = "drop"\nkeep_bodies = ["main"]\n'
+            )
+            self.assertEqual(find_config(nested / "lib.py"), (root / "loppers.toml").resolve())
+            self.assertEqual(find_config(nested), (root / "loppers.toml").resolve())
 
+            options = load_config(nested / "lib.py").resolve()
+            self.assertTrue(options.strip_tests)
+            self.assertEqual(options.imports, "collapse")
+            python = options.for_language("python")
+            self.assertEqual((python.imports, python.keep_bodies), ("drop", ("main",)))
+            self.assertTrue(python.strip_tests)
 
-class TestMixedFileTypes(unittest.TestCase):
-    """Test mixed file type handling."""
+            # Explicit options win over the per-language tables too
+            options = load_config(nested).resolve({"imports": "keep", "strip_tests": False})
+            for language in ("python", "rust"):
+                with self.subTest(language=language):
+                    self.assertEqual(options.for_language(language).imports, "keep")
+                    self.assertFalse(options.for_language(language).strip_tests)
 
-    def test_get_skeleton_on_unsupported_file_as_is(self) -> None:
-        """Test that unsupported files can't be extracted without language."""
-        with tempfile.NamedTemporaryFile(mode="w", suffix=".json", delete=False) as f:
-            f.write('{"key": "value"}\n')
-            f.flush()
-            path = Path(f.name)
+            # A config closer to the path shadows the one above it
+            (root / "crates" / ".loppers.toml").write_text("max_depth = 1\n")
+            options = load_config(nested).resolve()
+            self.assertEqual((options.max_depth, options.strip_tests), (1, False))
+            self.assertEqual(load_config(nested, discover=False), LopperConfig())
+            config = load_config(nested, config_path=root / "loppers.toml", discover=False)
+            self.assertEqual(config.path, root / "loppers.toml")
+            with self.assertRaises(FileNotFoundError):
+                load_config(nested, config_path=root / "missing.toml")
 
-            try:
-                # Should raise ValueError for unsupported file type
-                with self.assertRaises(ValueError):
-                    get_skeleton(path)
-            finally:
-                path.unlink()
+    def test_malformed_config(self) -> None:
+        """Test that invalid config files raise ConfigError naming the file."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = Path(tmpdir) / "loppers.toml"
+            for text, message in (
+                ("strip_tests = \n", "Invalid value"),
+                ("max_bodylines = 3\n", "Unknown options: max_bodylines"),
+                ("[lang.klingon]\nimports = 'drop'\n", "Unknown languages in lang: klingon"),
+                ("[lang.python]\nimports = 'all'\n", "Invalid imports 'all'"),
+                ("lang = 1\n", "lang must be a table"),
+            ):
+                path.write_text(text)
+                with self.subTest(text=text), self.assertRaises(ConfigError) as caught:
+                    read_config(path)
+                self.assertIn(str(path), str(caught.exception))
+                self.assertIn(message, str(caught.exception))
+                self.assertEqual(caught.exception.path, path)
 
 
-class TestExtractWithUnsupportedFiles(unittest.TestCase):
-    """Test extract command behavior with unsupported files."""
+class TestDocComments(unittest.TestCase):
+    """Test doc comment handling."""
 
-    def test_extract_requires_language_for_unsupported(self) -> None:
-        """Test that extract requires -l for unsupported file types."""
-        with tempfile.NamedTemporaryFile(mode="w", suffix=".md", delete=False) as f:
-            f.write("# Test\n")
-            f.flush()
-            path = Path(f.name)
+    rust_code: str = """
+/// Adds one to the input.
+fn add_one(x: i32) -> i32 {
+    // bump the value
+    x + 1
+}
+"""
 
-            try:
-                # Should raise ValueError for unsupported file type
-                with self.assertRaises(ValueError):
-                    get_skeleton(path)
-            finally:
-                path.unlink()
+    def test_rust_doc_comment_kept(self) -> None:
+        """Test that Rust doc comments survive while body comments are elided."""
+        skeleton = extract_skeleton(
+            self.rust_code, "rust", options=LopperOptions(keep_doc_comments=True)
+        )
+        self.assertIn("/// Adds one to the input.\nfn add_one(x: i32) -> i32 {", skeleton)
+        self.assertNotIn("// bump the value", skeleton)
 
-    def test_extract_with_explicit_language(self) -> None:
-        """Test that extract works with explicit language for any file."""
-        with tempfile.NamedTemporaryFile(mode="w", suffix=".md", delete=False) as f:
-            f.write("def hello():\n    print('hi')\n")
-            f.flush()
-            path = Path(f.name)
+    def test_rust_doc_comment_dropped(self) -> None:
+        """Test that Rust doc comments are removed when not kept."""
+        skeleton = extract_skeleton(
+            self.rust_code, "rust", options=LopperOptions(keep_doc_comments=False)
+        )
+        self.assertNotIn("Adds one", skeleton)
+        self.assertIn("fn add_one(x: i32) -> i32 {", skeleton)
 
-            try:
-                # With explicit language, should work
-                skeleton = extract_skeleton(path.read_text(), "python")
-                self.assertIn("def hello()", skeleton)
-                self.assertNotIn("print('hi')", skeleton)
-            finally:
-                path.unlink()
+    def test_python_docstring_dropped(self) -> None:
+        """Test that Python docstrings are removed along with the body when not kept."""
+        code = '''
+class Greeter:
+    """Greets people."""
 
+    def greet(self, name):
+        """Say hello."""
+        return f"Hello {name}"
+'''
+        skeleton = extract_skeleton(code, "python", options=LopperOptions(keep_doc_comments=False))
+        self.assertIn("class Greeter:", skeleton)
+        self.assertIn("def greet(self, name):", skeleton)
+        self.assertNotIn("Greets people", skeleton)
+        self.assertNotIn("Say hello", skeleton)
 
-class TestIgnorePatternsWithMixedFiles(unittest.TestCase):
+    def test_jsdoc_kept_by_default(self) -> None:
+        """Test that JSDoc blocks are kept by default."""
+        code = """
+/**
+ * Greets someone.
+ */
+function greet(name) {
+    return `Hello ${name}`;
+}
+"""
+        skeleton = extract_skeleton(code, "typescript")
+        self.assertIn("* Greets someone.", skeleton)
+        self.assertNotIn("return", skeleton)
+
+    def test_bash_function_comments(self) -> None:
+        """Test that comments directly above shell functions are their doc comments."""
+        code = (
+            "#!/bin/bash\n"
+            "# Deploy the app.\n"
+            "# Usage: deploy ENV\n"
+            "deploy() {\n"
+            "    make release\n"
+            "}\n"
+            "\n"
+            "# ---- Helpers ----\n"
+            "\n"
+            "function log {\n"
+            '    echo "$@" >&2\n'
+            "}\n"
+        )
+        skeleton = extract_skeleton(code, "bash")
+        self.assertIn("# Deploy the app.\n# Usage: deploy ENV\ndeploy() {", skeleton)
+        self.assertIn("function log {", skeleton)
+        self.assertNotIn("make release", skeleton)
+        self.assertNotIn("echo", skeleton)
+        skeleton = extract_skeleton(code, "bash", options=LopperOptions(keep_doc_comments=False))
+        self.assertNotIn("Deploy the app", skeleton)
+        self.assertNotIn("Usage", skeleton)
+        self.assertIn("# ---- Helpers ----", skeleton)
+        self.assertTrue(skeleton.startswith("#!/bin/bash\n"))
+        self.assertIn("deploy() {", skeleton)
+
+
+class TestLeadingTrivia(unittest.TestCase):
+    """Test doc comments and attributes attached above items."""
+
+    code: str = """//! Calculator utilities.
+
+/** Block doc for the struct. */
+#[derive(
+    Debug,
+    Clone,
+)]
+pub struct Calculator {
+    value: i32,
+}
+
+/// Creates values.
+#[cfg(all(
+    feature = "std",
+    not(test)
+))]
+#[inline]
+pub fn make() -> i32 {
+    42
+}
+"""
+
+    def test_leading_trivia_kept_by_default(self) -> None:
+        """Test that inner/block doc comments and multi-line attributes are kept."""
+        skeleton = extract_skeleton(self.code, "rust")
+        self.assertIn("//! Calculator utilities.", skeleton)
+        self.assertIn("/** Block doc for the struct. */", skeleton)
+        self.assertIn("#[derive(\n    Debug,\n    Clone,\n)]\npub struct Calculator {", skeleton)
+        self.assertIn('#[cfg(all(\n    feature = "std",\n    not(test)\n))]', skeleton)
+        self.assertIn("#[inline]\npub fn make() -> i32 {", skeleton)
+        self.assertNotIn("42", skeleton)
+
+    def test_attributes_dropped(self) -> None:
+        """Test that attributes are removed when not kept."""
+        skeleton = extract_skeleton(self.code, "rust", options=LopperOptions(keep_attributes=False))
+        self.assertNotIn("#[", skeleton)
+        self.assertIn("/** Block doc for the struct. */\npub struct Calculator {", skeleton)
+        self.assertIn("/// Creates values.\npub fn make() -> i32 {", skeleton)
+
+    def test_doc_comments_dropped_attributes_kept(self) -> None:
+        """Test that doc comments can be dropped independently of attributes."""
+        skeleton = extract_skeleton(
+            self.code, "rust", options=LopperOptions(keep_doc_comments=False)
+        )
+        self.assertNotIn("//!", skeleton)
+        self.assertNotIn("/**", skeleton)
+        self.assertNotIn("///", skeleton)
+        self.assertIn("#[inline]\npub fn make() -> i32 {", skeleton)
+
+    def test_attributes_stay_above_elided_items(self) -> None:
+        """Test that attributes stay attached to items whose code is elided."""
+        code = (
+            "#![allow(dead_code)]\n\n"
+            "#[derive(\n    Debug,\n    Serialize,\n    Deserialize,\n)]\n"
+            "pub struct Config {\n    name: String,\n}\n\n"
+            "#[tokio::main]\nasync fn main() {\n    run().await;\n}\n"
+        )
+        options = LopperOptions(body_placeholders=True, collapse_types=True)
+        skeleton = extract_skeleton(code, "rust", options=options)
+        self.assertTrue(skeleton.startswith("#![allow(dead_code)]\n"))
+        self.assertIn(
+            "#[derive(\n    Debug,\n    Serialize,\n    Deserialize,\n)]\npub struct Config {",
+            skeleton,
+        )
+        self.assertIn("#[tokio::main]\nasync fn main() {", skeleton)
+        self.assertNotIn("run()", skeleton)
+
+    def test_decorator_arguments_kept(self) -> None:
+        """Test that decorator arguments are never truncated by default."""
+        code = (
+            "@app.route(\n    '/users', key=lambda request: request.user.id, cache=True\n)\n"
+            "def users():\n    return []\n"
+        )
+        skeleton = extract_skeleton(code, "python")
+        self.assertEqual(
+            skeleton,
+            "@app.route(\n    '/users', key=lambda request: request.user.id, cache=True\n)\n"
+            "def users():\n    ...",
+        )
+
+    def test_max_attr_len(self) -> None:
+        """Test that only the arguments of attributes longer than the limit are elided."""
+        skeleton = extract_skeleton(self.code, "rust", options=LopperOptions(max_attr_len=10))
+        self.assertIn("#[derive(...)]\npub struct Calculator {", skeleton)
+        self.assertIn("#[cfg(...)]\n#[inline]\npub fn make() -> i32 {", skeleton)
+        code = "@app.route('/users', key=lambda request: request.id)\ndef users():\n    return []\n"
+        skeleton = extract_skeleton(code, "python", options=LopperOptions(max_attr_len=20))
+        self.assertEqual(skeleton, "@app.route(...)\ndef users():\n    ...")
+        with self.assertRaises(ValueError):
+            LopperOptions(max_attr_len=-1)
+
+    def test_member_decorators_dropped_with_private_members(self) -> None:
+        """Test that decorators of dropped TypeScript members go with them."""
+        code = (
+            "class Api {\n"
+            "    @Get('/items')\n    list() {\n        return [];\n    }\n\n"
+            "    @Memoize()\n    private load() {\n        return [];\n    }\n"
+            "}\n"
+        )
+        skeleton = extract_skeleton(code, "typescript", options=LopperOptions(visibility="crate"))
+        self.assertIn("    @Get('/items')\n    list() {", skeleton)
+        self.assertNotIn("@Memoize", skeleton)
+        self.assertNotIn("load", skeleton)
+
+    def test_python_decorators_dropped(self) -> None:
+        """Test that Python decorators are removed when attributes are not kept."""
+        code = "class A:\n    @property\n    def name(self):\n        return 1\n"
+        skeleton = extract_skeleton(code, "python", options=LopperOptions(keep_attributes=False))
+        self.assertEqual(skeleton, "class A:\n    def name(self):\n        ...")
+
+
+class TestMaxBodyLines(unittest.TestCase):
+    """Test keeping short bodies verbatim."""
+
+    code: str = """
+fn fibonacci(n: u32) -> u32 {
+    if n <= 1 {
+        return n;
+    }
+    fibonacci(n - 1) + fibonacci(n - 2)
+}
+
+impl Calculator {
+    pub fn new(initial: i32) -> Self {
+        Calculator { value: initial }
+    }
+}
+"""
+
+    def test_body_at_threshold_kept(self) -> None:
+        """Test that a body spanning exactly the threshold is kept."""
+        skeleton = extract_skeleton(self.code, "rust", options=LopperOptions(max_body_lines=3))
+        self.assertIn("Calculator { value: initial }", skeleton)
+        self.assertNotIn("return n;", skeleton)
+
+    def test_body_above_threshold_elided(self) -> None:
+        """Test that a body longer than the threshold is elided."""
+        skeleton = extract_skeleton(self.code, "rust", options=LopperOptions(max_body_lines=2))
+        self.assertNotIn("Calculator { value: initial }", skeleton)
+
+    def test_zero_threshold_matches_default(self) -> None:
+        """Test that a threshold of 0 behaves like the default."""
+        skeleton = extract_skeleton(self.code, "rust", options=LopperOptions(max_body_lines=0))
+        self.assertEqual(skeleton, extract_skeleton(self.code, "rust"))
+
+
+class TestBodyHeadLines(unittest.TestCase):
+    """Test keeping the leading statements of elided bodies."""
+
+    rust_code: str = (
+        "fn fibonacci(n: u32) -> u32 {\n"
+        "    if n <= 1 {\n"
+        "        return n;\n"
+        "    }\n"
+        "    fibonacci(n - 1) + fibonacci(n - 2)\n"
+        "}\n"
+    )
+
+    def test_whole_statements_kept(self) -> None:
+        """Test that statements fitting in the head are kept and the rest is marked."""
+        skeleton = extract_skeleton(
+            self.rust_code, "rust", options=LopperOptions(body_head_lines=3)
+        )
+        self.assertEqual(
+            skeleton,
+            "fn fibonacci(n: u32) -> u32 {\n"
+            "    if n <= 1 {\n"
+            "        return n;\n"
+            "    }\n"
+            "    /* ... */\n"
+            "}",
+        )
+
+    def test_statements_never_split(self) -> None:
+        """Test that a statement crossing the head limit is elided whole."""
+        skeleton = extract_skeleton(
+            self.rust_code, "rust", options=LopperOptions(body_head_lines=1)
+        )
+        self.assertEqual(skeleton, "fn fibonacci(n: u32) -> u32 {\n    /* ... */\n}")
+
+    def test_placeholder_counts_elided_lines(self) -> None:
+        """Test that placeholders count only the elided lines."""
+        options = LopperOptions(body_head_lines=3, body_placeholders=True)
+        skeleton = extract_skeleton(self.rust_code, "rust", options=options)
+        self.assertIn("    }\n    /* 1 line */\n}", skeleton)
+
+    def test_python_head_is_valid_python(self) -> None:
+        """Test that Python bodies keep their head and end with `...`."""
+        code = (
+            "def scale(x):\n"
+            '    """Scale x."""\n'
+            "    if x is None:\n"
+            "        return 0\n"
+            "    y = x * 2\n"
+            "    return y\n"
+        )
+        skeleton = extract_skeleton(code, "python", options=LopperOptions(body_head_lines=2))
+        ast.parse(skeleton)
+        self.assertEqual(
+            skeleton,
+            'def scale(x):\n    """Scale x."""\n    if x is None:\n        return 0\n    ...',
+        )
+
+    def test_short_bodies_kept(self) -> None:
+        """Test that bodies fitting in the head are kept whole."""
+        code = "fn one() -> u32 {\n    1\n}\n"
+        skeleton = extract_skeleton(code, "rust", options=LopperOptions(body_head_lines=1))
+        self.assertEqual(skeleton, "fn one() -> u32 {\n    1\n}")
+
+
+class TestBodyPlaceholders(unittest.TestCase):
+    """Test replacing elided bodies with line-count placeholders."""
+
+    options: LopperOptions = LopperOptions(body_placeholders=True)
+
+    def test_rust_placeholders(self) -> None:
+        """Test that brace bodies keep their braces around the line count."""
+        code = (
+            "fn fibonacci(n: u32) -> u32 {\n"
+            "    if n <= 1 {\n"
+            "        return n;\n"
+            "    }\n"
+            "    fibonacci(n - 1) + fibonacci(n - 2)\n"
+            "}\n"
+            "\n"
+            "fn noop() {}\n"
+            "fn one() -> i32 { 1 }\n"
+        )
+        skeleton = extract_skeleton(code, "rust", options=self.options)
+        self.assertEqual(
+            skeleton,
+            "fn fibonacci(n: u32) -> u32 { /* 6 lines */ }\n"
+            "\n"
+            "fn noop() {}\n"
+            "fn one() -> i32 { /* 1 line */ }",
+        )
+
+    def test_python_placeholders(self) -> None:
+        """Test that Python bodies become `...` and docstrings are not counted."""
+        code = (
+            "def add(a, b):\n"
+            '    """Add numbers."""\n'
+            "    total = a + b\n"
+            "    return total\n"
+            "\n"
+            "def one(): return 1\n"
+        )
+        skeleton = extract_skeleton(code, "python", options=self.options)
+        self.assertEqual(
+            skeleton,
+            "def add(a, b):\n"
+            '    """Add numbers."""\n'
+            "    ...  # 2 lines\n"
+            "\n"
+            "def one(): ...  # 1 line",
+        )
+
+    def test_nested_bodies_share_one_placeholder(self) -> None:
+        """Test that functions nested in an elided body add no placeholder of their own."""
+        code = "def outer():\n    def inner():\n        return 1\n    return inner\n"
+        skeleton = extract_skeleton(code, "python", options=self.options)
+        self.assertEqual(skeleton, "def outer():\n    ...  # 3 lines")
+
+    def test_fixed_placeholder(self) -> None:
+        """Test that a fixed placeholder goes between braces or on its own line."""
+        options = LopperOptions(placeholder="/* ... */")
+        code = "fn f() {\n    1\n}\n\nfn noop() {}\n"
+        skeleton = extract_skeleton(code, "rust", options=options)
+        self.assertEqual(skeleton, "fn f() { /* ... */ }\n\nfn noop() {}")
+        skeleton = extract_skeleton(
+            "def f():\n    return 1\n", "python", options=LopperOptions(placeholder="pass")
+        )
+        self.assertEqual(skeleton, "def f():\n    pass")
+
+    def test_one_line_body_without_placeholder(self) -> None:
+        """Test that one-line bodies keep their braces when no placeholder replaces them."""
+        code = "fn one() -> i32 { 1 }\nfn noop() {}\n"
+        for options in (LopperOptions(), LopperOptions(placeholder="")):
+            with self.subTest(options=options):
+                skeleton = extract_skeleton(code, "rust", options=options)
+                self.assertEqual(skeleton, "fn one() -> i32 {}\nfn noop() {}")
+
+    def test_line_counts_match_removed_lines(self) -> None:
+        """Test that counts cover every body line, one for single-line bodies."""
+        counts: list[int] = []
+
+        def record(lines: int) -> str:
+            counts.append(lines)
+            return f"/* {lines} */"
+
+        code = (
+            "fn three() {\n"
+            "    1\n"
+            "}\n"
+            "fn one() { 1 }\n"
+            "fn two() { let x = 1;\n"
+            "    x }\n"
+        )
+        skeleton = extract_skeleton(code, "rust", options=LopperOptions(placeholder=record))
+        self.assertEqual(counts, [3, 1, 2])
+        # Each body collapses onto the line of its opening brace
+        removed = len(code.splitlines()) - len(skeleton.splitlines())
+        self.assertEqual(removed, sum(count - 1 for count in counts))
+        self.assertEqual(
+            skeleton, "fn three() { /* 3 */ }\nfn one() { /* 1 */ }\nfn two() { /* 2 */ }"
+        )
+
+        options = LopperOptions(placeholder="...  # {count}")
+        code = 'def f():\n    """Doc."""\n    a = 1\n    return a\n'
+        skeleton = extract_skeleton(code, "python", options=options)
+        self.assertEqual(skeleton, 'def f():\n    """Doc."""\n    ...  # 2')
+
+    def test_placeholder_comment(self) -> None:
+        """Test that comment placeholders use each language's comment syntax."""
+        options = LopperOptions(placeholder_comment="elided, {count}")
+        skeleton = extract_skeleton("fn f() {\n    1\n}\n", "rust", options=options)
+        self.assertEqual(skeleton, "fn f() { /* elided, 3 */ }")
+        skeleton = extract_skeleton("def f():\n    return 1\n", "python", options=options)
+        self.assertEqual(skeleton, "def f():\n    ...  # elided, 1")
+        skeleton = extract_skeleton("function f()\n  return 1\nend\n", "lua", options=options)
+        self.assertIn("  -- elided, 1\n", skeleton)
+
+    def test_invalid_placeholder(self) -> None:
+        """Test that placeholders with unknown fields are rejected."""
+        with self.assertRaises(ValueError):
+            LopperOptions(placeholder="/* {size} */")
+        with self.assertRaises(ValueError):
+            LopperOptions(placeholder_comment="{size}")
+        with self.assertRaises(ValueError):
+            LopperOptions(placeholder="...", placeholder_comment="elided")
+
+
+class TestSizeAnnotations(unittest.TestCase):
+    """Test annotating elided bodies with their size."""
+
+    options: ClassVar[LopperOptions] = LopperOptions(annotate_sizes=True)
+
+    def test_rust_sample(self) -> None:
+        """Test that each elided body of sample.rs counts its original lines."""
+        options = dataclasses.replace(self.options, annotate_complexity=True)
+        lines = extract_skeleton(SAMPLE_RS, "rust", options=options).splitlines()
+        for line in (
+            "fn fibonacci(n: u32) -> u32 { // 6 lines, 1 branch",
+            "    pub fn new(initial: i32) -> Self { // 3 lines, 0 branches",
+            "    pub fn add(&self, x: i32, y: i32) -> i32 { // 3 lines, 0 branches",
+            "    fn process(&self) { // 4 lines, 0 branches",
+            "    fn add(&self, x: i32) -> i32 { // 3 lines, 0 branches",
+            "    fn multiply(&self, x: i32) -> i32 { // 3 lines, 0 branches",
+        ):
+            self.assertIn(line, lines)
+        # Trait declarations have no body to annotate
+        self.assertIn("    fn add(&self, x: i32) -> i32;", lines)
+
+    def test_placeholders_keep_their_lines(self) -> None:
+        """Test that the count follows the closing brace of a placeholder, or a stub."""
+        code = TestBodyHeadLines.rust_code
+        skeleton = extract_skeleton(
+            code, "rust", options=dataclasses.replace(self.options, body_placeholders=True)
+        )
+        self.assertEqual(skeleton, "fn fibonacci(n: u32) -> u32 { /* 6 lines */ } // 6 lines")
+        skeleton = extract_skeleton(
+            code,
+            "rust",
+            options=dataclasses.replace(self.options, body_placeholders=True, body_braces="block"),
+        )
+        self.assertEqual(
+            skeleton, "fn fibonacci(n: u32) -> u32 { // 6 lines\n    /* 6 lines */\n}"
+        )
+        skeleton = extract_skeleton(code, "rust", options=self.options)
+        self.assertEqual(skeleton, "fn fibonacci(n: u32) -> u32 { // 6 lines\n}")
+
+    def test_python(self) -> None:
+        """Test that Python counts go after the stub, or after a kept docstring."""
+        code = (
+            "def scale(x):\n"
+            "    return x * 2\n"
+            "\n"
+            "\n"
+            "def shift(x):\n"
+            '    """Shift x."""\n'
+            "    y = x\n"
+            "    return y + 1\n"
+        )
+        skeleton = extract_skeleton(code, "python", options=self.options)
+        ast.parse(skeleton)
+        self.assertEqual(
+            skeleton,
+            'def scale(x):\n    ...  # 1 line\n\n\ndef shift(x):\n    """Shift x."""  # 3 lines',
+        )
+
+    def test_nested_branches_not_counted(self) -> None:
+        """Test that the branches of nested functions do not count toward their parent."""
+        code = (
+            "function outer(x) {\n"
+            "  if (x) {\n"
+            "    return 1;\n"
+            "  }\n"
+            "  function inner(y) {\n"
+            "    while (y) {}\n"
+            "  }\n"
+            "}\n"
+        )
+        options = dataclasses.replace(self.options, annotate_complexity=True)
+        self.assertEqual(
+            extract_skeleton(code, "javascript", options=options),
+            "function outer(x) { // 8 lines, 1 branch\n}",
+        )
+
+    def test_invalid_options(self) -> None:
+        """Test that complexity annotations require size annotations."""
+        with self.assertRaises(ValueError):
+            LopperOptions(annotate_complexity=True)
+
+
+class TestImports(unittest.TestCase):
+    """Test dropping and collapsing import statements."""
+
+    RUST_CODE = (
+        "use std::fmt;\n"
+        "use std::io::{self, Read};\n"
+        "\n"
+        "use crate::config::Config;\n"
+        "\n"
+        "fn main() {\n"
+        "    run();\n"
+        "}\n"
+    )
+
+    def test_imports_kept_by_default(self) -> None:
+        """Test that import statements are kept by default."""
+        skeleton = extract_skeleton(self.RUST_CODE, "rust")
+        self.assertIn("use std::io::{self, Read};", skeleton)
+        self.assertIn("use crate::config::Config;", skeleton)
+
+    def test_drop_imports(self) -> None:
+        """Test that dropped imports leave the rest of the file untouched."""
+        skeleton = extract_skeleton(self.RUST_CODE, "rust", options=LopperOptions(imports="drop"))
+        self.assertNotIn("use ", skeleton)
+        self.assertEqual(skeleton.strip(), "fn main() {\n}")
+
+    def test_collapse_imports_per_group(self) -> None:
+        """Test that each group of imports collapses into its own summary, lone ones kept."""
+        skeleton = extract_skeleton(
+            self.RUST_CODE, "rust", options=LopperOptions(imports="collapse")
+        )
+        self.assertEqual(
+            skeleton,
+            "// 2 imports omitted\n\nuse crate::config::Config;\n\nfn main() {\n}",
+        )
+
+    def test_collapse_stops_at_other_code(self) -> None:
+        """Test that only imports directly following each other are folded together."""
+        code = (
+            "use std::fmt;\n"
+            "use std::io;\n"
+            "const LIMIT: usize = 8;\n"
+            "use std::mem;\n"
+            "// Local modules\n"
+            "use crate::config;\n"
+        )
+        skeleton = extract_skeleton(code, "rust", options=LopperOptions(imports="collapse"))
+        self.assertEqual(
+            skeleton,
+            "// 2 imports omitted\n"
+            "const LIMIT: usize = 8;\n"
+            "use std::mem;\n"
+            "// Local modules\n"
+            "use crate::config;",
+        )
+
+    def test_collapse_python_imports(self) -> None:
+        """Test that Python summaries use Python comments."""
+        code = (
+            "import os\n"
+            "from typing import Any\n"
+            "\n"
+            "\n"
+            "def cwd() -> Any:\n"
+            "    import sys\n"
+            "    return os.getcwd()\n"
+        )
+        options = LopperOptions(imports="collapse", max_body_lines=5)
+        self.assertEqual(
+            extract_skeleton(code, "python", options=options),
+            "# 2 imports omitted\n"
+            "\n"
+            "\n"
+            "def cwd() -> Any:\n"
+            "    import sys\n"
+            "    return os.getcwd()",
+        )
+
+    def test_collapse_typescript_imports_and_requires(self) -> None:
+        """Test that `import type` and `require` lines collapse together."""
+        code = (
+            "const fs = require('fs');\n"
+            "import type { Config } from './config';\n"
+            "import path from 'path';\n"
+            "\n"
+            "function load() {\n"
+            "  return fs.readFileSync(path.join('a', 'b'));\n"
+            "}\n"
+        )
+        skeleton = extract_skeleton(code, "typescript", options=LopperOptions(imports="collapse"))
+        self.assertEqual(skeleton, "// 3 imports omitted\n\nfunction load() {\n}")
+
+    def test_go_import_block_counts_specs(self) -> None:
+        """Test that a Go import block counts each imported package."""
+        code = (
+            "package main\n"
+            "\n"
+            "import (\n"
+            '\t"fmt"\n'
+            '\t"os"\n'
+            ")\n"
+            "\n"
+            "func main() {\n"
+            "\tfmt.Println(os.Args)\n"
+            "}\n"
+        )
+        skeleton = extract_skeleton(code, "go", options=LopperOptions(imports="collapse"))
+        self.assertEqual(
+            skeleton, "package main\n\n// 2 imports omitted\n\nfunc main() {\n}"
+        )
+
+    def test_invalid_imports_rejected(self) -> None:
+        """Test that an unknown import handling is rejected."""
+        with self.assertRaises(ValueError):
+            LopperOptions(imports="hide")  # type: ignore[arg-type]
+
+
+class TestStripTests(unittest.TestCase):
+    """Test removing test code."""
+
+    RUST_CODE = (
+        "pub fn add(a: i32, b: i32) -> i32 {\n"
+        "    a + b\n"
+        "}\n"
+        "\n"
+        "#[cfg(not(test))]\n"
+        "fn real() -> bool {\n"
+        "    true\n"
+        "}\n"
+        "\n"
+        '#[cfg(all(test, feature = "slow"))]\n'
+        "fn slow_helper() {\n"
+        "    add(1, 1);\n"
+        "}\n"
+        "\n"
+        "#[cfg(test)]\n"
+        "mod tests {\n"
+        "    use super::*;\n"
+        "\n"
+        "    #[test]\n"
+        "    fn adds() {\n"
+        "        assert_eq!(add(1, 2), 3);\n"
+        "    }\n"
+        "}\n"
+    )
+
+    def test_tests_kept_by_default(self) -> None:
+        """Test that test code is kept unless stripped."""
+        skeleton = extract_skeleton(self.RUST_CODE, "rust")
+        self.assertIn("#[cfg(test)]\nmod tests {", skeleton)
+
+    def test_strip_rust_test_gated_items(self) -> None:
+        """Test that `cfg(test)` and `cfg(all(test, ...))` items collapse into one marker."""
+        skeleton = extract_skeleton(
+            self.RUST_CODE, "rust", options=LopperOptions(strip_tests=True)
+        )
+        self.assertEqual(
+            skeleton,
+            "pub fn add(a: i32, b: i32) -> i32 {\n"
+            "}\n"
+            "\n"
+            "#[cfg(not(test))]\n"
+            "fn real() -> bool {\n"
+            "}\n"
+            "\n"
+            "// tests omitted (2 items)",
+        )
+
+    def test_skip_tests_in_outline(self) -> None:
+        """Test that test items leave the outline whole, nested symbols included."""
+        names = [symbol.name for symbol in extract_symbols(self.RUST_CODE, "rust")]
+        self.assertEqual(names, ["add", "real", "slow_helper", "tests"])
+        options = LopperOptions(skip_tests=True)
+        outline = extract_symbols(self.RUST_CODE, "rust", options=options)
+        self.assertEqual([symbol.name for symbol in outline], ["add", "real"])
+        code = (
+            "mod checks {\n"
+            "    // Helpers for #[test] functions\n"
+            "    pub fn tested() -> bool {\n"
+            '        "#[test]".is_empty()\n'
+            "    }\n"
+            "\n"
+            "    #[test]\n"
+            "    fn checks_out() {}\n"
+            "\n"
+            "    #[bench]\n"
+            "    #[inline]\n"
+            "    fn bench_check(b: &mut Bencher) {}\n"
+            "}\n"
+        )
+        (checks,) = extract_symbols(code, "rust", options=options)
+        self.assertEqual([child.name for child in checks.children], ["tested"])
+        skeleton = extract_skeleton(code, "rust", options=LopperOptions(strip_tests=True))
+        self.assertTrue(skeleton.endswith("    // tests omitted (2 items)\n}"))
+
+    def test_strip_rust_test_functions(self) -> None:
+        """Test that `#[test]` functions outside a test module are removed in place."""
+        code = (
+            "mod checks {\n"
+            "    pub fn check() -> bool {\n"
+            "        true\n"
+            "    }\n"
+            "\n"
+            "    #[test]\n"
+            "    fn checks_out() {\n"
+            "        assert!(check());\n"
+            "    }\n"
+            "}\n"
+        )
+        skeleton = extract_skeleton(code, "rust", options=LopperOptions(strip_tests=True))
+        self.assertEqual(
+            skeleton,
+            "mod checks {\n"
+            "    pub fn check() -> bool {\n"
+            "    }\n"
+            "\n"
+            "    // tests omitted (1 item)\n"
+            "}",
+        )
+
+    def test_strip_python_tests(self) -> None:
+        """Test that `test_` functions and `TestCase` classes are removed."""
+        code = (
+            "import unittest\n"
+            "\n"
+            "\n"
+            "def add(a, b):\n"
+            "    return a + b\n"
+            "\n"
+            "\n"
+            "def test_add():\n"
+            "    assert add(1, 2) == 3\n"
+            "\n"
+            "\n"
+            "class TestAdd(unittest.TestCase):\n"
+            "    def test_zero(self):\n"
+            "        self.assertEqual(add(0, 0), 0)\n"
+        )
+        skeleton = extract_skeleton(code, "python", options=LopperOptions(strip_tests=True))
+        self.assertEqual(
+            skeleton,
+            "import unittest\n\n\ndef add(a, b):\n    ...\n\n\n# tests omitted (2 items)",
+        )
+
+    def test_strip_go_tests(self) -> None:
+        """Test that Go test functions are removed, but not look-alike names."""
+        code = (
+            "package calc\n"
+            "\n"
+            "func Add(a, b int) int {\n"
+            "\treturn a + b\n"
+            "}\n"
+            "\n"
+            "func TestAdd(t *testing.T) {\n"
+            "\tif Add(1, 2) != 3 {\n"
+            "\t\tt.Fail()\n"
+            "\t}\n"
+            "}\n"
+            "\n"
+            "func Testify() bool {\n"
+            "\treturn true\n"
+            "}\n"
+        )
+        skeleton = extract_skeleton(code, "go", options=LopperOptions(strip_tests=True))
+        self.assertEqual(
+            skeleton,
+            "package calc\n"
+            "\n"
+            "func Add(a, b int) int {\n"
+            "}\n"
+            "\n"
+            "// tests omitted (1 item)\n"
+            "\n"
+            "func Testify() bool {\n"
+            "}",
+        )
+
+    def test_strip_javascript_test_blocks(self) -> None:
+        """Test that top-level `describe`, `it` and `test` calls are removed."""
+        code = (
+            "export function add(a, b) {\n"
+            "  return a + b;\n"
+            "}\n"
+            "\n"
+            "describe('add', () => {\n"
+            "  it('adds', () => {\n"
+            "    expect(add(1, 2)).toBe(3);\n"
+            "  });\n"
+            "});\n"
+            "\n"
+            "test.skip('later', () => {});\n"
+        )
+        skeleton = extract_skeleton(
+            code, "javascript", options=LopperOptions(strip_tests=True, body_placeholders=True)
+        )
+        self.assertEqual(
+            skeleton, "export function add(a, b) { /* 3 lines */ }\n\n// tests omitted (2 items)"
+        )
+
+
+class TestSignatureLength(unittest.TestCase):
+    """Test shortening signatures longer than a limit."""
+
+    def test_rust_many_parameters(self) -> None:
+        """Test that trailing parameters are cut in favor of a count, keeping the return type."""
+        parameters = ", ".join(f"p{i}: i32" for i in range(50))
+        code = (
+            f"pub fn wide({parameters}) -> Result<(), String> {{\n    Ok(())\n}}\n"
+            "\n"
+            "pub fn narrow(a: i32) -> i32 {\n    a\n}\n"
+        )
+        options = LopperOptions(max_signature_len=80)
+        skeleton = extract_skeleton(code, "rust", options=options)
+        self.assertIn(
+            "pub fn wide(p0: i32, p1: i32, p2: i32, /* +47 more */) -> Result<(), String> {",
+            skeleton,
+        )
+        self.assertIn("pub fn narrow(a: i32) -> i32 {", skeleton)
+        self.assertNotIn("p3", skeleton)
+
+    def test_rust_where_clause(self) -> None:
+        """Test that a long where clause collapses before any parameter is cut."""
+        bounds = ",\n".join(f"    T{i}: Clone + Send + Sync + 'static" for i in range(10))
+        code = f"pub fn bound<T0>(value: T0) -> T0\nwhere\n{bounds}\n{{\n    value\n}}\n"
+        skeleton = extract_skeleton(code, "rust", options=LopperOptions(max_signature_len=60))
+        self.assertIn("pub fn bound<T0>(value: T0) -> T0\nwhere /* ... */\n{", skeleton)
+
+    def test_rust_type_parameters(self) -> None:
+        """Test that a long type parameter list collapses before any parameter is cut."""
+        generics = ", ".join(f"T{i}: Clone + Send + Sync" for i in range(10))
+        code = f"pub fn generic<{generics}>(value: T0) -> T0 {{\n    value\n}}\n"
+        skeleton = extract_skeleton(code, "rust", options=LopperOptions(max_signature_len=60))
+        self.assertIn("pub fn generic</* +10 more */>(value: T0) -> T0 {", skeleton)
+
+    def test_typescript_inline_object_type(self) -> None:
+        """Test that a parameter is cut whole rather than inside its type."""
+        fields = " ".join(f"field{i}: string;" for i in range(200))
+        code = (
+            f"export function configure(options: {{ {fields} }}): Config {{\n"
+            "  return make(options);\n"
+            "}\n"
+        )
+        skeleton = extract_skeleton(
+            code, "typescript", options=LopperOptions(max_signature_len=80)
+        )
+        self.assertIn("export function configure(/* +1 more */): Config {", skeleton)
+        self.assertNotIn("field0", skeleton)
+        with self.assertRaises(ValueError):
+            LopperOptions(max_signature_len=-1)
+
+
+class TestExpandAliases(unittest.TestCase):
+    """Test expanding the references to local type aliases."""
+
+    options: ClassVar[LopperOptions] = LopperOptions(expand_aliases=True)
+
+    def test_rust_generic_alias(self) -> None:
+        """Test that type arguments are substituted and unresolvable references kept."""
+        code = (
+            "pub type Result<T> = std::result::Result<T, Error>;\n"
+            "type Id = u64;\n"
+            "type List = Vec<List>;\n"
+            "\n"
+            "pub fn load(id: Id) -> Result<Vec<Id>> {\n"
+            "    todo!()\n"
+            "}\n"
+            "\n"
+            "pub fn raw() -> Result<(), Error> {\n"
+            "    Ok(())\n"
+            "}\n"
+            "\n"
+            "pub fn fmt(list: List) -> std::fmt::Result {\n"
+            "    Ok(())\n"
+            "}\n"
+        )
+        skeleton = extract_skeleton(code, "rust", options=self.options)
+        self.assertIn("pub type Result<T> = std::result::Result<T, Error>;\n", skeleton)
+        self.assertIn(
+            "pub fn load(id: u64) -> std::result::Result<Vec<u64>, Error> {", skeleton
+        )
+        self.assertIn("pub fn raw() -> Result<(), Error> {", skeleton)
+        self.assertIn("pub fn fmt(list: Vec<List>) -> std::fmt::Result {", skeleton)
+        self.assertIn("pub fn load(id: Id) -> Result<Vec<Id>> {", extract_skeleton(code, "rust"))
+
+    def test_type_parameters_shadow_aliases(self) -> None:
+        """Test that a type parameter named like an alias is not expanded in its item."""
+        code = (
+            "type T = u64;\n"
+            "\n"
+            "pub fn first<'a, T: Clone>(items: &'a [T]) -> T {\n"
+            "    todo!()\n"
+            "}\n"
+            "\n"
+            "pub fn id(x: T) -> T {\n"
+            "    x\n"
+            "}\n"
+        )
+        skeleton = extract_skeleton(code, "rust", options=self.options)
+        self.assertIn("pub fn first<'a, T: Clone>(items: &'a [T]) -> T {", skeleton)
+        self.assertIn("pub fn id(x: u64) -> u64 {", skeleton)
+
+    def test_rust_alias_declared_twice(self) -> None:
+        """Test that an alias declared in two modules is not expanded."""
+        code = (
+            "mod a {\n    pub type Id = u32;\n}\n"
+            "mod b {\n    pub type Id = u64;\n}\n"
+            "pub fn get(id: Id) {\n    run(id);\n}\n"
+        )
+        skeleton = extract_skeleton(code, "rust", options=self.options)
+        self.assertIn("pub fn get(id: Id) {", skeleton)
+
+    def test_typescript_function_alias(self) -> None:
+        """Test that function types are parenthesized where they are expanded."""
+        code = (
+            "type Handler<T> = (event: T) => void;\n"
+            "\n"
+            "export function on(handler: Handler<string> | null): void {\n"
+            '  handler?.("x");\n'
+            "}\n"
+        )
+        skeleton = extract_skeleton(code, "typescript", options=self.options)
+        self.assertIn(
+            "export function on(handler: ((event: string) => void) | null): void", skeleton
+        )
+
+
+class TestRedactLiterals(unittest.TestCase):
+    """Test redacting the literals of kept code."""
+
+    def test_redact_rust_literals(self) -> None:
+        """Test that constants and kept bodies are redacted, but not docs or identifiers."""
+        code = (
+            '/// Limit of "retries".\n'
+            '#[serde(rename = "max")]\n'
+            "pub const MAX: u32 = 1000;\n"
+            'const NAME: &str = "secret-token";\n'
+            "\n"
+            'extern "C" {\n'
+            "    fn abs(x: i32) -> i32;\n"
+            "}\n"
+            "\n"
+            "fn scale(v2: u64) -> u64 {\n"
+            "    let total = v2 * 10;\n"
+            "    total\n"
+            "}\n"
+        )
+        options = LopperOptions(redact_literals=True, max_body_lines=4)
+        self.assertEqual(
+            extract_skeleton(code, "rust", options=options),
+            '/// Limit of "retries".\n'
+            '#[serde(rename = "max")]\n'
+            "pub const MAX: u32 = 0;\n"
+            'const NAME: &str = "\u2026";\n'
+            "\n"
+            'extern "C" {\n'
+            "    fn abs(x: i32) -> i32;\n"
+            "}\n"
+            "\n"
+            "fn scale(v2: u64) -> u64 {\n"
+            "    let total = v2 * 0;\n"
+            "    total\n"
+            "}",
+        )
+
+    def test_redact_python_defaults(self) -> None:
+        """Test that default values are redacted and docstrings kept."""
+        code = (
+            '"""Module "docs" 42."""\n'
+            "\n"
+            "RETRIES = 3\n"
+            'URL = f"https://{HOST}/api"\n'
+            "\n"
+            "\n"
+            'def fetch(path: str = "/", timeout: float = 2.5):\n'
+            '    """Fetch "path"."""\n'
+            '    return get(path, retries=RETRIES, token="abc")\n'
+        )
+        skeleton = extract_skeleton(code, "python", options=LopperOptions(redact_literals=True))
+        self.assertEqual(
+            skeleton,
+            '"""Module "docs" 42."""\n'
+            "\n"
+            "RETRIES = 0\n"
+            'URL = "\u2026"\n'
+            "\n"
+            "\n"
+            'def fetch(path: str = "\u2026", timeout: float = 0):\n'
+            '    """Fetch "path"."""',
+        )
+
+    def test_literals_kept_by_default(self) -> None:
+        """Test that literals are kept unless redacted."""
+        skeleton = extract_skeleton("const MAX: u32 = 1000;\n", "rust")
+        self.assertEqual(skeleton, "const MAX: u32 = 1000;")
+
+
+class TestMaxLiteralLen(unittest.TestCase):
+    """Test truncating the long literals of kept code."""
+
+    def test_truncate_generated_table(self) -> None:
+        """Test that a huge constant array keeps its declaration and first elements."""
+        elements = ", ".join(str(n) for n in range(100_000))
+        code = f"pub static TABLE: [u32; 100000] = [{elements}];\n"
+        options = LopperOptions(max_literal_len=16)
+        skeleton = extract_skeleton(code, "rust", options=options)
+        elided = len(elements) - len("0, 1, 2, 3, 4,")
+        self.assertEqual(
+            skeleton,
+            f"pub static TABLE: [u32; 100000] = [0, 1, 2, 3, 4, /* {elided} bytes elided */];",
+        )
+        self.assertLess(len(skeleton), len(code) // 1000)
+
+        # Items whose bodies are kept keep their literals whole
+        kept = LopperOptions(max_literal_len=16, keep_bodies=("TABLE",))
+        self.assertEqual(extract_skeleton(code, "rust", options=kept), code.rstrip())
+        self.assertEqual(extract_skeleton(code, "rust"), code.rstrip())
+
+    def test_truncate_python_literals(self) -> None:
+        """Test that maps end with a line comment and strings note the count in quotes."""
+        code = 'COLORS = {"red": 1, "green": 2, "blue": 3}\nBANNER = "Hello, wonderful world"\n'
+        skeleton = extract_skeleton(code, "python", options=LopperOptions(max_literal_len=12))
+        self.assertEqual(
+            skeleton,
+            'COLORS = {"red": 1,  # 22 bytes elided\n}\n'
+            'BANNER = "Hello, wond... (11 bytes elided)"',
+        )
+
+    def test_invalid_max_literal_len(self) -> None:
+        """Test that a negative limit is rejected."""
+        with self.assertRaises(ValueError):
+            LopperOptions(max_literal_len=-1)
+
+
+class TestPrototypes(unittest.TestCase):
+    """Test turning C and C++ function definitions into prototypes."""
+
+    options: LopperOptions = LopperOptions(prototypes=True)
+
+    def test_c_definitions_become_prototypes(self) -> None:
+        """Test that C functions become prototypes while macros and structs stay."""
+        code = (
+            "#include <stdio.h>\n"
+            "\n"
+            "#define MAX(a, b) ((a) > (b) ? (a) : (b))\n"
+            "\n"
+            "struct point {\n"
+            "    int x;\n"
+            "    int y;\n"
+            "};\n"
+            "\n"
+            "static int add(int a, int b)\n"
+            "{\n"
+            "    return a + b;\n"
+            "}\n"
+            "\n"
+            "int *first(int *items) {\n"
+            "    return items;\n"
+            "}\n"
+        )
+        self.assertEqual(
+            extract_skeleton(code, "c", options=self.options),
+            "#include <stdio.h>\n"
+            "\n"
+            "#define MAX(a, b) ((a) > (b) ? (a) : (b))\n"
+            "\n"
+            "struct point {\n"
+            "    int x;\n"
+            "    int y;\n"
+            "};\n"
+            "\n"
+            "static int add(int a, int b);\n"
+            "\n"
+            "int *first(int *items);",
+        )
+
+    def test_cpp_methods_become_prototypes(self) -> None:
+        """Test that inline and out-of-line methods keep their signatures only."""
+        code = (
+            "class Counter {\n"
+            "public:\n"
+            "    Counter() : count_(0) {}\n"
+            "    Counter(const Counter&) = default;\n"
+            "    int get() const {\n"
+            "        return count_;\n"
+            "    }\n"
+            "private:\n"
+            "    int count_;\n"
+            "};\n"
+            "\n"
+            "void Counter::bump() {\n"
+            "    auto twice = [](int x) { return x * 2; };\n"
+            "    count_ = twice(count_);\n"
+            "}\n"
+        )
+        self.assertEqual(
+            extract_skeleton(code, "cpp", options=self.options),
+            "class Counter {\n"
+            "public:\n"
+            "    Counter();\n"
+            "    Counter(const Counter&) = default;\n"
+            "    int get() const;\n"
+            "private:\n"
+            "    int count_;\n"
+            "};\n"
+            "\n"
+            "void Counter::bump();",
+        )
+
+    def test_short_bodies_kept(self) -> None:
+        """Test that bodies kept by `max_body_lines` stay definitions."""
+        code = "int one(void) { return 1; }\n\nint two(void) {\n    return 2;\n}\n"
+        options = LopperOptions(prototypes=True, max_body_lines=1)
+        self.assertEqual(
+            extract_skeleton(code, "c", options=options),
+            "int one(void) { return 1; }\n\nint two(void);",
+        )
+
+
+class TestStubMode(unittest.TestCase):
+    """Test Rust skeletons that still compile."""
+
+    options: ClassVar[LopperOptions] = LopperOptions(stub_mode=True)
+
+    code: ClassVar[str] = """\
+use std::collections::HashMap;
+use std::fmt;
+
+/// Largest key accepted.
+pub const MAX_KEY: usize = 1 << 10;
+static GREETING: &str = "hello";
+
+macro_rules! square {
+    ($x:expr) => {
+        $x * $x
+    };
+}
+
+pub struct Cache<'a, K: fmt::Debug> {
+    entries: HashMap<K, &'a str>,
+}
+
+pub enum Event {
+    Hit { key: usize },
+    Miss,
+}
+
+pub trait Store {
+    fn get(&self, key: usize) -> Option<&str>;
+
+    fn describe(&self) -> String {
+        format!("{} entries", square!(2))
+    }
+}
+
+impl<'a, K> Cache<'a, K>
+where
+    K: fmt::Debug + std::hash::Hash + Eq,
+{
+    pub fn new() -> Self {
+        Cache { entries: HashMap::new() }
+    }
+
+    pub fn lookup<Q: ?Sized>(&self, key: &Q) -> Option<&'a str>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: std::hash::Hash + Eq,
+    {
+        self.entries.get(key).copied()
+    }
+}
+"""
+
+    expected: ClassVar[str] = """\
+use std::collections::HashMap;
+use std::fmt;
+
+/// Largest key accepted.
+pub const MAX_KEY: usize = 1 << 10;
+static GREETING: &str = "hello";
+
+macro_rules! square {
+    ($x:expr) => {
+        $x * $x
+    };
+}
+
+pub struct Cache<'a, K: fmt::Debug> {
+    entries: HashMap<K, &'a str>,
+}
+
+pub enum Event {
+    Hit { key: usize },
+    Miss,
+}
+
+pub trait Store {
+    fn get(&self, key: usize) -> Option<&str>;
+
+    fn describe(&self) -> String;
+}
+
+impl<'a, K> Cache<'a, K>
+where
+    K: fmt::Debug + std::hash::Hash + Eq,
+{
+    pub fn new() -> Self { unimplemented!() }
+
+    pub fn lookup<Q: ?Sized>(&self, key: &Q) -> Option<&'a str>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: std::hash::Hash + Eq,
+    { unimplemented!() }
+}"""
+
+    def test_stub(self) -> None:
+        """Test that bodies become stubs and default trait methods declarations."""
+        self.assertEqual(extract_skeleton(self.code, "rust", options=self.options), self.expected)
+
+    def test_sample_stub_parses(self) -> None:
+        """Test that the stub of the Rust sample parses without errors."""
+        skeleton = extract_skeleton(SAMPLE_RS, "rust", options=self.options)
+        self.assertIn("    fn process(&self) { unimplemented!() }\n", skeleton)
+        self.assertIn("    fn add(&self, x: i32) -> i32;\n", skeleton)
+        self.assertFalse(get_extractor("rust").parse(skeleton.encode()).root_node.has_error)
+
+    @unittest.skipUnless(shutil.which("rustc"), "rustc is not installed")
+    def test_stub_compiles(self) -> None:
+        """Test that rustc type-checks the stubs as a library crate."""
+        for code in (self.code, SAMPLE_RS):
+            with tempfile.TemporaryDirectory() as tmpdir:
+                path = Path(tmpdir) / "lib.rs"
+                path.write_text(extract_skeleton(code, "rust", options=self.options))
+                command = ["rustc", "--crate-type", "lib", "--edition", "2021"]
+                result = subprocess.run(
+                    [*command, "--emit", "metadata", "--out-dir", tmpdir, str(path)],
+                    capture_output=True,
+                    text=True,
+                    check=False,
+                )
+                self.assertEqual(result.returncode, 0, result.stderr)
+
+    def test_prototypes_keep_default_methods(self) -> None:
+        """Test that without stubs, prototypes leave Rust default methods elided as usual."""
+        skeleton = extract_skeleton(self.code, "rust", options=LopperOptions(prototypes=True))
+        self.assertEqual(skeleton, extract_skeleton(self.code, "rust"))
+        self.assertIn("    fn describe(&self) -> String {", skeleton)
+
+    def test_other_languages_unaffected(self) -> None:
+        """Test that languages without a stub body are lopped as usual."""
+        code = "def run():\n    go()\n"
+        self.assertEqual(
+            extract_skeleton(code, "python", options=self.options), extract_skeleton(code, "python")
+        )
+
+    def test_conflicting_options(self) -> None:
+        """Test that options changing the kept code cannot be combined with stubs."""
+        with self.assertRaises(ValueError):
+            LopperOptions(stub_mode=True, struct_fields="collapse")
+        with self.assertRaises(ValueError):
+            LopperOptions(stub_mode=True, imports="drop")
+        for conflict in (
+            {"placeholder": "{lines}"},
+            {"placeholder_comment": "elided"},
+            {"body_head_lines": 2},
+        ):
+            with self.subTest(conflict=conflict), self.assertRaises(ValueError):
+                LopperOptions(stub_mode=True, **conflict)
+
+
+class TestKeepBodies(unittest.TestCase):
+    """Test keeping the bodies of items selected by pattern."""
+
+    def test_qualified_path_keeps_both_impls(self) -> None:
+        """Test that `Calculator::add` keeps the inherent and trait bodies only."""
+        options = LopperOptions(keep_bodies=("Calculator::add",))
+        skeleton = extract_skeleton(SAMPLE_RS, "rust", options=options)
+        self.assertIn("pub fn add(&self, x: i32, y: i32) -> i32 {\n        x + y\n    }", skeleton)
+        self.assertIn("fn add(&self, x: i32) -> i32 {\n        self.value + x\n    }", skeleton)
+        self.assertNotIn("self.value * x", skeleton)
+        self.assertNotIn("fibonacci(n - 1)", skeleton)
+
+        options = LopperOptions(keep_bodies=["<Calculator as Arithmetic>::add", "fib*"])
+        skeleton = extract_skeleton(SAMPLE_RS, "rust", options=options)
+        self.assertIn("self.value + x", skeleton)
+        self.assertIn("fibonacci(n - 1)", skeleton)
+        self.assertNotIn("x + y", skeleton)
+
+    def test_patterns_are_case_sensitive(self) -> None:
+        """Test that patterns only match names with the same case."""
+        skeleton = extract_skeleton(
+            SAMPLE_RS, "rust", options=LopperOptions(keep_bodies=("calculator::add",))
+        )
+        self.assertEqual(skeleton, extract_skeleton(SAMPLE_RS, "rust"))
+        with self.assertRaises(ValueError):
+            LopperOptions(keep_bodies="fibonacci")  # type: ignore[arg-type]
+
+
+class TestFocus(unittest.TestCase):
+    """Test keeping the items a diff touches whole."""
+
+    code = (
+        "pub struct Calculator {\n"
+        "    total: i32,\n"
+        "}\n"
+        "\n"
+        "impl Calculator {\n"
+        "    /// Adds a value.\n"
+        "    pub fn add(&mut self, x: i32) {\n"
+        "        self.total += x;\n"
+        "    }\n"
+        "\n"
+        "    pub fn process(&mut self, values: &[i32]) {\n"
+        "        for value in values {\n"
+        "            self.add(*value * 2);\n"
+        "        }\n"
+        "    }\n"
+        "}\n"
+        "\n"
+        "fn helper() {\n"
+        '    println!("hi");\n'
+        "}\n"
+    )
+
+    def test_diff_line_ranges(self) -> None:
+        """Test that added lines and deletion points are found in the new file's lines."""
+        diff = (
+            "diff --git a/src/lib.rs b/src/lib.rs\n"
+            "--- a/src/lib.rs\n"
+            "+++ b/src/lib.rs\n"
+            "@@ -12,3 +12,4 @@ impl Calculator {\n"
+            "         for value in values {\n"
+            "-            self.add(*value);\n"
+            "+            self.add(*value * 2);\n"
+            "+            self.log();\n"
+            "         }\n"
+            "@@ -30,2 +31,0 @@\n"
+            "--- removed line starting with dashes\n"
+            "-x\n"
+            "diff --git a/old.rs b/old.rs\n"
+            "--- a/old.rs\n"
+            "+++ /dev/null\n"
+            "@@ -1 +0,0 @@\n"
+            "-fn old() {}\n"
+            "--- /dev/null\n"
+            "+++ b/new.py\n"
+            "@@ -0,0 +1,2 @@\n"
+            "+def f():\n"
+            "+    pass\n"
+            "\\ No newline at end of file\n"
+        )
+        self.assertEqual(
+            diff_line_ranges(diff), {"src/lib.rs": [(13, 14), (31, 31)], "new.py": [(1, 2)]}
+        )
+
+    def test_touched_method_keeps_body(self) -> None:
+        """Test that a line changed inside `Calculator::process` keeps only its body."""
+        diff = (
+            "--- a/lib.rs\n"
+            "+++ b/lib.rs\n"
+            "@@ -13 +13 @@\n"
+            "-            self.add(*value);\n"
+            "+            self.add(*value * 2);\n"
+        )
+        focus = diff_line_ranges(diff)["lib.rs"]
+        skeleton = lop_with_focus(self.code, "rust", focus)
+        self.assertIn("self.add(*value * 2);", skeleton)
+        self.assertNotIn("self.total += x", skeleton)
+        self.assertNotIn("println!", skeleton)
+        self.assertIn("pub fn add(&mut self, x: i32)", skeleton)
+        self.assertEqual(lop_with_focus(self.code, "rust", []), extract_skeleton(self.code, "rust"))
+
+    def test_doc_comment_focuses_item(self) -> None:
+        """Test that a changed doc comment keeps its item whole, and a type line the type."""
+        skeleton = lop_with_focus(self.code, "rust", [(6, 6), (2, 2)])
+        self.assertIn("self.total += x", skeleton)
+        self.assertNotIn("self.add(*value * 2)", skeleton)
+        skeleton = lop_with_focus(self.code, "rust", [(5, 5)])
+        self.assertIn("self.total += x", skeleton)
+        self.assertIn("self.add(*value * 2)", skeleton)
+
+
+class TestLopSymbol(unittest.TestCase):
+    """Test extracting a symbol and the items related to it."""
+
+    def test_rust_type_and_impls(self) -> None:
+        """Test that a struct comes with both impl blocks, in source order, and nothing else."""
+        skeleton = lop_symbol(SAMPLE_RS, "rust", "Calculator")
+        self.assertTrue(skeleton.startswith("pub struct Calculator {\n    value: i32,\n}\n\n"))
+        inherent = skeleton.index("impl Calculator {")
+        self.assertLess(inherent, skeleton.index("impl Arithmetic for Calculator {"))
+        self.assertIn("pub fn new(initial: i32) -> Self", skeleton)
+        self.assertNotIn("Calculator { value: initial }", skeleton)
+        self.assertNotIn("fibonacci", skeleton)
+        self.assertNotIn("pub trait Arithmetic", skeleton)
+
+    def test_python_class_and_functions(self) -> None:
+        """Test that a class comes with the functions mentioning it in their signature."""
+        code = (
+            "import os\n"
+            "\n"
+            "\n"
+            "class Cache:\n"
+            "    def get(self, key):\n"
+            "        return self.data[key]\n"
+            "\n"
+            "\n"
+            "def make_cache() -> Cache:\n"
+            "    return Cache()\n"
+            "\n"
+            "\n"
+            "def unrelated():\n"
+            "    return os.getcwd()\n"
+        )
+        skeleton = lop_symbol(code, "python", "Cache")
+        self.assertTrue(skeleton.startswith("class Cache:\n    def get(self, key):"))
+        self.assertIn("\n\ndef make_cache() -> Cache:", skeleton)
+        self.assertNotIn("import os", skeleton)
+        self.assertNotIn("unrelated", skeleton)
+
+    def test_unknown_symbol(self) -> None:
+        """Test that a missing symbol is reported with the top-level names found."""
+        with self.assertRaises(SymbolNotFoundError) as caught:
+            lop_symbol(SAMPLE_RS, "rust", "Calculater")
+        self.assertEqual(caught.exception.symbol, "Calculater")
+        self.assertEqual(caught.exception.names, ["fibonacci", "Calculator", "Arithmetic"])
+        self.assertIn("Calculator", str(caught.exception))
+
+
+class TestDiffSkeleton(unittest.TestCase):
+    """Test skeletons of the symbols changed between two versions of a source."""
+
+    old: ClassVar[str] = (
+        "pub struct Calculator {\n    value: i32,\n}\n\n"
+        "impl Calculator {\n"
+        "    pub fn add(&self, x: i32) -> i32 {\n        self.value + x\n    }\n\n"
+        "    pub fn reset(&mut self) {\n        self.value = 0;\n    }\n\n"
+        "    fn trace(&self) {\n        println!(\"{}\", self.value);\n    }\n"
+        "}\n\n"
+        "fn unchanged() {\n    run();\n}\n"
+    )
+    new: ClassVar[str] = (
+        "pub struct Calculator {\n    value: i64,\n}\n\n"
+        "impl Calculator {\n"
+        "    pub fn add(&self, x: i64) -> i64 {\n        self.value + x\n    }\n\n"
+        "    pub fn reset(&mut self) {\n        self.value = 1;\n    }\n\n"
+        "    pub fn clear(&mut self) {}\n"
+        "}\n\n"
+        "fn unchanged() {\n        run();\n}\n"
+    )
+
+    def test_changed_symbols(self) -> None:
+        """Test that only changed items are reported, nested changes apart."""
+        diff = skeletonize_diff(self.old, self.new, "rust")
+        self.assertEqual(
+            [(change.kind, change.symbol.qualified_name) for change in diff.changes],
+            [
+                ("body_changed", "Calculator"),
+                ("modified", "Calculator::add"),
+                ("body_changed", "Calculator::reset"),
+                ("added", "Calculator::clear"),
+                ("removed", "Calculator::trace"),
+            ],
+        )
+        self.assertEqual(diff.changes[1].old_symbol.signature, "pub fn add(&self, x: i32) -> i32")
+        self.assertTrue(
+            diff.skeleton.startswith(
+                "// body_changed: Calculator\npub struct Calculator {\n    value: i64,\n}\n\n"
+                "// modified: Calculator::add\n    pub fn add(&self, x: i64) -> i64"
+            )
+        )
+        self.assertNotIn("self.value + x", diff.skeleton)
+        self.assertIn("// removed: Calculator::trace\n    fn trace(&self)", diff.skeleton)
+
+    def test_signature_changes_only(self) -> None:
+        """Test that body changes can be left out, and hidden symbols are."""
+        options = LopperOptions(visibility="public")
+        diff = skeletonize_diff(self.old, self.new, "rust", options=options, body_changes=False)
+        self.assertEqual(
+            [(change.kind, change.symbol.name) for change in diff.changes],
+            [("modified", "add"), ("added", "clear")],
+        )
+        self.assertEqual(skeletonize_diff(self.old, self.old, "rust").skeleton, "")
+
+
+class TestElidedRegions(unittest.TestCase):
+    """Test reporting the regions a skeleton elides."""
+
+    def test_round_trip(self) -> None:
+        """Test that expanding every region gives back the sample byte for byte."""
+        for options in (
+            LopperOptions(),
+            LopperOptions(body_placeholders=True),
+            LopperOptions(body_placeholders=True, keep_doc_comments=False, imports="collapse"),
+        ):
+            result = extract_with_regions(SAMPLE_RS, "rust", options=options)
+            self.assertEqual(result.skeleton, extract_skeleton(SAMPLE_RS, "rust", options=options))
+            self.assertEqual(result.expand(), SAMPLE_RS)
+            self.assertEqual(result.expand([]), result.skeleton)
+
+    def test_region_spans(self) -> None:
+        """Test that spans cover the placeholder and the removed source of an item."""
+        options = LopperOptions(body_placeholders=True)
+        result = extract_with_regions(SAMPLE_RS, "rust", options=options)
+        region = result.regions[0]
+        (start, end), (original_start, original_end) = region.output_span, region.original_span
+        self.assertEqual((region.item_name, region.item_kind), ("fibonacci", "function"))
+        self.assertEqual(result.skeleton.encode()[start:end], b" /* 6 lines */ ")
+        self.assertEqual(
+            SAMPLE_RS.encode()[original_start:original_end].strip()[:10], b"if n <= 1 "
+        )
+        expanded = result.expand([region])
+        self.assertIn("fibonacci(n - 1) + fibonacci(n - 2)", expanded)
+        self.assertNotIn("let closure", expanded)
+        last = result.regions[-1]
+        self.assertEqual(last.original_span[1], len(SAMPLE_RS.encode()))
+        self.assertIsNone(last.item_name)
+
+        with self.assertRaises(ValueError):
+            extract_with_regions(SAMPLE_RS, "rust", options=LopperOptions(line_numbers=True))
+
+
+class TestMaxDepth(unittest.TestCase):
+    """Test limiting how deep nested items are kept."""
+
+    def test_top_level_only(self) -> None:
+        """Test that depth 0 keeps top-level items and marks their hidden members."""
+        skeleton = extract_skeleton(SAMPLE_RS, "rust", options=LopperOptions(max_depth=0))
+        self.assertEqual(
+            skeleton,
+            "fn fibonacci(n: u32) -> u32 {\n"
+            "}\n"
+            "\n"
+            "pub struct Calculator {\n"
+            "    value: i32,\n"
+            "}\n"
+            "\n"
+            "pub trait Arithmetic {\n"
+            "    // 2 items hidden\n"
+            "}\n"
+            "\n"
+            "impl Calculator {\n"
+            "    // 3 items hidden\n"
+            "}\n"
+            "\n"
+            "impl Arithmetic for Calculator {\n"
+            "    // 2 items hidden\n"
+            "}",
+        )
+
+    def test_deeper_limits(self) -> None:
+        """Test that depth 1 keeps methods, and only the deepest items are hidden."""
+        skeleton = extract_skeleton(SAMPLE_RS, "rust", options=LopperOptions(max_depth=1))
+        self.assertEqual(skeleton, extract_skeleton(SAMPLE_RS, "rust"))
+        code = (
+            "class Outer:\n"
+            "    class Inner:\n"
+            "        def method(self):\n"
+            "            return 1\n"
+            "\n"
+            "    def run(self):\n"
+            "        return 2\n"
+        )
+        skeleton = extract_skeleton(code, "python", options=LopperOptions(max_depth=1))
+        self.assertEqual(
+            skeleton,
+            "class Outer:\n"
+            "    class Inner:\n"
+            "        ...  # 1 item hidden\n"
+            "\n"
+            "    def run(self):\n"
+            "        ...",
+        )
+
+    def test_nested_modules_and_other_options(self) -> None:
+        """Test depth limits with nested modules, visibility and kept bodies."""
+        code = (
+            "pub mod outer {\n"
+            "    pub mod inner {\n"
+            "        pub fn deep() {}\n"
+            "    }\n"
+            "    fn private() {}\n"
+            "    pub fn shallow() {}\n"
+            "}\n"
+        )
+        options = LopperOptions(max_depth=1, visibility="crate")
+        self.assertEqual(
+            extract_skeleton(code, "rust", options=options),
+            "pub mod outer {\n"
+            "    pub mod inner {\n"
+            "        // 1 item hidden\n"
+            "    }\n"
+            "    pub fn shallow() {}\n"
+            "}",
+        )
+
+        options = LopperOptions(max_depth=0, keep_bodies=("Calculator::add",))
+        skeleton = extract_skeleton(SAMPLE_RS, "rust", options=options)
+        self.assertIn("pub fn add(&self, x: i32, y: i32) -> i32 {\n        x + y\n    }", skeleton)
+        self.assertIn("fn add(&self, x: i32) -> i32 {\n        self.value + x\n    }", skeleton)
+        self.assertNotIn("fn multiply(&self, x: i32) -> i32 {", skeleton)
+        self.assertEqual(skeleton.count("// 1 item hidden"), 3)
+
+    def test_items_in_elided_bodies_not_counted(self) -> None:
+        """Test that functions nested in an elided body add no marker."""
+        code = "def outer():\n    def inner():\n        return 1\n    return inner\n"
+        options = LopperOptions(max_depth=0)
+        self.assertEqual(extract_skeleton(code, "python", options=options), "def outer():\n    ...")
+        with self.assertRaises(ValueError):
+            LopperOptions(max_depth=-1)
+
+
+class TestQueryExtraction(unittest.TestCase):
+    """Test extraction guided by user-supplied tree-sitter queries."""
+
+    code: str = """mod inner {
+    pub fn helper() -> i32 {
+        1
+    }
+}
+
+fn main() {
+    run();
+}
+"""
+
+    def test_query_captures(self) -> None:
+        """Test that marked nodes are kept whole or have their bodies elided."""
+        query = (
+            "(mod_item) @keep.signature\n"
+            '((function_item name: (identifier) @_name) @keep.whole (#eq? @_name "main"))'
+        )
+        skeleton = skeletonize_with_query(self.code, "rust", query)
+        self.assertEqual(skeleton, "mod inner {\n}\n\nfn main() {\n    run();\n}")
+        skeleton = skeletonize_with_query(
+            self.code, "rust", "(mod_item body: (_) @prune.body)", options=LopperOptions()
+        )
+        self.assertEqual(skeleton, "mod inner {\n}\n\nfn main() {\n}")
+
+    def test_invalid_queries(self) -> None:
+        """Test that malformed queries and unknown captures raise helpful errors."""
+        for query in ("(mod_item", "(no_such_node) @keep.whole"):
+            with self.assertRaisesRegex(InvalidQueryError, "Invalid rust query"):
+                skeletonize_with_query(self.code, "rust", query)
+        with self.assertRaisesRegex(InvalidQueryError, "@keep, @prune.bodies"):
+            skeletonize_with_query(self.code, "rust", "(mod_item) @keep (block) @prune.bodies")
+        self.assertIsInstance(InvalidQueryError("query"), ValueError)
+
+
+class TestRegisterLanguage(unittest.TestCase):
+    """Test adding languages with grammars of their own."""
+
+    def register(self, config: LanguageConfig, extensions: list[str]) -> None:
+        """Register a language for the duration of the test."""
+        register_language(config, extensions=extensions)
+        self.addCleanup(LANGUAGE_CONFIGS.pop, config.name)
+        for extension in extensions:
+            self.addCleanup(EXTENSION_TO_LANGUAGE.pop, extension)
+
+    def test_register_language(self) -> None:
+        """Test that a registered language is detected and lopped like a built-in one."""
+        config = LanguageConfig(
+            name="toy-json",
+            grammar=tree_sitter_language_pack.get_language("json"),
+            body_query="(pair value: (object) @body)",
+        )
+        self.register(config, [".toyjson"])
+        self.assertEqual(get_language("toyjson"), "toy-json")
+        source = '{\n  "name": "loppers",\n  "scripts": {\n    "test": "pytest"\n  }\n}\n'
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = Path(tmpdir) / "package.toyjson"
+            path.write_text(source)
+            skeleton = get_skeleton(path, options=LopperOptions(body_placeholders=True))
+        self.assertEqual(skeleton, '{\n  "name": "loppers",\n  "scripts": { /* 3 lines */ }\n}')
+        with self.assertRaises(ValueError):
+            register_language(config)
+
+    def test_invalid_query(self) -> None:
+        """Test that a query written for another grammar is rejected at registration."""
+        config = LanguageConfig(
+            name="toy-json",
+            grammar=tree_sitter_language_pack.get_language("json"),
+            body_query="(function_item body: (block) @body)",
+        )
+        with self.assertRaises(InvalidQueryError):
+            register_language(config)
+        self.assertNotIn("toy-json", LANGUAGE_CONFIGS)
+        with self.assertRaises(ValueError):
+            register_language(LanguageConfig(name="rust", body_query="(block) @body"))
+
+
+class TestDocuments(unittest.TestCase):
+    """Test lopping the code embedded in Markdown, HTML and component documents."""
+
+    def test_markdown_code_blocks(self) -> None:
+        """Test that only tagged blocks are lopped and fences are kept verbatim."""
+        untagged = "```\nfn untouched() {\n    body();\n}\n```\n"
+        document = f"# Calculator\n\n```rust\n{SAMPLE_RS}```\n\nSee also:\n\n{untagged}"
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = Path(tmpdir) / "calculator.md"
+            path.write_text(document)
+            skeleton = get_skeleton(path)
+
+        lopped = extract_skeleton(SAMPLE_RS, "rust")
+        self.assertNotEqual(lopped, SAMPLE_RS.rstrip())
+        expected = f"# Calculator\n\n```rust\n{lopped}\n```\n\nSee also:\n\n{untagged}"
+        self.assertEqual(skeleton, expected.rstrip())
+
+    def test_nested_blocks(self) -> None:
+        """Test indented, tilde-fenced and broken blocks, and the document line map."""
+        code = "def run():\n    return 1\n"
+        document = (
+            f"- Step:\n  ~~~ {{.python title=x}}\n{textwrap.indent(code, '  ')}  ~~~\n"
+            "```rust\nfn broken( {\n```\n~~~unknown\nx\n"
+        )
+        result = extract_skeleton_result(document, "markdown")
+        lopped = textwrap.indent(extract_skeleton(code, "python"), "  ")
+        self.assertTrue(
+            result.skeleton.startswith(f"- Step:\n  ~~~ {{.python title=x}}\n{lopped}\n  ~~~\n")
+        )
+        self.assertTrue(result.skeleton.endswith("```\n~~~unknown\nx"))
+        self.assertEqual(result.source_map.lines[:3], [(1, 1), (2, 2), (3, 3)])
+        self.assertTrue(result.diagnostics)
+        for diagnostic in result.diagnostics:
+            self.assertGreaterEqual(diagnostic.start_line, 7)
+            self.assertGreaterEqual(diagnostic.start_byte, document.index("fn broken"))
+            self.assertLessEqual(diagnostic.end_byte, document.index("```\n~~~"))
+
+        # Only a fence of the opening character closes a block
+        self.assertEqual(
+            markdown_code_blocks("~~~py\n```\n~~~\n"), [EmbeddedCode(6, 10, "python")]
+        )
+
+    def test_vue_scripts(self) -> None:
+        """Test that each script of a component is lopped in its language, in place."""
+        setup = (
+            "const count = ref(0)\n"
+            "function bump(step: number): void {\n  count.value += step\n}\n"
+        )
+        plain = "export default {\n  mounted() {\n    console.log('ready')\n  },\n}\n"
+        template = "<template>\n  <button @click=\"bump(1)\">{{ count }}</button>\n</template>\n"
+        style = "<style scoped>\nbutton { color: red; }\n</style>"
+        component = (
+            f'{template}\n<script setup lang="ts">\n{setup}</script>\n\n'
+            f"<script>\n{plain}</script>\n\n{style}\n"
+        )
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = Path(tmpdir) / "Counter.vue"
+            path.write_text(component)
+            skeleton = get_skeleton(path)
+
+        lopped_setup = extract_skeleton(setup, "typescript")
+        lopped_plain = extract_skeleton(plain, "javascript")
+        self.assertNotIn("count.value += step", lopped_setup)
+        self.assertNotIn("console.log", lopped_plain)
+        self.assertEqual(
+            skeleton,
+            f'{template}\n<script setup lang="ts">\n{lopped_setup}\n</script>\n\n'
+            f"<script>\n{lopped_plain}\n</script>\n\n{style}",
+        )
+        self.assertEqual(
+            [block.language for block in script_blocks(component)], ["typescript", "javascript"]
+        )
+
+    def test_html_scripts(self) -> None:
+        """Test that indented scripts keep their indentation and data scripts are kept."""
+        code = "function greet(name) {\n  alert(name)\n}\n"
+        data = '<script type="application/json">\n{"greet": true}\n</script>\n'
+        page = (
+            f"<body>\n  {data}  <script>\n{textwrap.indent(code, '    ')}  </script>\n"
+            "  <script>greet('inline')</script>\n</body>\n"
+        )
+        result = extract_skeleton_result(page, "html")
+        lopped = textwrap.indent(extract_skeleton(code, "javascript"), "    ")
+        self.assertEqual(
+            result.skeleton,
+            f"<body>\n  {data}  <script>\n{lopped}\n  </script>\n"
+            "  <script>greet('inline')</script>\n</body>",
+        )
+        closing = result.skeleton.split("\n").index("  </script>") + 1
+        self.assertEqual(result.source_map.original_line(closing), 9)
+
+
+class TestNotebooks(unittest.TestCase):
+    """Test lopping the code cells of Jupyter notebooks."""
+
+    code: str = "def area(r):\n    return 3.14 * r * r\n"
+    notebook: ClassVar[dict[str, object]] = {
+        "cells": [
+            {"cell_type": "markdown", "metadata": {}, "source": ["# Areas\n", "Circles."]},
+            {
+                "cell_type": "code",
+                "execution_count": 7,
+                "metadata": {},
+                "outputs": [{"output_type": "stream", "name": "stdout", "text": ["78.5\n"]}],
+                "source": ["%matplotlib inline\n", *code.splitlines(keepends=True), "!ls"],
+            },
+        ],
+        "metadata": {"kernelspec": {"name": "python3", "language": "python"}},
+        "nbformat": 4,
+        "nbformat_minor": 5,
+    }
+
+    def test_text_format(self) -> None:
+        """Test that cells are flattened, code lopped and magics kept."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = Path(tmpdir) / "areas.ipynb"
+            path.write_text(json.dumps(self.notebook))
+            skeleton = get_skeleton(path)
+
+        lopped = extract_skeleton(self.code, "python")
+        self.assertNotIn("3.14", lopped)
+        self.assertEqual(
+            skeleton,
+            "# %% [cell 1] markdown\n# Areas\nCircles.\n\n"
+            f"# %% [cell 2]\n%matplotlib inline\n{lopped}\n!ls",
+        )
+
+    def test_json_format(self) -> None:
+        """Test that the cleaned notebook drops outputs and execution counts."""
+        options = LopperOptions(notebook_format="json")
+        cleaned = json.loads(lop_notebook(json.dumps(self.notebook), options=options))
+        markdown, code = cleaned["cells"]
+        self.assertEqual(markdown, self.notebook["cells"][0])  # type: ignore[index]
+        self.assertEqual((code["outputs"], code["execution_count"]), ([], None))
+        self.assertEqual(code["source"][0], "%matplotlib inline\n")
+        self.assertNotIn("3.14", "".join(code["source"]))
+        self.assertEqual(cleaned["metadata"], self.notebook["metadata"])
+
+    def test_malformed_notebooks(self) -> None:
+        """Test that malformed notebooks raise InvalidNotebookError with their path."""
+        for text in ("{", "[]", '{"cells": [{"cell_type": "code"}]}'):
+            with self.subTest(text=text), self.assertRaises(InvalidNotebookError):
+                lop_notebook(text)
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = Path(tmpdir) / "broken.ipynb"
+            path.write_text('{"cells": 1}')
+            with self.assertRaises(LopError) as caught:
+                get_skeleton(path)
+        self.assertEqual(caught.exception.path, path)
+
+
+class TestLineNumbers(unittest.TestCase):
+    """Test numbering skeleton lines with their source lines."""
+
+    def test_numbers_follow_source_lines(self) -> None:
+        """Test that lines after an elided body keep their source line numbers."""
+        code = (
+            "def add(a, b):\n"
+            "    total = a + b\n"
+            "    return total\n"
+            "\n"
+            "\n"
+            "def one():\n"
+            "    return 1\n"
+        )
+        skeleton = extract_skeleton(code, "python", options=LopperOptions(line_numbers=True))
+        self.assertEqual(
+            skeleton,
+            "1\u2502 def add(a, b):\n"
+            "2\u2502     ...\n"
+            "4\u2502\n"
+            "5\u2502\n"
+            "6\u2502 def one():\n"
+            "7\u2502     ...",
+        )
+
+    def test_gutter_width_fits_last_line(self) -> None:
+        """Test that gutters are right-aligned to the widest line number."""
+        code = (
+            "//! Math.\n"
+            "fn fibonacci(n: u32) -> u32 {\n"
+            "    if n <= 1 {\n"
+            "        return n;\n"
+            "    }\n"
+            "    fibonacci(n - 1) + fibonacci(n - 2)\n"
+            "}\n"
+            "\n"
+            "fn noop() {}\n"
+            "\n"
+            "/// One.\n"
+            "fn one() -> i32 { 1 }\n"
+        )
+        options = LopperOptions(line_numbers=True, body_placeholders=True)
+        self.assertEqual(
+            extract_skeleton(code, "rust", options=options),
+            " 1\u2502 //! Math.\n"
+            " 2\u2502 fn fibonacci(n: u32) -> u32 { /* 6 lines */ }\n"
+            " 8\u2502\n"
+            " 9\u2502 fn noop() {}\n"
+            "10\u2502\n"
+            "11\u2502 /// One.\n"
+            "12\u2502 fn one() -> i32 { /* 1 line */ }",
+        )
+
+    def test_source_map(self) -> None:
+        """Test mapping skeleton lines to source lines past placeholders and dropped items."""
+        code = (
+            "pub fn add(a: i32, b: i32) -> i32 {\n"
+            "    let sum = a + b;\n"
+            "    sum\n"
+            "}\n"
+            "\n"
+            "fn hidden() {\n"
+            "    todo!()\n"
+            "}\n"
+            "\n"
+            "pub fn one() -> i32 {\n"
+            "    1\n"
+            "}\n"
+        )
+        for line_numbers in (False, True):
+            options = LopperOptions(
+                body_placeholders=True, visibility="public", line_numbers=line_numbers
+            )
+            source_map = extract_skeleton_result(code, "rust", options=options).source_map
+            self.assertEqual(source_map.lines, [(1, 4), (5, 5), (9, 9), (10, 12)])
+            self.assertEqual(source_map.elided, [(6, 8)])
+            self.assertEqual(source_map.original_line(4), 10)
+            self.assertEqual(source_map.output_line(3), 1)
+            self.assertIsNone(source_map.output_line(7))
+            self.assertEqual(source_map.output_line(11), 4)
+        with self.assertRaises(IndexError):
+            source_map.original_line(5)
+
+    def test_source_map_of_removed_lines(self) -> None:
+        """Test that body lines removed without a placeholder are reported as elided."""
+        code = "fn main() {\n    run();\n    exit();\n}\n"
+        source_map = extract_skeleton_result(code, "rust").source_map
+        self.assertEqual(source_map.lines, [(1, 1), (4, 4)])
+        self.assertEqual(source_map.elided, [(2, 3)])
+        self.assertEqual(source_map.output_line(4), 2)
+
+
+class TestIndentation(unittest.TestCase):
+    """Test re-indenting skeletons and laying out the braces of placeholders."""
+
+    def test_reindents_by_symbol_depth(self) -> None:
+        """Test that items are indented by their depth, whatever their source indentation."""
+        code = (
+            "impl Calculator {\n"
+            "        pub fn new(initial: i32) -> Self {\n"
+            "            Calculator { value: initial }\n"
+            "        }\n"
+            "\n"
+            "  fn process(&self) {\n"
+            "      let result = 5;\n"
+            "  }\n"
+            "}\n"
+        )
+        options = LopperOptions(indent_width=4)
+        self.assertEqual(
+            extract_skeleton(code, "rust", options=options),
+            "impl Calculator {\n"
+            "    pub fn new(initial: i32) -> Self {}\n"
+            "\n"
+            "    fn process(&self) {}\n"
+            "}",
+        )
+        extractor = get_extractor("rust", options)
+        self.assertEqual(
+            extractor.extract_result(code).source_map,
+            get_extractor("rust").extract_result(code).source_map,
+        )
+
+    def test_sample_two_spaces(self) -> None:
+        """Test that every level of sample.rs takes two spaces."""
+        expected = "\n".join(
+            line[(len(line) - len(line.lstrip())) // 2 :]
+            for line in extract_skeleton(SAMPLE_RS, "rust").splitlines()
+        )
+        skeleton = extract_skeleton(SAMPLE_RS, "rust", options=LopperOptions(indent_width=2))
+        self.assertEqual(skeleton, expected)
+        self.assertIn("\n  pub fn new(initial: i32) -> Self {}\n", skeleton)
+
+    def test_python_strings(self) -> None:
+        """Test that docstrings are re-indented while other multi-line strings are kept."""
+        code = (
+            "class Greeter:\n"
+            '    """Greets people.\n'
+            "\n"
+            "    Politely.\n"
+            '    """\n'
+            "\n"
+            '    TEMPLATE = """Hello,\n'
+            '    {name}"""\n'
+            "\n"
+            "    def greet(self, name):\n"
+            "        return name\n"
+        )
+        skeleton = extract_skeleton(code, "python", options=LopperOptions(indent_width=2))
+        self.assertEqual(
+            skeleton,
+            "class Greeter:\n"
+            '  """Greets people.\n'
+            "\n"
+            "  Politely.\n"
+            '  """\n'
+            "\n"
+            '  TEMPLATE = """Hello,\n'
+            '    {name}"""\n'
+            "\n"
+            "  def greet(self, name):\n"
+            "    ...",
+        )
+
+    def test_block_braces(self) -> None:
+        """Test that block placeholders go on their own line, above the closing brace."""
+        code = (
+            "impl Calculator {\n"
+            "    pub fn add(&self, x: i32) -> i32 {\n"
+            "        self.value + x\n"
+            "    }\n"
+            "\n"
+            "    fn noop() {}\n"
+            "}\n"
+            "\n"
+            "fn one() -> i32 { 1 }\n"
+        )
+        options = LopperOptions(body_placeholders=True, body_braces="block")
+        self.assertEqual(
+            extract_skeleton(code, "rust", options=options),
+            "impl Calculator {\n"
+            "    pub fn add(&self, x: i32) -> i32 {\n"
+            "        /* 3 lines */\n"
+            "    }\n"
+            "\n"
+            "    fn noop() {}\n"
+            "}\n"
+            "\n"
+            "fn one() -> i32 {\n"
+            "    /* 1 line */\n"
+            "}",
+        )
+        options = dataclasses.replace(options, indent_width=2)
+        self.assertTrue(
+            extract_skeleton(code, "rust", options=options).startswith(
+                "impl Calculator {\n"
+                "  pub fn add(&self, x: i32) -> i32 {\n"
+                "    /* 3 lines */\n"
+                "  }\n"
+            )
+        )
+
+    def test_invalid_options(self) -> None:
+        """Test that indent widths must be positive and brace styles known."""
+        with self.assertRaises(ValueError):
+            LopperOptions(indent_width=0)
+        with self.assertRaises(ValueError):
+            LopperOptions(body_braces="allman")  # type: ignore[arg-type]
+        with self.assertRaises(ValueError):
+            extract_with_regions(SAMPLE_RS, "rust", options=LopperOptions(indent_width=2))
+
+
+class TestSortOrder(unittest.TestCase):
+    """Test sorting the items of skeletons alphabetically."""
+
+    options: ClassVar[LopperOptions] = LopperOptions(sort="alphabetical")
+
+    def test_rust_sample(self) -> None:
+        """Test that top-level items and the methods of each impl block are sorted apart."""
+        skeleton = extract_skeleton(SAMPLE_RS, "rust", options=self.options)
+        default = extract_skeleton(SAMPLE_RS, "rust")
+        self.assertEqual(sorted(skeleton.splitlines()), sorted(default.splitlines()))
+        lines, position = f"\n{skeleton}", 0
+        for header in (
+            "pub trait Arithmetic {",
+            "    fn add(&self, x: i32) -> i32;",
+            "    fn multiply(&self, x: i32) -> i32;",
+            "pub struct Calculator {",
+            "impl Calculator {",
+            "    pub fn add(&self, x: i32, y: i32) -> i32",
+            "    pub fn new(initial: i32) -> Self",
+            "    fn process(&self)",
+            "impl Arithmetic for Calculator {",
+            "    fn add(&self, x: i32) -> i32 {",
+            "    fn multiply(&self, x: i32) -> i32 {",
+            "fn fibonacci(n: u32) -> u32",
+        ):
+            position = lines.find(f"\n{header}", position) + 1
+            self.assertGreater(position, 0, header)
+
+    def test_python_trivia_moves_with_items(self) -> None:
+        """Test that decorators move with their methods while docstrings stay in place."""
+        code = (
+            "class Greeter:\n"
+            '    """Greets people."""\n'
+            "\n"
+            "    @staticmethod\n"
+            "    def wave():\n"
+            "        pass\n"
+            "\n"
+            "    def greet(self, name):\n"
+            "        return name\n"
+            "\n"
+            "\n"
+            "def main():\n"
+            "    pass\n"
+        )
+        self.assertEqual(
+            extract_skeleton(code, "python", options=self.options),
+            "class Greeter:\n"
+            '    """Greets people."""\n'
+            "\n"
+            "    def greet(self, name):\n"
+            "        ...\n"
+            "\n"
+            "    @staticmethod\n"
+            "    def wave():\n"
+            "        ...\n"
+            "\n"
+            "\n"
+            "def main():\n"
+            "    ...",
+        )
+
+    def test_sort_symbols(self) -> None:
+        """Test that names sort regardless of case, then by case, then by position."""
+        symbols = [
+            Symbol("function", name, f"fn {name}()", line, line, start_byte=line * 10)
+            for line, name in enumerate(("b", "a", "B", "b"), start=1)
+        ]
+        symbols[0].children = [
+            Symbol("function", "z", "fn z()", 2, 2, start_byte=12),
+            Symbol("function", "y", "fn y()", 3, 3, start_byte=13),
+        ]
+        ordered = sort_symbols(symbols)
+        self.assertEqual(
+            [(symbol.name, symbol.start_line) for symbol in ordered],
+            [("a", 2), ("B", 3), ("b", 1), ("b", 4)],
+        )
+        self.assertEqual([child.name for child in ordered[2].children], ["y", "z"])
+        self.assertEqual([child.name for child in symbols[0].children], ["z", "y"])
+
+    def test_invalid_options(self) -> None:
+        """Test that sort orders must be known, and reordered regions cannot be expanded."""
+        with self.assertRaises(ValueError):
+            LopperOptions(sort="reverse")  # type: ignore[arg-type]
+        with self.assertRaises(ValueError):
+            extract_with_regions(SAMPLE_RS, "rust", options=self.options)
+
+
+class TestSectionComments(unittest.TestCase):
+    """Test keeping section comments above dropped items."""
+
+    code: ClassVar[str] = (
+        "pub fn run() {\n"
+        "    parse();\n"
+        "}\n"
+        "\n"
+        "// ---- Parsing ----\n"
+        "/// Parse the input.\n"
+        "fn parse() {\n"
+        "    todo!()\n"
+        "}\n"
+    )
+
+    def test_banner_kept_above_private_item(self) -> None:
+        """Test that a banner stays while the private item below it is dropped."""
+        options = LopperOptions(visibility="public", keep_section_comments=True)
+        skeleton = extract_skeleton(self.code, "rust", options=options)
+        self.assertIn("// ---- Parsing ----", skeleton)
+        self.assertNotIn("Parse the input", skeleton)
+        self.assertNotIn("fn parse", skeleton)
+
+    def test_banner_kept_above_elided_item(self) -> None:
+        """Test that a banner stays above a kept item whose body is elided."""
+        code = self.code.replace("fn parse", "pub fn parse")
+        options = LopperOptions(visibility="public", keep_section_comments=True)
+        skeleton = extract_skeleton(code, "rust", options=options)
+        self.assertIn("// ---- Parsing ----\n/// Parse the input.\npub fn parse()", skeleton)
+        self.assertNotIn("todo!", skeleton)
+
+    def test_doc_comment_not_banner(self) -> None:
+        """Test that a doc comment shaped like a banner goes with its item."""
+        code = self.code.replace("// ---- Parsing ----\n", "").replace(
+            "/// Parse the input.", "/// ---- Parse the input ----"
+        )
+        options = LopperOptions(visibility="public", keep_section_comments=True)
+        self.assertNotIn("Parse the input", extract_skeleton(code, "rust", options=options))
+
+    def test_python_region_markers(self) -> None:
+        """Test that Python region markers are section comments, with or without a space."""
+        code = (
+            "def run():\n"
+            "    pass\n"
+            "\n"
+            "#region helpers\n"
+            "def _helper():\n"
+            "    pass\n"
+            "# endregion\n"
+            "def _other():\n"
+            "    pass\n"
+        )
+        options = LopperOptions(visibility="public", keep_section_comments=True)
+        skeleton = extract_skeleton(code, "python", options=options)
+        self.assertIn("#region helpers", skeleton)
+        self.assertIn("# endregion", skeleton)
+        self.assertNotIn("_helper", skeleton)
+        self.assertNotIn("_other", skeleton)
+
+    def test_banner_dropped_by_default(self) -> None:
+        """Test that without the option the banner goes with the item it is attached to."""
+        skeleton = extract_skeleton(
+            self.code, "rust", options=LopperOptions(visibility="public")
+        )
+        self.assertNotIn("Parsing", skeleton)
+        self.assertIn("pub fn run()", skeleton)
+
+    def test_custom_pattern(self) -> None:
+        """Test that a custom pattern decides which comments are section comments."""
+        code = self.code.replace("// ---- Parsing ----", "// Group: parsing")
+        options = LopperOptions(visibility="public", keep_section_comments=True)
+        self.assertNotIn("Group", extract_skeleton(code, "rust", options=options))
+        options = dataclasses.replace(options, section_comment_pattern=r"Group:")
+        self.assertIn("// Group: parsing", extract_skeleton(code, "rust", options=options))
+
+    def test_invalid_pattern(self) -> None:
+        """Test that invalid section comment patterns are rejected."""
+        with self.assertRaises(ValueError):
+            LopperOptions(section_comment_pattern="(")
+
+
+class TestVisibilityFilter(unittest.TestCase):
+    """Test emitting only the public API."""
+
+    options: ClassVar[LopperOptions] = LopperOptions(visibility="public")
+
+    def test_rust_sample_public_only(self) -> None:
+        """Test that private items are dropped and impl methods filtered one by one."""
+        skeleton = extract_skeleton(SAMPLE_RS, "rust", options=self.options)
+        self.assertNotIn("fn fibonacci", skeleton)
+        self.assertNotIn("fn process", skeleton)
+        self.assertIn("pub fn new(initial: i32) -> Self {", skeleton)
+        self.assertIn("pub fn add(&self, x: i32, y: i32) -> i32 {", skeleton)
+        self.assertIn("impl Calculator {", skeleton)
+        # Trait items and trait impl methods are implicitly public
+        self.assertIn("    fn multiply(&self, x: i32) -> i32;", skeleton)
+        self.assertIn(
+            "impl Arithmetic for Calculator {\n    fn add(&self, x: i32) -> i32 {", skeleton
+        )
+
+    def test_rust_private_item_trivia_dropped(self) -> None:
+        """Test that doc comments and attributes of private items go with them."""
+        code = (
+            "/// Public.\n"
+            "pub fn shown() {}\n"
+            "/// Private.\n"
+            "#[inline]\n"
+            "fn hidden() {}\n"
+            "pub(crate) struct Internal;\n"
+        )
+        skeleton = extract_skeleton(code, "rust", options=LopperOptions(visibility="crate"))
+        self.assertEqual(skeleton, "/// Public.\npub fn shown()\npub(crate) struct Internal;")
+
+    def test_rust_crate_visible_items(self) -> None:
+        """Test that `pub(crate)` and `pub(super)` items are only dropped at the public level."""
+        code = (
+            "pub fn shown() {}\n"
+            "pub(crate) fn internal() {}\n"
+            "pub(super) fn parental() {}\n"
+            "pub(self) fn hidden() {}\n"
+        )
+        self.assertEqual(
+            extract_skeleton(code, "rust", options=LopperOptions(visibility="crate")),
+            "pub fn shown()\npub(crate) fn internal()\npub(super) fn parental()",
+        )
+        self.assertEqual(extract_skeleton(code, "rust", options=self.options), "pub fn shown()")
+
+    def test_rust_hidden_parent_drops_children(self) -> None:
+        """Test that public items inside a dropped item, and its `impl` blocks, go with it."""
+        code = (
+            "mod private {\n"
+            "    pub fn exported() {}\n"
+            "}\n"
+            "\n"
+            "pub(crate) struct Internal;\n"
+            "\n"
+            "impl Internal {\n"
+            "    pub fn method(&self) {}\n"
+            "}\n"
+            "\n"
+            "pub struct Shown;\n"
+            "\n"
+            "impl Shown {\n"
+            "    pub fn show(&self) {}\n"
+            "}\n"
+        )
+        skeleton = extract_skeleton(code, "rust", options=self.options)
+        self.assertNotIn("exported", skeleton)
+        self.assertNotIn("Internal", skeleton)
+        self.assertNotIn("method", skeleton)
+        self.assertIn("impl Shown {\n    pub fn show(&self) {}\n}", skeleton)
+
+    def test_typescript_private_members(self) -> None:
+        """Test that `private` and `#`-prefixed TypeScript members are dropped."""
+        code = (
+            "export class Counter {\n"
+            "    #count = 0;\n"
+            "    #bump() {\n"
+            "        this.#count += 1;\n"
+            "    }\n"
+            "    private reset() {\n"
+            "        this.#count = 0;\n"
+            "    }\n"
+            "    increment() {\n"
+            "        this.#bump();\n"
+            "    }\n"
+            "}\n"
+        )
+        skeleton = extract_skeleton(code, "typescript", options=self.options)
+        self.assertNotIn("#bump", skeleton)
+        self.assertNotIn("reset", skeleton)
+        self.assertIn("    increment() {\n    }", skeleton)
+
+    def test_go_unexported_names(self) -> None:
+        """Test that lowercase Go names are dropped."""
+        code = (
+            "package calc\n"
+            "\n"
+            "func Add(a, b int) int {\n"
+            "\treturn add(a, b)\n"
+            "}\n"
+            "\n"
+            "func add(a, b int) int {\n"
+            "\treturn a + b\n"
+            "}\n"
+        )
+        skeleton = extract_skeleton(code, "go", options=self.options)
+        self.assertEqual(skeleton, "package calc\n\nfunc Add(a, b int) int {\n}")
+
+    def test_python_private_names(self) -> None:
+        """Test that underscore-prefixed Python names are private, dunders are not."""
+        code = (
+            "class A:\n"
+            "    def __init__(self):\n"
+            "        pass\n"
+            "\n"
+            "    @property\n"
+            "    def _secret(self):\n"
+            "        pass\n"
+            "\n"
+            "def _helper():\n"
+            "    pass\n"
+        )
+        skeleton = extract_skeleton(code, "python", options=self.options)
+        self.assertEqual(skeleton, "class A:\n    def __init__(self):\n        ...")
+
+    def test_invalid_visibility(self) -> None:
+        """Test that unknown visibility settings are rejected."""
+        with self.assertRaises(ValueError):
+            LopperOptions(visibility="internal")  # type: ignore[arg-type]
+
+
+class TestEmptyContainers(unittest.TestCase):
+    """Test handling the containers whose items were all filtered out."""
+
+    def test_rust_nested_modules_cascade(self) -> None:
+        """Test that a module holding only an emptied impl block counts as empty."""
+        code = (
+            "pub mod outer {\n"
+            "    pub mod inner {\n"
+            "        /// Helpers.\n"
+            "        impl super::Calculator {\n"
+            "            fn helper(&self) -> i32 {\n"
+            "                1\n"
+            "            }\n"
+            "        }\n"
+            "    }\n"
+            "}\n"
+            "\n"
+            "pub struct Calculator;\n"
+        )
+
+        def lop(empty_containers: str) -> str:
+            options = LopperOptions(visibility="public", empty_containers=empty_containers)
+            return extract_skeleton(code, "rust", options=options)
+
+        self.assertIn("impl super::Calculator {", lop("keep"))
+        self.assertEqual(lop("drop").strip(), "pub struct Calculator;")
+        self.assertEqual(
+            lop("collapse"),
+            "pub mod outer { /* all items filtered */ }\n\npub struct Calculator;",
+        )
+
+    def test_python_class_of_private_methods(self) -> None:
+        """Test that a class whose methods were all private is dropped or collapsed."""
+        code = (
+            "class Cache:\n"
+            '    """Caches values."""\n'
+            "\n"
+            "    def _load(self):\n"
+            "        return 1\n"
+            "\n"
+            "    def _store(self, value):\n"
+            "        self.value = value\n"
+            "\n"
+            "\n"
+            "class Public:\n"
+            "    def get(self):\n"
+            "        return self._load()\n"
+            "\n"
+            "    def _load(self):\n"
+            "        return 1\n"
+        )
+        options = LopperOptions(visibility="public", empty_containers="drop")
+        dropped = extract_skeleton(code, "python", options=options)
+        self.assertEqual(dropped.strip(), "class Public:\n    def get(self):\n        ...")
+
+        options = dataclasses.replace(options, empty_containers="collapse")
+        collapsed = extract_skeleton(code, "python", options=options)
+        self.assertTrue(collapsed.startswith("class Cache: ...  # all items filtered\n"))
+        self.assertIn("class Public:\n    def get(self):\n        ...", collapsed)
+        self.assertNotIn("_load", collapsed)
+
+        unfiltered = LopperOptions(empty_containers="drop")
+        self.assertEqual(
+            extract_skeleton(code, "python", options=unfiltered), extract_skeleton(code, "python")
+        )
+
+    def test_containers_with_other_members_kept(self) -> None:
+        """Test that fields and re-exports keep a container whose items were dropped."""
+        code = (
+            "@dataclass\n"
+            "class Point:\n"
+            '    """A point."""\n'
+            "\n"
+            "    x: int\n"
+            "    y: int\n"
+            "\n"
+            "    def _norm(self):\n"
+            "        return 1\n"
+        )
+        for mode in ("drop", "collapse"):
+            options = LopperOptions(visibility="public", empty_containers=mode)
+            skeleton = extract_skeleton(code, "python", options=options)
+            self.assertIn("class Point:\n", skeleton)
+            self.assertIn("    x: int\n    y: int", skeleton)
+            self.assertNotIn("_norm", skeleton)
+        code = (
+            "pub mod prelude {\n"
+            "    pub use crate::parser::Parser;\n"
+            "\n"
+            "    fn helper() {}\n"
+            "}\n"
+        )
+        options = LopperOptions(visibility="public", empty_containers="drop")
+        skeleton = extract_skeleton(code, "rust", options=options)
+        self.assertIn("pub mod prelude {\n    pub use crate::parser::Parser;", skeleton)
+        self.assertNotIn("helper", skeleton)
+
+    def test_invalid_mode(self) -> None:
+        """Test that unknown modes are rejected."""
+        with self.assertRaises(ValueError):
+            LopperOptions(empty_containers="hide")  # type: ignore[arg-type]
+
+
+class TestDedupSymbols(unittest.TestCase):
+    """Test collapsing runs of repeated items."""
+
+    RUST_CODE = (
+        "/// Pure red.\n"
+        "pub fn red() -> Color {\n    Color(255, 0, 0)\n}\n"
+        "\n"
+        "pub fn green() -> Color {\n    Color(0, 255, 0)\n}\n"
+        "\n"
+        "pub fn blue() -> Color {\n    Color(0, 0, 255)\n}\n"
+        "\n"
+        "pub fn mix(a: Color, b: Color) -> Color {\n    a.blend(b)\n}\n"
+        "\n"
+        "#[cfg(unix)]\n"
+        "pub fn home() -> PathBuf {\n    unix_home()\n}\n"
+        "\n"
+        "#[cfg(windows)]\n"
+        "pub fn home() -> PathBuf {\n    windows_home()\n}\n"
+    )
+
+    def test_same_shaped_functions(self) -> None:
+        """Test that functions only differing by name collapse into the first one."""
+        options = LopperOptions(dedup_symbols="signature")
+        skeleton = extract_skeleton(self.RUST_CODE, "rust", options=options)
+        self.assertIn("/// Pure red.\npub fn red() -> Color {\n}\n\n// +2 similar\n", skeleton)
+        self.assertNotIn("green", skeleton)
+        self.assertNotIn("blue", skeleton)
+        self.assertIn("pub fn mix(a: Color, b: Color) -> Color {", skeleton)
+        self.assertEqual(skeleton.count("pub fn home()"), 1)
+        self.assertNotIn("windows", skeleton)
+
+    def test_imports_not_grouped(self) -> None:
+        """Test that runs of imports are left to the imports option."""
+        code = "use std::fs;\nuse std::io;\nuse std::path;\n\n" + self.RUST_CODE
+        options = LopperOptions(dedup_symbols="signature")
+        skeleton = extract_skeleton(code, "rust", options=options)
+        self.assertTrue(skeleton.startswith("use std::fs;\nuse std::io;\nuse std::path;\n"))
+
+    def test_same_names(self) -> None:
+        """Test that grouping by name only collapses the items of the same name."""
+        options = LopperOptions(dedup_symbols="name")
+        skeleton = extract_skeleton(self.RUST_CODE, "rust", options=options)
+        self.assertIn("pub fn blue() -> Color {", skeleton)
+        self.assertIn("#[cfg(unix)]\npub fn home() -> PathBuf {\n}\n\n// +1 similar", skeleton)
+        self.assertNotIn("windows", skeleton)
+        self.assertEqual(
+            extract_skeleton(self.RUST_CODE, "rust", options=LopperOptions(dedup_symbols="off")),
+            extract_skeleton(self.RUST_CODE, "rust"),
+        )
+        with self.assertRaises(ValueError):
+            LopperOptions(dedup_symbols="kind")  # type: ignore[arg-type]
+
+    def test_python_methods(self) -> None:
+        """Test that repeated methods collapse inside their class, which is kept."""
+        code = (
+            "class Color:\n"
+            "    def red(self) -> int:\n        return self.value[0]\n"
+            "\n"
+            "    def green(self) -> int:\n        return self.value[1]\n"
+            "\n"
+            "    def blue(self) -> int:\n        return self.value[2]\n"
+        )
+        options = LopperOptions(dedup_symbols="signature")
+        skeleton = extract_skeleton(code, "python", options=options)
+        self.assertTrue(skeleton.startswith("class Color:\n    def red(self) -> int:\n"))
+        self.assertIn("    # +2 similar", skeleton)
+        self.assertNotIn("green", skeleton)
+
+
+class TestTypeBodies(unittest.TestCase):
+    """Test keeping or collapsing type bodies."""
+
+    def test_struct_fields_kept_by_default(self) -> None:
+        """Test that struct fields and trait items survive by default."""
+        skeleton = extract_skeleton(SAMPLE_RS, "rust")
+        self.assertIn("pub struct Calculator {\n    value: i32,\n}", skeleton)
+        self.assertIn("    fn add(&self, x: i32) -> i32;", skeleton)
+
+    def test_field_attributes_kept(self) -> None:
+        """Test that field and variant attributes stay with their fields."""
+        code = (
+            "pub enum Shape {\n"
+            "    #[serde(rename = \"circle\")]\n"
+            "    Circle { radius: f64 },\n"
+            "    Square(f64),\n"
+            "}\n"
+        )
+        self.assertEqual(extract_skeleton(code, "rust"), code.rstrip())
+
+    def test_collapse_types(self) -> None:
+        """Test that collapse_types keeps only the type headers."""
+        options = LopperOptions(collapse_types=True)
+        skeleton = extract_skeleton(SAMPLE_RS, "rust", options=options)
+        self.assertIn("pub struct Calculator {\n}", skeleton)
+        self.assertIn("pub trait Arithmetic {\n}", skeleton)
+        self.assertNotIn("value: i32", skeleton)
+
+    def test_collapse_python_class_fields(self) -> None:
+        """Test that class-level fields are collapsed in Python, docstrings are kept."""
+        code = (
+            "class Point:\n"
+            '    """A point."""\n'
+            "    x: int = 0\n"
+            "    y: int\n"
+            "\n"
+            "    def norm(self):\n"
+            "        return 0\n"
+        )
+        self.assertIn("    y: int", extract_skeleton(code, "python"))
+        skeleton = extract_skeleton(code, "python", options=LopperOptions(collapse_types=True))
+        self.assertEqual(
+            skeleton, 'class Point:\n    """A point."""\n\n    def norm(self):\n        ...'
+        )
+
+    def test_collapse_members(self) -> None:
+        """Test that struct_fields and enum_variants collapse lists into counts."""
+        code = (
+            "pub struct Config {\n"
+            "    /// The name.\n"
+            "    pub name: String,\n"
+            "    #[serde(skip)]\n"
+            "    cache: Vec<u8>,\n"
+            "    pub(crate) depth: u32,\n"
+            "}\n"
+            "\n"
+            "pub enum Color {\n"
+            "    Red,\n"
+            "    Green,\n"
+            "    Blue,\n"
+            "}\n"
+        )
+        options = LopperOptions(struct_fields="collapse", enum_variants="collapse")
+        self.assertEqual(
+            extract_skeleton(code, "rust", options=options),
+            "pub struct Config { /* 3 fields */ }\n\npub enum Color { /* 3 variants */ }",
+        )
+        skeleton = extract_skeleton(code, "rust", options=LopperOptions(struct_fields="public"))
+        self.assertEqual(
+            skeleton,
+            "pub struct Config {\n"
+            "    /// The name.\n"
+            "    pub name: String,\n"
+            "}\n"
+            "\n"
+            "pub enum Color {\n"
+            "    Red,\n"
+            "    Green,\n"
+            "    Blue,\n"
+            "}",
+        )
+
+    def test_public_go_fields(self) -> None:
+        """Test that unexported Go fields are dropped, embedded ones by their type name."""
+        code = (
+            "type Server struct {\n"
+            "\tAddr    string\n"
+            "\thandler http.Handler\n"
+            "\t*log.Logger\n"
+            "}\n"
+        )
+        skeleton = extract_skeleton(code, "go", options=LopperOptions(struct_fields="public"))
+        self.assertEqual(skeleton, "type Server struct {\n\tAddr    string\n\t*log.Logger\n}")
+
+
+class TestKnownImpls(unittest.TestCase):
+    """Test collapsing trait implementations made of methods only."""
+
+    code: ClassVar[str] = (
+        "impl fmt::Display for Config {\n"
+        "    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {\n"
+        '        write!(f, "{}", self.name)\n'
+        "    }\n"
+        "}\n"
+        "\n"
+        "impl Iterator for Iter {\n"
+        "    type Item = u8;\n"
+        "\n"
+        "    fn next(&mut self) -> Option<u8> {\n"
+        "        None\n"
+        "    }\n"
+        "}\n"
+        "\n"
+        "impl Send for Config {}\n"
+    )
+
+    def test_sample_trait_impl(self) -> None:
+        """Test that the trait impl of sample.rs collapses while the inherent impl stays."""
+        options = LopperOptions(collapse_known_impls=True)
+        skeleton = extract_skeleton(SAMPLE_RS, "rust", options=options)
+        self.assertTrue(skeleton.endswith("\n\nimpl Arithmetic for Calculator { ... }"))
+        self.assertIn("    pub fn new(initial: i32) -> Self {}\n", skeleton)
+        self.assertIn("    fn add(&self, x: i32) -> i32;\n", skeleton)
+
+    def test_associated_items_kept(self) -> None:
+        """Test that impls with associated types keep their members, and empty ones stay."""
+        options = LopperOptions(collapse_known_impls=True)
+        self.assertEqual(
+            extract_skeleton(self.code, "rust", options=options),
+            "impl fmt::Display for Config { ... }\n"
+            "\n"
+            "impl Iterator for Iter {\n"
+            "    type Item = u8;\n"
+            "\n"
+            "    fn next(&mut self) -> Option<u8> {}\n"
+            "}\n"
+            "\n"
+            "impl Send for Config {}",
+        )
+
+    def test_kept_bodies_not_collapsed(self) -> None:
+        """Test that an impl holding a method whose body is kept stays expanded."""
+        options = LopperOptions(collapse_known_impls=True, keep_bodies=("Config::fmt",))
+        skeleton = extract_skeleton(self.code, "rust", options=options)
+        self.assertTrue(
+            skeleton.startswith(
+                "impl fmt::Display for Config {\n"
+                "    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {\n"
+                '        write!(f, "{}", self.name)\n'
+                "    }\n"
+                "}\n"
+            )
+        )
+
+    def test_trait_patterns(self) -> None:
+        """Test that trait patterns collapse impls whatever they hold, or keep them."""
+        options = LopperOptions(collapse_impl_traits=("Iter*",), keep_impl_traits=("Display",))
+        self.assertEqual(
+            extract_skeleton(self.code, "rust", options=options),
+            "impl fmt::Display for Config {\n"
+            "    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {}\n"
+            "}\n"
+            "\n"
+            "impl Iterator for Iter { ... }\n"
+            "\n"
+            "impl Send for Config {}",
+        )
+        options = LopperOptions.from_dict({"collapse_impl_traits": ["From", "fmt::Debug"]})
+        self.assertEqual(options.collapse_impl_traits, ("From", "fmt::Debug"))
+        self.assertEqual(options.to_dict()["collapse_impl_traits"], ["From", "fmt::Debug"])
+        with self.assertRaises(ValueError):
+            LopperOptions(keep_impl_traits="Display")  # type: ignore[arg-type]
+
+
+class TestSyntaxErrors(unittest.TestCase):
+    """Test extracting from sources that do not parse cleanly."""
+
+    broken: str = SAMPLE_RS.replace("    }\n}\n\nimpl Arithmetic", "    }\n\nimpl Arithmetic", 1)
+
+    def test_items_still_lopped(self) -> None:
+        """Test that a missing impl brace leaves the standalone function elided."""
+        self.assertNotEqual(self.broken, SAMPLE_RS)
+        result = extract_skeleton_result(self.broken, "rust")
+        self.assertTrue(result.has_errors)
+        self.assertIn("fn fibonacci(n: u32) -> u32", result.skeleton)
+        self.assertNotIn("fibonacci(n - 1)", result.skeleton)
+        self.assertIn("impl Arithmetic for Calculator", result.skeleton)
+
+    def test_diagnostics(self) -> None:
+        """Test that diagnostics locate the region missing its closing brace."""
+        broken = SAMPLE_RS.rstrip().removesuffix("}")
+        result = extract_skeleton_result(broken, "rust")
+        self.assertTrue(result.diagnostics)
+        end = len(broken.encode())
+        for diagnostic in result.diagnostics:
+            self.assertIn(diagnostic.kind, ("error", "missing"))
+            self.assertLessEqual(0, diagnostic.start_byte)
+            self.assertLessEqual(diagnostic.start_byte, diagnostic.end_byte)
+            self.assertLessEqual(diagnostic.end_byte, end)
+            self.assertGreaterEqual(diagnostic.start_line, 32)
+        for item in ("fn fibonacci(n: u32) -> u32", "pub struct Calculator", "impl Calculator {"):
+            self.assertIn(item, result.skeleton)
+        self.assertNotIn("closure(5)", result.skeleton)
+
+    def test_clean_source_has_no_errors(self) -> None:
+        """Test that a clean source reports no syntax errors."""
+        result = extract_skeleton_result(SAMPLE_RS, "rust")
+        self.assertEqual(result.syntax_errors, 0)
+        self.assertEqual(result.skeleton, extract_skeleton(SAMPLE_RS, "rust"))
+
+    def test_drop_errors(self) -> None:
+        """Test that dropping errors keeps the well-formed items."""
+        options = LopperOptions(on_error="drop")
+        result = extract_skeleton_result(self.broken, "rust", options=options)
+        self.assertTrue(result.has_errors)
+        self.assertIn("fn fibonacci(n: u32) -> u32", result.skeleton)
+        self.assertIn("pub struct Calculator", result.skeleton)
+        self.assertNotIn("fibonacci(n - 1)", result.skeleton)
+
+    def test_fail_on_errors(self) -> None:
+        """Test that on_error="fail" raises and rejects unknown modes."""
+        with self.assertRaises(ParseFailedError):
+            extract_skeleton(self.broken, "rust", options=LopperOptions(on_error="fail"))
+        self.assertEqual(
+            extract_skeleton(SAMPLE_RS, "rust", options=LopperOptions(on_error="fail")),
+            extract_skeleton(SAMPLE_RS, "rust"),
+        )
+        with self.assertRaises(ValueError):
+            LopperOptions(on_error="ignore")  # type: ignore[arg-type]
+
+
+class TestIncremental(unittest.TestCase):
+    """Test re-extracting skeletons after edits with incremental parsing."""
+
+    def test_random_edits(self) -> None:
+        """Test that skeletons after random edits match fresh extractions."""
+        fragments = ["", "x", "}", "{", "\n", "fn added() {\n    1\n}\n", "    let y = 2;\n"]
+        rng = random.Random(3)
+        source = SAMPLE_RS
+        lopper = IncrementalLopper(source, "rust")
+        self.assertEqual(lopper.skeleton, extract_skeleton(source, "rust"))
+        for _ in range(60):
+            start = rng.randrange(len(source) + 1)
+            end = min(len(source), start + rng.randrange(12))
+            text = rng.choice(fragments)
+            edit = InputEdit.replacing(source, start, end, text)
+            source = source[:start] + text + source[end:]
+            with self.subTest(edit=edit):
+                self.assertEqual(lopper.edit(edit, source), extract_skeleton(source, "rust"))
+        self.assertEqual(lopper.source, source)
+
+    def test_symbol_changes(self) -> None:
+        """Test that edits report the symbols they add, remove and change."""
+        lopper = IncrementalLopper(SAMPLE_RS, "rust")
+        self.assertEqual(lopper.symbol_changes(), SymbolChanges())
+        start = SAMPLE_RS.index("x + y")
+        skeleton = lopper.apply_edit(start, start + len("x + y"), "x - y")
+        self.assertEqual(skeleton, extract_skeleton(lopper.source, "rust"))
+        changes = lopper.symbol_changes()
+        self.assertEqual((changes.added, changes.removed), ([], []))
+        self.assertEqual(
+            [(s.kind, s.name, s.trait) for s in changes.changed],
+            [("impl", "Calculator", None), ("method", "add", None)],
+        )
+
+        end = len(lopper.source.encode())
+        lopper.apply_edit(end, end, "\nfn added() {}\n")
+        changes = lopper.symbol_changes()
+        self.assertEqual([s.name for s in changes.added], ["added"])
+        self.assertEqual(changes.changed, [])
+        start = lopper.source.index("fn fibonacci")
+        lopper.apply_edit(start, lopper.source.index("pub struct"), "")
+        self.assertEqual([s.name for s in lopper.symbol_changes().removed], ["fibonacci"])
+
+    def test_mismatched_edit(self) -> None:
+        """Test that an edit not matching the sources is rejected and changes nothing."""
+        lopper = IncrementalLopper(SAMPLE_RS, "rust")
+        with self.assertRaises(ValueError):
+            lopper.edit(InputEdit.replacing(SAMPLE_RS, 0, 2, "x"), SAMPLE_RS)
+        with self.assertRaises(ValueError):
+            InputEdit.replacing(SAMPLE_RS, 5, 2, "")
+        self.assertEqual(lopper.source, SAMPLE_RS)
+        self.assertEqual(
+            InputEdit.replacing("ab\ncd", 4, 5, "x\nyz"),
+            InputEdit(4, 5, 8, (1, 1), (1, 2), (2, 2)),
+        )
+
+
+class TestTokenBudget(unittest.TestCase):
+    """Test fitting skeletons to a token budget."""
+
+    code: str = (
+        "class Model:\n"
+        "    def run(self):\n"
+        "        return _helper(1)\n"
+        "\n"
+        "def api():\n"
+        "    return 2\n"
+        "\n"
+        "def _helper(x):\n"
+        "    return x\n"
+    )
+
+    def test_fitting_skeleton_unchanged(self) -> None:
+        """Test that a skeleton within budget is returned as is."""
+        result = fit_to_budget(self.code, "python", 1000)
+        self.assertEqual(result.skeleton, extract_skeleton(self.code, "python"))
+        self.assertEqual(result.dropped, 0)
+        self.assertTrue(result.fits)
+
+    def test_private_helpers_dropped_first(self) -> None:
+        """Test that private items go before public ones."""
+        expected = "class Model:\n    def run(self):\n        ...\n\ndef api():\n    ..."
+        result = fit_to_budget(self.code, "python", estimate_tokens(expected))
+        self.assertEqual(result.skeleton, expected)
+        self.assertEqual(result.dropped, 1)
+        self.assertEqual(result.tokens, estimate_tokens(expected))
+
+    def test_top_level_types_kept_longest(self) -> None:
+        """Test custom estimators and that types outlive functions and methods."""
+        result = fit_to_budget(
+            self.code, "python", 2, estimator=lambda text: text.count("\n") + 1
+        )
+        self.assertEqual(result.skeleton, "class Model:\n    ...")
+        self.assertEqual(result.dropped, 3)
+
+    def test_referenced_symbols_kept_longer(self) -> None:
+        """Test that reference counts order the symbols of a group."""
+        expected = "class Model:\n    def run(self):\n        ..."
+        result = fit_to_budget(
+            self.code, "python", estimate_tokens(expected), references={"run": 3}
+        )
+        self.assertEqual(result.skeleton, expected)
+        self.assertEqual(result.dropped, 2)
+
+    def test_empty_budget(self) -> None:
+        """Test that every symbol, nested ones included, can be dropped."""
+        result = fit_to_budget(self.code, "python", 0)
+        self.assertEqual(result.skeleton, "")
+        self.assertEqual(result.dropped, 4)
+
+    def test_negative_budget(self) -> None:
+        """Test that negative budgets are rejected."""
+        with self.assertRaises(ValueError):
+            fit_to_budget(self.code, "python", -1)
+
+
+class TestFileBudget(unittest.TestCase):
+    """Test fitting several files together to a token budget."""
+
+    files: dict[str, str] = {
+        "main.py": (
+            "def main():\n    a = 1\n    b = 2\n    return a + b\n\ndef short():\n    return 1\n"
+        ),
+        "util.py": "def _hidden():\n    return 0\n\ndef shown():\n    return 1\n",
+        "notes.txt": "hello\n",
+    }
+
+    def fit(self, max_tokens: int) -> BudgetReport:
+        with tempfile.TemporaryDirectory() as tmpdir:
+            for name, content in self.files.items():
+                (Path(tmpdir) / name).write_text(content)
+            return fit_files_to_budget(
+                list(self.files),
+                max_tokens,
+                root=tmpdir,
+                estimator=lambda text: text.count("\n") + 1,
+            )
+
+    def test_full_source_when_it_fits(self) -> None:
+        """Test that files within budget are included verbatim."""
+        report = self.fit(16)
+        self.assertEqual([file.level for file in report.files], ["full", "full", "full"])
+        self.assertEqual(report.tokens, 16)
+        self.assertTrue(report.text.startswith("--- main.py\ndef main():\n    a = 1\n"))
+
+    def test_longest_bodies_elided_first(self) -> None:
+        """Test that only bodies longer than the fitted threshold are elided."""
+        report = self.fit(15)
+        self.assertEqual([file.level for file in report.files], ["elided", "elided", "full"])
+        self.assertEqual(report.files[0].max_body_lines, 2)
+        self.assertEqual(
+            report.files[0].text, "def main():\n    ...\n\ndef short():\n    return 1"
+        )
+        self.assertEqual(report.tokens, 14)
+
+    def test_private_items_then_files_dropped_from_the_last(self) -> None:
+        """Test that later files lose private items, then whole files go."""
+        report = self.fit(12)
+        self.assertEqual([file.level for file in report.files], ["elided", "public", "full"])
+        self.assertEqual(report.files[1].text, "def shown():\n    ...")
+
+        report = self.fit(6)
+        self.assertEqual([file.level for file in report.files], ["public", "dropped", "dropped"])
+        self.assertEqual(report.text, "--- main.py\ndef main():\n    ...\n\ndef short():\n    ...")
+        self.assertTrue(report.fits)
+        self.assertEqual(report, self.fit(6))
+
+        report = self.fit(0)
+        self.assertEqual(report.text, "")
+        self.assertTrue(report.fits)
+
+
+class TestStats(unittest.TestCase):
+    """Test compression statistics of skeletons."""
+
+    code = (
+        "pub struct Calculator {\n"
+        "    value: i32,\n"
+        "}\n"
+        "\n"
+        "impl Calculator {\n"
+        "    pub fn new() -> Self {\n"
+        "        Calculator { value: 0 }\n"
+        "    }\n"
+        "\n"
+        "    pub fn get(&self) -> i32 { self.value }\n"
+        "}\n"
+        "\n"
+        "fn helper() {\n"
+        '    println!("hi");\n'
+        "}\n"
+    )
+
+    def test_sizes_and_elided_items(self) -> None:
+        """Test that sizes match the skeleton and items with elided code are counted."""
+        stats = lop_stats(self.code, "rust")
+        skeleton = extract_skeleton(self.code, "rust")
+        self.assertEqual(
+            (stats.original_bytes, stats.original_lines, stats.original_tokens),
+            (len(self.code), 15, estimate_tokens(self.code)),
+        )
+        self.assertEqual(
+            (stats.output_bytes, stats.output_lines, stats.output_tokens),
+            (len(skeleton), len(skeleton.splitlines()), estimate_tokens(skeleton)),
+        )
+        self.assertEqual(stats.elided, {"function": 1, "method": 2})
+        self.assertEqual(stats.saved_tokens, stats.original_tokens - stats.output_tokens)
+
+        options = LopperOptions(collapse_types=True, visibility="public")
+        self.assertEqual(
+            lop_stats(self.code, "rust", options=options).elided,
+            {"function": 1, "method": 2, "struct": 1},
+        )
+        self.assertEqual(lop_stats(self.code, "rust", estimator=len).original_tokens, 210)
+
+    def test_totals_skip_failed_files(self) -> None:
+        """Test that totals only sum measured files and the others are listed apart."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir)
+            (root / "a.rs").write_text(self.code)
+            (root / "b.py").write_text("def f():\n    return 1\n")
+            (root / "notes.txt").write_text("notes\n")
+            report = collect_stats(["a.rs", "b.py", "notes.txt", "missing.rs"], root=root)
+        self.assertEqual([path for path, _ in report.files], ["a.rs", "b.py"])
+        self.assertEqual(report.skipped, ["notes.txt"])
+        self.assertEqual([path for path, _ in report.failed], ["missing.rs"])
+        first, second = (stats for _, stats in report.files)
+        self.assertEqual(report.total, first + second)
+        self.assertEqual(report.total.elided, {"function": 2, "method": 2})
+        table = report.table().splitlines()
+        self.assertEqual(table[0].split(), ["path", "lines", "bytes", "tokens", "saved", "elided"])
+        self.assertTrue(table[3].startswith("total"))
+        self.assertEqual(
+            table[4:], ["skipped: notes.txt", f"failed: missing.rs ({report.failed[0][1]})"]
+        )
+
+    def test_census(self) -> None:
+        """Test that defined items and elided lines are counted and summed per language."""
+        stats = lop_stats(self.code, "rust")
+        self.assertEqual(stats.symbols, {"function": 1, "impl": 1, "method": 2, "struct": 1})
+        self.assertTrue(0 < stats.elided_lines < stats.original_lines)
+
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir)
+            (root / "a.rs").write_text(self.code)
+            (root / "b.rs").write_text("fn main() {\n    run();\n}\n")
+            (root / "c.py").write_text("def f():\n    return 1\n")
+            report = collect_stats(["a.rs", "b.rs", "c.py"], root=root)
+        self.assertEqual(report.languages, {"a.rs": "rust", "b.rs": "rust", "c.py": "python"})
+        self.assertEqual(list(report.by_language), ["python", "rust"])
+        count, rust = report.by_language["rust"]
+        self.assertEqual((count, rust.original_lines), (2, 18))
+        self.assertEqual(rust.symbols, {"function": 2, "impl": 1, "method": 2, "struct": 1})
+        census = report.census().splitlines()
+        self.assertEqual(census[0].split(), ["language", "files", "lines", "elided", "items"])
+        self.assertEqual(census[1].split()[:3], ["python", "1", "2"])
+        self.assertEqual(census[3].split()[:3], ["total", "3", "20"])
+
+
+class TestSymbols(unittest.TestCase):
+    """Test structured symbol extraction."""
+
+    def test_rust_sample_outline(self) -> None:
+        """Test the symbol tree extracted from the Rust sample."""
+        symbols = extract_symbols(SAMPLE_RS, "rust")
+        outline = [(s.kind, s.name, [c.name for c in s.children]) for s in symbols]
+        self.assertEqual(
+            outline,
+            [
+                ("function", "fibonacci", []),
+                ("struct", "Calculator", []),
+                ("trait", "Arithmetic", ["add", "multiply"]),
+                ("impl", "Calculator", ["new", "add", "process"]),
+                ("impl", "Calculator", ["add", "multiply"]),
+            ],
+        )
+        trait_impl = symbols[4]
+        self.assertEqual(trait_impl.signature, "impl Arithmetic for Calculator")
+        self.assertEqual(trait_impl.children[0].kind, "method")
+        self.assertEqual(trait_impl.children[0].signature, "fn add(&self, x: i32) -> i32")
+        self.assertEqual(symbols[2].children[1].signature, "fn multiply(&self, x: i32) -> i32")
+        self.assertEqual((symbols[0].start_line, symbols[0].end_line), (1, 6))
+
+    def test_return_types_with_braces(self) -> None:
+        """Test that return types holding braces and arrows are kept whole."""
+        code = (
+            "fn make() -> impl Fn() -> i32 {\n"
+            "    || 1\n"
+            "}\n"
+            "\n"
+            "fn table() -> [u8; { 2 + 2 }] {\n"
+            "    [0; 4]\n"
+            "}\n"
+        )
+        self.assertEqual(
+            extract_skeleton(code, "rust"),
+            "fn make() -> impl Fn() -> i32 {}\n\nfn table() -> [u8; { 2 + 2 }] {}",
+        )
+        self.assertEqual(
+            [symbol.signature for symbol in extract_symbols(code, "rust")],
+            ["fn make() -> impl Fn() -> i32", "fn table() -> [u8; { 2 + 2 }]"],
+        )
+
+    def test_signature_text(self) -> None:
+        """Test signatures of nodes parsed by the caller, with and without bodies."""
+        source = (
+            b"trait Shape {\n"
+            b"    fn area(&self) -> f64;\n"
+            b"}\n"
+            b"\n"
+            b"pub fn scale(shape: &dyn Shape, factor: f64)\n"
+            b"    -> f64\n"
+            b"{\n"
+            b"    shape.area() * factor\n"
+            b"}\n"
+        )
+        root = get_extractor("rust").parse(source).root_node
+        trait, function = root.named_children
+        declaration = trait.child_by_field_name("body").named_children[0]
+        self.assertEqual(signature_text(declaration, source), "fn area(&self) -> f64")
+        self.assertEqual(
+            signature_text(function, source),
+            "pub fn scale(shape: &dyn Shape, factor: f64)\n    -> f64",
+        )
+        python = b"def area(self) -> float:\n    return 0.0\n"
+        node = get_extractor("python").parse(python).root_node.named_children[0]
+        self.assertEqual(signature_text(node, python), "def area(self) -> float")
+
+    def test_group_impls(self) -> None:
+        """Test that impl items join their type's members and orphan impls stay apart."""
+        orphan = "impl Default for Config {\n    fn default() -> Self { todo!() }\n}\n"
+        code = f"{SAMPLE_RS}\n{orphan}"
+        symbols = extract_symbols(code, "rust", group_impls=True)
+        self.assertEqual(
+            [(s.kind, s.name) for s in symbols],
+            [
+                ("function", "fibonacci"),
+                ("struct", "Calculator"),
+                ("trait", "Arithmetic"),
+                ("impl", "Config"),
+            ],
+        )
+        members = [(c.name, c.parent, c.trait) for c in symbols[1].children]
+        self.assertEqual(
+            members,
+            [
+                ("new", "Calculator", None),
+                ("add", "Calculator", None),
+                ("process", "Calculator", None),
+                ("add", "Calculator", "Arithmetic"),
+                ("multiply", "Calculator", "Arithmetic"),
+            ],
+        )
+        self.assertEqual(symbols[3].children[0].trait, "Default")
+        self.assertEqual(len(extract_symbols(code, "rust")), 6)
+
+    def test_closures_omitted(self) -> None:
+        """Test that anonymous closures are not reported as symbols."""
+        process = extract_symbols(SAMPLE_RS, "rust")[3].children[2]
+        self.assertEqual(process.name, "process")
+        self.assertEqual(process.children, [])
+
+    def test_keep_closures(self) -> None:
+        """Test that closures are listed under the function they are written in."""
+        process = extract_symbols(SAMPLE_RS, "rust", keep_closures=True)[3].children[2]
+        self.assertEqual(
+            [(c.kind, c.name, c.signature, c.parent, c.start_line) for c in process.children],
+            [("closure", "<closure>", "|x|", "process", 27)],
+        )
+        self.assertIsNone(process.children[0].visibility)
+
+        code = (
+            "const handler = (req) => req.body;\n"
+            "function routes(app) {\n"
+            "    app.get('/', (req, res) => {\n"
+            "        res.send(items.map(function (item) { return item.id; }));\n"
+            "    });\n"
+            "}\n"
+        )
+        symbols = extract_symbols(code, "javascript", keep_closures=True)
+        self.assertEqual(
+            [(s.kind, s.name) for s in symbols], [("function", "handler"), ("function", "routes")]
+        )
+        callback = symbols[1].children[0]
+        self.assertEqual((callback.kind, callback.signature), ("closure", "(req, res) =>"))
+        self.assertEqual([c.signature for c in callback.children], ["function (item)"])
+        self.assertEqual(symbols[0].children, [])
+
+    def test_python_methods(self) -> None:
+        """Test that Python functions inside classes are reported as methods."""
+        code = "class A(Base):\n    def run(self):\n        pass\n\ndef main():\n    pass\n"
+        symbols = extract_symbols(code, "python")
+        self.assertEqual(
+            [(s.kind, s.signature) for s in symbols],
+            [("class", "class A(Base)"), ("function", "def main()")],
+        )
+        self.assertEqual(symbols[0].children[0].kind, "method")
+
+    def test_module_functions(self) -> None:
+        """Test that Ruby module functions are methods, and Rust module functions are not."""
+        ruby = extract_symbols("module Greeting\n  def hello\n    'hi'\n  end\nend\n", "ruby")
+        self.assertEqual([(c.kind, c.name) for c in ruby[0].children], [("method", "hello")])
+        rust = extract_symbols("pub mod util {\n    pub fn run() {}\n}\n", "rust")
+        self.assertEqual([(c.kind, c.name) for c in rust[0].children], [("function", "run")])
+
+    def test_symbols_to_json(self) -> None:
+        """Test JSON serialization of the symbol tree."""
+        data = json.loads(symbols_to_json(extract_symbols(SAMPLE_RS, "rust")))
+        self.assertEqual(data[1]["kind"], "struct")
+        self.assertEqual(data[1]["name"], "Calculator")
+        self.assertEqual(
+            set(data[3]["children"][0]),
+            {
+                "kind",
+                "name",
+                "signature",
+                "visibility",
+                "parent",
+                "qualified_name",
+                "trait",
+                "is_async",
+                "is_unsafe",
+                "is_const",
+                "is_extern",
+                "start_line",
+                "end_line",
+                "start_column",
+                "end_column",
+                "start_byte",
+                "end_byte",
+                "signature_end_byte",
+                "children",
+            },
+        )
+
+    def test_signature_byte_ranges(self) -> None:
+        """Test that signature byte ranges index the UTF-8 encoded source."""
+        encoded = SAMPLE_RS.encode()
+        for symbol in iter_symbols(extract_symbols(SAMPLE_RS, "rust")):
+            with self.subTest(name=symbol.name):
+                signature = encoded[symbol.start_byte : symbol.signature_end_byte]
+                self.assertEqual(signature.decode(), symbol.signature)
+                self.assertLessEqual(symbol.signature_end_byte, symbol.end_byte)
+        code = '# Grüße\ndef grüß(name: str) -> str:\n    return "¡hola!"\n'
+        (symbol,) = extract_symbols(code, "python")
+        span = code.encode()[symbol.start_byte : symbol.signature_end_byte]
+        self.assertEqual(span.decode(), "def grüß(name: str) -> str")
+        self.assertEqual(symbol.start_byte, len("# Grüße\n".encode()))
+
+    def test_symbols_json_round_trip(self) -> None:
+        """Test that symbols read back from JSON equal the serialized ones."""
+        symbols = extract_symbols(SAMPLE_RS, "rust")
+        self.assertEqual(symbols_from_json(symbols_to_json(symbols)), symbols)
+        self.assertEqual(symbols_from_json(symbols_to_json(symbols, indent=None)), symbols)
+        self.assertEqual(source_hash(SAMPLE_RS), source_hash(SAMPLE_RS))
+        self.assertNotEqual(source_hash(SAMPLE_RS), source_hash(SAMPLE_RS + "\n"))
+        for text in ('{"kind": "function"}', '[{"kind": "function"}]', "[1]", "["):
+            with self.subTest(text=text), self.assertRaises(ValueError):
+                symbols_from_json(text)
+
+    def test_rank_symbols(self) -> None:
+        """Test that symbols are ranked by the references to them across files."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir)
+            (root / "lib.rs").write_text(
+                "pub struct Config {\n"
+                "    pub verbose: bool,\n"
+                "}\n"
+                "\n"
+                "pub fn helper(config: &Config) -> bool {\n"
+                "    config.verbose\n"
+                "}\n"
+                "\n"
+                "pub fn unused() {}\n"
+            )
+            (root / "main.rs").write_text(
+                "fn main() {\n"
+                "    let config = Config { verbose: true };\n"
+                "    helper(&config);\n"
+                "    helper(&config);\n"
+                "    helper(&config);\n"
+                "}\n"
+            )
+            (root / "notes.txt").write_text("helper\n")
+            ranked = rank_symbols(["lib.rs", "main.rs", "notes.txt"], root=root)
+        self.assertEqual(
+            [(item.path, item.symbol.name, item.references, item.files) for item in ranked],
+            [
+                ("lib.rs", "helper", 3, 1),
+                ("lib.rs", "Config", 2, 1),
+                ("lib.rs", "unused", 0, 0),
+                ("main.rs", "main", 0, 0),
+            ],
+        )
+
+    def test_qualifier_flags(self) -> None:
+        """Test that async, unsafe, const and extern qualifiers become flags."""
+        code = (
+            "pub async fn fetch() {}\n"
+            "pub const fn zero() -> u32 { 0 }\n"
+            "unsafe fn raw() {}\n"
+            'pub extern "C" fn callback() {}\n'
+            "unsafe impl Send for Handle {}\n"
+            'extern "C" {\n'
+            "    fn abs(x: i32) -> i32;\n"
+            "}\n"
+            "fn plain() {}\n"
+        )
+        symbols = {symbol.name: symbol for symbol in iter_symbols(extract_symbols(code, "rust"))}
+        flags = {
+            name: [
+                flag
+                for flag in ("async", "unsafe", "const", "extern")
+                if getattr(symbol, f"is_{flag}")
+            ]
+            for name, symbol in symbols.items()
+        }
+        self.assertEqual(flags["fetch"], ["async"])
+        self.assertEqual(flags["zero"], ["const"])
+        self.assertEqual(flags["raw"], ["unsafe"])
+        self.assertEqual(flags["callback"], ["extern"])
+        self.assertEqual(flags["Handle"], ["unsafe"])
+        self.assertEqual(flags["abs"], ["extern"])
+        self.assertEqual(flags["plain"], [])
+
+        python = extract_symbols("async def run():\n    pass\n\ndef stop():\n    pass\n", "python")
+        self.assertEqual([symbol.is_async for symbol in python], [True, False])
+        data = json.loads(symbols_to_json(python))
+        self.assertEqual([item["name"] for item in data if item["is_async"]], ["run"])
+
+    def test_go_methods_name_receiver_type(self) -> None:
+        """Test that Go methods are attributed to their receiver's type."""
+        code = (
+            "package stack\n\n"
+            "var (\n    count int\n    Name = \"stack\"\n)\n\n"
+            "type Stack[T any] struct {\n    items []T\n}\n\n"
+            "func (s *Stack[T]) Push(item T) {\n    s.items = append(s.items, item)\n}\n\n"
+            "func (s Stack[T]) Len() int {\n    return len(s.items)\n}\n\n"
+            "func New[T any]() *Stack[T] {\n    return &Stack[T]{}\n}\n"
+        )
+        symbols = extract_symbols(code, "go")
+        self.assertEqual(
+            [(symbol.kind, symbol.name, symbol.parent) for symbol in symbols],
+            [
+                ("variable", "count", None),
+                ("variable", "Name", None),
+                ("struct", "Stack", None),
+                ("method", "Push", "Stack"),
+                ("method", "Len", "Stack"),
+                ("function", "New", None),
+            ],
+        )
+        self.assertEqual(symbols[3].signature, "func (s *Stack[T]) Push(item T)")
+        self.assertEqual([symbol.visibility for symbol in symbols[:2]], ["private", "public"])
+
+    def test_rust_macros(self) -> None:
+        """Test that macro definitions are outlined, exported ones as public."""
+        symbols = extract_symbols(MACROS_RS, "rust")
+        self.assertEqual(
+            [(symbol.kind, symbol.name, symbol.signature, symbol.visibility) for symbol in symbols],
+            [
+                ("macro", "hashmap", "macro_rules! hashmap", "pub"),
+                ("macro", "square", "macro_rules! square", "private"),
+            ],
+        )
+
+    def test_rust_generic_signatures(self) -> None:
+        """Test that signatures span their generic parameters and where-clauses."""
+        symbols = extract_symbols(GENERICS_RS, "rust")
+        signatures = {symbol.name: symbol.signature for symbol in iter_symbols(symbols)}
+        self.assertEqual(
+            signatures["describe"],
+            "pub fn describe<K, V, const N: usize>(pairs: [(K, V); N]) -> String\n"
+            "where\n"
+            "    K: Debug + Ord,\n"
+            "    V: Debug + Clone + Default,",
+        )
+        self.assertEqual(
+            signatures["get"],
+            "pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V>\n"
+            "    where\n"
+            "        K: std::borrow::Borrow<Q>,\n"
+            "        Q: Eq,",
+        )
+        self.assertEqual(
+            signatures["visit"],
+            "fn visit<T>(&mut self, value: T) -> Result<(), String>\n"
+            "    where\n"
+            "        T: Debug + 'de",
+        )
+
+    def test_find_symbols(self) -> None:
+        """Test finding same-named symbols with the context telling them apart."""
+        symbols = extract_symbols(SAMPLE_RS, "rust")
+        self.assertEqual(
+            [(s.parent, s.trait, s.start_line, s.end_line) for s in find_symbols(symbols, "add")],
+            [
+                ("Arithmetic", None, 13, 13),
+                ("Calculator", None, 22, 24),
+                ("Calculator", "Arithmetic", 33, 35),
+            ],
+        )
+        self.assertEqual(find_symbols(symbols, "ad"), [])
+
+    def test_qualified_names(self) -> None:
+        """Test that symbols in nested modules get qualified names through them."""
+        code = (
+            "mod outer {\n"
+            "    pub mod inner {\n"
+            "        pub fn run() {}\n"
+            "        impl<T> Stack<T> {\n"
+            "            fn push(&mut self) {}\n"
+            "        }\n"
+            "    }\n"
+            "    fn run() {}\n"
+            "}\n"
+            "mod generated;\n"
+            "fn run() {}\n"
+        )
+        symbols = extract_symbols(code, "rust")
+        self.assertEqual(
+            [(s.kind, s.qualified_name) for s in iter_symbols(symbols)],
+            [
+                ("module", "outer"),
+                ("module", "outer::inner"),
+                ("function", "outer::inner::run"),
+                ("impl", "outer::inner::Stack"),
+                ("method", "outer::inner::Stack::push"),
+                ("function", "outer::run"),
+                ("module", "generated"),
+                ("function", "run"),
+            ],
+        )
+        self.assertEqual(symbols[1].signature, "mod generated")
+        self.assertEqual(symbols[1].children, [])
+        self.assertEqual(
+            [s.start_line for s in find_symbols(symbols, "outer::inner::run")], [3]
+        )
+        self.assertEqual(len(find_symbols(symbols, "run")), 3)
+
+        code = "class Outer:\n    class Inner:\n        def run(self):\n            pass\n"
+        (outer,) = extract_symbols(code, "python")
+        self.assertEqual(outer.children[0].children[0].qualified_name, "Outer.Inner.run")
+        code = "package main\n\nfunc (s *Square) Area() int {\n\treturn 0\n}\n"
+        self.assertEqual(extract_symbols(code, "go")[0].qualified_name, "Square.Area")
+
+    def test_find_matching_symbols(self) -> None:
+        """Test case-insensitive substring search over nested symbols."""
+        symbols = extract_symbols(SAMPLE_RS, "rust")
+        names = [symbol.name for symbol in find_matching_symbols(symbols, "MULT")]
+        self.assertEqual(names, ["multiply", "multiply"])
+        self.assertEqual(
+            [symbol.kind for symbol in find_matching_symbols(symbols, "calc")],
+            ["struct", "impl", "impl"],
+        )
+
+    def test_extract_item_by_path(self) -> None:
+        """Test extracting the full source of an item by its qualified path."""
+        item = extract_item(SAMPLE_RS, "rust", "Calculator::process")
+        self.assertIsNotNone(item)
+        self.assertEqual(
+            item.text,
+            "    fn process(&self) {\n"
+            "        let closure = |x| x * 2;\n"
+            "        let result = closure(5);\n"
+            "    }",
+        )
+        self.assertEqual((item.start_line, item.end_line), (26, 29))
+        self.assertEqual(item.symbol.kind, "method")
+
+        fibonacci = extract_item(SAMPLE_RS, "rust", "fibonacci")
+        self.assertIsNotNone(fibonacci)
+        self.assertEqual((fibonacci.start_line, fibonacci.end_line), (1, 6))
+
+    def test_extract_item_trait_impl(self) -> None:
+        """Test that inherent items win unless a trait implementation is named."""
+        inherent = extract_item(SAMPLE_RS, "rust", "Calculator::add")
+        trait_impl = extract_item(SAMPLE_RS, "rust", "<Calculator as Arithmetic>::add")
+        declaration = extract_item(SAMPLE_RS, "rust", "Arithmetic::add")
+        self.assertIn("x + y", inherent.text)
+        self.assertIn("self.value + x", trait_impl.text)
+        self.assertEqual(declaration.text, "    fn add(&self, x: i32) -> i32;")
+
+    def test_extract_item_unresolved(self) -> None:
+        """Test that paths not resolving to an item return None."""
+        for path in ("Calculator::missing", "process", "<Calculator as Display>::add", ""):
+            self.assertIsNone(extract_item(SAMPLE_RS, "rust", path), path)
+
+    def test_extract_item_python_decorated(self) -> None:
+        """Test dotted paths and that decorators come with the item."""
+        code = "class A:\n    @property\n    def name(self):\n        return 'a'\n"
+        item = extract_item(code, "python", "A.name")
+        self.assertIsNotNone(item)
+        self.assertEqual(item.text, "    @property\n    def name(self):\n        return 'a'")
+        self.assertEqual((item.start_line, item.end_line), (2, 4))
+
+    def test_visibility_and_parent(self) -> None:
+        """Test visibility and parent names of the Rust sample symbols."""
+        symbols = extract_symbols(SAMPLE_RS, "rust")
+        inherent, trait_impl = symbols[3], symbols[4]
+        self.assertEqual(
+            [(s.name, s.visibility) for s in symbols],
+            [
+                ("fibonacci", "private"),
+                ("Calculator", "pub"),
+                ("Arithmetic", "pub"),
+                ("Calculator", None),
+                ("Calculator", None),
+            ],
+        )
+        self.assertEqual(
+            [(c.name, c.visibility) for c in inherent.children],
+            [("new", "pub"), ("add", "pub"), ("process", "private")],
+        )
+        self.assertEqual(trait_impl.children[0].visibility, "pub")
+        self.assertEqual(trait_impl.children[0].parent, "Calculator")
+        self.assertIsNone(symbols[0].parent)
+
+    def test_spans(self) -> None:
+        """Test byte and column spans against the original source."""
+        source = "def a():\n    pass\n\nclass B:\n    x = 1\n"
+        symbols = extract_symbols(source, "python")
+        a, b = symbols
+        self.assertEqual(source[a.start_byte : a.end_byte], "def a():\n    pass")
+        self.assertEqual((a.start_column, a.end_line, a.end_column), (0, 2, 8))
+        self.assertEqual(source[b.start_byte : b.end_byte], "class B:\n    x = 1")
+
+
+class TestLanguageDetection(unittest.TestCase):
+    """Test detecting languages from file names and shebang lines."""
+
+    def test_shebang_interpreters(self) -> None:
+        """Test interpreters named directly, through env and with versions."""
+        cases = {
+            "#!/usr/bin/env -S python3 -u\nprint(1)\n": "python",
+            "#!/usr/bin/python3.11\n": "python",
+            "#!/bin/bash\n": "bash",
+            "#! /bin/sh -e\n": "bash",
+            "#!/usr/bin/env NODE_ENV=production node\n": "javascript",
+            "#!/usr/bin/env ruby\n": "ruby",
+        }
+        for content, language in cases.items():
+            self.assertEqual(detect_language("script", content), language, content)
+
+    def test_unknown_content(self) -> None:
+        """Test that files without a shebang or known name are not detected."""
+        self.assertIsNone(detect_language("script", "print(1)\n"))
+        self.assertIsNone(detect_language("script", "#!/usr/bin/env\n"))
+        self.assertIsNone(detect_language("script", "#!/usr/bin/env perl\n"))
+        self.assertIsNone(detect_language("script"))
+
+    def test_sniffed_content(self) -> None:
+        """Test guessing the language of code without a name or shebang."""
+        cases = {
+            "import os\n\ndef main():\n    pass\n": "python",
+            "package main\n\nfunc main() {\n\tx := 1\n}\n": "go",
+            "const fs = require('fs');\nconsole.log(fs);\n": "javascript",
+            "interface A {\n  x: number;\n}\nexport const a: A = { x: 1 };\n": "typescript",
+        }
+        for content, language in cases.items():
+            self.assertEqual(detect_language(None, content, sniff=True), language, content)
+            self.assertEqual(sniff_language(content), language, content)
+        self.assertIsNone(detect_language(None, "print(1)\n", sniff=True))
+        self.assertIsNone(detect_language(None, "Some notes.\nMore notes.\n", sniff=True))
+        self.assertEqual(
+            detect_language(None, "#!/bin/bash\nimport os\ndef main():\n", sniff=True), "bash"
+        )
+        self.assertEqual(detect_language("a.rb", "import os\ndef main():\n", sniff=True), "ruby")
+
+    def test_extension_wins(self) -> None:
+        """Test that a known extension takes precedence over the shebang."""
+        self.assertEqual(detect_language("tool.rb", "#!/usr/bin/env python3\n"), "ruby")
+        self.assertEqual(detect_language("tool.cgi", "#!/usr/bin/env python3\n"), "python")
+
+    def test_well_known_file_names(self) -> None:
+        """Test file names mapped without extension or shebang."""
+        self.assertEqual(detect_language("pkg/BUILD"), "python")
+        self.assertEqual(detect_language("Rakefile"), "ruby")
+        self.assertEqual(detect_language("lib/tasks/db.rake"), "ruby")
+        self.assertEqual(detect_language("shop.gemspec"), "ruby")
+        self.assertEqual(detect_language(Path("ci") / "Jenkinsfile"), "groovy")
+
+    def test_extensionless_scripts_extracted(self) -> None:
+        """Test that shebang scripts are found and extracted from disk."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir)
+            (root / "deploy").write_text(
+                "#!/bin/bash\nset -e\n\nrun() {\n    echo start\n    make all\n}\n"
+            )
+            (root / "notes").write_text("just text\n")
+            self.assertEqual(detect_file_language(root / "deploy"), "bash")
+            self.assertIsNone(detect_file_language(root / "notes"))
+            self.assertEqual(list(walk_dir(root)), ["deploy"])
+            self.assertEqual(
+                get_skeleton(root / "deploy"), "#!/bin/bash\nset -e\n\nrun() {\n}"
+            )
+
+
+class TestBinaryFileDetection(unittest.TestCase):
+    """Test binary file detection."""
+
+    def test_text_file_not_binary(self) -> None:
+        """Test that text files are not detected as binary."""
+        with tempfile.NamedTemporaryFile(mode="w", suffix=".txt", delete=False) as f:
+            f.write("Hello, this is a text file\n")
+            f.flush()
+            path = Path(f.name)
+            try:
+                self.assertFalse(is_binary(str(path)))
+            finally:
+                path.unlink()
+
+    def test_binary_extension_detected(self) -> None:
+        """Test that files with known binary extensions are detected."""
+        # binaryornot detects based on known extensions and null bytes
+        # Test with a known binary extension + null byte content
+        with tempfile.NamedTemporaryFile(suffix=".pyc", delete=False, mode="wb") as f:
+            f.write(b"Python compiled\x00binary\x00content")
+            f.flush()
+            path = Path(f.name)
+            try:
+                self.assertTrue(is_binary(str(path)))
+            finally:
+                path.unlink()
+
+    def test_null_byte_detection(self) -> None:
+        """Test that files with null bytes are detected as binary."""
+        with tempfile.NamedTemporaryFile(mode="wb", suffix=".txt", delete=False) as f:
+            # Files with null bytes fail UTF-8 decoding
+            f.write(b"Some text\x00with null bytes")
+            f.flush()
+            path = Path(f.name)
+            try:
+                self.assertTrue(is_binary(str(path)))
+            finally:
+                path.unlink()
+
+    def test_python_file_not_binary(self) -> None:
+        """Test that Python files are not detected as binary."""
+        with tempfile.NamedTemporaryFile(mode="w", suffix=".py", delete=False) as f:
+            f.write("def hello():\n    print('hello')\n")
+            f.flush()
+            path = Path(f.name)
+            try:
+                self.assertFalse(is_binary(str(path)))
+            finally:
+                path.unlink()
+
+    def test_empty_file_not_binary(self) -> None:
+        """Test that empty files are not detected as binary."""
+        with tempfile.NamedTemporaryFile(delete=False) as f:
+            f.flush()
+            path = Path(f.name)
+            try:
+                self.assertFalse(is_binary(str(path)))
+            finally:
+                path.unlink()
+
+    def test_image_extension_detected(self) -> None:
+        """Test that binary image files with null bytes are detected."""
+        # binaryornot detects binary content via null bytes
+        test_cases = [
+            (".jpg", b"\xFF\xD8\xFF\xE0\x00image"),  # JPEG with null byte
+            (".png", b"\x89PNG\r\n\x1a\n\x00data"),  # PNG with null byte
+            (".gif", b"GIF89a\x00binary"),  # GIF with null byte
+        ]
+        for ext, content in test_cases:
+            with tempfile.NamedTemporaryFile(suffix=ext, delete=False, mode="wb") as f:
+                f.write(content)
+                f.flush()
+                path = Path(f.name)
+                try:
+                    self.assertTrue(is_binary(str(path)), f"{ext} should be detected as binary")
+                finally:
+                    path.unlink()
+
+
+class TestFindFilesWithMixedTypes(unittest.TestCase):
+    """Test find_files returns all non-binary text files."""
+
+    def test_find_files_includes_non_code_files(self) -> None:
+        """Test that find_files includes markdown, json, yaml, etc."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            tmppath = Path(tmpdir)
+
+            # Create various file types
+            (tmppath / "readme.md").write_text("# Test\n")
+            (tmppath / "config.json").write_text('{"key": "value"}\n')
+            (tmppath / "data.yaml").write_text("key: value\n")
+            (tmppath / "script.py").write_text("def hello():\n    pass\n")
+
+            # Find files
+            files = find_files(tmppath, recursive=False)
+
+            # All text files should be included
+            self.assertIn("readme.md", files)
+            self.assertIn("config.json", files)
+            self.assertIn("data.yaml", files)
+            self.assertIn("script.py", files)
+
+    def test_find_files_excludes_binary_files(self) -> None:
+        """Test that find_files excludes binary files."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            tmppath = Path(tmpdir)
+
+            # Create text and binary files
+            (tmppath / "text.txt").write_text("text content\n")
+            (tmppath / "binary.bin").write_bytes(b"\x00\x01\x02\x03")
+
+            files = find_files(tmppath, recursive=False)
+
+            self.assertIn("text.txt", files)
+            self.assertNotIn("binary.bin", files)
+
+
+class TestMixedFileTypes(unittest.TestCase):
+    """Test mixed file type handling."""
+
+    def test_get_skeleton_on_unsupported_file_as_is(self) -> None:
+        """Test that unsupported files can't be extracted without language."""
+        with tempfile.NamedTemporaryFile(mode="w", suffix=".json", delete=False) as f:
+            f.write('{"key": "value"}\n')
+            f.flush()
+            path = Path(f.name)
+
+            try:
+                # Should raise ValueError for unsupported file type
+                with self.assertRaises(ValueError):
+                    get_skeleton(path)
+            finally:
+                path.unlink()
+
+
+class TestExtractWithUnsupportedFiles(unittest.TestCase):
+    """Test extract command behavior with unsupported files."""
+
+    def test_extract_requires_language_for_unsupported(self) -> None:
+        """Test that extract requires -l for unsupported file types."""
+        with tempfile.NamedTemporaryFile(mode="w", suffix=".txt", delete=False) as f:
+            f.write("# Test\n")
+            f.flush()
+            path = Path(f.name)
+
+            try:
+                # Should raise ValueError for unsupported file type
+                with self.assertRaises(ValueError):
+                    get_skeleton(path)
+            finally:
+                path.unlink()
+
+    def test_extract_with_explicit_language(self) -> None:
+        """Test that extract works with explicit language for any file."""
+        with tempfile.NamedTemporaryFile(mode="w", suffix=".md", delete=False) as f:
+            f.write("def hello():\n    print('hi')\n")
+            f.flush()
+            path = Path(f.name)
+
+            try:
+                # With explicit language, should work
+                skeleton = extract_skeleton(path.read_text(), "python")
+                self.assertIn("def hello()", skeleton)
+                self.assertNotIn("print('hi')", skeleton)
+            finally:
+                path.unlink()
+
+
+class TestIgnorePatternsWithMixedFiles(unittest.TestCase):
     """Test ignore patterns work correctly with all file types."""
 
     def test_default_ignore_patterns_apply(self) -> None:
@@ -697,6 +5161,1053 @@ class TestConcatenateFiles(unittest.TestCase):
                 concatenate_files(file_path, ["file.py"])
 
 
+class TestGetSkeletons(unittest.TestCase):
+    """Test skeleton extraction over a directory."""
+
+    def test_summary_counts(self) -> None:
+        """Test that supported files are processed and the rest skipped."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir)
+            (root / "pkg").mkdir()
+            (root / "pkg" / "lib.rs").write_text("fn add(a: i32) -> i32 {\n    a + 1\n}\n")
+            (root / "main.py").write_text("def main():\n    print('hi')\n")
+            (root / "notes.txt").write_text("plain text\n")
+
+            report = get_skeletons(root)
+
+            self.assertEqual([path for path, _ in report.skeletons], ["main.py", "pkg/lib.rs"])
+            self.assertEqual(report.processed, 2)
+            self.assertEqual(report.skipped, ["notes.txt"])
+            self.assertEqual(report.failed, [])
+            self.assertNotIn("print", report.skeletons[0][1])
+
+    def test_respects_ignore_file(self) -> None:
+        """Test that paths matched by a .ignore file are not visited."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir)
+            (root / ".ignore").write_text("generated/\n")
+            (root / "generated").mkdir()
+            (root / "generated" / "out.py").write_text("def out():\n    pass\n")
+            (root / "app.py").write_text("def app():\n    pass\n")
+
+            report = get_skeletons(root)
+
+            self.assertEqual([path for path, _ in report.skeletons], ["app.py"])
+
+    def test_symlink_cycle(self) -> None:
+        """Test that a symlink pointing back to an ancestor is not followed forever."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir)
+            (root / "src").mkdir()
+            (root / "src" / "lib.py").write_text("def f():\n    pass\n")
+            (root / "src" / "loop").symlink_to(root, target_is_directory=True)
+
+            report = get_skeletons(root)
+
+            self.assertEqual([path for path, _ in report.skeletons], ["src/lib.py"])
+
+    def test_parallel_matches_sequential(self) -> None:
+        """Test that parallel workers produce the same report as a sequential run."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir)
+            for i in range(12):
+                (root / f"m{i:02d}.py").write_text(f"def f{i}():\n    return {i}\n")
+                (root / f"m{i:02d}.rs").write_text(f"fn f{i}() -> i32 {{\n    {i}\n}}\n")
+            (root / "data.csv").write_text("a,b\n")
+
+            sequential = get_skeletons(root)
+            parallel = get_skeletons(root, max_workers=3)
+
+            self.assertEqual(parallel, sequential)
+            self.assertEqual(parallel.processed, 24)
+            self.assertEqual(parallel.skipped, ["data.csv"])
+
+    def test_include_exclude(self) -> None:
+        """Test that test directories are left out before any file is read."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir)
+            (root / "src" / "tests").mkdir(parents=True)
+            (root / "src" / "lib.rs").write_text("fn lib() -> i32 {\n    1\n}\n")
+            (root / "src" / "tests" / "lib_test.rs").write_text("fn check() {}\n")
+            (root / "tests").mkdir()
+            (root / "tests" / "smoke.py").write_text("def test_smoke():\n    pass\n")
+            (root / "build.py").write_text("def build():\n    pass\n")
+            (root / "notes.txt").write_text("plain text\n")
+
+            report = get_skeletons(root, exclude=["**/tests/**"])
+            self.assertEqual([path for path, _ in report.skeletons], ["build.py", "src/lib.rs"])
+            self.assertEqual(report.skipped, ["notes.txt"])
+
+            report = get_skeletons(root, include=["src/**/*.rs"], exclude=["**/tests/**"])
+            self.assertEqual([path for path, _ in report.skeletons], ["src/lib.rs"])
+            self.assertEqual(report.skipped, [])
+
+
+class TestWalkDir(unittest.TestCase):
+    """Test source file discovery with ignore files."""
+
+    def test_nested_ignore_files(self) -> None:
+        """Test that ignore files apply relative to the directory containing them."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir)
+            (root / "artifacts").mkdir()
+            (root / "artifacts" / "build.rs").write_text("fn main() {}\n")
+            (root / "crate" / "gen").mkdir(parents=True)
+            (root / "crate" / ".gitignore").write_text("gen/\n*.tmp.py\n")
+            (root / "crate" / "gen" / "out.rs").write_text("fn out() {}\n")
+            (root / "crate" / "lib.rs").write_text("fn lib() {}\n")
+            (root / "crate" / "scratch.tmp.py").write_text("x = 1\n")
+            (root / "gen").mkdir()
+            (root / "gen" / "kept.py").write_text("x = 1\n")
+            (root / "NOTES.txt").write_text("# Notes\n")
+            (root / ".ignore").write_text("artifacts/\n")
+
+            self.assertEqual(list(walk_dir(root)), ["crate/lib.rs", "gen/kept.py"])
+            self.assertEqual(
+                sorted(walk_dir(root, respect_gitignore=False)),
+                [
+                    "artifacts/build.rs",
+                    "crate/gen/out.rs",
+                    "crate/lib.rs",
+                    "crate/scratch.tmp.py",
+                    "gen/kept.py",
+                ],
+            )
+
+    def test_git_info_exclude(self) -> None:
+        """Test that the repository's .git/info/exclude is respected."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir)
+            (root / ".git" / "info").mkdir(parents=True)
+            (root / ".git" / "info" / "exclude").write_text("local.py\n")
+            (root / "local.py").write_text("x = 1\n")
+            (root / "main.py").write_text("x = 1\n")
+
+            self.assertEqual(list(walk_dir(root)), ["main.py"])
+            self.assertEqual(find_files(root), ["main.py"])
+
+    def test_include_patterns_combine(self) -> None:
+        """Test that a file matching any include is kept unless an exclude matches it."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir)
+            (root / "app").mkdir()
+            (root / "app" / "main.py").write_text("x = 1\n")
+            (root / "app" / "main_test.py").write_text("x = 1\n")
+            (root / "lib.rs").write_text("fn lib() {}\n")
+            (root / "README.md").write_text("# Readme\n")
+
+            self.assertEqual(
+                find_files(root, include=["*.py", "*.rs"], exclude=["*_test.py"]),
+                ["app/main.py", "lib.rs"],
+            )
+            self.assertEqual(
+                list(walk_dir(root, include=["app/**"])), ["app/main.py", "app/main_test.py"]
+            )
+
+        self.assertTrue(matches_pattern("src/net/tests/io.rs", ["**/tests/**"]))
+        self.assertTrue(matches_pattern(Path("src/a/b.rs"), ["docs/*", "src/**/*.rs"]))
+        self.assertFalse(matches_pattern("lib/b.rs", ["src/**/*.rs"]))
+        self.assertFalse(matches_pattern("src/b.rs", []))
+
+
+class TestParallelExtraction(unittest.TestCase):
+    """Test batch extraction across worker processes."""
+
+    def test_results_in_input_order(self) -> None:
+        """Test that results follow input order and failures don't abort the batch."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir)
+            paths: list[Path] = []
+            for i in range(20):
+                path = root / f"mod{i}.py"
+                path.write_text(f"def f{i}():\n    return {i}\n")
+                paths.append(path)
+            paths.insert(5, root / "missing.py")
+            (root / "notes.txt").write_text("plain text\n")
+            paths.append(root / "notes.txt")
+
+            results = get_skeletons_parallel(paths, max_workers=2)
+
+            self.assertEqual([result.path for result in results], [str(p) for p in paths])
+            self.assertFalse(results[5].ok)
+            self.assertIn("FileNotFoundError", results[5].error)
+            self.assertFalse(results[-1].ok)
+            self.assertEqual(results[0].skeleton, "def f0():\n    ...")
+            self.assertEqual(results[6].skeleton, "def f5():\n    ...")
+            self.assertEqual(sum(result.ok for result in results), 20)
+
+    def test_extractor_reused_per_thread(self) -> None:
+        """Test that extractors are cached per language and options."""
+        self.assertIs(get_extractor("python"), get_extractor("python", LopperOptions()))
+        self.assertIsNot(
+            get_extractor("python"), get_extractor("python", LopperOptions(keep_attributes=False))
+        )
+
+    def test_extractor_pool_bounded(self) -> None:
+        """Test that each thread keeps only its most recently used extractors."""
+        first = get_extractor("python", LopperOptions(max_body_lines=1))
+        recent = get_extractor("python", LopperOptions(max_body_lines=2))
+        for lines in range(3, 40):
+            get_extractor("python", LopperOptions(max_body_lines=lines))
+            self.assertIs(get_extractor("python", LopperOptions(max_body_lines=2)), recent)
+        self.assertIsNot(get_extractor("python", LopperOptions(max_body_lines=1)), first)
+
+
+class TestCache(unittest.TestCase):
+    """Test the on-disk cache of extraction results."""
+
+    def test_second_run_parses_nothing(self) -> None:
+        """Test that rerunning over an unchanged tree reads every skeleton from the cache."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir) / "src"
+            root.mkdir()
+            for i in range(1000):
+                (root / f"mod{i}.py").write_text(f"def f{i}():\n    return {i}\n")
+            cache = LopCache.open(Path(tmpdir) / "cache")
+
+            first = get_skeletons(root, cache=cache)
+            self.assertEqual((cache.hits, cache.misses), (0, 1000))
+
+            parse = SkeletonExtractor.parse
+            with mock.patch.object(
+                SkeletonExtractor, "parse", autospec=True, side_effect=parse
+            ) as parsed:
+                second = get_skeletons(root, cache=cache)
+            self.assertEqual(parsed.call_count, 0)
+            self.assertEqual((cache.hits, cache.misses), (1000, 1000))
+            self.assertEqual(second.skeletons, first.skeletons)
+            self.assertIn(("mod7.py", "def f7():\n    ..."), second.skeletons)
+
+    def test_key_changes_with_source_language_and_options(self) -> None:
+        """Test that a result is reused only for the same source, language and options."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            cache = LopCache.open(tmpdir)
+            source = "def f():\n    return 1\n"
+            result = extract_skeleton_result(source, "python")
+            cache.put(source, "python", result)
+
+            self.assertEqual(cache.get(source, "python", LopperOptions()), result)
+            self.assertIsNone(cache.get(source + "\n", "python"))
+            self.assertIsNone(cache.get(source, "starlark"))
+            self.assertIsNone(cache.get(source, "python", LopperOptions(keep_doc_comments=False)))
+
+            custom = LopperOptions(placeholder=lambda lines: f"... ({lines} lines)")
+            self.assertIsNone(cache.key(source, "python", custom))
+            cache.put(source, "python", result, custom)
+            self.assertEqual((cache.hits, cache.misses), (1, 3))
+
+    def test_hit_matches_extraction_with_errors(self) -> None:
+        """Test that a cached result keeps the diagnostics and source map of a parse."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            cache = LopCache.open(tmpdir)
+            source = "def f(:\n    return 1\n\ndef g():\n    return 2\n"
+            result = extract_skeleton_result(source, "python")
+            self.assertTrue(result.has_errors)
+            cache.put(source, "python", result)
+            self.assertEqual(cache.get(source, "python"), result)
+
+    def test_stats_cached_with_skeletons(self) -> None:
+        """Test that measuring unchanged files again reads their statistics back."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir) / "src"
+            root.mkdir()
+            for i in range(3):
+                (root / f"mod{i}.py").write_text(f"def f{i}():\n    return {i}\n")
+            files = sorted(path.name for path in root.iterdir())
+            cache = LopCache.open(Path(tmpdir) / "cache")
+            get_skeletons(root, cache=cache)
+
+            first = collect_stats(files, root=root, cache=cache)
+            self.assertEqual((cache.hits, cache.misses), (0, 6))
+            parse = SkeletonExtractor.parse
+            with mock.patch.object(
+                SkeletonExtractor, "parse", autospec=True, side_effect=parse
+            ) as parsed:
+                second = collect_stats(files, root=root, cache=cache)
+                report = get_skeletons(root, cache=cache)
+            self.assertEqual(parsed.call_count, 0)
+            self.assertEqual(second.files, first.files)
+            self.assertEqual(second.total.symbols, {"function": 3})
+            self.assertEqual(report.processed, 3)
+
+    def test_unwritable_cache_is_skipped(self) -> None:
+        """Test that a failed write leaves no entry or temporary file behind."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            cache = LopCache.open(tmpdir)
+            source = "def f():\n    return 1\n"
+            with mock.patch("loppers.cache.os.replace", side_effect=OSError("disk full")):
+                cache.put(source, "python", extract_skeleton_result(source, "python"))
+            self.assertEqual(list(Path(tmpdir).rglob("*.*")), [])
+            self.assertIsNone(cache.get(source, "python"))
+
+            (Path(tmpdir) / "src").mkdir()
+            (Path(tmpdir) / "src" / "mod.py").write_text(source)
+            with mock.patch.object(Path, "mkdir", side_effect=OSError("read-only")):
+                report = get_skeletons(Path(tmpdir) / "src", cache=cache)
+            self.assertEqual(report.skeletons, [("mod.py", "def f():\n    ...")])
+            self.assertEqual(report.failed, [])
+
+    def test_prune_removes_least_recently_used(self) -> None:
+        """Test that pruning removes the oldest entries until the cache fits."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            cache = LopCache.open(tmpdir)
+            sources = [f"def f{i}():\n    return {i}\n" for i in range(3)]
+            for i, source in enumerate(sources):
+                cache.put(source, "python", ExtractionResult(f"def f{i}():\n    ..."))
+                key = cache.key(source, "python")
+                assert key is not None
+                entry = Path(tmpdir) / key[:2] / f"{key}.json"
+                os.utime(entry, (1_000_000 + i, 1_000_000 + i))
+            entry_size = cache.size() // 3
+
+            self.assertEqual(cache.prune(cache.size()), 0)
+            self.assertEqual(cache.prune(entry_size * 2), 1)
+            self.assertIsNone(cache.get(sources[0], "python"))
+            self.assertIsNotNone(cache.get(sources[2], "python"))
+            self.assertEqual(cache.prune(0), 2)
+            self.assertEqual(cache.size(), 0)
+            with self.assertRaises(ValueError):
+                cache.prune(-1)
+
+
+class TestAsyncLopper(unittest.TestCase):
+    """Test extraction from asynchronous code and the JSON-lines server."""
+
+    def setUp(self) -> None:
+        """Reset the count of extractions running at once."""
+        self.lock = threading.Lock()
+        self.running = self.peak = 0
+
+    def slow_extract(
+        self, source: str, language: str, *, options: LopperOptions | None = None
+    ) -> str:
+        """Stand in for a slow extraction, tracking how many run at once."""
+        with self.lock:
+            self.running += 1
+            self.peak = max(self.peak, self.running)
+        time.sleep(0.05)
+        with self.lock:
+            self.running -= 1
+        if language == "cobol":
+            raise ValueError("Unsupported language: cobol")
+        return source.upper()
+
+    def test_lop(self) -> None:
+        """Test that skeletons match synchronous extraction and batches report failures."""
+
+        async def run() -> tuple[str, dict[int, LopResult]]:
+            async with AsyncLopper(max_workers=2) as lopper:
+                skeleton = await lopper.lop(SAMPLE_RS, "rust")
+                batch = lopper.lop_many([(SAMPLE_RS, "rust"), ("x", "cobol")])
+                return skeleton, {result.index: result async for result in batch}
+
+        skeleton, results = asyncio.run(run())
+        self.assertEqual(skeleton, extract_skeleton(SAMPLE_RS, "rust"))
+        self.assertEqual(results[0].skeleton, skeleton)
+        self.assertFalse(results[1].ok)
+        self.assertIn("UnsupportedLanguageError", results[1].error)
+
+    def test_concurrency_limit(self) -> None:
+        """Test that no more than max_workers extractions run at once."""
+
+        async def run() -> dict[int, LopResult]:
+            async with AsyncLopper(max_workers=2) as lopper:
+                sources = [*((f"s{i}", "rust") for i in range(6)), ("bad", "cobol")]
+                return {result.index: result async for result in lopper.lop_many(sources)}
+
+        with mock.patch("loppers.service.extract_skeleton", self.slow_extract):
+            results = asyncio.run(run())
+        self.assertEqual(self.peak, 2)
+        self.assertEqual(sorted(results), list(range(7)))
+        self.assertEqual(results[3].skeleton, "S3")
+        self.assertFalse(results[6].ok)
+        with self.assertRaises(ValueError):
+            AsyncLopper(max_workers=0)
+
+    def test_graceful_shutdown(self) -> None:
+        """Test that closing waits for submitted extractions and rejects later ones."""
+
+        async def run() -> list[str]:
+            lopper = AsyncLopper(max_workers=1)
+            calls = [asyncio.ensure_future(lopper.lop(f"s{i}", "rust")) for i in range(3)]
+            await asyncio.sleep(0)
+            await lopper.aclose()
+            self.assertEqual(self.running, 0)
+            self.assertTrue(all(call.done() for call in calls))
+            with self.assertRaises(RuntimeError):
+                await lopper.lop("late", "rust")
+            await lopper.aclose()
+            return [call.result() for call in calls]
+
+        with mock.patch("loppers.service.extract_skeleton", self.slow_extract):
+            self.assertEqual(asyncio.run(run()), ["S0", "S1", "S2"])
+
+    def test_serve(self) -> None:
+        """Test that every request read is answered, errors included, before serve returns."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = Path(tmpdir) / "lib.rs"
+            path.write_text("fn f() {}\n")
+            requests = [
+                {"id": 1, "source": "fn main() {}", "language": "rust"},
+                {"id": 2, "path": str(path), "language": "rust"},
+                {"id": 3, "path": str(Path(tmpdir) / "missing.rs"), "language": "rust"},
+                {"id": 4, "source": "fn main() {}"},
+                {"id": 5, "source": "x", "language": "rust", "options": {"bogus": True}},
+            ]
+            lines = [*(json.dumps(request) for request in requests), "", "{not json"]
+            output = io.StringIO()
+            with mock.patch("loppers.service.extract_skeleton", self.slow_extract):
+                serve(io.StringIO("\n".join(lines) + "\n"), output, max_workers=2)
+        responses = {
+            response["id"]: response
+            for response in map(json.loads, output.getvalue().splitlines())
+        }
+        self.assertEqual(len(responses), 6)
+        self.assertEqual(responses[1], {"id": 1, "skeleton": "FN MAIN() {}"})
+        self.assertEqual(responses[2], {"id": 2, "skeleton": "FN F() {}\n"})
+        self.assertEqual(responses[3]["error"]["type"], "FileNotFoundError")
+        self.assertEqual(responses[4]["error"]["type"], "ValueError")
+        self.assertIn("Unknown options: bogus", responses[5]["error"]["message"])
+        self.assertEqual(responses[None]["error"]["type"], "JSONDecodeError")
+        self.assertLessEqual(self.peak, 2)
+
+    def test_serve_with_placeholder_function(self) -> None:
+        """Test that requests over options with a placeholder function merge their own."""
+        requests = [
+            {"id": 1, "source": "fn f() {\n    1\n}\n", "language": "rust"},
+            {
+                "id": 2,
+                "source": "#[inline]\nfn f() {\n    1\n}\n",
+                "language": "rust",
+                "options": {"keep_attributes": False},
+            },
+        ]
+        output = io.StringIO()
+        options = LopperOptions(placeholder=lambda lines: f"/* {lines} */")
+        serve(
+            io.StringIO("".join(json.dumps(request) + "\n" for request in requests)),
+            output,
+            options=options,
+        )
+        responses = {
+            response["id"]: response
+            for response in map(json.loads, output.getvalue().splitlines())
+        }
+        self.assertEqual(responses[1], {"id": 1, "skeleton": "fn f() { /* 1 */ }"})
+        self.assertEqual(responses[2], {"id": 2, "skeleton": "fn f() { /* 1 */ }"})
+
+
+class TestShortcuts(unittest.TestCase):
+    """Test the shortcuts taking extraction options as keyword arguments."""
+
+    def test_lop(self) -> None:
+        """Test that keyword options apply, and unknown or invalid ones raise TypeError."""
+        self.assertEqual(
+            lop(SAMPLE_RS, "rust", visibility="public", max_body_lines=2),
+            extract_skeleton(
+                SAMPLE_RS, "rust", options=LopperOptions(visibility="public", max_body_lines=2)
+            ),
+        )
+        with self.assertRaisesRegex(TypeError, "Unknown options: visiblity"):
+            lop(SAMPLE_RS, "rust", visiblity="public")
+        with self.assertRaisesRegex(TypeError, "Invalid max_body_lines '3': expected"):
+            lop(SAMPLE_RS, "rust", max_body_lines="3")
+        with self.assertRaisesRegex(TypeError, "internal"):
+            lop(SAMPLE_RS, "rust", visibility="internal")
+
+    def test_files_and_symbols(self) -> None:
+        """Test lopping files and directories, and symbols as dicts."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir)
+            (root / "src").mkdir()
+            (root / "src" / "lib.rs").write_text(SAMPLE_RS)
+            (root / "notes.txt").write_text("notes\n")
+            options = LopperOptions(imports="drop")
+            expected = get_skeleton(root / "src" / "lib.rs", options=options)
+            self.assertEqual(lop_file(root / "src" / "lib.rs", imports="drop"), expected)
+            self.assertEqual(lop_dir(root, imports="drop"), {"src/lib.rs": expected})
+            with self.assertRaises(TypeError):
+                lop_dir(root, imports="all")
+        self.assertEqual(
+            lop_symbols(SAMPLE_RS, "rust", group_impls=True),
+            [symbol.to_dict() for symbol in extract_symbols(SAMPLE_RS, "rust", group_impls=True)],
+        )
+        code = "fn run() {}\n\n#[test]\nfn check() {}\n"
+        symbols = lop_symbols(code, "rust", skip_tests=True)
+        self.assertEqual([symbol["name"] for symbol in symbols], ["run"])
+
+    def test_supported_languages(self) -> None:
+        """Test that every supported language is listed once, in order."""
+        languages = supported_languages()
+        self.assertEqual(languages, sorted(set(languages)))
+        self.assertTrue({"rust", "python", "markdown", "notebook"} <= set(languages))
+
+
+class TestStreaming(unittest.TestCase):
+    """Test piecewise extraction and incremental reparsing."""
+
+    def test_stream_file_matches_get_skeleton(self) -> None:
+        """Test that a memory-mapped file yields the same skeleton in pieces."""
+        sample = Path(__file__).parent.parent / "examples" / "sample.rs"
+        pieces = list(stream_skeleton(sample))
+        self.assertGreater(len(pieces), 1)
+        self.assertEqual("".join(pieces), get_skeleton(sample))
+
+    def test_stream_markdown_file(self) -> None:
+        """Test that a Markdown file yields the skeleton of its code blocks whole."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = Path(tmpdir) / "README.md"
+            path.write_text("# Usage\n\n```python\ndef f():\n    return 1\n```\n")
+            pieces = list(stream_skeleton(path))
+            self.assertEqual(pieces, [get_skeleton(path)])
+            self.assertIn("def f():\n    ...", pieces[0])
+
+    def test_stream_empty_file(self) -> None:
+        """Test that an empty file yields no pieces."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = Path(tmpdir) / "empty.py"
+            path.write_text("")
+            self.assertEqual(list(stream_skeleton(path)), [])
+
+    def test_stream_reader_small_chunks(self) -> None:
+        """Test that chunked reads splitting multi-byte characters decode correctly."""
+        code = 'fn größe() -> &\'static str {\n    "äöü"\n}\n\n/// Ünïcode.\nfn b() {}\n'
+        reader = io.BytesIO(code.encode())
+        skeleton = "".join(stream_skeleton_reader(reader, "rust", chunk_size=3))
+        self.assertEqual(skeleton, extract_skeleton(code, "rust"))
+
+    def test_invalid_utf8_rejected(self) -> None:
+        """Test that undecodable sources raise ValueError."""
+        reader = io.BytesIO(b"fn f() {}\n// \xff\n")
+        with self.assertRaises(ValueError):
+            "".join(stream_skeleton_reader(reader, "rust"))
+
+    def test_lop_to_writer(self) -> None:
+        """Test that the skeleton is written to text and binary streams and measured."""
+        sample = Path(__file__).parent.parent / "examples" / "sample.rs"
+        text, binary = io.StringIO(), io.BytesIO()
+        stats = lop_to_writer(sample, text)
+        self.assertEqual(lop_to_writer(sample, binary), stats)
+        self.assertEqual(text.getvalue(), get_skeleton(sample))
+        self.assertEqual(binary.getvalue().decode(), get_skeleton(sample))
+        expected = lop_stats(SAMPLE_RS, "rust")
+        self.assertEqual(
+            stats,
+            dataclasses.replace(expected, elided_lines=0, symbols={}, elided={}),
+        )
+
+    @unittest.skipUnless(os.environ.get("LOPPERS_MEMORY_TEST"), "set LOPPERS_MEMORY_TEST to run")
+    def test_lop_to_writer_memory(self) -> None:
+        """Test that a 100 MB file is lopped without holding its text in Python memory."""
+        line = "    let v{0} = x.wrapping_mul({0}).rotate_left({1}) ^ (x >> {2}); // generated\n"
+        body = "".join(line.format(i, i % 13, i % 7) for i in range(12))
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = Path(tmpdir) / "generated.rs"
+            with path.open("w") as file:
+                for n in range(100 * 2**20 // len(body)):
+                    file.write(f"pub fn f{n}(x: u64) -> u64 {{\n{body}    x\n}}\n")
+            size = path.stat().st_size
+            tracemalloc.start()
+            try:
+                with open(os.devnull, "wb") as sink:
+                    stats = lop_to_writer(path, sink)
+                _, peak = tracemalloc.get_traced_memory()
+            finally:
+                tracemalloc.stop()
+        self.assertEqual(stats.original_bytes, size)
+        self.assertLess(stats.output_bytes, size // 5)
+        # A copy of the source text alone would take the size of the file
+        self.assertLess(peak, size)
+
+    def test_incremental_reparse(self) -> None:
+        """Test extracting from a tree reparsed incrementally after an edit."""
+        extractor = SkeletonExtractor("python")
+        old_source = "def f():\n    return 1\n"
+        new_source = "def f():\n    return 10\n"
+        tree = extractor.parse(old_source)
+        tree.edit(
+            start_byte=20,
+            old_end_byte=21,
+            new_end_byte=22,
+            start_point=(1, 11),
+            old_end_point=(1, 12),
+            new_end_point=(1, 13),
+        )
+        new_tree = extractor.parse(new_source, tree)
+        self.assertEqual(
+            extractor.extract(new_source, tree=new_tree), extractor.extract(new_source)
+        )
+
+
+class TestErrors(unittest.TestCase):
+    """Test the error types raised for unsupported and broken inputs."""
+
+    def test_unsupported_file(self) -> None:
+        """Test that an undetected file raises UnsupportedLanguageError with its path."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = Path(tmpdir) / "notes.txt"
+            path.write_text("plain text\n")
+            with self.assertRaises(UnsupportedLanguageError) as context:
+                get_skeleton(path)
+            self.assertEqual(context.exception.path, path)
+            self.assertEqual(context.exception.extension, ".txt")
+            self.assertIsInstance(context.exception, ValueError)
+
+    def test_unsupported_language(self) -> None:
+        """Test that an unknown language identifier is reported by name."""
+        with self.assertRaises(UnsupportedLanguageError) as context:
+            extract_skeleton("x", "cobol")
+        self.assertEqual(context.exception.language, "cobol")
+        self.assertIsNone(context.exception.path)
+
+    def test_invalid_utf8_file(self) -> None:
+        """Test that undecodable files raise InvalidUtf8Error with their path."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = Path(tmpdir) / "bad.py"
+            path.write_bytes(b"def f():\n    return '\xff'\n")
+            with self.assertRaises(InvalidUtf8Error) as context:
+                get_skeleton(path)
+            self.assertEqual(context.exception.path, path)
+            with self.assertRaises(InvalidUtf8Error) as context:
+                "".join(stream_skeleton(path))
+            self.assertEqual(context.exception.path, path)
+
+    def test_errors_survive_pickling(self) -> None:
+        """Test that errors keep their attributes across worker processes."""
+        error = UnsupportedLanguageError("Unsupported file type: .txt", extension=".txt")
+        copy = pickle.loads(pickle.dumps(error))
+        self.assertIsInstance(copy, UnsupportedLanguageError)
+        self.assertEqual(str(copy), str(error))
+        self.assertEqual(copy.extension, ".txt")
+
+    def test_batch_separates_skipped_from_failed(self) -> None:
+        """Test that batch results tell unsupported files from broken ones."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir)
+            (root / "notes.txt").write_text("plain text\n")
+            (root / "bad.py").write_bytes(b"x = '\xff'\n")
+            results = get_skeletons_parallel([root / "notes.txt", root / "bad.py"], max_workers=2)
+            self.assertTrue(results[0].skipped)
+            self.assertIsInstance(results[0].exception, UnsupportedLanguageError)
+            self.assertFalse(results[1].skipped)
+            self.assertIsInstance(results[1].exception, LopError)
+            self.assertIn("InvalidUtf8Error", results[1].error)
+
+
+class TestBundle(unittest.TestCase):
+    """Test bundling the skeletons of several files."""
+
+    FILES: ClassVar[list[LoppedFile]] = [
+        LoppedFile("/repo/src/lib.rs", "pub fn run()\n"),
+        LoppedFile("/repo/src/private.py", ""),
+        LoppedFile("/repo/docs/notes.txt", "```\nquoted\n```", language="markdown"),
+    ]
+    APP_PY: str = "class App:\n    def main(self):\n        pass\n"
+
+    def test_plain_bundle(self) -> None:
+        """Test that files keep their order, relative paths and skip empty skeletons."""
+        self.assertEqual(
+            render_bundle(self.FILES, root="/repo"),
+            "=== src/lib.rs ===\npub fn run()\n\n=== docs/notes.txt ===\n```\nquoted\n```",
+        )
+
+    def test_markdown_bundle(self) -> None:
+        """Test that skeletons are fenced with their language and empty ones noted."""
+        self.assertEqual(
+            render_bundle(self.FILES, format="markdown", header="## {path}", show_empty=True),
+            "## /repo/src/lib.rs\n```rust\npub fn run()\n```\n\n"
+            "## /repo/src/private.py\n(no public items)\n\n"
+            "## /repo/docs/notes.txt\n````markdown\n```\nquoted\n```\n````",
+        )
+
+    def test_invalid_bundle_arguments(self) -> None:
+        """Test that unknown formats and broken header templates are rejected."""
+        with self.assertRaises(ValueError):
+            render_bundle(self.FILES, format="html")  # type: ignore[arg-type]
+        with self.assertRaises(ValueError):
+            render_bundle(self.FILES, header="=== {name} ===")
+
+    def test_repo_map(self) -> None:
+        """Test that a repo map has sorted path headers and a file and symbol count."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir)
+            (root / "src").mkdir()
+            (root / "src" / "lib.rs").write_text("pub fn run() {\n    go();\n}\nfn go() {}\n")
+            (root / "app.py").write_text(self.APP_PY)
+            (root / "notes.txt").write_text("notes\n")
+            options = LopperOptions(visibility="public")
+            repo_map = render_repo_map(get_skeletons(root, options=options), root, options=options)
+        self.assertEqual(
+            repo_map,
+            "## app.py\n"
+            f"{extract_skeleton(self.APP_PY, 'python')}\n"
+            "\n"
+            "## src/lib.rs\n"
+            "pub fn run() {\n}\n"
+            "\n"
+            "2 files, 3 symbols",
+        )
+
+
+class TestCtags(unittest.TestCase):
+    """Test rendering symbols as ctags tag files."""
+
+    def test_tag_lines(self) -> None:
+        """Test that tags have language kind letters, scopes and are sorted by name."""
+        add = Symbol("method", "add", "pub fn add(&mut self)", 6, 8, parent="Calculator")
+        rust = [
+            Symbol("struct", "Calculator", "pub struct Calculator", 1, 3),
+            Symbol("impl", "Calculator", "impl Calculator", 5, 9, children=[add]),
+            Symbol("trait", "Shape", "pub trait Shape", 11, 11),
+            Symbol("type", "Id", "type Id = u32;", 12, 12),
+        ]
+        go = [
+            Symbol("method", "Area", "func (s *Square) Area() int", 3, 5, parent="Square"),
+            Symbol("struct", "Square", "type Square struct", 7, 9),
+        ]
+        tags = render_ctags({"src/lib.rs": rust, "square.go": go}, languages={"src/lib.rs": "rust"})
+        lines = tags.splitlines()
+        self.assertTrue(all(line.startswith("!_TAG_") for line in lines[:4]))
+        self.assertIn("!_TAG_FILE_SORTED\t1\t", lines[1])
+        self.assertEqual(
+            lines[4:],
+            [
+                'Area\tsquare.go\t3;"\tm\tstruct:Square',
+                'Calculator\tsrc/lib.rs\t1;"\ts',
+                'Calculator\tsrc/lib.rs\t5;"\tc',
+                'Id\tsrc/lib.rs\t12;"\tT',
+                'Shape\tsrc/lib.rs\t11;"\tt',
+                'Square\tsquare.go\t7;"\ts',
+                'add\tsrc/lib.rs\t6;"\tm\tstruct:Calculator',
+            ],
+        )
+        self.assertTrue(tags.endswith("\n"))
+
+    def test_search_patterns(self) -> None:
+        """Test that given sources address tags by escaped patterns of their first line."""
+        source = "pub trait Shape {}\nfn split(path: &str) {\n    run();\n}\n"
+        symbols = [
+            Symbol("trait", "Shape", "pub trait Shape", 1, 1),
+            Symbol("function", "split", "fn split(path: &str)", 2, 4),
+            Symbol("interface", "Area", "interface Area", 9, 9),
+        ]
+        tags = render_ctags({"lib.rs": symbols}, sources={"lib.rs": source})
+        self.assertEqual(
+            tags.splitlines()[4:],
+            [
+                'Area\tlib.rs\t9;"\tt',
+                'Shape\tlib.rs\t/^pub trait Shape {}$/;"\tt',
+                'split\tlib.rs\t/^fn split(path: &str) {$/;"\tf',
+            ],
+        )
+        escaped = render_ctags(
+            {"a.rs": [Symbol("function", "f", "fn f()", 1, 1)]},
+            sources={"a.rs": 'fn f() { g("a/b\\\\c") }\n'},
+        )
+        self.assertIn('f\ta.rs\t/^fn f() { g("a\\/b\\\\\\\\c") }$/;"\tf', escaped)
+        # Form feeds and Unicode line separators do not end lines
+        numbered = render_ctags(
+            {"a.rs": [Symbol("function", "f", "fn f()", 2, 2)]},
+            sources={"a.rs": "// a\fb\u2028c\r\nfn f() {}\r\n"},
+        )
+        self.assertIn('f\ta.rs\t/^fn f() {}$/;"\tf', numbered)
+
+
+class TestOutline(unittest.TestCase):
+    """Test rendering symbols as outlines grouped by kind and sorted by name."""
+
+    expected: ClassVar[str] = (
+        "pub struct Calculator\n"
+        "    pub fn add(&self, x: i32, y: i32) -> i32\n"
+        "    pub fn new(initial: i32) -> Self\n"
+        "    fn process(&self)\n"
+        "\n"
+        "pub trait Arithmetic\n"
+        "    fn add(&self, x: i32) -> i32\n"
+        "    fn multiply(&self, x: i32) -> i32\n"
+        "\n"
+        "impl Arithmetic for Calculator { add, multiply }\n"
+        "\n"
+        "fn fibonacci(n: u32) -> u32"
+    )
+
+    def test_rust_sample_outline(self) -> None:
+        """Test that inherent impl items join their type and trait impls name theirs."""
+        symbols = extract_symbols(SAMPLE_RS, "rust")
+        self.assertEqual(render_outline(symbols), self.expected)
+        self.assertEqual(render_outline(list(reversed(symbols))), self.expected)
+
+    def test_impl_items(self) -> None:
+        """Test that impl blocks can keep their items, ordered stably by trait."""
+        add = Symbol("method", "add", "fn add(&self)", 2, 2, parent="Calculator")
+        trait_add = Symbol(
+            "method", "add", "fn add(&self)", 5, 5, parent="Calculator", trait="Arithmetic"
+        )
+        symbols = [
+            Symbol("impl", "Calculator", "impl Calculator", 1, 3, children=[add]),
+            Symbol(
+                "impl",
+                "Calculator",
+                "impl Arithmetic for Calculator",
+                4,
+                6,
+                trait="Arithmetic",
+                children=[trait_add],
+            ),
+        ]
+        options = OutlineOptions(impl_items="signatures", merge_impls=False, indent="  ")
+        expected = (
+            "impl Calculator\n  fn add(&self)\nimpl Arithmetic for Calculator\n  fn add(&self)"
+        )
+        self.assertEqual(render_outline(symbols, options=options), expected)
+        self.assertEqual(render_outline(symbols[::-1], options=options), expected)
+        outline = render_outline(symbols, options=OutlineOptions(impl_items="none"))
+        self.assertEqual(
+            outline, "impl Calculator\n    fn add(&self)\nimpl Arithmetic for Calculator"
+        )
+        with self.assertRaises(ValueError):
+            OutlineOptions(impl_items="all")
+
+    def test_go_methods_under_type(self) -> None:
+        """Test that Go methods are listed under their receiver type, signatures on one line."""
+        symbols = [
+            Symbol("method", "Push", "func (s *Stack) Push(\n\titem int,\n)", 5, 7, parent="Stack"),
+            Symbol("function", "New", "func New() *Stack", 9, 11),
+            Symbol("method", "Len", "func (s Stack) Len() int", 13, 15, parent="Stack"),
+            Symbol("struct", "Stack", "type Stack struct", 1, 3),
+            Symbol("constant", "Max", "Max = 10", 17, 17),
+        ]
+        self.assertEqual(
+            render_outline(symbols),
+            "type Stack struct\n"
+            "    func (s Stack) Len() int\n"
+            "    func (s *Stack) Push( item int, )\n"
+            "\n"
+            "func New() *Stack\n"
+            "\n"
+            "Max = 10",
+        )
+
+
+class TestCli(unittest.TestCase):
+    """Test running the extract command in a pipe."""
+
+    def run_cli(self, *argv: str, stdin: str = "") -> tuple[int, str, str]:
+        """Run the CLI, returning its exit status, stdout and stderr."""
+        stdout, stderr = io.StringIO(), io.StringIO()
+        status = 0
+        with (
+            mock.patch("sys.argv", ["loppers", *argv]),
+            mock.patch("sys.stdin", io.StringIO(stdin)),
+            contextlib.redirect_stdout(stdout),
+            contextlib.redirect_stderr(stderr),
+        ):
+            try:
+                main()
+            except SystemExit as e:
+                status = e.code if isinstance(e.code, int) else 1
+        return status, stdout.getvalue(), stderr.getvalue()
+
+    def test_stdin(self) -> None:
+        """Test that "-" reads stdin and only the skeleton reaches stdout."""
+        code = "fn f() -> i32 {\n    1\n}\n"
+        expected = (0, extract_skeleton(code, "rust") + "\n", "")
+        self.assertEqual(self.run_cli("extract", "--lang", "rust", "-", stdin=code), expected)
+        self.assertEqual(self.run_cli("extract", "-l", "rust", stdin=code), expected)
+        self.assertEqual(self.run_cli("--lang", "rust", stdin=code), expected)
+
+    def test_serve(self) -> None:
+        """Test that serve answers JSON requests from stdin with the options of its flags."""
+        code = "fn f() -> i32 {\n    1\n}\n\npub fn g() {}\n"
+        request = json.dumps({"id": "a", "source": code, "language": "rust"})
+        status, stdout, _ = self.run_cli("serve", "-j", "1", "--public-only", stdin=request)
+        skeleton = extract_skeleton(code, "rust", options=LopperOptions(visibility="public"))
+        self.assertEqual((status, json.loads(stdout)), (0, {"id": "a", "skeleton": skeleton}))
+
+    def test_stdin_language_guessed(self) -> None:
+        """Test that the language of stdin is guessed from its code without --lang."""
+        code = "use std::fmt;\n\nfn f() -> i32 {\n    let mut x = 1;\n    x\n}\n"
+        expected = (0, extract_skeleton(code, "rust") + "\n", "")
+        self.assertEqual(self.run_cli("extract", stdin=code), expected)
+        self.assertEqual(self.run_cli(stdin=code), expected)
+
+    def test_lang_overrides_detection(self) -> None:
+        """Test that --lang applies to files whose extension says otherwise."""
+        code = "fn f() {\n    g();\n}\n"
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = Path(tmpdir) / "snippet.txt"
+            path.write_text(code)
+            status, stdout, _ = self.run_cli("extract", "--lang", "rust", str(path))
+        self.assertEqual((status, stdout), (0, extract_skeleton(code, "rust") + "\n"))
+
+    def test_outline_format(self) -> None:
+        """Test that --format outline prints the sorted outline of a file, not a directory."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = Path(tmpdir) / "sample.rs"
+            path.write_text(SAMPLE_RS)
+            status, stdout, _ = self.run_cli("extract", "--format", "outline", str(path))
+            self.assertEqual((status, stdout), (0, TestOutline.expected + "\n"))
+            for format in ("outline", "json"):
+                status, _, stderr = self.run_cli("extract", "--format", format, tmpdir)
+                self.assertEqual(status, 1)
+                self.assertIn(f"--format {format} requires a file, not a directory", stderr)
+
+    def test_symbol(self) -> None:
+        """Test that --symbol prints one symbol's items, and lists the names of a typo."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = Path(tmpdir) / "sample.rs"
+            path.write_text(SAMPLE_RS)
+            status, stdout, _ = self.run_cli("extract", "--symbol", "Calculator", str(path))
+            expected = lop_symbol(SAMPLE_RS, "rust", "Calculator") + "\n"
+            self.assertEqual((status, stdout), (0, expected))
+            status, _, stderr = self.run_cli("extract", "--symbol", "Calc", str(path))
+            self.assertEqual(status, 1)
+            self.assertIn("top-level symbols: fibonacci, Calculator, Arithmetic", stderr)
+            status, _, stderr = self.run_cli("extract", "--symbol", "Calculator", tmpdir)
+            self.assertEqual(status, 1)
+
+    def test_markdown_format(self) -> None:
+        """Test that skeletons are fenced in their language, past any backticks inside."""
+        code = "/// ```\n/// run();\n/// ```\nfn run() {\n    go();\n}\n"
+        status, stdout, _ = self.run_cli("extract", "-f", "markdown", "-l", "rust", stdin=code)
+        skeleton = extract_skeleton(code, "rust")
+        self.assertEqual((status, stdout), (0, f"## <stdin>\n````rust\n{skeleton}\n````\n"))
+        python_code, rust_code = "def run():\n    go()\n", "fn run() {\n    go();\n}\n"
+        with tempfile.TemporaryDirectory() as tmpdir:
+            (Path(tmpdir) / "b.rs").write_text(rust_code)
+            (Path(tmpdir) / "a.py").write_text(python_code)
+            status, stdout, _ = self.run_cli("extract", "--format", "markdown", tmpdir)
+        self.assertEqual(status, 0)
+        self.assertEqual(
+            stdout,
+            f"## a.py\n```python\n{extract_skeleton(python_code, 'python')}\n```\n\n"
+            f"## b.rs\n```rust\n{extract_skeleton(rust_code, 'rust')}\n```\n",
+        )
+
+    def test_color(self) -> None:
+        """Test that --color paints text output, and auto leaves pipes uncolored."""
+        code = "pub fn f(x: i32) -> Point {\n    let y = x;\n    Point::new(y)\n}\n"
+        options = LopperOptions(placeholder_comment="{lines}")
+        skeleton = extract_skeleton(code, "rust", options=options)
+        colored = highlight(skeleton, "rust")
+        self.assertEqual(re.sub(r"\x1b\[\d+m", "", colored), skeleton)
+        self.assertIn(f"{ANSI_COLORS['keyword']}fn\x1b[0m", colored)
+        self.assertIn(f"{ANSI_COLORS['type']}Point\x1b[0m", colored)
+        self.assertRegex(colored, re.escape(ANSI_COLORS["placeholder"]) + r"/\* \d+ lines \*/")
+        self.assertEqual(highlight("# Title\n", "markdown"), "# Title\n")
+
+        argv = ("extract", "-l", "rust", "--placeholder-comment", "{lines}")
+        self.assertEqual(self.run_cli(*argv, stdin=code), (0, skeleton + "\n", ""))
+        self.assertEqual(self.run_cli(*argv, "--color", "always", stdin=code)[1], colored + "\n")
+        with mock.patch.dict(os.environ), mock.patch("sys.stdout", mock.Mock(isatty=lambda: True)):
+            os.environ.pop("NO_COLOR", None)
+            self.assertTrue(use_color(argparse.Namespace(color="auto")))
+            self.assertFalse(use_color(argparse.Namespace(color="never")))
+            os.environ["NO_COLOR"] = "1"
+            self.assertFalse(use_color(argparse.Namespace(color="auto")))
+            self.assertTrue(use_color(argparse.Namespace(color="always")))
+        status, stdout, _ = self.run_cli(*argv, "--color", "always", "--format", "json", stdin=code)
+        self.assertEqual(status, 0)
+        self.assertNotIn("\x1b", stdout)
+
+    def test_ctags_format(self) -> None:
+        """Test that --format ctags writes a tag file of every file of a directory."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir)
+            (root / "app.py").write_text("class App:\n    def run(self):\n        pass\n")
+            (root / "notes.md").write_text("# Notes\n")
+            status, stdout, _ = self.run_cli("extract", "--format", "ctags", tmpdir)
+        self.assertEqual(status, 0)
+        app = (root / "app.py").as_posix()
+        self.assertEqual(
+            stdout.splitlines()[4:],
+            [
+                f'App\t{app}\t/^class App:$/;"\tc',
+                f'run\t{app}\t/^    def run(self):$/;"\tm\tclass:App',
+            ],
+        )
+
+    def test_ctags_skip_tests(self) -> None:
+        """Test that --skip-tests leaves the files of __tests__ directories out of ctags."""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir)
+            (root / "__tests__").mkdir()
+            (root / "app.js").write_text("function run() {}\n")
+            (root / "__tests__" / "app.test.js").write_text("function check() {}\n")
+            _, stdout, _ = self.run_cli("extract", "--format", "ctags", tmpdir)
+            self.assertIn("check\t", stdout)
+            status, stdout, _ = self.run_cli("extract", "--format", "ctags", "--skip-tests", tmpdir)
+        self.assertEqual(status, 0)
+        self.assertIn("run\t", stdout)
+        self.assertNotIn("check\t", stdout)
+        self.assertTrue(is_test_file("src/__tests__/app.test.js"))
+        self.assertFalse(is_test_file("src/__tests__"))
+
+    def test_out_dir(self) -> None:
+        """Test that skeletons mirror the source tree, rewriting only changed files."""
+        python_code, rust_code = "def run():\n    go()\n", "fn run() {\n    go();\n}\n"
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root, out = Path(tmpdir) / "src", Path(tmpdir) / "out"
+            (root / "core").mkdir(parents=True)
+            (root / "a.py").write_text(python_code)
+            (root / "core" / "b.rs").write_text(rust_code)
+            status, stdout, _ = self.run_cli("extract", str(root), "--out-dir", str(out))
+            self.assertEqual((status, stdout), (0, ""))
+            self.assertEqual(
+                (out / "core" / "b.rs").read_text(), extract_skeleton(rust_code, "rust") + "\n"
+            )
+            self.assertEqual(
+                (out / "a.py").read_text(), extract_skeleton(python_code, "python") + "\n"
+            )
+
+            os.utime(out / "a.py", (0, 0))
+            (root / "core" / "b.rs").write_text("fn stop() {\n    halt();\n}\n")
+            status, _, stderr = self.run_cli("extract", str(root), "--out-dir", str(out), "-v")
+            self.assertEqual(status, 0)
+            self.assertEqual((out / "a.py").stat().st_mtime, 0)
+            self.assertEqual((out / "core" / "b.rs").read_text(), "fn stop() {\n}\n")
+            self.assertIn(f"Written 1 file to {out} (1 unchanged)", stderr)
+
+            status, _, stderr = self.run_cli("extract", str(root), "--out-dir", str(out), "-o", "x")
+            self.assertNotEqual(status, 0)
+            self.assertIn("--out-dir cannot be combined with --output", stderr)
+
+    def test_config_file(self) -> None:
+        """Test that a config file above FILE sets defaults that flags override."""
+        code = "import os\n\ndef main():\n    run()\n"
+        with tempfile.TemporaryDirectory() as tmpdir:
+            root = Path(tmpdir)
+            (root / "pkg").mkdir()
+            (root / "pkg" / "app.py").write_text(code)
+            (root / ".loppers.toml").write_text('[lang.python]\nimports = "drop"\n')
+            path = str(root / "pkg" / "app.py")
+            status, stdout, _ = self.run_cli("extract", path)
+            self.assertEqual(status, 0)
+            self.assertIn("def main():", stdout)
+            self.assertNotIn("import os", stdout)
+            for flags in (["--imports", "keep"], ["--no-config"]):
+                with self.subTest(flags=flags):
+                    status, stdout, _ = self.run_cli("extract", path, *flags)
+                    self.assertEqual(status, 0)
+                    self.assertIn("import os", stdout)
+
+            (root / "broken.toml").write_text("imports = [\n")
+            status, _, stderr = self.run_cli("extract", path, "--config", str(root / "broken.toml"))
+            self.assertNotEqual(status, 0)
+            self.assertIn(f"Invalid config {root / 'broken.toml'}", stderr)
+
+    def test_errors_go_to_stderr(self) -> None:
+        """Test that errors leave stdout empty and exit with a nonzero status."""
+        status, stdout, stderr = self.run_cli("extract", "-", stdin="fn f() {}\n")
+        self.assertNotEqual(status, 0)
+        self.assertEqual(stdout, "")
+        self.assertIn("-l/--lang", stderr)
+        status, stdout, stderr = self.run_cli("extract", "--lang", "cobol", "-", stdin="")
+        self.assertNotEqual(status, 0)
+        self.assertEqual(stdout, "")
+        self.assertIn("Error", stderr)
+
+
 class TestFileSizeFormatting(unittest.TestCase):
     """Test file size formatting utility."""
 